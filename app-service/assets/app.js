@@ -21,8 +21,23 @@ logoutLink.addEventListener("click", (e) => {
     });
 });
 
+// The JWT cookie only lives for a few minutes; when it has expired,
+// try to renew it silently with the refresh token before giving up.
+const fetchProtected = () => fetch('/protected').then(response => {
+    if (response.status !== 401) {
+        return response;
+    }
+
+    let refreshUrl = new URL("/refresh", logoutLink.href);
+
+    return fetch(refreshUrl, {
+        method: 'POST',
+        credentials: 'include', // This will include cookies in the request
+    }).then(refreshResponse => refreshResponse.ok ? fetch('/protected') : response);
+});
+
 (() => {
-    fetch('/protected').then(response => {
+    fetchProtected().then(response => {
         if (response.ok) {
             loginLink.style.display = "none";
             logoutLink.style.display = "block";
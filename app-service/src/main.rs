@@ -1,14 +1,22 @@
-use std::env;
+use std::{
+    env,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 use askama::Template;
 use axum::{
-    http::StatusCode,
-    response::{Html, IntoResponse},
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
     routing::get,
     Json, Router,
 };
 use axum_extra::extract::CookieJar;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use tower::{Layer, Service};
 use tower_http::services::ServeDir;
 
 #[tokio::main]
@@ -16,7 +24,11 @@ async fn main() {
     let app = Router::new()
         .nest_service("/assets", ServeDir::new("assets"))
         .route("/", get(root))
-        .route("/protected", get(protected));
+        .route("/protected", get(protected))
+        .route(
+            "/reports",
+            get(reports).route_layer(RequirePermission("reports:read")),
+        );
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
 
@@ -46,43 +58,169 @@ async fn root() -> impl IntoResponse {
     Html(template.render().unwrap())
 }
 
-async fn protected(jar: CookieJar) -> impl IntoResponse {
-    let jwt_cookie = match jar.get("jwt") {
-        Some(cookie) => cookie,
-        None => {
-            return StatusCode::UNAUTHORIZED.into_response();
-        }
+async fn protected(verified: VerifiedToken) -> impl IntoResponse {
+    // Every token that gets past VerifiedToken is a user's, but the auth service doesn't promise it
+    let Some(user_id) = verified.user_id else {
+        return StatusCode::UNAUTHORIZED.into_response();
     };
 
-    let api_client = reqwest::Client::builder().build().unwrap();
+    Json(ProtectedRouteResponse {
+        img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
+        user_id,
+    })
+    .into_response()
+}
+
+// Only users granted `reports:read` by one of their roles get here, see RequirePermission
+async fn reports() -> impl IntoResponse {
+    Json(ReportsResponse {
+        reports: vec!["Monthly active users".to_owned(), "Signups".to_owned()],
+    })
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
 
-    let verify_token_body = serde_json::json!({
-        "token": &jwt_cookie.value(),
-    });
+// What the auth service says about the token of the request. It identifies users by a stable id
+// rather than their email address, so that is what data kept for a user should be keyed on.
+#[derive(Deserialize)]
+struct VerifiedToken {
+    #[serde(rename = "userId")]
+    user_id: Option<String>,
+    // Granted to the user through their roles
+    #[serde(default)]
+    permissions: Vec<String>,
+    // Set for tokens issued to OAuth clients, which are meant for those clients' own APIs
+    #[serde(rename = "clientId")]
+    client_id: Option<String>,
+}
 
-    let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
-    let url = format!("http://{}:3000/verify-token", auth_hostname);
+impl VerifiedToken {
+    fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+}
 
-    let response = match api_client.post(&url).json(&verify_token_body).send().await {
-        Ok(response) => response,
-        Err(_) => {
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for VerifiedToken {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // Scripts send a personal access token as a bearer token instead of the cookie
+        let jar = CookieJar::from_headers(&parts.headers);
+        let token = bearer_token(&parts.headers)
+            .or_else(|| jar.get("jwt").map(|c| c.value()))
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        let api_client = reqwest::Client::builder().build().unwrap();
+
+        let verify_token_body = serde_json::json!({
+            "token": token,
+        });
+
+        let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
+        let url = format!("http://{}:3000/verify-token", auth_hostname);
+
+        let response = api_client
+            .post(&url)
+            .json(&verify_token_body)
+            .send()
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        match response.status() {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::BAD_REQUEST => {
+                Err(StatusCode::UNAUTHORIZED)
+            }
+            reqwest::StatusCode::OK => {
+                let verified = response
+                    .json::<VerifiedToken>()
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+                // Only first-party logins and personal access tokens are accepted here, a token a
+                // user granted a third-party app doesn't log them in to this one
+                match verified.client_id {
+                    Some(_) => Err(StatusCode::UNAUTHORIZED),
+                    None => Ok(verified),
+                }
+            }
+            _ => Err(StatusCode::INTERNAL_SERVER_ERROR),
         }
-    };
+    }
+}
+
+// Lets only requests whose token grants the permission through to the routes it is applied to,
+// e.g. `.route_layer(RequirePermission("reports:read"))`. Others get 401 without a valid token
+// and 403 without the permission. auth-service guards its own routes with a layer of the same name.
+#[derive(Clone, Copy)]
+struct RequirePermission(&'static str);
+
+impl<S> Layer<S> for RequirePermission {
+    type Service = RequirePermissionService<S>;
 
-    match response.status() {
-        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::BAD_REQUEST => {
-            StatusCode::UNAUTHORIZED.into_response()
+    fn layer(&self, inner: S) -> Self::Service {
+        RequirePermissionService {
+            inner,
+            permission: self.0,
         }
-        reqwest::StatusCode::OK => Json(ProtectedRouteResponse {
-            img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
+    }
+}
+
+#[derive(Clone)]
+struct RequirePermissionService<S> {
+    inner: S,
+    permission: &'static str,
+}
+
+impl<S> Service<Request> for RequirePermissionService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // The service that was polled ready handles the request, its clone the next one
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let permission = self.permission;
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+
+            let verified = match VerifiedToken::from_request_parts(&mut parts, &()).await {
+                Ok(verified) => verified,
+                Err(status) => return Ok(status.into_response()),
+            };
+
+            if !verified.has_permission(permission) {
+                return Ok(StatusCode::FORBIDDEN.into_response());
+            }
+
+            inner.call(Request::from_parts(parts, body)).await
         })
-        .into_response(),
-        _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
 
 #[derive(Serialize)]
 pub struct ProtectedRouteResponse {
     pub img_url: String,
+    pub user_id: String,
+}
+
+#[derive(Serialize)]
+pub struct ReportsResponse {
+    pub reports: Vec<String>,
 }
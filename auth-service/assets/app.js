@@ -32,6 +32,20 @@ signupLoginLink.addEventListener("click", (e) => {
 
 // -----------------------------------------------------
 
+// Set when /authorize or the device page sent the user here to log in before going back.
+// Only those paths are followed, so the page can't be used to redirect elsewhere.
+const returnTo = new URLSearchParams(window.location.search).get("return_to");
+
+function loggedIn() {
+    if (returnTo !== null && (returnTo.startsWith("/authorize?") || returnTo.startsWith("/device?"))) {
+        window.location.assign(returnTo);
+    } else {
+        alert("You have successfully logged in.");
+    }
+}
+
+// -----------------------------------------------------
+
 const loginForm = document.getElementById("login-form");
 const loginButton = document.getElementById("login-form-submit");
 const loginErrAlter = document.getElementById("login-err-alert");
@@ -66,7 +80,7 @@ loginButton.addEventListener("click", (e) => {
             loginForm.email.value = "";
             loginForm.password.value = "";
             loginErrAlter.style.display = "none";
-            alert("You have successfully logged in.");
+            loggedIn();
         } else {
             response.json().then(data => {
                 let error_msg = data.error;
@@ -145,10 +159,22 @@ TwoFAButton.addEventListener("click", (e) => {
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
             TwoFAErrAlter.style.display = "none";
-            alert("You have successfully logged in.");
+            loggedIn();
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
+        } else if (response.status === 429) {
+            // The login attempt is used up, so start over from the login form
+            response.json().then(data => {
+                TwoFAForm.email.value = "";
+                TwoFAForm.email_code.value = "";
+                TwoFAForm.login_attempt_id.value = "";
+                TwoFAErrAlter.style.display = "none";
+                loginErrAlter.innerHTML = `<span><strong>Error: </strong>${data.error}</span>`;
+                loginErrAlter.style.display = "block";
+                loginSection.style.display = "block";
+                twoFASection.style.display = "none";
+            });
         } else {
             response.json().then(data => {
                 let error_msg = data.error;
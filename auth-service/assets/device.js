@@ -0,0 +1,61 @@
+const deviceSection = document.getElementById("device-section");
+const deviceDoneSection = document.getElementById("device-done-section");
+const deviceDoneTitle = document.getElementById("device-done-title");
+
+const deviceForm = document.getElementById("device-form");
+const deviceApproveButton = document.getElementById("device-form-approve");
+const deviceDenyButton = document.getElementById("device-form-deny");
+const deviceErrAlert = document.getElementById("device-err-alert");
+
+// Devices can link here with the code filled in
+const userCode = new URLSearchParams(window.location.search).get("user_code");
+if (userCode !== null) {
+    deviceForm.user_code.value = userCode;
+}
+
+function verifyDevice(approve) {
+    const userCode = deviceForm.user_code.value;
+
+    fetch('/device/verify', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ userCode, approve }),
+    }).then(response => {
+        if (response.ok) {
+            deviceErrAlert.style.display = "none";
+            deviceDoneTitle.textContent = approve ? "Device connected" : "Device denied";
+            deviceSection.style.display = "none";
+            deviceDoneSection.style.display = "block";
+            return;
+        }
+
+        response.json().then(data => {
+            // Users log in first, and come back here with the code they entered
+            if (response.status === 401 || data.error === "Missing auth token") {
+                const returnTo = `/device?${new URLSearchParams({ user_code: userCode })}`;
+                window.location.assign(`/?${new URLSearchParams({ return_to: returnTo })}`);
+                return;
+            }
+
+            let error_msg = data.error;
+            if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                deviceErrAlert.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                deviceErrAlert.style.display = "block";
+            } else {
+                deviceErrAlert.style.display = "none";
+            }
+        });
+    });
+}
+
+deviceApproveButton.addEventListener("click", (e) => {
+    e.preventDefault();
+    verifyDevice(true);
+});
+
+deviceDenyButton.addEventListener("click", (e) => {
+    e.preventDefault();
+    verifyDevice(false);
+});
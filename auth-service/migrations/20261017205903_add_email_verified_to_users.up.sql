@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Users that signed up before verification existed keep being able to log in
+ALTER TABLE users ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE users ALTER COLUMN email_verified SET DEFAULT FALSE;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- TOTP secrets are stored encrypted with TOTP_ENCRYPTION_KEY
+ALTER TABLE users ADD COLUMN two_fa_method TEXT NOT NULL DEFAULT 'email';
+ALTER TABLE users ADD COLUMN totp_secret BYTEA;
+ALTER TABLE users ADD COLUMN pending_totp_secret BYTEA;
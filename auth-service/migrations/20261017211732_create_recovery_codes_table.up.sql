@@ -0,0 +1,7 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS recovery_codes(
+   id SERIAL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   code_hash TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS recovery_codes_email_idx ON recovery_codes(email);
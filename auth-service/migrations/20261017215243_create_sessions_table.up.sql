@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS sessions(
+   id TEXT PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   created_at TIMESTAMPTZ NOT NULL,
+   last_seen_at TIMESTAMPTZ NOT NULL,
+   user_agent TEXT,
+   ip_address TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS sessions_email_idx ON sessions(email);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS users_purge_at_idx;
+ALTER TABLE users DROP COLUMN IF EXISTS purge_at;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Users scheduled for deletion are hidden until they are purged at this time
+ALTER TABLE users ADD COLUMN purge_at TIMESTAMPTZ;
+CREATE INDEX IF NOT EXISTS users_purge_at_idx ON users(purge_at) WHERE purge_at IS NOT NULL;
@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- Existing users get a random ID. The email address stays unique, so the tables that
+-- refer to users by email keep working and follow address changes through ON UPDATE CASCADE.
+ALTER TABLE users ADD COLUMN id UUID NOT NULL DEFAULT gen_random_uuid();
+ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
+
+ALTER TABLE recovery_codes DROP CONSTRAINT recovery_codes_email_fkey;
+ALTER TABLE sessions DROP CONSTRAINT sessions_email_fkey;
+
+ALTER TABLE users DROP CONSTRAINT users_pkey;
+ALTER TABLE users ADD PRIMARY KEY (id);
+
+ALTER TABLE recovery_codes ADD CONSTRAINT recovery_codes_email_fkey
+   FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE sessions ADD CONSTRAINT sessions_email_fkey
+   FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS oauth_clients(
+   id TEXT PRIMARY KEY,
+   name TEXT NOT NULL,
+   secret_hash TEXT,
+   redirect_uris TEXT[] NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
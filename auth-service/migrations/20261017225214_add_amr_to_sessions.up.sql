@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Sessions started before this only ever checked the password
+ALTER TABLE sessions ADD COLUMN amr TEXT[] NOT NULL DEFAULT '{pwd}';
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Scopes machine clients may request for themselves with the client credentials grant
+ALTER TABLE oauth_clients ADD COLUMN scopes TEXT[] NOT NULL DEFAULT '{}';
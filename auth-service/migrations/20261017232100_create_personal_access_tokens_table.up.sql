@@ -0,0 +1,13 @@
+-- Add up migration script here
+-- Tokens belong to the user for good, so they refer to the ID rather than the email address
+CREATE TABLE IF NOT EXISTS personal_access_tokens(
+   id TEXT PRIMARY KEY,
+   user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+   name TEXT NOT NULL,
+   token_hash TEXT NOT NULL UNIQUE,
+   scopes TEXT[] NOT NULL DEFAULT '{}',
+   created_at TIMESTAMPTZ NOT NULL,
+   last_used_at TIMESTAMPTZ,
+   expires_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS personal_access_tokens_user_id_idx ON personal_access_tokens(user_id);
@@ -0,0 +1,18 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS roles(
+   name TEXT PRIMARY KEY
+);
+CREATE TABLE IF NOT EXISTS permissions(
+   name TEXT PRIMARY KEY
+);
+CREATE TABLE IF NOT EXISTS role_permissions(
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   permission TEXT NOT NULL REFERENCES permissions(name) ON DELETE CASCADE,
+   PRIMARY KEY (role, permission)
+);
+-- Roles belong to the user for good, so they refer to the ID rather than the email address
+CREATE TABLE IF NOT EXISTS user_roles(
+   user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   PRIMARY KEY (user_id, role)
+);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Time step of the last accepted authenticator app code, so no code is accepted twice
+ALTER TABLE users ADD COLUMN totp_time_step BIGINT;
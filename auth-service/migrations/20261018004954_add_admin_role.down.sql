@@ -0,0 +1,3 @@
+-- Add down migration script here
+DELETE FROM roles WHERE name = 'admin';
+DELETE FROM permissions WHERE name = 'roles:write';
@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- Users with this role manage the roles of everyone else. The first one is assigned by hand:
+-- INSERT INTO user_roles (user_id, role) SELECT id, 'admin' FROM users WHERE email = '...';
+INSERT INTO roles (name) VALUES ('admin') ON CONFLICT (name) DO NOTHING;
+INSERT INTO permissions (name) VALUES ('roles:write') ON CONFLICT (name) DO NOTHING;
+INSERT INTO role_permissions (role, permission) VALUES ('admin', 'roles:write')
+   ON CONFLICT (role, permission) DO NOTHING;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- The admin endpoints that took the admin API token are granted through the admin role instead
+INSERT INTO permissions (name) VALUES ('clients:write'), ('accounts:unlock')
+   ON CONFLICT (name) DO NOTHING;
+INSERT INTO role_permissions (role, permission)
+   VALUES ('admin', 'clients:write'), ('admin', 'accounts:unlock')
+   ON CONFLICT (role, permission) DO NOTHING;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+-- Signing keys shared by every instance, so they all verify what any of them signed
+CREATE TABLE IF NOT EXISTS signing_keys(
+   kid TEXT PRIMARY KEY,
+   algorithm TEXT NOT NULL,
+   -- PKCS#8 document or HMAC secret, encrypted with SIGNING_KEY_ENCRYPTION_KEY
+   private_key BYTEA NOT NULL,
+   activated_at TIMESTAMPTZ NOT NULL,
+   retired_at TIMESTAMPTZ
+);
@@ -0,0 +1,6 @@
+-- Add up migration script here
+INSERT INTO permissions (name) VALUES ('signing_keys:rotate')
+   ON CONFLICT (name) DO NOTHING;
+INSERT INTO role_permissions (role, permission)
+   VALUES ('admin', 'signing_keys:rotate')
+   ON CONFLICT (role, permission) DO NOTHING;
@@ -1,12 +1,31 @@
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 use tokio::sync::RwLock;
 
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::{
+    domain::{
+        AuthorizationCodeStore, BannedTokenStore, ClientCredentials, DeviceCodeStore, EmailClient,
+        JwtConfig, Keyring, LoginAttemptStore, OAuthClientStore, PasswordResetTokenStore,
+        PersonalAccessTokenStore, RefreshTokenStore, RoleStore, SessionStore, SigningKeyStore,
+        TwoFACodeStore, UserStore,
+    },
+    utils::authenticated_user::AuthTokenPrecedence,
+};
 
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+pub type PasswordResetTokenStoreType = Arc<RwLock<dyn PasswordResetTokenStore + Send + Sync>>;
+pub type LoginAttemptStoreType = Arc<RwLock<dyn LoginAttemptStore + Send + Sync>>;
+pub type SessionStoreType = Arc<RwLock<dyn SessionStore + Send + Sync>>;
+pub type OAuthClientStoreType = Arc<RwLock<dyn OAuthClientStore + Send + Sync>>;
+pub type AuthorizationCodeStoreType = Arc<RwLock<dyn AuthorizationCodeStore + Send + Sync>>;
+pub type DeviceCodeStoreType = Arc<RwLock<dyn DeviceCodeStore + Send + Sync>>;
+pub type PersonalAccessTokenStoreType = Arc<RwLock<dyn PersonalAccessTokenStore + Send + Sync>>;
+pub type RoleStoreType = Arc<RwLock<dyn RoleStore + Send + Sync>>;
+pub type SigningKeyStoreType = Arc<RwLock<dyn SigningKeyStore + Send + Sync>>;
 pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
+pub type KeyringType = Arc<RwLock<Keyring>>;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -14,20 +33,78 @@ pub struct AppState {
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
     pub email_client: EmailClientType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub login_attempt_store: LoginAttemptStoreType,
+    pub session_store: SessionStoreType,
+    pub keyring: KeyringType,
+    // Where the keyring is shared with the other instances, see tasks::reload_keyring
+    pub signing_key_store: SigningKeyStoreType,
+    // How long deleted accounts are kept before they are purged, None deletes them right away
+    pub account_deletion_grace_period_seconds: Option<u64>,
+    pub jwt_config: JwtConfig,
+    // Services allowed to introspect and revoke tokens, see routes::introspect and routes::revoke
+    pub oauth_clients: Vec<ClientCredentials>,
+    // Applications users log in to through /authorize and /token
+    pub oauth_client_store: OAuthClientStoreType,
+    pub authorization_code_store: AuthorizationCodeStoreType,
+    // Devices waiting for their users to approve them, see routes::device_authorization
+    pub device_code_store: DeviceCodeStoreType,
+    // Tokens users create for their scripts, see routes::personal_access_tokens
+    pub personal_access_token_store: PersonalAccessTokenStoreType,
+    // Whether the Authorization header or the auth cookie wins when a request sends both
+    pub auth_token_precedence: AuthTokenPrecedence,
+    // Roles of users, whose permissions go into their JWT auth tokens
+    pub role_store: RoleStoreType,
+    // Proxies whose X-Forwarded-For header is trusted, see utils::client_info
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
         two_fa_code_store: TwoFACodeStoreType,
         email_client: EmailClientType,
+        refresh_token_store: RefreshTokenStoreType,
+        password_reset_token_store: PasswordResetTokenStoreType,
+        login_attempt_store: LoginAttemptStoreType,
+        session_store: SessionStoreType,
+        keyring: KeyringType,
+        signing_key_store: SigningKeyStoreType,
+        account_deletion_grace_period_seconds: Option<u64>,
+        jwt_config: JwtConfig,
+        oauth_clients: Vec<ClientCredentials>,
+        oauth_client_store: OAuthClientStoreType,
+        authorization_code_store: AuthorizationCodeStoreType,
+        device_code_store: DeviceCodeStoreType,
+        personal_access_token_store: PersonalAccessTokenStoreType,
+        auth_token_precedence: AuthTokenPrecedence,
+        role_store: RoleStoreType,
+        trusted_proxies: Vec<IpAddr>,
     ) -> Self {
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
             email_client,
+            refresh_token_store,
+            password_reset_token_store,
+            login_attempt_store,
+            session_store,
+            keyring,
+            signing_key_store,
+            account_deletion_grace_period_seconds,
+            jwt_config,
+            oauth_clients,
+            oauth_client_store,
+            authorization_code_store,
+            device_code_store,
+            personal_access_token_store,
+            auth_token_precedence,
+            role_store,
+            trusted_proxies,
         }
     }
 }
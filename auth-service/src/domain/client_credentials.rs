@@ -0,0 +1,64 @@
+use color_eyre::eyre::{eyre, Result};
+use ring::digest;
+use secrecy::{ExposeSecret, Secret};
+
+// Credentials a service, like the API gateway in front of a resource server, authenticates
+// with using HTTP Basic auth
+#[derive(Debug, Clone)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: Secret<String>,
+}
+
+impl ClientCredentials {
+    // Credentials in the form `client_id:client_secret`
+    pub fn parse(value: &str) -> Result<Self> {
+        let (client_id, client_secret) = value
+            .split_once(':')
+            .ok_or(eyre!("client credentials must be in the form id:secret"))?;
+
+        if client_id.is_empty() || client_secret.is_empty() {
+            return Err(eyre!("client id and secret must not be empty"));
+        }
+
+        Ok(Self {
+            client_id: client_id.to_owned(),
+            client_secret: Secret::new(client_secret.to_owned()),
+        })
+    }
+
+    pub fn matches(&self, client_id: &str, client_secret: &str) -> bool {
+        // Compare digests so the comparison time does not depend on how much of the secret matches
+        let expected = digest::digest(
+            &digest::SHA256,
+            self.client_secret.expose_secret().as_bytes(),
+        );
+        let actual = digest::digest(&digest::SHA256, client_secret.as_bytes());
+
+        self.client_id == client_id && expected.as_ref() == actual.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let credentials = ClientCredentials::parse("gateway:s3cr:et").unwrap();
+        assert_eq!(credentials.client_id, "gateway");
+        assert_eq!(credentials.client_secret.expose_secret(), "s3cr:et");
+
+        for value in ["gateway", "gateway:", ":secret", ""] {
+            assert!(ClientCredentials::parse(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let credentials = ClientCredentials::parse("gateway:secret").unwrap();
+        assert!(credentials.matches("gateway", "secret"));
+        assert!(!credentials.matches("gateway", "secret2"));
+        assert!(!credentials.matches("other", "secret"));
+    }
+}
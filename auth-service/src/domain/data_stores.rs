@@ -1,10 +1,15 @@
-use crate::domain::{Email, Password};
+use crate::domain::{
+    CodeChallenge, Email, KeyringEntry, OAuthClient, Password, PersonalAccessToken, Role,
+    SigningKey, TotpSecret, UserRoles,
+};
 
-use super::User;
+use super::{verify_password_hash, AuthenticationMethod, User, UserId};
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::{eyre, Context, Report, Result};
-use rand::Rng;
+use rand::{distributions::Alphanumeric, Rng};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -12,8 +17,81 @@ use uuid::Uuid;
 pub trait UserStore {
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password)
         -> Result<(), UserStoreError>;
+    async fn verify_email(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    // Moves the user and everything stored with them to the new, verified address.
+    // UserAlreadyExists if the new address is taken. Only changes it while the token version
+    // is still the given one and increments it, so the same change can't be confirmed twice.
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+        token_version: i32,
+    ) -> Result<(), UserStoreError>;
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError>;
+    // Every JWT and refresh token carries the version it was issued with;
+    // incrementing it revokes all of them at once.
+    async fn get_token_version(&self, email: &Email) -> Result<i32, UserStoreError>;
+    async fn increment_token_version(&mut self, email: &Email) -> Result<i32, UserStoreError>;
+    // Authenticator app secret handed out during enrollment, until the user confirms it works
+    async fn set_pending_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError>;
+    async fn get_pending_totp_secret(
+        &self,
+        email: &Email,
+    ) -> Result<Option<TotpSecret>, UserStoreError>;
+    // Switch the user to authenticator app 2FA and discard the pending secret. The time step
+    // of the code that confirmed the secret counts as used.
+    async fn enable_totp(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+        time_step: u64,
+    ) -> Result<(), UserStoreError>;
+    async fn get_totp_secret(&self, email: &Email) -> Result<Option<TotpSecret>, UserStoreError>;
+    // Records the time step of an accepted authenticator app code. InvalidCredentials unless it
+    // is later than the last one, so every code can only be used once.
+    async fn use_totp_time_step(
+        &mut self,
+        email: &Email,
+        time_step: u64,
+    ) -> Result<(), UserStoreError>;
+    // Replaces all recovery codes of the user
+    async fn set_recovery_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), UserStoreError>;
+    // Hashes of the unused recovery codes of the user. Checking a code against them is slow,
+    // so it's done with StoredRecoveryCode::matches once the store is released.
+    async fn get_recovery_codes(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<StoredRecoveryCode>, UserStoreError>;
+    // Removes the code, so each one can only be used once. InvalidCredentials if it was
+    // removed in the meantime.
+    async fn remove_recovery_code(&mut self, email: &Email, id: i32) -> Result<(), UserStoreError>;
+    async fn count_recovery_codes(&self, email: &Email) -> Result<usize, UserStoreError>;
+    // Removes the user along with their recovery codes and sessions
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    // Hides the user as if they were deleted, until purge_deleted_users removes them once
+    // `purge_at` (Unix timestamp in seconds) has passed
+    async fn schedule_deletion(
+        &mut self,
+        email: &Email,
+        purge_at: i64,
+    ) -> Result<(), UserStoreError>;
+    // Removes the users whose deletion is due and returns their emails
+    async fn purge_deleted_users(&mut self) -> Result<Vec<Email>, UserStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -65,12 +143,37 @@ pub trait TwoFACodeStore {
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    // Removes the code and returns it, so that of concurrent requests with the same code only
+    // one gets it
+    async fn take_code(
+        &mut self,
+        email: &Email,
+    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    // Counts an attempt at a 2FA code. It has to be called before the code is checked, so
+    // concurrent guesses can't get past the limit. Returns the number of attempts left, or
+    // TooManyAttempts once they are used up. The count is kept for as long as a code, so
+    // starting a new login attempt or enrollment doesn't reset it.
+    async fn record_attempt(&mut self, key: &TwoFAAttemptKey) -> Result<u32, TwoFACodeStoreError>;
+}
+
+// Number of codes that can be tried per key, e.g. per login attempt before the user has to
+// log in again
+pub const MAX_2FA_ATTEMPTS: u32 = 5;
+
+// What attempts at 2FA codes are counted against
+#[derive(Debug, Clone)]
+pub enum TwoFAAttemptKey {
+    Login(LoginAttemptId),
+    // Codes checked to confirm a new authenticator app, see confirm_totp
+    TotpEnrollment(Email),
 }
 
 #[derive(Debug, Error)]
 pub enum TwoFACodeStoreError {
     #[error("Login attempt ID not found")]
     LoginAttemptIdNotFound,
+    #[error("Too many failed attempts")]
+    TooManyAttempts,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -80,6 +183,391 @@ impl PartialEq for TwoFACodeStoreError {
         matches!(
             (self, other),
             (Self::LoginAttemptIdNotFound, Self::LoginAttemptIdNotFound)
+                | (Self::TooManyAttempts, Self::TooManyAttempts)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError>;
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError>;
+    async fn get_family_head(
+        &self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<RefreshToken, RefreshTokenStoreError>;
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RefreshTokenStoreError {
+    #[error("Refresh token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RefreshTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PasswordResetTokenStore {
+    // Replaces any earlier token for the email, so only the latest reset email can be used
+    async fn add_token(
+        &mut self,
+        token: PasswordResetToken,
+        email: Email,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+    // Removes the token, so each one can only be used once
+    async fn take_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PasswordResetTokenStoreError {
+    #[error("Password reset token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasswordResetTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Clients registered for the authorization code flow
+#[async_trait::async_trait]
+pub trait OAuthClientStore {
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError>;
+    async fn get_client(&self, id: &str) -> Result<OAuthClient, OAuthClientStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum OAuthClientStoreError {
+    #[error("Client not found")]
+    ClientNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for OAuthClientStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ClientNotFound, Self::ClientNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait AuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError>;
+    // Removes the code, so each one can only be exchanged once
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum AuthorizationCodeStoreError {
+    #[error("Authorization code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for AuthorizationCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Device authorization requests (RFC 8628) waiting for a user to enter their user code
+#[async_trait::async_trait]
+pub trait DeviceCodeStore {
+    async fn add_code(
+        &mut self,
+        device_code: DeviceCode,
+        authorization: DeviceAuthorization,
+    ) -> Result<(), DeviceCodeStoreError>;
+    // Records the user's decision. Each user code can only be entered once.
+    async fn resolve(
+        &mut self,
+        user_code: &UserCode,
+        status: DeviceAuthorizationStatus,
+    ) -> Result<(), DeviceCodeStoreError>;
+    // Leaves the request as it is, so polls with a device code of another client don't use it up
+    async fn get_code(
+        &self,
+        device_code: &DeviceCode,
+    ) -> Result<DeviceAuthorization, DeviceCodeStoreError>;
+    // Removes the resolved request, so the device only gets tokens once
+    async fn take_code(
+        &mut self,
+        device_code: &DeviceCode,
+    ) -> Result<DeviceAuthorization, DeviceCodeStoreError>;
+    // Records that the device polled just now. False if it polled less than
+    // DEVICE_CODE_POLL_INTERVAL_SECONDS ago.
+    async fn record_poll(&mut self, device_code: &DeviceCode)
+        -> Result<bool, DeviceCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum DeviceCodeStoreError {
+    #[error("Device code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for DeviceCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PersonalAccessTokenStore {
+    async fn add_token(
+        &mut self,
+        token: PersonalAccessToken,
+    ) -> Result<(), PersonalAccessTokenStoreError>;
+    // Expired tokens are returned too, they are kept for the user to see
+    async fn get_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<PersonalAccessToken, PersonalAccessTokenStoreError>;
+    // Tokens of the user, newest first
+    async fn get_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Vec<PersonalAccessToken>, PersonalAccessTokenStoreError>;
+    // Records that the token was used just now
+    async fn touch_token(&mut self, id: &str) -> Result<(), PersonalAccessTokenStoreError>;
+    // TokenNotFound unless the token belongs to the user
+    async fn remove_token(
+        &mut self,
+        user_id: &UserId,
+        id: &str,
+    ) -> Result<(), PersonalAccessTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PersonalAccessTokenStoreError {
+    #[error("Personal access token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PersonalAccessTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Roles users are assigned, which the permissions in their JWT auth tokens come from
+#[async_trait::async_trait]
+pub trait RoleStore {
+    // Creates the role, or replaces the permissions of an existing one
+    async fn set_role(&mut self, role: Role) -> Result<(), RoleStoreError>;
+    // RoleNotFound if there is no such role. Assigning a role the user has already does nothing.
+    async fn assign_role(&mut self, user_id: &UserId, role: &str) -> Result<(), RoleStoreError>;
+    // RoleNotFound unless the user has the role
+    async fn unassign_role(&mut self, user_id: &UserId, role: &str) -> Result<(), RoleStoreError>;
+    // Empty for users without roles
+    async fn get_user_roles(&self, user_id: &UserId) -> Result<UserRoles, RoleStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RoleStoreError {
+    #[error("Role not found")]
+    RoleNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RoleStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::RoleNotFound, Self::RoleNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Sessions of logged in users, so they can see where they are logged in and log out a device
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_session(&self, id: &RefreshTokenFamilyId) -> Result<Session, SessionStoreError>;
+    // Sessions of the user, most recently used first
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    // Records that the session was used just now
+    async fn touch_session(&mut self, id: &RefreshTokenFamilyId) -> Result<(), SessionStoreError>;
+    async fn remove_session(&mut self, id: &RefreshTokenFamilyId) -> Result<(), SessionStoreError>;
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SessionStoreError {
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for SessionStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SessionNotFound, Self::SessionNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Throttles logins, so guessing passwords is limited by more than the cost of hashing them
+#[async_trait::async_trait]
+pub trait LoginAttemptStore {
+    // Counts a login attempt within the sliding window of the key. Once the limit of the key
+    // is reached the attempt is not counted and RateLimited tells how long to wait.
+    async fn record_attempt(&mut self, key: &RateLimitKey) -> Result<(), LoginAttemptStoreError>;
+    // Returns AccountLocked while the account is locked
+    async fn check_lockout(&self, email: &Email) -> Result<(), LoginAttemptStoreError>;
+    // Counts a failed login and locks the account once there are MAX_FAILED_LOGINS in a row.
+    // Returns whether this failure locked the account.
+    async fn record_failure(&mut self, email: &Email) -> Result<bool, LoginAttemptStoreError>;
+    // Forgets the failed logins and lifts any lockout
+    async fn clear_failures(&mut self, email: &Email) -> Result<(), LoginAttemptStoreError>;
+}
+
+// Length of the sliding window login attempts are counted in
+pub const LOGIN_ATTEMPT_WINDOW_SECONDS: u64 = 900; // 15 minutes
+pub const MAX_LOGIN_ATTEMPTS_PER_ACCOUNT: u32 = 20;
+// Higher than per account, since many users can share an address behind a NAT
+pub const MAX_LOGIN_ATTEMPTS_PER_IP: u32 = 100;
+pub const MAX_FAILED_LOGINS: u32 = 5;
+pub const ACCOUNT_LOCKOUT_SECONDS: u64 = 900; // 15 minutes
+
+// What login attempts are counted against
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    Account(Email),
+    Ip(IpAddr),
+}
+
+impl RateLimitKey {
+    pub fn max_attempts(&self) -> u32 {
+        match self {
+            Self::Account(_) => MAX_LOGIN_ATTEMPTS_PER_ACCOUNT,
+            Self::Ip(_) => MAX_LOGIN_ATTEMPTS_PER_IP,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LoginAttemptStoreError {
+    #[error("Too many login attempts")]
+    RateLimited { retry_after_seconds: u64 },
+    #[error("Account locked")]
+    AccountLocked { retry_after_seconds: u64 },
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for LoginAttemptStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::RateLimited { .. }, Self::RateLimited { .. })
+                | (Self::AccountLocked { .. }, Self::AccountLocked { .. })
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+// Signing keys shared by every instance, so a rotation reaches all of them
+#[async_trait::async_trait]
+pub trait SigningKeyStore {
+    // Keys that are still in use or were retired after the given time
+    async fn get_keys(
+        &self,
+        retired_after: DateTime<Utc>,
+    ) -> Result<Vec<KeyringEntry>, SigningKeyStoreError>;
+    // Keeps a key that signed tokens before it was stored, e.g. one that was only configured
+    // through the environment. Keys already stored are left as they are.
+    async fn add_retired_key(
+        &mut self,
+        key: SigningKey,
+        retired_at: DateTime<Utc>,
+    ) -> Result<(), SigningKeyStoreError>;
+    // Makes `key` sign tokens from `activates_at` on, retiring the current key at that time
+    async fn rotate(
+        &mut self,
+        key: SigningKey,
+        activates_at: DateTime<Utc>,
+    ) -> Result<(), SigningKeyStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SigningKeyStoreError {
+    #[error("Key already exists")]
+    KeyAlreadyExists,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for SigningKeyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::KeyAlreadyExists, Self::KeyAlreadyExists)
                 | (Self::UnexpectedError(_), Self::UnexpectedError(_))
         )
     }
@@ -89,13 +577,11 @@ impl PartialEq for TwoFACodeStoreError {
 pub struct TwoFACode(Secret<String>);
 
 impl TwoFACode {
+    // Codes from authenticator apps can start with a zero, so any six digits are accepted
     pub fn parse(code: Secret<String>) -> Result<Self> {
-        let code_as_u32 = code
-            .expose_secret()
-            .parse::<u32>()
-            .wrap_err("Invalid 2FA code")?;
+        let value = code.expose_secret();
 
-        if (100_000..=999_999).contains(&code_as_u32) {
+        if value.len() == 6 && value.chars().all(|c| c.is_ascii_digit()) {
             Ok(Self(code))
         } else {
             Err(eyre!("Invalid 2FA code"))
@@ -168,6 +654,396 @@ impl AsRef<Secret<String>> for LoginAttemptId {
     }
 }
 
+// One-time code that stands in for a 2FA code when the user lost access to their
+// mailbox or authenticator app. Formatted as two groups of five characters, like abcde-12345.
+#[derive(Debug, Clone)]
+pub struct RecoveryCode(Secret<String>);
+
+impl RecoveryCode {
+    pub fn parse(code: Secret<String>) -> Result<Self> {
+        let code = code.expose_secret().trim().to_ascii_lowercase();
+
+        let is_valid = code.len() == 2 * RECOVERY_CODE_GROUP_LENGTH + 1
+            && code.char_indices().all(|(i, c)| match i {
+                RECOVERY_CODE_GROUP_LENGTH => c == '-',
+                _ => c.is_ascii_lowercase() || c.is_ascii_digit(),
+            });
+
+        if is_valid {
+            Ok(Self(Secret::new(code)))
+        } else {
+            Err(eyre!("Invalid recovery code"))
+        }
+    }
+
+    // A full set, as handed out on 2FA enrollment
+    pub fn generate_set() -> Vec<Self> {
+        (0..RECOVERY_CODE_COUNT).map(|_| Self::default()).collect()
+    }
+}
+
+// Recovery code as the store keeps it, hashed like a password
+#[derive(Debug, Clone)]
+pub struct StoredRecoveryCode {
+    pub id: i32,
+    pub code_hash: Secret<String>,
+}
+
+impl StoredRecoveryCode {
+    pub async fn matches(&self, code: &RecoveryCode) -> bool {
+        verify_password_hash(self.code_hash.clone(), code.as_ref().to_owned())
+            .await
+            .is_ok()
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        const CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+        let mut rng = rand::thread_rng();
+        let mut group = || -> String {
+            (0..RECOVERY_CODE_GROUP_LENGTH)
+                .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+                .collect()
+        };
+        Self(Secret::new(format!("{}-{}", group(), group())))
+    }
+}
+
+impl PartialEq for RecoveryCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl AsRef<Secret<String>> for RecoveryCode {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_GROUP_LENGTH: usize = 5;
+
+// Opaque refresh token handed out alongside the JWT cookie.
+// Only the server-side record gives it any meaning.
+#[derive(Debug, Clone)]
+pub struct RefreshToken(Secret<String>);
+
+impl RefreshToken {
+    pub fn parse(token: Secret<String>) -> Result<Self> {
+        if is_random_token(token.expose_secret()) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid refresh token"))
+        }
+    }
+}
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        Self(random_token())
+    }
+}
+
+impl PartialEq for RefreshToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl AsRef<Secret<String>> for RefreshToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+// Every login starts a new family; each rotation keeps the family and replaces its head.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RefreshTokenFamilyId(String);
+
+impl RefreshTokenFamilyId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = Uuid::parse_str(&id).wrap_err("Invalid refresh token family Id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for RefreshTokenFamilyId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for RefreshTokenFamilyId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshTokenRecord {
+    pub email: Email,
+    pub family_id: RefreshTokenFamilyId,
+    // Token version of the user when the family was started
+    pub token_version: i32,
+    // Set when the family was started for a client through /token rather than by logging in
+    pub client_grant: Option<ClientGrant>,
+}
+
+impl RefreshTokenRecord {
+    pub fn new(email: Email, family_id: RefreshTokenFamilyId, token_version: i32) -> Self {
+        Self {
+            email,
+            family_id,
+            token_version,
+            client_grant: None,
+        }
+    }
+}
+
+// What a user allowed an OAuth client, carried by the tokens issued to it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientGrant {
+    pub client_id: String,
+    pub scope: Option<String>,
+}
+
+impl ClientGrant {
+    // Whether the space separated scope includes `scope`
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .as_deref()
+            .is_some_and(|granted| granted.split_whitespace().any(|granted| granted == scope))
+    }
+}
+
+// Single-use code handed to a client through its redirect URI by /authorize, to be exchanged
+// for tokens at /token
+#[derive(Debug, Clone)]
+pub struct AuthorizationCode(Secret<String>);
+
+impl AuthorizationCode {
+    pub fn parse(code: Secret<String>) -> Result<Self> {
+        if is_random_token(code.expose_secret()) {
+            Ok(Self(code))
+        } else {
+            Err(eyre!("Invalid authorization code"))
+        }
+    }
+}
+
+impl Default for AuthorizationCode {
+    fn default() -> Self {
+        Self(random_token())
+    }
+}
+
+impl PartialEq for AuthorizationCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl AsRef<Secret<String>> for AuthorizationCode {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+// Everything /authorize was asked for, checked again when the code is exchanged
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorizationGrant {
+    pub client_grant: ClientGrant,
+    pub redirect_uri: String,
+    pub code_challenge: CodeChallenge,
+    pub user_id: UserId,
+    // Device the user logged in from, recorded with the session the code starts
+    pub user_agent: Option<String>,
+    pub ip_address: IpAddr,
+    // Passed on to the ID token, see OpenID Connect Core section 2
+    pub nonce: Option<String>,
+    // When and how the user logged in, taken from the session they authorized the client in
+    pub auth_time: i64,
+    pub amr: Vec<AuthenticationMethod>,
+}
+
+// Code a device polls /token with until its user approved it, see RFC 8628 section 3.2
+#[derive(Debug, Clone)]
+pub struct DeviceCode(Secret<String>);
+
+impl DeviceCode {
+    pub fn parse(code: Secret<String>) -> Result<Self> {
+        if is_random_token(code.expose_secret()) {
+            Ok(Self(code))
+        } else {
+            Err(eyre!("Invalid device code"))
+        }
+    }
+}
+
+impl Default for DeviceCode {
+    fn default() -> Self {
+        Self(random_token())
+    }
+}
+
+impl PartialEq for DeviceCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl AsRef<Secret<String>> for DeviceCode {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+// Code the user types in on the device page. Consonants only, so it can't spell words and
+// is easy to type on a phone, see RFC 8628 section 6.1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserCode(String);
+
+impl UserCode {
+    // Case, dashes and spaces don't matter, users may type the code any way they like
+    pub fn parse(code: &str) -> Result<Self> {
+        let code: String = code
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+
+        if code.len() == USER_CODE_LENGTH && code.chars().all(|c| USER_CODE_CHARSET.contains(c)) {
+            Ok(Self(code))
+        } else {
+            Err(eyre!("Invalid user code"))
+        }
+    }
+
+    // The code as shown to the user, e.g. WDJB-MJHT
+    pub fn formatted(&self) -> String {
+        let (first, second) = self.0.split_at(USER_CODE_LENGTH / 2);
+        format!("{}-{}", first, second)
+    }
+}
+
+impl Default for UserCode {
+    fn default() -> Self {
+        let charset = USER_CODE_CHARSET.as_bytes();
+        let mut rng = rand::thread_rng();
+        let code = (0..USER_CODE_LENGTH)
+            .map(|_| charset[rng.gen_range(0..charset.len())] as char)
+            .collect();
+        Self(code)
+    }
+}
+
+impl AsRef<str> for UserCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+const USER_CODE_LENGTH: usize = 8;
+const USER_CODE_CHARSET: &str = "BCDFGHJKLMNPQRSTVWXZ";
+
+// What a device asked for at /device/code, and what its user made of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceAuthorization {
+    pub client_grant: ClientGrant,
+    pub user_code: UserCode,
+    // The device itself, recorded with the session it starts
+    pub user_agent: Option<String>,
+    pub ip_address: IpAddr,
+    pub status: DeviceAuthorizationStatus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceAuthorizationStatus {
+    Pending,
+    Approved(UserId),
+    Denied,
+}
+
+// A login on one device. Its ID is that of the refresh token family started at login,
+// and the `sid` of every JWT issued for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: RefreshTokenFamilyId,
+    pub email: Email,
+    // Unix timestamps in seconds
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    pub user_agent: Option<String>,
+    pub ip_address: IpAddr,
+    // How the user logged in
+    pub amr: Vec<AuthenticationMethod>,
+}
+
+impl Session {
+    pub fn new(email: Email, user_agent: Option<String>, ip_address: IpAddr) -> Self {
+        let now = Utc::now().timestamp();
+
+        Self {
+            id: RefreshTokenFamilyId::default(),
+            email,
+            created_at: now,
+            last_seen_at: now,
+            user_agent,
+            ip_address,
+            amr: vec![AuthenticationMethod::Password],
+        }
+    }
+}
+
+// Single-use token emailed to a user who forgot their password
+#[derive(Debug, Clone)]
+pub struct PasswordResetToken(Secret<String>);
+
+impl PasswordResetToken {
+    pub fn parse(token: Secret<String>) -> Result<Self> {
+        if is_random_token(token.expose_secret()) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid password reset token"))
+        }
+    }
+}
+
+impl Default for PasswordResetToken {
+    fn default() -> Self {
+        Self(random_token())
+    }
+}
+
+impl PartialEq for PasswordResetToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl AsRef<Secret<String>> for PasswordResetToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+const RANDOM_TOKEN_LENGTH: usize = 64;
+
+pub(crate) fn random_token() -> Secret<String> {
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(RANDOM_TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+    Secret::new(token)
+}
+
+fn is_random_token(value: &str) -> bool {
+    value.len() == RANDOM_TOKEN_LENGTH && value.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
 #[cfg(test)]
 mod tests {
     use secrecy::Secret;
@@ -198,6 +1074,12 @@ mod tests {
         assert!(TwoFACode::parse(code).is_err());
     }
 
+    #[test]
+    fn code_with_leading_zero_is_accepted() {
+        let code = Secret::new("012345".to_string());
+        assert!(TwoFACode::parse(code).is_ok());
+    }
+
     use super::LoginAttemptId;
 
     #[test]
@@ -217,4 +1099,136 @@ mod tests {
         let id = Secret::new(uuid::Uuid::new_v4().to_string());
         assert!(LoginAttemptId::parse(id).is_ok());
     }
+
+    use super::{RefreshToken, RefreshTokenFamilyId};
+
+    #[test]
+    fn generated_refresh_token_is_accepted() {
+        let token = RefreshToken::default();
+        assert!(RefreshToken::parse(token.as_ref().clone()).is_ok());
+    }
+
+    #[test]
+    fn generated_refresh_tokens_are_unique() {
+        assert_ne!(RefreshToken::default(), RefreshToken::default());
+    }
+
+    #[test]
+    fn short_refresh_token_is_rejected() {
+        let token = Secret::new("abc123".to_string());
+        assert!(RefreshToken::parse(token).is_err());
+    }
+
+    #[test]
+    fn non_alphanumeric_refresh_token_is_rejected() {
+        let token = Secret::new(format!("{}-", "a".repeat(63)));
+        assert!(RefreshToken::parse(token).is_err());
+    }
+
+    use super::PasswordResetToken;
+
+    #[test]
+    fn generated_password_reset_token_is_accepted() {
+        let token = PasswordResetToken::default();
+        assert!(PasswordResetToken::parse(token.as_ref().clone()).is_ok());
+    }
+
+    #[test]
+    fn invalid_password_reset_token_is_rejected() {
+        let token = Secret::new("not a token".to_string());
+        assert!(PasswordResetToken::parse(token).is_err());
+    }
+
+    use super::{RecoveryCode, RECOVERY_CODE_COUNT};
+    use secrecy::ExposeSecret;
+
+    #[test]
+    fn generated_recovery_codes_are_accepted() {
+        let codes = RecoveryCode::generate_set();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+
+        for code in codes {
+            assert_eq!(RecoveryCode::parse(code.as_ref().clone()).unwrap(), code);
+        }
+    }
+
+    #[test]
+    fn recovery_code_is_normalized() {
+        let code = RecoveryCode::parse(Secret::new(" ABCDE-12345 ".to_string())).unwrap();
+        assert_eq!(code.as_ref().expose_secret(), "abcde-12345");
+    }
+
+    #[test]
+    fn invalid_recovery_code_is_rejected() {
+        for code in [
+            "",
+            "abcde12345",
+            "abcde-1234",
+            "abcde_12345",
+            "abcd-e12345",
+            "123456",
+        ] {
+            assert!(RecoveryCode::parse(Secret::new(code.to_string())).is_err());
+        }
+    }
+
+    use super::AuthorizationCode;
+
+    #[test]
+    fn generated_authorization_code_is_accepted() {
+        let code = AuthorizationCode::default();
+        assert!(AuthorizationCode::parse(code.as_ref().clone()).is_ok());
+    }
+
+    #[test]
+    fn invalid_authorization_code_is_rejected() {
+        let code = Secret::new("not a code".to_string());
+        assert!(AuthorizationCode::parse(code).is_err());
+    }
+
+    use super::ClientGrant;
+
+    #[test]
+    fn client_grant_has_scope() {
+        let grant = ClientGrant {
+            client_id: "client".to_string(),
+            scope: Some("openid  email".to_string()),
+        };
+        assert!(grant.has_scope("openid"));
+        assert!(grant.has_scope("email"));
+        assert!(!grant.has_scope("open"));
+
+        let grant = ClientGrant {
+            scope: None,
+            ..grant
+        };
+        assert!(!grant.has_scope("openid"));
+    }
+
+    use super::UserCode;
+
+    #[test]
+    fn generated_user_code_is_accepted() {
+        let code = UserCode::default();
+        assert_eq!(UserCode::parse(&code.formatted()).unwrap(), code);
+    }
+
+    #[test]
+    fn user_code_is_normalized() {
+        let code = UserCode::parse(" wdjb-mjht ").unwrap();
+        assert_eq!(code.as_ref(), "WDJBMJHT");
+        assert_eq!(code.formatted(), "WDJB-MJHT");
+    }
+
+    #[test]
+    fn invalid_user_code_is_rejected() {
+        for code in ["", "WDJB-MJH", "WDJB-MJHTX", "WDJA-MJHT", "WDJB-MJH1"] {
+            assert!(UserCode::parse(code).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn invalid_family_id_is_rejected() {
+        assert!(RefreshTokenFamilyId::parse("invalid-uuid".to_string()).is_err());
+    }
 }
@@ -13,6 +13,99 @@ pub enum AuthAPIError {
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Email not verified")]
+    EmailNotVerified,
+    #[error("Invalid signing key")]
+    InvalidSigningKey,
+    #[error("2FA not enabled")]
+    TwoFANotEnabled,
+    #[error("Too many 2FA attempts")]
+    TwoFAAttemptsExceeded,
+    #[error("Too many TOTP confirmation attempts")]
+    TotpAttemptsExceeded,
+    #[error("Too many login attempts")]
+    TooManyLoginAttempts { retry_after_seconds: u64 },
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Invalid client")]
+    InvalidClient,
+    #[error("Invalid user code")]
+    InvalidUserCode,
+    #[error("Personal access token not found")]
+    PersonalAccessTokenNotFound,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Role not found")]
+    RoleNotFound,
+    #[error("Missing permission")]
+    MissingPermission,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
+
+// Errors of the OAuth 2.0 endpoints, which clients expect in the format of RFC 6749
+#[derive(Debug, Error)]
+pub enum OAuthError {
+    #[error("Invalid request: {0}")]
+    InvalidRequest(&'static str),
+    #[error("Invalid client")]
+    InvalidClient,
+    #[error("Invalid grant: {0}")]
+    InvalidGrant(&'static str),
+    #[error("Unauthorized client")]
+    UnauthorizedClient,
+    #[error("Invalid scope")]
+    InvalidScope,
+    #[error("Unsupported grant type")]
+    UnsupportedGrantType,
+    #[error("Unsupported response type")]
+    UnsupportedResponseType,
+    // Device authorization grant errors, see RFC 8628 section 3.5
+    #[error("Authorization pending")]
+    AuthorizationPending,
+    #[error("Slow down")]
+    SlowDown,
+    #[error("Access denied")]
+    AccessDenied,
+    #[error("Expired token")]
+    ExpiredToken,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl OAuthError {
+    // Value of the `error` parameter
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::InvalidClient => "invalid_client",
+            Self::InvalidGrant(_) => "invalid_grant",
+            Self::UnauthorizedClient => "unauthorized_client",
+            Self::InvalidScope => "invalid_scope",
+            Self::UnsupportedGrantType => "unsupported_grant_type",
+            Self::UnsupportedResponseType => "unsupported_response_type",
+            Self::AuthorizationPending => "authorization_pending",
+            Self::SlowDown => "slow_down",
+            Self::AccessDenied => "access_denied",
+            Self::ExpiredToken => "expired_token",
+            Self::UnexpectedError(_) => "server_error",
+        }
+    }
+
+    // Value of the `error_description` parameter, meant for the developer of the client
+    pub fn description(&self) -> Option<&'static str> {
+        match self {
+            Self::InvalidRequest(description) | Self::InvalidGrant(description) => {
+                Some(description)
+            }
+            Self::InvalidClient => Some("Invalid client credentials"),
+            Self::UnauthorizedClient => Some("The client is not allowed to use this grant type"),
+            Self::InvalidScope => Some("The client is not allowed this scope"),
+            Self::AuthorizationPending => Some("The user has not approved the device yet"),
+            Self::SlowDown => Some("Polling too often, wait longer between requests"),
+            Self::AccessDenied => Some("The user denied the device"),
+            Self::ExpiredToken => Some("The device code has expired, start over"),
+            _ => None,
+        }
+    }
+}
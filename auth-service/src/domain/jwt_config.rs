@@ -0,0 +1,51 @@
+use color_eyre::eyre::{eyre, Result};
+
+// What JWT auth tokens are issued for, and how strictly they are checked
+#[derive(Debug, Clone)]
+pub struct JwtConfig {
+    // Put in the `iss` claim, tokens from any other issuer are rejected
+    pub issuer: String,
+    // Put in the `aud` claim, tokens are accepted when they name at least one of them
+    pub audiences: Vec<String>,
+    // Clock skew tolerated when checking `exp`, `nbf` and `iat`
+    pub leeway_seconds: u64,
+    // Whether tokens carry the email address of the user next to their id
+    pub email_claim: bool,
+}
+
+impl JwtConfig {
+    pub fn new(
+        issuer: String,
+        audiences: Vec<String>,
+        leeway_seconds: u64,
+        email_claim: bool,
+    ) -> Result<Self> {
+        if issuer.is_empty() {
+            return Err(eyre!("JWT issuer must not be empty"));
+        }
+
+        if audiences.is_empty() || audiences.iter().any(String::is_empty) {
+            return Err(eyre!("JWT audiences must not be empty"));
+        }
+
+        Ok(Self {
+            issuer,
+            audiences,
+            leeway_seconds,
+            email_claim,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_rejects_empty_values() {
+        assert!(JwtConfig::new("auth".to_owned(), vec!["app".to_owned()], 60, false).is_ok());
+        assert!(JwtConfig::new(String::new(), vec!["app".to_owned()], 60, false).is_err());
+        assert!(JwtConfig::new("auth".to_owned(), vec![], 60, false).is_err());
+        assert!(JwtConfig::new("auth".to_owned(), vec![String::new()], 60, false).is_err());
+    }
+}
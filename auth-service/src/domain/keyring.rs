@@ -0,0 +1,245 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::{eyre, Result};
+
+use super::SigningKey;
+
+// The active key signs new tokens. Keys it replaced are kept for verification
+// until every token they signed has expired, so a rotation logs nobody out.
+pub struct Keyring {
+    entries: Vec<KeyringEntry>,
+    retention: Duration,
+}
+
+// A key along with when it signs tokens, as kept in the signing key store
+#[derive(Clone)]
+pub struct KeyringEntry {
+    pub key: SigningKey,
+    // Tokens are signed with the key from then on. It is accepted before, so every instance
+    // knows the key by the time the first token it signed reaches them.
+    pub activated_at: DateTime<Utc>,
+    // When the next key took over, None while the key is the latest one
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+impl Keyring {
+    pub fn new(active: SigningKey, retention_seconds: i64) -> Result<Self> {
+        let entry = KeyringEntry {
+            key: active,
+            activated_at: Utc::now(),
+            retired_at: None,
+        };
+
+        Self::from_entries(vec![entry], retention_seconds)
+    }
+
+    // Keyring of the stored keys, one of which has to be active
+    pub fn from_entries(entries: Vec<KeyringEntry>, retention_seconds: i64) -> Result<Self> {
+        let retention = Duration::try_seconds(retention_seconds)
+            .ok_or(eyre!("invalid key retention: {}", retention_seconds))?;
+
+        let mut keyring = Self {
+            entries: Vec::new(),
+            retention,
+        };
+        keyring.replace_entries(entries)?;
+
+        Ok(keyring)
+    }
+
+    // Take over the keys as they were reloaded from the store
+    pub fn replace_entries(&mut self, mut entries: Vec<KeyringEntry>) -> Result<()> {
+        let now = Utc::now();
+
+        if !entries.iter().any(|entry| entry.is_active(now)) {
+            return Err(eyre!("none of the signing keys is active"));
+        }
+
+        entries.sort_by_key(|entry| entry.activated_at);
+        self.entries = entries;
+
+        Ok(())
+    }
+
+    pub fn active(&self) -> &SigningKey {
+        let now = Utc::now();
+
+        // Entries are sorted by activation, and there is always one that was active
+        let entry = self
+            .entries
+            .iter()
+            .rev()
+            .find(|entry| entry.is_active(now))
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.activated_at <= now)
+            })
+            .unwrap_or(&self.entries[0]);
+
+        &entry.key
+    }
+
+    // Make `key` the active key from `activates_at` on and keep the current one around for
+    // verification. Stores do the same in `SigningKeyStore::rotate`.
+    pub fn rotate(&mut self, key: SigningKey, activates_at: DateTime<Utc>) -> Result<()> {
+        self.ensure_unknown(&key)?;
+
+        for entry in &mut self.entries {
+            if entry.retired_at.is_none() {
+                entry.retired_at = Some(activates_at);
+            }
+        }
+
+        self.entries.push(KeyringEntry {
+            key,
+            activated_at: activates_at,
+            retired_at: None,
+        });
+
+        Ok(())
+    }
+
+    // Key with the given id, as long as tokens signed with it are still accepted
+    pub fn find(&self, kid: &str) -> Option<&SigningKey> {
+        self.keys().find(|key| key.kid() == kid)
+    }
+
+    // Active key first, followed by the upcoming keys and the retired keys that are still valid
+    pub fn keys(&self) -> impl Iterator<Item = &SigningKey> {
+        let active = self.active();
+        let valid_after = Utc::now() - self.retention;
+
+        std::iter::once(active).chain(
+            self.entries
+                .iter()
+                .rev()
+                .filter(move |entry| entry.retired_at.is_none_or(|at| at > valid_after))
+                .map(|entry| &entry.key)
+                .filter(move |key| key.kid() != active.kid()),
+        )
+    }
+
+    fn ensure_unknown(&self, key: &SigningKey) -> Result<()> {
+        match self
+            .entries
+            .iter()
+            .find(|entry| entry.key.kid() == key.kid())
+        {
+            Some(_) => Err(eyre!("key {} is already in the keyring", key.kid())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl KeyringEntry {
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.activated_at <= now && self.retired_at.is_none_or(|at| at > now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    fn key(secret: &str) -> SigningKey {
+        SigningKey::from_secret(&Secret::new(secret.to_owned()))
+    }
+
+    #[test]
+    fn rotate_keeps_previous_key_for_verification() {
+        let mut keyring = Keyring::new(key("first"), 600).unwrap();
+        let first_kid = keyring.active().kid().to_owned();
+
+        keyring.rotate(key("second"), Utc::now()).unwrap();
+
+        assert_eq!(keyring.active().kid(), key("second").kid());
+        assert!(keyring.find(&first_kid).is_some());
+        assert_eq!(keyring.keys().count(), 2);
+    }
+
+    #[test]
+    fn upcoming_key_is_accepted_before_it_signs() {
+        let mut keyring = Keyring::new(key("first"), 600).unwrap();
+        let first_kid = keyring.active().kid().to_owned();
+
+        keyring
+            .rotate(key("second"), Utc::now() + Duration::minutes(2))
+            .unwrap();
+
+        assert_eq!(keyring.active().kid(), first_kid);
+        assert!(keyring.find(key("second").kid()).is_some());
+    }
+
+    #[test]
+    fn expired_keys_are_not_accepted() {
+        let mut keyring = Keyring::new(key("first"), 0).unwrap();
+        let first_kid = keyring.active().kid().to_owned();
+
+        keyring.rotate(key("second"), Utc::now()).unwrap();
+
+        assert!(keyring.find(&first_kid).is_none());
+        assert_eq!(keyring.keys().count(), 1);
+    }
+
+    #[test]
+    fn retired_keys_expire_after_retention_since_retirement() {
+        let now = Utc::now();
+        let entries = vec![
+            KeyringEntry {
+                key: key("first"),
+                activated_at: now - Duration::hours(3),
+                retired_at: Some(now - Duration::hours(2)),
+            },
+            KeyringEntry {
+                key: key("second"),
+                activated_at: now - Duration::hours(2),
+                retired_at: Some(now - Duration::minutes(30)),
+            },
+            KeyringEntry {
+                key: key("third"),
+                activated_at: now - Duration::minutes(30),
+                retired_at: None,
+            },
+        ];
+
+        // Loading the keys again, e.g. after a restart, doesn't extend their retention
+        let keyring = Keyring::from_entries(entries, 3600).unwrap();
+
+        assert_eq!(keyring.active().kid(), key("third").kid());
+        assert!(keyring.find(key("second").kid()).is_some());
+        assert!(keyring.find(key("first").kid()).is_none());
+    }
+
+    #[test]
+    fn keys_without_an_active_one_are_rejected() {
+        let entries = vec![KeyringEntry {
+            key: key("first"),
+            activated_at: Utc::now() + Duration::minutes(2),
+            retired_at: None,
+        }];
+
+        assert!(Keyring::from_entries(entries, 600).is_err());
+    }
+
+    #[test]
+    fn unknown_kid_is_not_found() {
+        let keyring = Keyring::new(key("first"), 600).unwrap();
+        assert!(keyring.find("unknown").is_none());
+    }
+
+    #[test]
+    fn rotating_to_the_active_key_is_rejected() {
+        let mut keyring = Keyring::new(key("first"), 600).unwrap();
+        assert!(keyring.rotate(key("first"), Utc::now()).is_err());
+    }
+
+    #[test]
+    fn rotating_to_a_known_key_twice_is_rejected() {
+        let mut keyring = Keyring::new(key("first"), 600).unwrap();
+        keyring.rotate(key("second"), Utc::now()).unwrap();
+        assert!(keyring.rotate(key("second"), Utc::now()).is_err());
+    }
+}
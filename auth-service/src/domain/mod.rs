@@ -1,13 +1,31 @@
+mod client_credentials;
 pub mod data_stores;
 pub mod email;
 pub mod email_client;
 mod error;
+mod jwt_config;
+mod keyring;
+mod oauth_client;
 mod password;
+mod personal_access_token;
+mod role;
+mod secret_cipher;
+mod signing_key;
+mod totp;
 mod user;
 
+pub use client_credentials::*;
 pub use data_stores::*;
 pub use email::*;
 pub use email_client::*;
 pub use error::*;
+pub use jwt_config::*;
+pub use keyring::*;
+pub use oauth_client::*;
 pub use password::*;
+pub use personal_access_token::*;
+pub use role::*;
+pub use secret_cipher::*;
+pub use signing_key::*;
+pub use totp::*;
 pub use user::*;
@@ -0,0 +1,332 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use color_eyre::eyre::{eyre, Result};
+use ring::digest;
+use secrecy::{ExposeSecret, Secret};
+use url::Url;
+use uuid::Uuid;
+
+use super::data_stores::random_token;
+
+// An application registered to log its users in through the authorization code flow or, without
+// a redirect URI, the device authorization grant, or a machine client like a backend job that
+// gets tokens of its own with the client credentials grant. Confidential clients, like web apps
+// with a backend, authenticate with a secret when they exchange a code. Public clients, like
+// single page, native and command-line apps, can't keep one and rely on PKCE or the user
+// approving the device alone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OAuthClient {
+    pub id: String,
+    pub name: String,
+    // Hex encoded SHA-256 digest of the secret, None for public clients
+    pub secret_hash: Option<String>,
+    // Codes are only ever sent to one of these, compared exactly. Empty for machine clients and
+    // devices.
+    pub redirect_uris: Vec<String>,
+    // What the client may request, from its users at /authorize and with the device
+    // authorization grant, or for itself with the client credentials grant
+    pub scopes: Vec<String>,
+}
+
+impl OAuthClient {
+    // Register a new client. The secret of a confidential client is returned once and only
+    // its digest is kept.
+    pub fn new(
+        name: String,
+        redirect_uris: Vec<String>,
+        scopes: Vec<String>,
+        confidential: bool,
+    ) -> Result<(Self, Option<Secret<String>>)> {
+        if name.trim().is_empty() {
+            return Err(eyre!("client name must not be empty"));
+        }
+
+        for redirect_uri in &redirect_uris {
+            validate_redirect_uri(redirect_uri)?;
+        }
+
+        for scope in &scopes {
+            validate_scope(scope)?;
+        }
+
+        let secret = confidential.then(random_token);
+
+        let client = Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            secret_hash: secret
+                .as_ref()
+                .map(|secret| hash_secret(secret.expose_secret())),
+            redirect_uris,
+            scopes,
+        };
+
+        Ok((client, secret))
+    }
+
+    pub fn is_confidential(&self) -> bool {
+        self.secret_hash.is_some()
+    }
+
+    pub fn allows_redirect_uri(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris.iter().any(|uri| uri == redirect_uri)
+    }
+
+    // Whether the client may use the client credentials grant. Tokens of its own can only be
+    // handed to a client that authenticates, and clients with redirect URIs act for their users.
+    pub fn is_machine_client(&self) -> bool {
+        self.is_confidential() && self.redirect_uris.is_empty() && !self.scopes.is_empty()
+    }
+
+    // Whether every scope of the space separated scope was registered for the client
+    pub fn allows_scope(&self, scope: &str) -> bool {
+        scope
+            .split_whitespace()
+            .all(|scope| self.scopes.iter().any(|allowed| allowed == scope))
+    }
+
+    // The scope a client credentials token is issued with. All of the client's scopes when none
+    // are requested, and None if it requests one it doesn't have.
+    pub fn grant_scope(&self, requested: Option<&str>) -> Option<String> {
+        let Some(requested) = requested else {
+            return Some(self.scopes.join(" "));
+        };
+
+        let requested: Vec<&str> = requested.split_whitespace().collect();
+
+        if requested.is_empty() || !self.allows_scope(&requested.join(" ")) {
+            return None;
+        }
+
+        Some(requested.join(" "))
+    }
+
+    // Public clients have no secret, so none matches
+    pub fn verify_secret(&self, secret: &str) -> bool {
+        // Compare digests so the comparison time does not depend on how much of the secret matches
+        self.secret_hash.as_deref() == Some(hash_secret(secret).as_str())
+    }
+}
+
+// Redirect URIs have to be absolute web addresses. Fragments are not allowed, the code is
+// added to the query.
+fn validate_redirect_uri(redirect_uri: &str) -> Result<()> {
+    let url = Url::parse(redirect_uri).map_err(|_| eyre!("invalid redirect URI"))?;
+
+    if !matches!(url.scheme(), "http" | "https") || url.fragment().is_some() {
+        return Err(eyre!(
+            "redirect URI must be an http(s) URL without a fragment"
+        ));
+    }
+
+    Ok(())
+}
+
+// Scopes are printable ASCII without spaces, quotes and backslashes, see RFC 6749 section 3.3
+pub(crate) fn validate_scope(scope: &str) -> Result<()> {
+    let is_valid = !scope.is_empty()
+        && scope
+            .chars()
+            .all(|c| c.is_ascii_graphic() && c != '"' && c != '\\');
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(eyre!("invalid scope"))
+    }
+}
+
+pub(crate) fn hash_secret(secret: &str) -> String {
+    digest::digest(&digest::SHA256, secret.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// PKCE (RFC 7636) code challenge sent to /authorize, proving that whoever exchanges the code
+// at /token started the flow. Only the S256 method is supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeChallenge(String);
+
+impl CodeChallenge {
+    // The base64url encoded SHA-256 digest of a code verifier
+    pub fn parse(challenge: String) -> Result<Self> {
+        let is_valid = challenge.len() == 43
+            && challenge
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        if is_valid {
+            Ok(Self(challenge))
+        } else {
+            Err(eyre!("Invalid code challenge"))
+        }
+    }
+
+    pub fn verify(&self, code_verifier: &str) -> bool {
+        // Verifiers are 43 to 128 unreserved characters
+        let is_valid_verifier = (43..=128).contains(&code_verifier.len())
+            && code_verifier
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-._~".contains(c));
+
+        if !is_valid_verifier {
+            return false;
+        }
+
+        let digest = digest::digest(&digest::SHA256, code_verifier.as_bytes());
+        URL_SAFE_NO_PAD.encode(digest.as_ref()) == self.0
+    }
+}
+
+impl AsRef<str> for CodeChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirect_uris() -> Vec<String> {
+        vec!["https://app.example.com/callback".to_owned()]
+    }
+
+    #[test]
+    fn test_new_confidential_client() {
+        let (client, secret) =
+            OAuthClient::new("App".to_owned(), redirect_uris(), vec![], true).unwrap();
+
+        assert!(client.is_confidential());
+        assert!(client.verify_secret(secret.unwrap().expose_secret()));
+        assert!(!client.verify_secret("wrong-secret"));
+    }
+
+    #[test]
+    fn test_new_public_client() {
+        let (client, secret) =
+            OAuthClient::new("App".to_owned(), redirect_uris(), vec![], false).unwrap();
+
+        assert!(secret.is_none());
+        assert!(!client.is_confidential());
+        assert!(!client.verify_secret(""));
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_clients() {
+        assert!(OAuthClient::new(" ".to_owned(), redirect_uris(), vec![], true).is_err());
+
+        for redirect_uri in [
+            "/callback",
+            "not a url",
+            "ftp://app.example.com/callback",
+            "https://app.example.com/callback#fragment",
+        ] {
+            let result = OAuthClient::new(
+                "App".to_owned(),
+                vec![redirect_uri.to_owned()],
+                vec![],
+                true,
+            );
+            assert!(result.is_err(), "{}", redirect_uri);
+        }
+    }
+
+    #[test]
+    fn test_new_machine_client() {
+        let scopes = vec!["reports:read".to_owned(), "reports:write".to_owned()];
+        let (client, secret) = OAuthClient::new("Job".to_owned(), vec![], scopes, true).unwrap();
+
+        assert!(secret.is_some());
+        assert!(client.is_machine_client());
+
+        // Clients acting for their users only don't get tokens of their own
+        let (client, _) =
+            OAuthClient::new("App".to_owned(), redirect_uris(), vec![], true).unwrap();
+        assert!(!client.is_machine_client());
+
+        for scopes in [vec!["".to_owned()], vec!["two words".to_owned()]] {
+            assert!(OAuthClient::new("Job".to_owned(), vec![], scopes, true).is_err());
+        }
+
+        // Public clients can't keep the secret that proves who gets the tokens, and clients
+        // with redirect URIs only request scopes from their users
+        let scopes = vec!["reports:read".to_owned()];
+        let (client, _) = OAuthClient::new("CLI".to_owned(), vec![], scopes, false).unwrap();
+        assert!(!client.is_machine_client());
+
+        let scopes = vec!["openid".to_owned()];
+        let (client, _) =
+            OAuthClient::new("App".to_owned(), redirect_uris(), scopes, true).unwrap();
+        assert!(!client.is_machine_client());
+    }
+
+    #[test]
+    fn test_allows_scope() {
+        let scopes = vec!["openid".to_owned(), "email".to_owned()];
+        let (client, _) =
+            OAuthClient::new("App".to_owned(), redirect_uris(), scopes, false).unwrap();
+
+        assert!(client.allows_scope("openid"));
+        assert!(client.allows_scope(" email  openid "));
+        assert!(client.allows_scope(""));
+        assert!(!client.allows_scope("openid admin"));
+        assert!(!client.allows_scope("open"));
+    }
+
+    #[test]
+    fn test_grant_scope() {
+        let scopes = vec!["reports:read".to_owned(), "reports:write".to_owned()];
+        let (client, _) = OAuthClient::new("Job".to_owned(), vec![], scopes, true).unwrap();
+
+        assert_eq!(
+            client.grant_scope(None).as_deref(),
+            Some("reports:read reports:write")
+        );
+        assert_eq!(
+            client.grant_scope(Some(" reports:read ")).as_deref(),
+            Some("reports:read")
+        );
+        assert_eq!(client.grant_scope(Some("reports:read admin")), None);
+        assert_eq!(client.grant_scope(Some(" ")), None);
+    }
+
+    #[test]
+    fn test_new_device_client() {
+        // Command-line tools and TVs log their users in without a redirect
+        let (client, secret) = OAuthClient::new("CLI".to_owned(), vec![], vec![], false).unwrap();
+
+        assert!(secret.is_none());
+        assert!(!client.is_machine_client());
+        assert!(!client.allows_redirect_uri("https://app.example.com/callback"));
+    }
+
+    #[test]
+    fn test_allows_redirect_uri() {
+        let (client, _) =
+            OAuthClient::new("App".to_owned(), redirect_uris(), vec![], true).unwrap();
+
+        assert!(client.allows_redirect_uri("https://app.example.com/callback"));
+        assert!(!client.allows_redirect_uri("https://app.example.com/callback/"));
+        assert!(!client.allows_redirect_uri("https://app.example.com/callback?next=/"));
+        assert!(!client.allows_redirect_uri("https://evil.example.com/callback"));
+    }
+
+    #[test]
+    fn test_code_challenge_verify() {
+        let challenge =
+            CodeChallenge::parse("bwWFMyPfdG9qreDhH2lmftFx_dFeLDalzcT1gb_j68g".to_owned()).unwrap();
+
+        assert!(challenge.verify("dBjftJeZ4CVP-mB92K27uhbUJU1p1r7wW1gFWFOEjXk"));
+        assert!(!challenge.verify("dBjftJeZ4CVP-mB92K27uhbUJU1p1r7wW1gFWFOEjXl"));
+        assert!(!challenge.verify("too-short"));
+    }
+
+    #[test]
+    fn test_code_challenge_parse() {
+        assert!(CodeChallenge::parse("a".repeat(43)).is_ok());
+        assert!(CodeChallenge::parse("a".repeat(42)).is_err());
+        assert!(CodeChallenge::parse(format!("{}=", "a".repeat(42))).is_err());
+    }
+}
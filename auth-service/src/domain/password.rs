@@ -1,4 +1,8 @@
-use color_eyre::eyre::{eyre, Result};
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
+    PasswordVerifier, Version,
+};
+use color_eyre::eyre::{eyre, Context, Result};
 use secrecy::{ExposeSecret, Secret};
 
 #[derive(Debug, Clone)]
@@ -30,6 +34,60 @@ impl AsRef<Secret<String>> for Password {
     }
 }
 
+#[tracing::instrument(name = "Verify password hash", skip_all)]
+pub(crate) async fn verify_password_hash(
+    expected_password_hash: Secret<String>,
+    password_candidate: Secret<String>,
+) -> Result<()> {
+    let current_span: tracing::Span = tracing::Span::current();
+
+    let result = tokio::task::spawn_blocking(move || {
+        // This code block ensures that the operations within the closure are executed within the context of the current span.
+        // This is especially useful for tracing operations that are performed in a different thread or task, such as within tokio::task::spawn_blocking.
+        current_span.in_scope(|| {
+            let expected_password_hash: PasswordHash<'_> =
+                PasswordHash::new(expected_password_hash.expose_secret())?;
+
+            Argon2::default()
+                .verify_password(
+                    password_candidate.expose_secret().as_bytes(),
+                    &expected_password_hash,
+                )
+                .wrap_err("failed to verify password hash")
+        })
+    })
+    .await;
+
+    result?
+}
+
+#[tracing::instrument(name = "Computing password hash", skip_all)]
+pub(crate) async fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
+    let current_span: tracing::Span = tracing::Span::current();
+
+    let result = tokio::task::spawn_blocking(move || {
+        // This code block ensures that the operations within the closure are executed within the context of the current span.
+        // This is especially useful for tracing operations that are performed in a different thread or task, such as within tokio::task::spawn_blocking.
+        current_span.in_scope(|| {
+            let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
+
+            let password_hash = Argon2::new(
+                Algorithm::Argon2id,
+                Version::V0x13,
+                Params::new(15000, 2, 1, None)?,
+            )
+            .hash_password(password.expose_secret().as_bytes(), &salt)?
+            .to_string();
+
+            Ok(Secret::new(password_hash))
+            //Err(eyre!("oh no!"))
+        })
+    })
+    .await;
+
+    result?
+}
+
 #[cfg(test)]
 mod tests {
     use super::Password;
@@ -0,0 +1,171 @@
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+
+use super::{
+    data_stores::random_token,
+    oauth_client::{hash_secret, validate_scope},
+    UserId, UserRoles,
+};
+
+// Long-lived token a user creates for their scripts, sent as a bearer token instead of the auth
+// cookie. Only its digest is kept, the token itself is shown once when it is created.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersonalAccessToken {
+    pub id: String,
+    pub user_id: UserId,
+    pub name: String,
+    // Hex encoded SHA-256 digest of the token
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    // Unix timestamps in seconds
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    // None for tokens that don't expire
+    pub expires_at: Option<i64>,
+}
+
+impl PersonalAccessToken {
+    pub fn new(
+        user_id: UserId,
+        name: String,
+        scopes: Vec<String>,
+        expires_in_seconds: Option<i64>,
+    ) -> Result<(Self, Secret<String>)> {
+        if name.trim().is_empty() {
+            return Err(eyre!("token name must not be empty"));
+        }
+
+        for scope in &scopes {
+            validate_scope(scope)?;
+        }
+
+        if expires_in_seconds.is_some_and(|seconds| seconds <= 0) {
+            return Err(eyre!("token must expire in the future"));
+        }
+
+        let secret = Secret::new(format!(
+            "{}{}",
+            PERSONAL_ACCESS_TOKEN_PREFIX,
+            random_token().expose_secret()
+        ));
+        let now = Utc::now().timestamp();
+
+        let token = Self {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            name,
+            token_hash: hash_secret(secret.expose_secret()),
+            scopes,
+            created_at: now,
+            last_used_at: None,
+            expires_at: expires_in_seconds.map(|seconds| now + seconds),
+        };
+
+        Ok((token, secret))
+    }
+
+    // The digest the token is looked up by
+    pub fn hash(token: &str) -> String {
+        hash_secret(token)
+    }
+
+    // Personal access tokens are told apart from JWTs by their prefix
+    pub fn is_personal_access_token(token: &str) -> bool {
+        token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now().timestamp())
+    }
+
+    // Space separated, like the scope of OAuth tokens
+    pub fn scope(&self) -> Option<String> {
+        (!self.scopes.is_empty()).then(|| self.scopes.join(" "))
+    }
+
+    // Permissions of the user that the token can be used for. Tokens only get the permissions
+    // among their scopes, so a token without scopes can't be used for anything privileged.
+    pub fn permissions(&self, roles: &UserRoles) -> Vec<String> {
+        roles
+            .permissions
+            .iter()
+            .filter(|permission| self.scopes.contains(permission))
+            .cloned()
+            .collect()
+    }
+}
+
+// Also lets secret scanners recognize leaked tokens
+const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "pat_";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_personal_access_token() {
+        let scopes = vec!["reports:read".to_owned()];
+        let (token, secret) =
+            PersonalAccessToken::new(UserId::default(), "CI".to_owned(), scopes, Some(3600))
+                .unwrap();
+
+        assert!(PersonalAccessToken::is_personal_access_token(
+            secret.expose_secret()
+        ));
+        assert_eq!(
+            PersonalAccessToken::hash(secret.expose_secret()),
+            token.token_hash
+        );
+        assert_eq!(token.scope().as_deref(), Some("reports:read"));
+        assert_eq!(token.expires_at, Some(token.created_at + 3600));
+        assert!(!token.is_expired());
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_tokens() {
+        let user_id = UserId::default();
+
+        assert!(PersonalAccessToken::new(user_id.clone(), " ".to_owned(), vec![], None).is_err());
+        assert!(PersonalAccessToken::new(
+            user_id.clone(),
+            "CI".to_owned(),
+            vec!["two words".to_owned()],
+            None
+        )
+        .is_err());
+        assert!(PersonalAccessToken::new(user_id, "CI".to_owned(), vec![], Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let (token, _) =
+            PersonalAccessToken::new(UserId::default(), "CI".to_owned(), vec![], None).unwrap();
+        assert!(!token.is_expired());
+        assert_eq!(token.scope(), None);
+
+        let token = PersonalAccessToken {
+            expires_at: Some(Utc::now().timestamp() - 1),
+            ..token
+        };
+        assert!(token.is_expired());
+    }
+
+    #[test]
+    fn test_permissions() {
+        let roles = UserRoles {
+            roles: vec!["analyst".to_owned()],
+            permissions: vec!["reports:read".to_owned(), "reports:write".to_owned()],
+        };
+        let scopes = vec!["reports:read".to_owned(), "profile".to_owned()];
+        let (scoped, _) =
+            PersonalAccessToken::new(UserId::default(), "CI".to_owned(), scopes, None).unwrap();
+        let (unscoped, _) =
+            PersonalAccessToken::new(UserId::default(), "CI".to_owned(), vec![], None).unwrap();
+
+        assert_eq!(scoped.permissions(&roles), vec!["reports:read"]);
+        assert!(unscoped.permissions(&roles).is_empty());
+    }
+}
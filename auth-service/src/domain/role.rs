@@ -0,0 +1,87 @@
+use color_eyre::eyre::Result;
+
+use super::oauth_client::validate_scope;
+
+// A named set of permissions, like `reports:read`, that users are assigned. Names follow the
+// same rules as scopes, so permissions can be granted to personal access tokens as scopes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Role {
+    pub name: String,
+    pub permissions: Vec<String>,
+}
+
+impl Role {
+    pub fn new(name: String, mut permissions: Vec<String>) -> Result<Self> {
+        validate_scope(&name)?;
+
+        for permission in &permissions {
+            validate_scope(permission)?;
+        }
+
+        permissions.sort();
+        permissions.dedup();
+
+        Ok(Self { name, permissions })
+    }
+}
+
+// Roles of a user, and the permissions they have through them. Both are sorted and free of
+// duplicates.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserRoles {
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+impl UserRoles {
+    pub fn new(roles: impl IntoIterator<Item = Role>) -> Self {
+        let mut user_roles = Self::default();
+
+        for role in roles {
+            user_roles.roles.push(role.name);
+            user_roles.permissions.extend(role.permissions);
+        }
+
+        user_roles.roles.sort();
+        user_roles.roles.dedup();
+        user_roles.permissions.sort();
+        user_roles.permissions.dedup();
+
+        user_roles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(name: &str, permissions: &[&str]) -> Role {
+        let permissions = permissions.iter().map(|p| p.to_string()).collect();
+        Role::new(name.to_owned(), permissions).unwrap()
+    }
+
+    #[test]
+    fn test_new_role() {
+        let role = role(
+            "analyst",
+            &["reports:write", "reports:read", "reports:read"],
+        );
+
+        assert_eq!(role.permissions, vec!["reports:read", "reports:write"]);
+
+        assert!(Role::new("".to_owned(), vec![]).is_err());
+        assert!(Role::new("two words".to_owned(), vec![]).is_err());
+        assert!(Role::new("analyst".to_owned(), vec!["".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_user_roles() {
+        let roles = UserRoles::new([
+            role("viewer", &["reports:read"]),
+            role("analyst", &["reports:read", "reports:write"]),
+        ]);
+
+        assert_eq!(roles.roles, vec!["analyst", "viewer"]);
+        assert_eq!(roles.permissions, vec!["reports:read", "reports:write"]);
+    }
+}
@@ -0,0 +1,98 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::eyre::{eyre, Context, Result};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use secrecy::{ExposeSecret, Secret};
+
+// Encrypts secrets with AES-256-GCM before they are stored, so a leaked database doesn't give
+// them away
+pub struct SecretCipher {
+    key: LessSafeKey,
+}
+
+impl SecretCipher {
+    // Cipher from a base64 encoded 256-bit key
+    pub fn new(key: &Secret<String>) -> Result<Self> {
+        let key = STANDARD
+            .decode(key.expose_secret())
+            .wrap_err("encryption key is not valid base64")?;
+
+        let key = UnboundKey::new(&AES_256_GCM, &key)
+            .map_err(|_| eyre!("encryption key must be 32 bytes long"))?;
+
+        Ok(Self {
+            key: LessSafeKey::new(key),
+        })
+    }
+
+    // The random nonce is prepended to the ciphertext
+    pub fn encrypt(&self, secret: &Secret<Vec<u8>>) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| eyre!("failed to generate nonce"))?;
+
+        let mut ciphertext = secret.expose_secret().clone();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut ciphertext,
+            )
+            .map_err(|_| eyre!("failed to encrypt secret"))?;
+
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    pub fn decrypt(&self, encrypted: &[u8]) -> Result<Secret<Vec<u8>>> {
+        if encrypted.len() < NONCE_LEN {
+            return Err(eyre!("encrypted secret is too short"));
+        }
+
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| eyre!("invalid secret nonce"))?;
+
+        let mut buffer = ciphertext.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut buffer)
+            .map_err(|_| eyre!("failed to decrypt secret"))?;
+
+        Ok(Secret::new(plaintext.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher() -> SecretCipher {
+        SecretCipher::new(&Secret::new(STANDARD.encode([7u8; 32]))).unwrap()
+    }
+
+    #[test]
+    fn encrypted_secret_roundtrips() {
+        let secret = Secret::new(b"signing key".to_vec());
+        let encrypted = cipher().encrypt(&secret).unwrap();
+
+        assert!(!encrypted
+            .windows(secret.expose_secret().len())
+            .any(|window| window == secret.expose_secret().as_slice()));
+
+        let decrypted = cipher().decrypt(&encrypted).unwrap();
+        assert_eq!(decrypted.expose_secret(), secret.expose_secret());
+    }
+
+    #[test]
+    fn secret_of_another_key_is_rejected() {
+        let encrypted = cipher()
+            .encrypt(&Secret::new(b"signing key".to_vec()))
+            .unwrap();
+        let other = SecretCipher::new(&Secret::new(STANDARD.encode([8u8; 32]))).unwrap();
+
+        assert!(other.decrypt(&encrypted).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use color_eyre::eyre::{eyre, Context, Result};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+    },
+    Algorithm, DecodingKey, EncodingKey,
+};
+use ring::{
+    digest,
+    rand::SystemRandom,
+    signature::{Ed25519KeyPair, KeyPair},
+};
+use secrecy::{ExposeSecret, Secret};
+
+// Key used to sign and verify JWTs.
+// The key id (kid) is the RFC 7638 thumbprint of the key, so it is stable across restarts.
+pub struct SigningKey {
+    kid: String,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    // base64url encoded Ed25519 public key, None for symmetric keys
+    public_key: Option<String>,
+    // PKCS#8 DER encoded Ed25519 private key, or the HS256 secret, so the key can be stored
+    private_key: Secret<Vec<u8>>,
+}
+
+impl SigningKey {
+    // HS256 key; every verifier needs to know the secret, so it is never published
+    pub fn from_secret(secret: &Secret<String>) -> Self {
+        Self::from_secret_bytes(secret.expose_secret().as_bytes())
+    }
+
+    // Key as it was stored, see `private_key`
+    pub fn from_private_key(algorithm: Algorithm, private_key: &Secret<Vec<u8>>) -> Result<Self> {
+        match algorithm {
+            Algorithm::EdDSA => Self::from_ed25519_pkcs8(private_key.expose_secret()),
+            Algorithm::HS256 => Ok(Self::from_secret_bytes(private_key.expose_secret())),
+            _ => Err(eyre!("unsupported signing key algorithm: {:?}", algorithm)),
+        }
+    }
+
+    fn from_secret_bytes(secret: &[u8]) -> Self {
+        let kid = thumbprint(&format!(
+            r#"{{"k":"{}","kty":"oct"}}"#,
+            URL_SAFE_NO_PAD.encode(secret)
+        ));
+
+        Self {
+            kid,
+            algorithm: Algorithm::HS256,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            public_key: None,
+            private_key: Secret::new(secret.to_vec()),
+        }
+    }
+
+    // EdDSA key from a PKCS#8 PEM encoded Ed25519 private key
+    pub fn from_ed25519_pem(pem: &Secret<String>) -> Result<Self> {
+        let pem = pem::parse(pem.expose_secret()).wrap_err("failed to parse signing key PEM")?;
+        Self::from_ed25519_pkcs8(pem.contents())
+    }
+
+    pub fn generate_ed25519() -> Result<Self> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| eyre!("failed to generate Ed25519 key pair"))?;
+        Self::from_ed25519_pkcs8(pkcs8.as_ref())
+    }
+
+    fn from_ed25519_pkcs8(der: &[u8]) -> Result<Self> {
+        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der)
+            .map_err(|e| eyre!("invalid Ed25519 private key: {}", e))?;
+        let public_key = key_pair.public_key().as_ref();
+        let x = URL_SAFE_NO_PAD.encode(public_key);
+        let kid = thumbprint(&format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, x));
+
+        Ok(Self {
+            kid,
+            algorithm: Algorithm::EdDSA,
+            encoding_key: EncodingKey::from_ed_der(der),
+            decoding_key: DecodingKey::from_ed_der(public_key),
+            public_key: Some(x),
+            private_key: Secret::new(der.to_vec()),
+        })
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    pub fn private_key(&self) -> &Secret<Vec<u8>> {
+        &self.private_key
+    }
+
+    // Public part of the key as a JWK, None for symmetric keys
+    pub fn to_jwk(&self) -> Option<Jwk> {
+        let x = self.public_key.clone()?;
+
+        Some(Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(KeyAlgorithm::EdDSA),
+                key_id: Some(self.kid.clone()),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x,
+            }),
+        })
+    }
+}
+
+impl Clone for SigningKey {
+    fn clone(&self) -> Self {
+        Self {
+            kid: self.kid.clone(),
+            algorithm: self.algorithm,
+            encoding_key: self.encoding_key.clone(),
+            decoding_key: self.decoding_key.clone(),
+            public_key: self.public_key.clone(),
+            private_key: Secret::new(self.private_key.expose_secret().clone()),
+        }
+    }
+}
+
+fn thumbprint(canonical_jwk: &str) -> String {
+    URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, canonical_jwk.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hs256_key_is_not_published() {
+        let key = SigningKey::from_secret(&Secret::new("secret".to_owned()));
+        assert_eq!(key.algorithm(), Algorithm::HS256);
+        assert!(key.to_jwk().is_none());
+    }
+
+    #[test]
+    fn same_secret_gives_same_kid() {
+        let first = SigningKey::from_secret(&Secret::new("secret".to_owned()));
+        let second = SigningKey::from_secret(&Secret::new("secret".to_owned()));
+        let other = SigningKey::from_secret(&Secret::new("other".to_owned()));
+        assert_eq!(first.kid(), second.kid());
+        assert_ne!(first.kid(), other.kid());
+    }
+
+    #[test]
+    fn stored_key_has_same_kid() {
+        for key in [
+            SigningKey::generate_ed25519().unwrap(),
+            SigningKey::from_secret(&Secret::new("secret".to_owned())),
+        ] {
+            let stored = SigningKey::from_private_key(key.algorithm(), key.private_key()).unwrap();
+            assert_eq!(stored.kid(), key.kid());
+        }
+    }
+
+    #[test]
+    fn ed25519_key_is_published_as_jwk() {
+        let key = SigningKey::generate_ed25519().unwrap();
+        let jwk = key.to_jwk().expect("Ed25519 key should have a JWK");
+
+        assert_eq!(key.algorithm(), Algorithm::EdDSA);
+        assert_eq!(jwk.common.key_id.as_deref(), Some(key.kid()));
+        assert_eq!(jwk.common.key_algorithm, Some(KeyAlgorithm::EdDSA));
+        assert!(matches!(
+            jwk.algorithm,
+            AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                curve: EllipticCurve::Ed25519,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn ed25519_key_is_parsed_from_pem() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pem = pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref()));
+
+        let first = SigningKey::from_ed25519_pem(&Secret::new(pem.clone())).unwrap();
+        let second = SigningKey::from_ed25519_pem(&Secret::new(pem)).unwrap();
+
+        assert_eq!(first.kid(), second.kid());
+    }
+
+    #[test]
+    fn invalid_pem_is_rejected() {
+        let pem = Secret::new("not a pem".to_owned());
+        assert!(SigningKey::from_ed25519_pem(&pem).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use color_eyre::eyre::{eyre, Context, Result};
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use secrecy::{ExposeSecret, Secret};
+
+use super::{Email, SecretCipher, TwoFACode};
+
+// RFC 6238 defaults, the only parameters every authenticator app supports
+const SECRET_LENGTH: usize = 20;
+const TIME_STEP_SECONDS: u64 = 30;
+const CODE_DIGITS: u32 = 6;
+// Codes of the neighbouring time steps are accepted too, to allow for clock drift
+const ALLOWED_DRIFT_STEPS: u64 = 1;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// Secret shared with the authenticator app of a user
+pub struct TotpSecret(Secret<Vec<u8>>);
+
+impl TotpSecret {
+    // Secret from its base32 encoding, as shown to the user during enrollment
+    pub fn parse(secret: Secret<String>) -> Result<Self> {
+        let bytes = base32_decode(secret.expose_secret()).wrap_err("Invalid TOTP secret")?;
+        if bytes.len() < SECRET_LENGTH {
+            return Err(eyre!("TOTP secret is too short"));
+        }
+        Ok(Self(Secret::new(bytes)))
+    }
+
+    pub fn generate() -> Result<Self> {
+        let mut bytes = vec![0u8; SECRET_LENGTH];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| eyre!("failed to generate TOTP secret"))?;
+        Ok(Self(Secret::new(bytes)))
+    }
+
+    pub fn to_base32(&self) -> Secret<String> {
+        Secret::new(base32_encode(self.0.expose_secret()))
+    }
+
+    // Key URI authenticator apps import the secret from, usually through a QR code
+    pub fn otpauth_uri(&self, issuer: &str, email: &Email) -> Secret<String> {
+        Secret::new(format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            percent_encode(issuer),
+            percent_encode(email.as_ref().expose_secret()),
+            self.to_base32().expose_secret(),
+            percent_encode(issuer),
+            CODE_DIGITS,
+            TIME_STEP_SECONDS
+        ))
+    }
+
+    // Code the authenticator app shows at the given time
+    pub fn code_at(&self, unix_time: u64) -> Secret<String> {
+        self.hotp(unix_time / TIME_STEP_SECONDS)
+    }
+
+    // Time step of the code if it is valid. Each step must only be accepted once, since
+    // anybody who saw the code could use it again while it is valid.
+    pub fn verify(&self, code: &TwoFACode) -> Option<u64> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        self.verify_at(code, now)
+    }
+
+    fn verify_at(&self, code: &TwoFACode, unix_time: u64) -> Option<u64> {
+        let counter = unix_time / TIME_STEP_SECONDS;
+
+        (counter.saturating_sub(ALLOWED_DRIFT_STEPS)..=counter + ALLOWED_DRIFT_STEPS)
+            .rev()
+            .find(|counter| self.hotp(*counter).expose_secret() == code.as_ref().expose_secret())
+    }
+
+    // RFC 4226 HOTP value for the counter
+    fn hotp(&self, counter: u64) -> Secret<String> {
+        let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, self.0.expose_secret());
+        let tag = hmac::sign(&key, &counter.to_be_bytes());
+        let digest = tag.as_ref();
+
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+
+        Secret::new(format!(
+            "{:0width$}",
+            binary % 10u32.pow(CODE_DIGITS),
+            width = CODE_DIGITS as usize
+        ))
+    }
+}
+
+impl Clone for TotpSecret {
+    fn clone(&self) -> Self {
+        Self(Secret::new(self.0.expose_secret().clone()))
+    }
+}
+
+// Encrypts TOTP secrets before they are stored,
+// so a leaked database doesn't give away anybody's second factor
+pub struct TotpSecretCipher {
+    cipher: SecretCipher,
+}
+
+impl TotpSecretCipher {
+    // Cipher from a base64 encoded 256-bit key
+    pub fn new(key: &Secret<String>) -> Result<Self> {
+        let cipher = SecretCipher::new(key).wrap_err("invalid TOTP encryption key")?;
+
+        Ok(Self { cipher })
+    }
+
+    pub fn encrypt(&self, secret: &TotpSecret) -> Result<Vec<u8>> {
+        self.cipher
+            .encrypt(&secret.0)
+            .wrap_err("failed to encrypt TOTP secret")
+    }
+
+    pub fn decrypt(&self, encrypted: &[u8]) -> Result<TotpSecret> {
+        self.cipher
+            .decrypt(encrypted)
+            .map(TotpSecret)
+            .wrap_err("failed to decrypt TOTP secret")
+    }
+}
+
+// RFC 4648 base32 without padding, which is what authenticator apps expect
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+// Lenient about case, spaces and padding, since users may type the secret in
+fn base32_decode(encoded: &str) -> Result<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a as char == c.to_ascii_uppercase())
+            .ok_or_else(|| eyre!("invalid base32 character"))?;
+
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+
+    Ok(decoded)
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use ring::aead::NONCE_LEN;
+
+    use super::*;
+
+    // The SHA-1 seed of the RFC 6238 test vectors, "12345678901234567890"
+    fn rfc_secret() -> TotpSecret {
+        TotpSecret::parse(Secret::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_owned())).unwrap()
+    }
+
+    fn code(value: &str) -> TwoFACode {
+        TwoFACode::parse(Secret::new(value.to_owned())).unwrap()
+    }
+
+    #[test]
+    fn codes_match_rfc_test_vectors() {
+        let secret = rfc_secret();
+
+        // Last six digits of the eight digit codes in RFC 6238, appendix B
+        for (time, expected) in [
+            (59, "287082"),
+            (1_111_111_109, "081804"),
+            (1_234_567_890, "005924"),
+            (2_000_000_000, "279037"),
+        ] {
+            assert_eq!(secret.code_at(time).expose_secret(), expected);
+        }
+    }
+
+    #[test]
+    fn codes_of_neighbouring_time_steps_are_accepted() {
+        let secret = rfc_secret();
+        let code = code("081804");
+
+        let time_step = 1_111_111_109 / TIME_STEP_SECONDS;
+
+        assert_eq!(secret.verify_at(&code, 1_111_111_109), Some(time_step));
+        assert_eq!(
+            secret.verify_at(&code, 1_111_111_109 + TIME_STEP_SECONDS),
+            Some(time_step)
+        );
+        assert_eq!(
+            secret.verify_at(&code, 1_111_111_109 - TIME_STEP_SECONDS),
+            Some(time_step)
+        );
+        assert_eq!(
+            secret.verify_at(&code, 1_111_111_109 + 3 * TIME_STEP_SECONDS),
+            None
+        );
+    }
+
+    #[test]
+    fn base32_roundtrips() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("mzxw6ytboi======").unwrap(), b"foobar");
+
+        let secret = TotpSecret::generate().unwrap();
+        let parsed = TotpSecret::parse(secret.to_base32()).unwrap();
+        assert_eq!(
+            parsed.code_at(59).expose_secret(),
+            secret.code_at(59).expose_secret()
+        );
+    }
+
+    #[test]
+    fn invalid_secret_is_rejected() {
+        assert!(TotpSecret::parse(Secret::new("not base32!".to_owned())).is_err());
+        assert!(TotpSecret::parse(Secret::new("MZXW6YTBOI".to_owned())).is_err());
+    }
+
+    #[test]
+    fn otpauth_uri_names_issuer_and_account() {
+        let email = Email::parse(Secret::new("user+1@example.com".to_owned())).unwrap();
+        let uri = rfc_secret().otpauth_uri("Auth Service", &email);
+
+        assert_eq!(
+            uri.expose_secret(),
+            "otpauth://totp/Auth%20Service:user%2B1%40example.com\
+             ?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Auth%20Service\
+             &algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    fn cipher() -> TotpSecretCipher {
+        TotpSecretCipher::new(&Secret::new(STANDARD.encode([7u8; 32]))).unwrap()
+    }
+
+    #[test]
+    fn encrypted_secret_roundtrips() {
+        let secret = rfc_secret();
+        let encrypted = cipher().encrypt(&secret).unwrap();
+
+        assert!(!encrypted
+            .windows(SECRET_LENGTH)
+            .any(|window| window == b"12345678901234567890"));
+
+        let decrypted = cipher().decrypt(&encrypted).unwrap();
+        assert_eq!(decrypted.code_at(59).expose_secret(), "287082");
+    }
+
+    #[test]
+    fn tampered_secret_is_rejected() {
+        let mut encrypted = cipher().encrypt(&rfc_secret()).unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+
+        assert!(cipher().decrypt(&encrypted).is_err());
+        assert!(cipher().decrypt(&encrypted[..NONCE_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn key_of_wrong_length_is_rejected() {
+        let key = Secret::new(STANDARD.encode([7u8; 16]));
+        assert!(TotpSecretCipher::new(&key).is_err());
+    }
+}
@@ -1,20 +1,118 @@
+use color_eyre::eyre::{eyre, Context, Result};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
 use crate::domain::{Email, Password};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct User {
+    pub id: UserId,
     pub email: Email,
     pub password: Password,
     pub requires_2fa: bool,
+    pub two_fa_method: TwoFAMethod,
+    pub email_verified: bool,
 }
 
 impl User {
     pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
         User {
+            id: UserId::default(),
             email,
             password,
             requires_2fa,
+            two_fa_method: TwoFAMethod::default(),
+            email_verified: false,
         }
     }
 
     // Additional methods for User can be added here
 }
+
+// Identifies a user for good, unlike the email address which can change
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UserId(String);
+
+impl UserId {
+    pub fn parse(id: String) -> Result<Self> {
+        let parsed_id = Uuid::parse_str(&id).wrap_err("Invalid user Id")?;
+        Ok(Self(parsed_id.to_string()))
+    }
+}
+
+impl Default for UserId {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for UserId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// How the second factor of users who require 2FA is checked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TwoFAMethod {
+    // A code is emailed on every login
+    #[default]
+    Email,
+    // The code is generated by an authenticator app, see TotpSecret
+    AuthenticatorApp,
+}
+
+impl TwoFAMethod {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "email" => Ok(Self::Email),
+            "authenticator_app" => Ok(Self::AuthenticatorApp),
+            _ => Err(eyre!("Invalid 2FA method")),
+        }
+    }
+}
+
+impl AsRef<str> for TwoFAMethod {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Email => "email",
+            Self::AuthenticatorApp => "authenticator_app",
+        }
+    }
+}
+
+// How a user proved who they are when they logged in, reported in the `amr` claim of ID tokens
+// with the values of RFC 8176
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuthenticationMethod {
+    #[serde(rename = "pwd")]
+    Password,
+    // The emailed 2FA code, or the one shown by an authenticator app
+    #[serde(rename = "otp")]
+    OneTimePassword,
+    // Any second factor was checked on top of the password
+    #[serde(rename = "mfa")]
+    MultiFactor,
+}
+
+impl AuthenticationMethod {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "pwd" => Ok(Self::Password),
+            "otp" => Ok(Self::OneTimePassword),
+            "mfa" => Ok(Self::MultiFactor),
+            _ => Err(eyre!("Invalid authentication method")),
+        }
+    }
+}
+
+impl AsRef<str> for AuthenticationMethod {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Password => "pwd",
+            Self::OneTimePassword => "otp",
+            Self::MultiFactor => "mfa",
+        }
+    }
+}
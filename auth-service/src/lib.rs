@@ -1,30 +1,50 @@
-use std::error::Error;
+use std::{error::Error, net::SocketAddr};
 
-use crate::utils::tracing::{make_span_with_request_id, on_request, on_response};
+use crate::utils::{
+    authenticated_user::RequirePermission,
+    constants::{
+        ACCOUNTS_UNLOCK_PERMISSION, CLIENTS_WRITE_PERMISSION, ROLES_WRITE_PERMISSION,
+        SIGNING_KEYS_ROTATE_PERMISSION,
+    },
+    tracing::{make_span_with_request_id, on_request, on_response},
+};
 use app_state::AppState;
 use axum::{
-    http::{Method, StatusCode},
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    http::{
+        header::{RETRY_AFTER, WWW_AUTHENTICATE},
+        Method, StatusCode,
+    },
+    middleware::AddExtension,
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{delete, get, post, put},
     serve::Serve,
     Json, Router,
 };
-use domain::AuthAPIError;
+use domain::{AuthAPIError, OAuthError};
 use redis::{Client, RedisResult};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
+use tower_http::{
+    cors::CorsLayer,
+    services::{ServeDir, ServeFile},
+    trace::TraceLayer,
+};
 
 pub mod app_state;
 pub mod domain;
 pub mod routes;
 pub mod services;
+pub mod tasks;
 pub mod utils;
 
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Serve<
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
@@ -37,7 +57,7 @@ impl Application {
             "http://157.245.90.45:8000".parse()?,
         ];
         let cors = CorsLayer::new()
-            .allow_methods([Method::GET, Method::POST])
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             // Allow cookies to be included in requests
             .allow_credentials(true)
             .allow_origin(allowed_origins);
@@ -45,10 +65,97 @@ impl Application {
         let router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
             .route("/signup", post(routes::signup))
+            .route("/verify-email", post(routes::verify_email))
+            .route(
+                "/verify-email/resend",
+                post(routes::resend_verification_email),
+            )
             .route("/login", post(routes::login))
+            .route("/unlock-account", post(routes::unlock_account))
             .route("/verify-2fa", post(routes::verify_2fa))
+            .route("/2fa/totp/enroll", post(routes::enroll_totp))
+            .route("/2fa/totp/confirm", post(routes::confirm_totp))
+            .route(
+                "/2fa/recovery-codes",
+                get(routes::count_recovery_codes).post(routes::regenerate_recovery_codes),
+            )
             .route("/logout", post(routes::logout))
+            .route("/logout-all", post(routes::logout_all))
+            .route("/refresh", post(routes::refresh))
+            .route("/change-password", post(routes::change_password))
+            .route("/change-email", post(routes::change_email))
+            .route("/change-email/confirm", post(routes::confirm_email_change))
+            .route("/account", delete(routes::delete_account))
+            .route("/sessions", get(routes::list_sessions))
+            .route("/sessions/:id", delete(routes::revoke_session))
+            .route(
+                "/personal-access-tokens",
+                get(routes::list_personal_access_tokens).post(routes::create_personal_access_token),
+            )
+            .route(
+                "/personal-access-tokens/:id",
+                delete(routes::revoke_personal_access_token),
+            )
+            .route(
+                "/password-reset/request",
+                post(routes::request_password_reset),
+            )
+            .route(
+                "/password-reset/confirm",
+                post(routes::confirm_password_reset),
+            )
             .route("/verify-token", post(routes::verify_token))
+            .route("/introspect", post(routes::introspect))
+            .route("/revoke", post(routes::revoke))
+            .route("/authorize", get(routes::authorize))
+            .route("/token", post(routes::token))
+            .route("/device/code", post(routes::device_authorization))
+            .route("/device/verify", post(routes::verify_device))
+            // The page users approve devices on, see routes::device_authorization
+            .route_service("/device", ServeFile::new("assets/device.html"))
+            .route("/userinfo", get(routes::userinfo).post(routes::userinfo))
+            .route("/.well-known/jwks.json", get(routes::jwks))
+            .route(
+                "/.well-known/openid-configuration",
+                get(routes::openid_configuration),
+            )
+            .route(
+                "/admin/rotate-signing-key",
+                post(routes::rotate_signing_key).route_layer(RequirePermission::new(
+                    app_state.clone(),
+                    SIGNING_KEYS_ROTATE_PERMISSION,
+                )),
+            )
+            .route(
+                "/admin/unlock-account",
+                post(routes::admin_unlock_account).route_layer(RequirePermission::new(
+                    app_state.clone(),
+                    ACCOUNTS_UNLOCK_PERMISSION,
+                )),
+            )
+            .route(
+                "/admin/clients",
+                post(routes::register_oauth_client).route_layer(RequirePermission::new(
+                    app_state.clone(),
+                    CLIENTS_WRITE_PERMISSION,
+                )),
+            )
+            .route(
+                "/admin/roles/:name",
+                put(routes::set_role).route_layer(RequirePermission::new(
+                    app_state.clone(),
+                    ROLES_WRITE_PERMISSION,
+                )),
+            )
+            .route(
+                "/admin/users/:id/roles/:name",
+                put(routes::assign_role)
+                    .delete(routes::unassign_role)
+                    .route_layer(RequirePermission::new(
+                        app_state.clone(),
+                        ROLES_WRITE_PERMISSION,
+                    )),
+            )
             .with_state(app_state)
             .layer(cors)
             .layer(
@@ -64,7 +171,11 @@ impl Application {
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Login attempts are rate limited per client address
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         // Create a new Application instance and return it
         Ok(Application { server, address })
@@ -85,6 +196,15 @@ impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         log_error_chain(&self);
 
+        // Tells rate limited clients when to try again, and other services how to authenticate
+        let header = match self {
+            AuthAPIError::TooManyLoginAttempts {
+                retry_after_seconds,
+            } => Some((RETRY_AFTER, retry_after_seconds.to_string())),
+            AuthAPIError::InvalidClient => Some((WWW_AUTHENTICATE, "Basic".to_owned())),
+            _ => None,
+        };
+
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
@@ -93,6 +213,30 @@ impl IntoResponse for AuthAPIError {
             }
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing auth token"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid auth token"),
+            AuthAPIError::EmailNotVerified => (StatusCode::FORBIDDEN, "Email address not verified"),
+            AuthAPIError::InvalidSigningKey => (StatusCode::BAD_REQUEST, "Invalid signing key"),
+            AuthAPIError::TwoFANotEnabled => (StatusCode::BAD_REQUEST, "2FA is not enabled"),
+            AuthAPIError::TwoFAAttemptsExceeded => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many failed attempts, please log in again",
+            ),
+            AuthAPIError::TotpAttemptsExceeded => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many failed attempts, please try again later",
+            ),
+            AuthAPIError::TooManyLoginAttempts { .. } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many login attempts, please try again later",
+            ),
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::InvalidClient => (StatusCode::UNAUTHORIZED, "Invalid client credentials"),
+            AuthAPIError::InvalidUserCode => (StatusCode::BAD_REQUEST, "Invalid or expired code"),
+            AuthAPIError::PersonalAccessTokenNotFound => {
+                (StatusCode::NOT_FOUND, "Personal access token not found")
+            }
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "User not found"),
+            AuthAPIError::RoleNotFound => (StatusCode::NOT_FOUND, "Role not found"),
+            AuthAPIError::MissingPermission => (StatusCode::FORBIDDEN, "Missing permission"),
             AuthAPIError::UnexpectedError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
             }
@@ -100,7 +244,41 @@ impl IntoResponse for AuthAPIError {
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
         });
-        (status, body).into_response()
+        match header {
+            Some(header) => (status, [header], body).into_response(),
+            None => (status, body).into_response(),
+        }
+    }
+}
+
+// Error response of the OAuth 2.0 endpoints, see RFC 6749 section 5.2
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OAuthErrorResponse {
+    pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_description: Option<String>,
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        log_error_chain(&self);
+
+        let status = match self {
+            OAuthError::InvalidClient => StatusCode::UNAUTHORIZED,
+            OAuthError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let body = Json(OAuthErrorResponse {
+            error: self.code().to_owned(),
+            error_description: self.description().map(str::to_owned),
+        });
+
+        match self {
+            OAuthError::InvalidClient => {
+                (status, [(WWW_AUTHENTICATE, "Basic")], body).into_response()
+            }
+            _ => (status, body).into_response(),
+        }
     }
 }
 
@@ -1,16 +1,35 @@
 use auth_service::{
     app_state::AppState,
-    domain::Email,
+    domain::{
+        Email, JwtConfig, Keyring, SecretCipher, SigningKey, SigningKeyStore, SigningKeyStoreError,
+        TotpSecretCipher,
+    },
     get_postgres_pool, get_redis_client,
     services::{
-        PostgresUserStore, PostmarkEmailClient, RedisBannedTokenStore, RedisTwoFACodeStore,
+        PostgresOAuthClientStore, PostgresPersonalAccessTokenStore, PostgresRoleStore,
+        PostgresSessionStore, PostgresSigningKeyStore, PostgresUserStore, PostmarkEmailClient,
+        RedisAuthorizationCodeStore, RedisBannedTokenStore, RedisDeviceCodeStore,
+        RedisLoginAttemptStore, RedisPasswordResetTokenStore, RedisRefreshTokenStore,
+        RedisTwoFACodeStore,
+    },
+    tasks::{
+        purge_deleted_accounts, reload_keyring, PURGE_DELETED_ACCOUNTS_INTERVAL,
+        RELOAD_KEYRING_INTERVAL,
     },
     utils::{
-        constants::{prod, DATABASE_URL, POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME},
+        auth::{EMAIL_VERIFICATION_TOKEN_TTL_SECONDS, SIGNING_KEY_ACTIVATION_DELAY_SECONDS},
+        constants::{
+            prod, ACCOUNT_DELETION_GRACE_PERIOD_SECONDS, AUTH_TOKEN_PRECEDENCE, DATABASE_URL,
+            JWT_AUDIENCES, JWT_EMAIL_CLAIM, JWT_ISSUER, JWT_LEEWAY_SECONDS, JWT_PREVIOUS_SECRET,
+            JWT_PREVIOUS_SIGNING_KEY, JWT_SECRET, JWT_SIGNING_KEY, OAUTH_CLIENTS,
+            POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME, SIGNING_KEY_ENCRYPTION_KEY, TOTP_ENCRYPTION_KEY,
+            TRUSTED_PROXIES,
+        },
         tracing::init_tracing,
     },
     Application,
 };
+use chrono::{Duration, Utc};
 use reqwest::Client;
 use secrecy::Secret;
 use sqlx::PgPool;
@@ -23,21 +42,83 @@ async fn main() {
     init_tracing().expect("Failed to initialize tracing");
 
     let pg_pool = configure_postgresql().await;
-    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+    let totp_cipher =
+        TotpSecretCipher::new(&TOTP_ENCRYPTION_KEY).expect("Failed to create TOTP secret cipher");
+    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(
+        pg_pool.clone(),
+        totp_cipher,
+    )));
+    let session_store = Arc::new(RwLock::new(PostgresSessionStore::new(pg_pool.clone())));
+    let oauth_client_store = Arc::new(RwLock::new(PostgresOAuthClientStore::new(pg_pool.clone())));
+    let personal_access_token_store = Arc::new(RwLock::new(PostgresPersonalAccessTokenStore::new(
+        pg_pool.clone(),
+    )));
+    let role_store = Arc::new(RwLock::new(PostgresRoleStore::new(pg_pool.clone())));
+    let signing_key_cipher = SecretCipher::new(&SIGNING_KEY_ENCRYPTION_KEY)
+        .expect("Failed to create signing key cipher");
+    let signing_key_store = Arc::new(RwLock::new(PostgresSigningKeyStore::new(
+        pg_pool,
+        signing_key_cipher,
+    )));
 
     let redis_conn = Arc::new(RwLock::new(configure_redis()));
     let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_conn.clone())));
     let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn.clone())));
+    let refresh_token_store =
+        Arc::new(RwLock::new(RedisRefreshTokenStore::new(redis_conn.clone())));
+    let password_reset_token_store = Arc::new(RwLock::new(RedisPasswordResetTokenStore::new(
+        redis_conn.clone(),
+    )));
+    let login_attempt_store =
+        Arc::new(RwLock::new(RedisLoginAttemptStore::new(redis_conn.clone())));
+    let authorization_code_store = Arc::new(RwLock::new(RedisAuthorizationCodeStore::new(
+        redis_conn.clone(),
+    )));
+    let device_code_store = Arc::new(RwLock::new(RedisDeviceCodeStore::new(redis_conn)));
 
     let email_client = Arc::new(configure_postmark_email_client());
 
+    let keyring = Arc::new(RwLock::new(
+        configure_keyring(&mut *signing_key_store.write().await).await,
+    ));
+
     let app_state = AppState::new(
         user_store,
         banned_token_store,
         two_fa_code_store,
         email_client,
+        refresh_token_store,
+        password_reset_token_store,
+        login_attempt_store,
+        session_store,
+        keyring,
+        signing_key_store,
+        *ACCOUNT_DELETION_GRACE_PERIOD_SECONDS,
+        configure_jwt(),
+        OAUTH_CLIENTS.clone(),
+        oauth_client_store,
+        authorization_code_store,
+        device_code_store,
+        personal_access_token_store,
+        *AUTH_TOKEN_PRECEDENCE,
+        role_store,
+        TRUSTED_PROXIES.clone(),
     );
 
+    // Without a grace period accounts are deleted right away, so there is nothing to purge
+    if app_state.account_deletion_grace_period_seconds.is_some() {
+        tokio::spawn(purge_deleted_accounts(
+            app_state.user_store.clone(),
+            PURGE_DELETED_ACCOUNTS_INTERVAL,
+        ));
+    }
+
+    tokio::spawn(reload_keyring(
+        app_state.keyring.clone(),
+        app_state.signing_key_store.clone(),
+        RELOAD_KEYRING_INTERVAL,
+    ));
+
     let app = Application::build(app_state, prod::APP_ADDRESS)
         .await
         .expect("Failed to build app");
@@ -67,6 +148,72 @@ fn configure_redis() -> redis::Connection {
         .expect("Failed to get Redis connection")
 }
 
+// The keyring is shared with the other instances through the signing key store. The keys
+// configured in the environment are added to it, so a deployment can rotate them as well.
+async fn configure_keyring(signing_key_store: &mut (dyn SigningKeyStore + Send + Sync)) -> Keyring {
+    let now = Utc::now();
+
+    let previous_keys = [
+        JWT_PREVIOUS_SIGNING_KEY.as_ref().map(|pem| {
+            SigningKey::from_ed25519_pem(pem).expect("Failed to parse previous JWT signing key")
+        }),
+        JWT_PREVIOUS_SECRET.as_ref().map(SigningKey::from_secret),
+    ];
+
+    // Keys already stored keep the time they were actually retired at
+    for key in previous_keys.into_iter().flatten() {
+        signing_key_store
+            .add_retired_key(key, now)
+            .await
+            .expect("Failed to store previous signing key");
+    }
+
+    let active = match JWT_SIGNING_KEY.as_ref() {
+        Some(pem) => SigningKey::from_ed25519_pem(pem).expect("Failed to parse JWT signing key"),
+        None => SigningKey::from_secret(&JWT_SECRET),
+    };
+
+    // Retired keys have to outlive the longest-lived tokens they signed
+    let retired_after = now - Duration::seconds(EMAIL_VERIFICATION_TOKEN_TTL_SECONDS);
+    let entries = signing_key_store
+        .get_keys(retired_after)
+        .await
+        .expect("Failed to load signing keys");
+
+    // A newly deployed key takes over once the instances still running the previous
+    // deployment have picked it up, right away when there is no key yet
+    if !entries.iter().any(|entry| entry.key.kid() == active.kid()) {
+        let activates_at = match entries.iter().any(|entry| entry.retired_at.is_none()) {
+            true => now + Duration::seconds(SIGNING_KEY_ACTIVATION_DELAY_SECONDS),
+            false => now,
+        };
+
+        match signing_key_store.rotate(active, activates_at).await {
+            // Another instance of the same deployment stored it first
+            Ok(()) | Err(SigningKeyStoreError::KeyAlreadyExists) => {}
+            Err(e) => panic!("Failed to store JWT signing key: {:?}", e),
+        }
+    }
+
+    let entries = signing_key_store
+        .get_keys(retired_after)
+        .await
+        .expect("Failed to load signing keys");
+
+    Keyring::from_entries(entries, EMAIL_VERIFICATION_TOKEN_TTL_SECONDS)
+        .expect("Failed to create keyring")
+}
+
+fn configure_jwt() -> JwtConfig {
+    JwtConfig::new(
+        JWT_ISSUER.clone(),
+        JWT_AUDIENCES.clone(),
+        *JWT_LEEWAY_SECONDS,
+        *JWT_EMAIL_CLAIM,
+    )
+    .expect("Invalid JWT configuration")
+}
+
 fn configure_postmark_email_client() -> PostmarkEmailClient {
     let http_client = Client::builder()
         .timeout(prod::email_client::TIMEOUT)
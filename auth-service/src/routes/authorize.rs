@@ -0,0 +1,208 @@
+use axum::{
+    extract::{Query, State},
+    http::Uri,
+    response::{IntoResponse, Redirect, Response},
+};
+use color_eyre::eyre::Context;
+use secrecy::ExposeSecret;
+use serde::Deserialize;
+use url::{form_urlencoded, Url};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthorizationCode, AuthorizationGrant, ClientGrant, CodeChallenge, OAuthClient,
+        OAuthClientStoreError, OAuthError,
+    },
+    utils::{authenticated_user::AuthenticatedSession, client_info::ClientInfo},
+};
+
+// Start of the OAuth 2.0 authorization code flow (RFC 6749 section 4.1), with PKCE (RFC 7636).
+// Logged in users are sent straight back to the client with a code. Everyone else is sent to
+// the login page first, which brings them back here afterwards. With the `openid` scope this is
+// the OpenID Connect authentication request, and the code gets an ID token as well.
+#[tracing::instrument(name = "Authorize", skip_all)]
+pub async fn authorize(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    authenticated: Option<AuthenticatedSession>,
+    uri: Uri,
+    Query(request): Query<AuthorizeRequest>,
+) -> Result<Response, OAuthError> {
+    // Errors are only sent to a redirect URI registered for the client, anything else would
+    // let this endpoint redirect to arbitrary sites
+    let (oauth_client, redirect_uri) = check_client(&state, &request).await?;
+    let client_state = request.state.as_deref();
+
+    let code_challenge = match check_request(&oauth_client, &request) {
+        Ok(code_challenge) => code_challenge,
+        Err(e) => return redirect_to_client(&redirect_uri, &error_params(&e), client_state),
+    };
+
+    let Some(AuthenticatedSession { session, user }) = authenticated else {
+        return Ok(redirect_to_login(&uri));
+    };
+
+    let code = AuthorizationCode::default();
+    let grant = AuthorizationGrant {
+        client_grant: ClientGrant {
+            client_id: request.client_id.unwrap_or_default(),
+            scope: request.scope,
+        },
+        redirect_uri: redirect_uri.clone(),
+        code_challenge,
+        user_id: user.id,
+        user_agent: client.user_agent,
+        ip_address: client.ip_address,
+        nonce: request.nonce,
+        auth_time: session.created_at,
+        amr: session.amr,
+    };
+
+    if let Err(e) = state
+        .authorization_code_store
+        .write()
+        .await
+        .add_code(code.clone(), grant)
+        .await
+    {
+        tracing::error!("failed to store authorization code: {:?}", e);
+        let e = OAuthError::UnexpectedError(e.into());
+        return redirect_to_client(&redirect_uri, &error_params(&e), client_state);
+    }
+
+    redirect_to_client(
+        &redirect_uri,
+        &[("code", code.as_ref().expose_secret())],
+        client_state,
+    )
+}
+
+// Make sure the client exists and the redirect URI is one of its own, and return both
+async fn check_client(
+    state: &AppState,
+    request: &AuthorizeRequest,
+) -> Result<(OAuthClient, String), OAuthError> {
+    let client_id = request
+        .client_id
+        .as_deref()
+        .ok_or(OAuthError::InvalidRequest("Missing client_id"))?;
+
+    let client = match state
+        .oauth_client_store
+        .read()
+        .await
+        .get_client(client_id)
+        .await
+    {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => {
+            return Err(OAuthError::InvalidRequest("Unknown client"))
+        }
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    let redirect_uri = request
+        .redirect_uri
+        .as_deref()
+        .ok_or(OAuthError::InvalidRequest("Missing redirect_uri"))?;
+
+    if !client.allows_redirect_uri(redirect_uri) {
+        return Err(OAuthError::InvalidRequest(
+            "redirect_uri is not registered for the client",
+        ));
+    }
+
+    Ok((client, redirect_uri.to_owned()))
+}
+
+// Check the parameters that errors can be redirected to the client for, and return the code
+// challenge
+fn check_request(
+    client: &OAuthClient,
+    request: &AuthorizeRequest,
+) -> Result<CodeChallenge, OAuthError> {
+    match request.response_type.as_deref() {
+        Some("code") => {}
+        Some(_) => return Err(OAuthError::UnsupportedResponseType),
+        None => return Err(OAuthError::InvalidRequest("Missing response_type")),
+    }
+
+    if !client.allows_scope(request.scope.as_deref().unwrap_or_default()) {
+        return Err(OAuthError::InvalidScope);
+    }
+
+    let code_challenge = request
+        .code_challenge
+        .clone()
+        .ok_or(OAuthError::InvalidRequest("Missing code_challenge"))?;
+
+    // The plain method would send the verifier itself through the browser
+    if request.code_challenge_method.as_deref() != Some("S256") {
+        return Err(OAuthError::InvalidRequest(
+            "code_challenge_method must be S256",
+        ));
+    }
+
+    CodeChallenge::parse(code_challenge)
+        .map_err(|_| OAuthError::InvalidRequest("Invalid code_challenge"))
+}
+
+fn error_params(e: &OAuthError) -> Vec<(&'static str, &'static str)> {
+    let mut params = vec![("error", e.code())];
+
+    if let Some(description) = e.description() {
+        params.push(("error_description", description));
+    }
+
+    params
+}
+
+// Send the user back to the client, passing on the state it gave us
+fn redirect_to_client(
+    redirect_uri: &str,
+    params: &[(&str, &str)],
+    client_state: Option<&str>,
+) -> Result<Response, OAuthError> {
+    let mut url = Url::parse(redirect_uri)
+        .wrap_err("registered redirect URI is invalid")
+        .map_err(OAuthError::UnexpectedError)?;
+
+    {
+        let mut query = url.query_pairs_mut();
+        query.extend_pairs(params);
+
+        if let Some(client_state) = client_state {
+            query.append_pair("state", client_state);
+        }
+    }
+
+    Ok(Redirect::to(url.as_str()).into_response())
+}
+
+// Send the user to the login page, which returns them to this very request once they're in
+fn redirect_to_login(uri: &Uri) -> Response {
+    let return_to = uri
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or(uri.path());
+
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("return_to", return_to)
+        .finish();
+
+    Redirect::to(&format!("/?{}", query)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeRequest {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub state: Option<String>,
+    pub scope: Option<String>,
+    // OpenID Connect clients bind the ID token to their request with it
+    pub nonce: Option<String>,
+}
@@ -0,0 +1,168 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use color_eyre::eyre::Result;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use tracing::Instrument;
+
+use super::delete_account::{confirm_identity, Confirmation};
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Password, TwoFACodeStoreError, UserStoreError},
+    utils::{
+        auth::{
+            generate_email_change_token, validate_email_change_token,
+            EMAIL_CHANGE_TOKEN_TTL_SECONDS,
+        },
+        authenticated_user::AuthenticatedSession,
+    },
+};
+
+// Start moving the account of the logged in user to a new address. The address only changes
+// once the link sent to it is confirmed, and the old address is told about the request.
+#[tracing::instrument(name = "Change email", skip_all)]
+pub async fn change_email(
+    State(state): State<AppState>,
+    AuthenticatedSession { user, .. }: AuthenticatedSession,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = user.email;
+
+    let new_email =
+        Email::parse(request.new_email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    if new_email == email {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    confirm_identity(&state, &email, Confirmation::Password(password)).await?;
+
+    // Like the password reset request, the response doesn't tell whether the new address
+    // belongs to another account
+    tokio::spawn(send_email_change_emails(state, email, new_email).in_current_span());
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+// Move the account to the new address with the token from the confirmation email. Every
+// session is revoked, so the user logs in again with the new address.
+#[tracing::instrument(name = "Confirm email change", skip_all)]
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    Json(request): Json<ConfirmEmailChangeRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (email, new_email, token_version) =
+        validate_email_change_token(&request.token, state.keyring.clone())
+            .await
+            .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    match state
+        .user_store
+        .write()
+        .await
+        .change_email(&email, &new_email, token_version)
+        .await
+    {
+        Ok(_) => {}
+        // Also when the token was used already, or the user logged out everywhere or changed
+        // their password since
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(UserStoreError::UserAlreadyExists) => return Err(AuthAPIError::UserAlreadyExists),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    {
+        let mut session_store = state.session_store.write().await;
+
+        for email in [&email, &new_email] {
+            session_store
+                .remove_sessions(email)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        }
+    }
+
+    match state
+        .two_fa_code_store
+        .write()
+        .await
+        .remove_code(&email)
+        .await
+    {
+        Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Send email change emails", skip_all)]
+async fn send_email_change_emails(state: AppState, email: Email, new_email: Email) {
+    match state.user_store.read().await.get_user(&new_email).await {
+        Ok(_) => return,
+        Err(UserStoreError::UserNotFound) => {}
+        Err(e) => {
+            tracing::error!("failed to look up user for email change: {:?}", e);
+            return;
+        }
+    }
+
+    if let Err(e) = send_confirmation_email(&state, &email, &new_email).await {
+        tracing::error!("failed to send email change confirmation email: {:?}", e);
+    }
+
+    let content = format!(
+        "We received a request to change the email address of your account to {}. \
+         If that wasn't you, change your password right away.",
+        new_email.as_ref().expose_secret()
+    );
+
+    if let Err(e) = state
+        .email_client
+        .send_email(&email, "Your email address is being changed", &content)
+        .await
+    {
+        tracing::error!("failed to send email change notification: {:?}", e);
+    }
+}
+
+#[tracing::instrument(name = "Send email change confirmation email", skip_all)]
+async fn send_confirmation_email(state: &AppState, email: &Email, new_email: &Email) -> Result<()> {
+    let token_version = state
+        .user_store
+        .read()
+        .await
+        .get_token_version(email)
+        .await?;
+    let token = generate_email_change_token(
+        email,
+        new_email,
+        token_version,
+        state.keyring.read().await.active(),
+    )?;
+
+    let content = format!(
+        "Use this token within the next {} minutes to confirm this as the new email address \
+         of your account: {}",
+        EMAIL_CHANGE_TOKEN_TTL_SECONDS / 60,
+        token.expose_secret()
+    );
+
+    state
+        .email_client
+        .send_email(new_email, "Confirm your new email address", &content)
+        .await
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    #[serde(rename = "newEmail")]
+    pub new_email: Secret<String>,
+    pub password: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmEmailChangeRequest {
+    pub token: Secret<String>,
+}
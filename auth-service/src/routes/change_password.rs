@@ -0,0 +1,123 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+use serde::Deserialize;
+use tracing::Instrument;
+
+use super::unlock_account::notify_account_locked;
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, AuthenticationMethod, LoginAttemptStoreError, Password},
+    utils::{
+        auth::start_session, authenticated_user::AuthenticatedSession, client_info::ClientInfo,
+    },
+};
+
+// Replace the password of the logged in user. Every other session is revoked,
+// the current one is kept alive with new tokens.
+#[tracing::instrument(name = "Change password", skip_all)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    AuthenticatedSession { user, .. }: AuthenticatedSession,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = user.email;
+
+    let (current_password, new_password) = match (
+        Password::parse(request.current_password),
+        Password::parse(request.new_password),
+    ) {
+        (Ok(current_password), Ok(new_password)) => (current_password, new_password),
+        _ => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    // Guessing the current password with a stolen session counts towards the login lockout
+    match state
+        .login_attempt_store
+        .read()
+        .await
+        .check_lockout(&email)
+        .await
+    {
+        Ok(_) => {}
+        Err(LoginAttemptStoreError::AccountLocked {
+            retry_after_seconds,
+        }) => {
+            return (
+                jar,
+                Err(AuthAPIError::TooManyLoginAttempts {
+                    retry_after_seconds,
+                }),
+            )
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    let (user, token_version) = {
+        let mut user_store = state.user_store.write().await;
+
+        if user_store
+            .validate_user(&email, &current_password)
+            .await
+            .is_err()
+        {
+            let mut login_attempt_store = state.login_attempt_store.write().await;
+
+            match login_attempt_store.record_failure(&email).await {
+                Ok(true) => {
+                    tokio::spawn(notify_account_locked(state.clone(), email).in_current_span());
+                }
+                Ok(false) => {}
+                Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+            }
+
+            return (jar, Err(AuthAPIError::IncorrectCredentials));
+        }
+
+        if let Err(e) = user_store.update_password(&email, new_password).await {
+            return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+        }
+
+        // Revokes every JWT and refresh token issued so far, including the current ones
+        let token_version = match user_store.increment_token_version(&email).await {
+            Ok(token_version) => token_version,
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        };
+
+        match user_store.get_user(&email).await {
+            Ok(user) => (user, token_version),
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        }
+    };
+
+    if let Err(e) = state
+        .session_store
+        .write()
+        .await
+        .remove_sessions(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    let amr = vec![AuthenticationMethod::Password];
+    let (auth_cookie, refresh_cookie) =
+        match start_session(&state, &user, token_version, amr, client).await {
+            Ok(cookies) => cookies,
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+        };
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
+
+    (updated_jar, Ok(StatusCode::OK))
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
@@ -0,0 +1,242 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::{cookie, CookieJar};
+use chrono::{DateTime, Utc};
+use secrecy::Secret;
+use serde::Deserialize;
+use tracing::Instrument;
+
+use super::{
+    unlock_account::notify_account_locked,
+    verify_2fa::{check_two_fa_code, use_recovery_code, SecondFactor},
+};
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, LoginAttemptStoreError, Password, TwoFACodeStoreError, UserStoreError,
+    },
+    utils::{
+        authenticated_user::AuthenticatedSession,
+        constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    },
+};
+
+// Delete the account of the logged in user, who has to confirm it with their password or
+// second factor. With a grace period the account is only hidden until it is purged.
+#[tracing::instrument(name = "Delete account", skip_all)]
+pub async fn delete_account(
+    State(state): State<AppState>,
+    AuthenticatedSession { user, .. }: AuthenticatedSession,
+    jar: CookieJar,
+    Json(request): Json<DeleteAccountRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = user.email;
+
+    let confirmation = match (request.password, request.two_fa_code) {
+        (Some(password), None) => Password::parse(password).map(Confirmation::Password).ok(),
+        (None, Some(two_fa_code)) => {
+            SecondFactor::parse(two_fa_code).map(Confirmation::SecondFactor)
+        }
+        _ => None,
+    };
+
+    let Some(confirmation) = confirmation else {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    };
+
+    if let Err(e) = confirm_identity(&state, &email, confirmation).await {
+        return (jar, Err(e));
+    }
+
+    let purge_at = state
+        .account_deletion_grace_period_seconds
+        .map(|grace_period_seconds| Utc::now().timestamp() + grace_period_seconds as i64);
+
+    // Removing the sessions revokes the JWT of the request along with every other one
+    if let Err(e) = remove_account_data(&state, &email, purge_at).await {
+        return (jar, Err(e));
+    }
+
+    tokio::spawn(send_account_deleted_email(state.clone(), email, purge_at).in_current_span());
+
+    let jar = jar
+        .remove(cookie::Cookie::from(JWT_COOKIE_NAME))
+        .remove(cookie::Cookie::from(REFRESH_COOKIE_NAME));
+
+    (jar, Ok(StatusCode::OK))
+}
+
+// Proof that the person changing the account is its owner, not just someone holding the session
+pub(crate) enum Confirmation {
+    Password(Password),
+    SecondFactor(SecondFactor),
+}
+
+// Check the confirmation. Failures count towards the login lockout like failed logins do.
+#[tracing::instrument(name = "Confirm identity", skip_all)]
+pub(crate) async fn confirm_identity(
+    state: &AppState,
+    email: &Email,
+    confirmation: Confirmation,
+) -> Result<(), AuthAPIError> {
+    match state
+        .login_attempt_store
+        .read()
+        .await
+        .check_lockout(email)
+        .await
+    {
+        Ok(_) => {}
+        Err(LoginAttemptStoreError::AccountLocked {
+            retry_after_seconds,
+        }) => {
+            return Err(AuthAPIError::TooManyLoginAttempts {
+                retry_after_seconds,
+            })
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let confirmed = match confirmation {
+        Confirmation::Password(password) => {
+            match state
+                .user_store
+                .read()
+                .await
+                .validate_user(email, &password)
+                .await
+            {
+                Ok(_) => true,
+                Err(UserStoreError::InvalidCredentials) => false,
+                Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+            }
+        }
+        // Emailed codes are only sent during login, so users of email 2FA confirm with a
+        // recovery code or their password
+        Confirmation::SecondFactor(SecondFactor::Code(two_fa_code)) => {
+            check_two_fa_code(state, email, &two_fa_code, None).await?
+        }
+        Confirmation::SecondFactor(SecondFactor::RecoveryCode(recovery_code)) => {
+            use_recovery_code(state, email, &recovery_code).await?
+        }
+    };
+
+    if confirmed {
+        return Ok(());
+    }
+
+    match state
+        .login_attempt_store
+        .write()
+        .await
+        .record_failure(email)
+        .await
+    {
+        Ok(true) => {
+            tokio::spawn(notify_account_locked(state.clone(), email.clone()).in_current_span());
+        }
+        Ok(false) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    Err(AuthAPIError::IncorrectCredentials)
+}
+
+// Delete the user, or schedule the deletion when `purge_at` is given, and remove everything
+// else stored about them: pending 2FA codes, failed logins, sessions and refresh tokens
+#[tracing::instrument(name = "Remove account data", skip_all)]
+async fn remove_account_data(
+    state: &AppState,
+    email: &Email,
+    purge_at: Option<i64>,
+) -> Result<(), AuthAPIError> {
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    {
+        let mut refresh_token_store = state.refresh_token_store.write().await;
+
+        for session in &sessions {
+            refresh_token_store
+                .revoke_family(&session.id)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        }
+    }
+
+    state
+        .session_store
+        .write()
+        .await
+        .remove_sessions(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    match state
+        .two_fa_code_store
+        .write()
+        .await
+        .remove_code(email)
+        .await
+    {
+        Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    state
+        .login_attempt_store
+        .write()
+        .await
+        .clear_failures(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let mut user_store = state.user_store.write().await;
+
+    let result = match purge_at {
+        Some(purge_at) => {
+            // Tokens the session checks above don't cover, e.g. refresh tokens of sessions
+            // that expired from the session store, stop working too
+            user_store
+                .increment_token_version(email)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+            user_store.schedule_deletion(email, purge_at).await
+        }
+        None => user_store.delete_user(email).await,
+    };
+
+    result.map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+#[tracing::instrument(name = "Send account deleted email", skip_all)]
+async fn send_account_deleted_email(state: AppState, email: Email, purge_at: Option<i64>) {
+    let content = match purge_at.and_then(|purge_at| DateTime::<Utc>::from_timestamp(purge_at, 0)) {
+        Some(purge_at) => format!(
+            "Your account has been deleted. Its data will be removed for good on {}.",
+            purge_at.format("%Y-%m-%d %H:%M UTC")
+        ),
+        None => "Your account and all of its data have been deleted.".to_owned(),
+    };
+
+    if let Err(e) = state
+        .email_client
+        .send_email(&email, "Your account has been deleted", &content)
+        .await
+    {
+        tracing::error!("failed to send account deleted email: {:?}", e);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountRequest {
+    // Either the password, or the 2FA code of an authenticator app or a recovery code
+    pub password: Option<Secret<String>>,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: Option<Secret<String>>,
+}
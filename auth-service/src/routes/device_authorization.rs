@@ -0,0 +1,148 @@
+use axum::{
+    extract::State,
+    http::{
+        header::{CACHE_CONTROL, PRAGMA},
+        HeaderMap, StatusCode,
+    },
+    response::IntoResponse,
+    Form, Json,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+use super::token::authenticate_oauth_client;
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, ClientGrant, DeviceAuthorization, DeviceAuthorizationStatus, DeviceCode,
+        DeviceCodeStoreError, OAuthError, UserCode,
+    },
+    utils::{
+        auth::{DEVICE_CODE_POLL_INTERVAL_SECONDS, DEVICE_CODE_TTL_SECONDS},
+        authenticated_user::AuthenticatedSession,
+        client_info::ClientInfo,
+    },
+};
+
+// Start of the OAuth 2.0 device authorization grant (RFC 8628) for clients that can't receive
+// a redirect, like command-line tools and TVs. The device shows the user code and the address
+// of the device page, and polls /token with the device code while the user approves it there.
+#[tracing::instrument(name = "Device authorization", skip_all)]
+pub async fn device_authorization(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    headers: HeaderMap,
+    Form(request): Form<DeviceAuthorizationRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let oauth_client = authenticate_oauth_client(
+        &state,
+        &headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_ref(),
+    )
+    .await?;
+
+    if !oauth_client.allows_scope(request.scope.as_deref().unwrap_or_default()) {
+        return Err(OAuthError::InvalidScope);
+    }
+
+    let device_code = DeviceCode::default();
+    let user_code = UserCode::default();
+
+    let authorization = DeviceAuthorization {
+        client_grant: ClientGrant {
+            client_id: oauth_client.id,
+            scope: request.scope,
+        },
+        user_code: user_code.clone(),
+        user_agent: client.user_agent,
+        ip_address: client.ip_address,
+        status: DeviceAuthorizationStatus::Pending,
+    };
+
+    state
+        .device_code_store
+        .write()
+        .await
+        .add_code(device_code.clone(), authorization)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    // The page is served under the issuer, like the endpoints in the OpenID configuration
+    let verification_uri = format!("{}/device", state.jwt_config.issuer.trim_end_matches('/'));
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("user_code", &user_code.formatted())
+        .finish();
+
+    let response = DeviceAuthorizationResponse {
+        device_code: device_code.as_ref().expose_secret().to_owned(),
+        user_code: user_code.formatted(),
+        verification_uri_complete: format!("{}?{}", verification_uri, query),
+        verification_uri,
+        expires_in: DEVICE_CODE_TTL_SECONDS,
+        interval: DEVICE_CODE_POLL_INTERVAL_SECONDS,
+    };
+
+    // The device code is as good as tokens once the user approves it
+    Ok((
+        [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")],
+        Json(response),
+    ))
+}
+
+// The device page sends the code the user entered here, once they are logged in
+#[tracing::instrument(name = "Verify device", skip_all)]
+pub async fn verify_device(
+    State(state): State<AppState>,
+    AuthenticatedSession { user, .. }: AuthenticatedSession,
+    Json(request): Json<VerifyDeviceRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user_code =
+        UserCode::parse(&request.user_code).map_err(|_| AuthAPIError::InvalidUserCode)?;
+
+    let status = match request.approve {
+        true => DeviceAuthorizationStatus::Approved(user.id),
+        false => DeviceAuthorizationStatus::Denied,
+    };
+
+    match state
+        .device_code_store
+        .write()
+        .await
+        .resolve(&user_code, status)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(DeviceCodeStoreError::CodeNotFound) => Err(AuthAPIError::InvalidUserCode),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceAuthorizationRequest {
+    pub client_id: Option<String>,
+    pub client_secret: Option<Secret<String>>,
+    pub scope: Option<String>,
+}
+
+// Successful response, see RFC 8628 section 3.2
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceAuthorizationResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    // The device page with the user code filled in, e.g. for a QR code
+    pub verification_uri_complete: String,
+    pub expires_in: u64,
+    // Seconds the device has to wait between polls
+    pub interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyDeviceRequest {
+    #[serde(rename = "userCode")]
+    pub user_code: String,
+    // False when the user doesn't recognize the request and denies it
+    pub approve: bool,
+}
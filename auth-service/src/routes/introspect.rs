@@ -0,0 +1,102 @@
+use axum::{extract::State, http::HeaderMap, response::IntoResponse, Form, Json};
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::AuthAPIError,
+    utils::auth::{authenticate_client, validate_any_token, AuthenticatedClient, ValidatedToken},
+};
+
+// OAuth 2.0 token introspection (RFC 7662) for resource servers and the gateways in front of
+// them. Tokens that are invalid, expired, revoked or unknown are all just inactive, and so are
+// the tokens of others to registered clients.
+#[tracing::instrument(name = "Introspect", skip_all)]
+pub async fn introspect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<IntrospectRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client = authenticate_client(&state, &headers).await?;
+
+    // Auth tokens of users and machine clients and personal access tokens are the only kinds we
+    // can introspect, so `token_type_hint` is ignored
+    let validated = validate_any_token(&request.token, &state)
+        .await
+        .ok()
+        .filter(|validated| match &client {
+            // Our services introspect tokens issued to any client
+            AuthenticatedClient::Service(_) => true,
+            AuthenticatedClient::Registered(_) => client.owns(validated.client_id()),
+        });
+
+    let response = match validated {
+        Some(ValidatedToken::User(claims, _)) => IntrospectResponse {
+            active: true,
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            nbf: Some(claims.nbf),
+            iss: Some(claims.iss),
+            aud: Some(claims.aud),
+            jti: Some(claims.jti),
+            // Only set for tokens issued to OAuth clients through /token
+            scope: claims.scope,
+            client_id: claims.client_id,
+        },
+        Some(ValidatedToken::Client(claims, _)) => IntrospectResponse {
+            active: true,
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            nbf: Some(claims.nbf),
+            iss: Some(claims.iss),
+            aud: Some(claims.aud),
+            jti: Some(claims.jti),
+            scope: Some(claims.scope),
+            client_id: Some(claims.client_id),
+        },
+        Some(ValidatedToken::PersonalAccessToken(token, user)) => IntrospectResponse {
+            active: true,
+            sub: Some(user.id.as_ref().to_owned()),
+            // Personal access tokens without an expiry are left without `exp`
+            exp: token.expires_at.map(|expires_at| expires_at as usize),
+            iat: Some(token.created_at as usize),
+            scope: token.scope(),
+            ..Default::default()
+        },
+        None => IntrospectResponse::default(),
+    };
+
+    Ok(Json(response))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IntrospectRequest {
+    pub token: Secret<String>,
+    pub token_type_hint: Option<String>,
+}
+
+// Only `active` is set for inactive tokens, so nothing about them is given away
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IntrospectResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+}
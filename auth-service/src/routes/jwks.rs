@@ -0,0 +1,19 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use jsonwebtoken::jwk::JwkSet;
+
+use crate::app_state::AppState;
+
+// Publish the public signing keys so other services can verify JWTs on their own.
+// Retired keys stay listed for as long as tokens signed with them are accepted.
+#[tracing::instrument(name = "JWKS", skip_all)]
+pub async fn jwks(State(state): State<AppState>) -> impl IntoResponse {
+    let keys = state
+        .keyring
+        .read()
+        .await
+        .keys()
+        .filter_map(|key| key.to_jwk())
+        .collect();
+
+    Json(JwkSet { keys })
+}
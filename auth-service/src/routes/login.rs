@@ -1,15 +1,23 @@
+use std::net::IpAddr;
+
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
+use tracing::Instrument;
 
+use super::unlock_account::notify_account_locked;
 use crate::app_state::AppState;
-use crate::domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode};
-use crate::utils::auth::generate_auth_cookie;
+use crate::domain::{
+    AuthAPIError, AuthenticationMethod, Email, LoginAttemptId, LoginAttemptStoreError, Password,
+    RateLimitKey, TwoFACode, TwoFAMethod, User,
+};
+use crate::utils::{auth::start_session, client_info::ClientInfo};
 
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -23,11 +31,24 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
+    if let Err(e) = check_login_limits(&state, &email, client.ip_address).await {
+        return (jar, Err(e));
+    }
+
     let user_store = &state.user_store.read().await;
 
-    match user_store.validate_user(&email, &password).await {
-        Ok(_) => {}
-        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    if user_store.validate_user(&email, &password).await.is_err() {
+        let mut login_attempt_store = state.login_attempt_store.write().await;
+
+        match login_attempt_store.record_failure(&email).await {
+            Ok(true) => {
+                tokio::spawn(notify_account_locked(state.clone(), email).in_current_span());
+            }
+            Ok(false) => {}
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        }
+
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
     }
 
     let user = match user_store.get_user(&email).await {
@@ -35,15 +56,79 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
+    // With 2FA the failures are only forgotten once the code is checked too, so that
+    // logging in again doesn't reset the count of failed codes, see verify_2fa
+    if !user.requires_2fa {
+        if let Err(e) = state
+            .login_attempt_store
+            .write()
+            .await
+            .clear_failures(&email)
+            .await
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+        }
+    }
+
+    if !user.email_verified {
+        return (jar, Err(AuthAPIError::EmailNotVerified));
+    }
+
+    let token_version = match user_store.get_token_version(&email).await {
+        Ok(token_version) => token_version,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
     match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+        true => handle_2fa(&user.email, user.two_fa_method, &state, jar).await,
+        false => handle_no_2fa(&user, token_version, client, &state, jar).await,
+    }
+}
+
+// Count the attempt against the client address and the account, and refuse locked accounts.
+// Unknown accounts are limited too, so the responses don't tell which accounts exist.
+#[tracing::instrument(name = "Check login limits", skip_all)]
+async fn check_login_limits(
+    state: &AppState,
+    email: &Email,
+    client_ip: IpAddr,
+) -> Result<(), AuthAPIError> {
+    let mut login_attempt_store = state.login_attempt_store.write().await;
+
+    for key in [
+        RateLimitKey::Ip(client_ip),
+        RateLimitKey::Account(email.clone()),
+    ] {
+        login_attempt_store
+            .record_attempt(&key)
+            .await
+            .map_err(too_many_login_attempts)?;
+    }
+
+    login_attempt_store
+        .check_lockout(email)
+        .await
+        .map_err(too_many_login_attempts)
+}
+
+fn too_many_login_attempts(e: LoginAttemptStoreError) -> AuthAPIError {
+    match e {
+        LoginAttemptStoreError::RateLimited {
+            retry_after_seconds,
+        }
+        | LoginAttemptStoreError::AccountLocked {
+            retry_after_seconds,
+        } => AuthAPIError::TooManyLoginAttempts {
+            retry_after_seconds,
+        },
+        e => AuthAPIError::UnexpectedError(e.into()),
     }
 }
 
 #[tracing::instrument(name = "Handle 2FA", skip_all)]
 async fn handle_2fa(
     email: &Email,
+    two_fa_method: TwoFAMethod,
     state: &AppState,
     jar: CookieJar,
 ) -> (
@@ -51,6 +136,8 @@ async fn handle_2fa(
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
     let login_attempt_id = LoginAttemptId::default();
+    // Users of an authenticator app are never sent this code, /verify-2fa checks
+    // the code of their app instead. The login attempt is tracked the same way.
     let two_fa_code = TwoFACode::default();
 
     if let Err(e) = state
@@ -63,17 +150,20 @@ async fn handle_2fa(
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
-    if let Err(e) = state
-        .email_client
-        .send_email(&email, "2AF Code", two_fa_code.as_ref().expose_secret())
-        .await
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError(e)));
+    if two_fa_method == TwoFAMethod::Email {
+        if let Err(e) = state
+            .email_client
+            .send_email(email, "2AF Code", two_fa_code.as_ref().expose_secret())
+            .await
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError(e)));
+        }
     }
 
     let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
         message: "2FA required".to_string(),
         login_attempt_id: login_attempt_id.as_ref().expose_secret().to_owned(),
+        two_fa_method,
     }));
 
     (jar, Ok((StatusCode::PARTIAL_CONTENT, response)))
@@ -81,18 +171,23 @@ async fn handle_2fa(
 
 #[tracing::instrument(name = "Handle No 2FA", skip_all)]
 async fn handle_no_2fa(
-    email: &Email,
+    user: &User,
+    token_version: i32,
+    client: ClientInfo,
+    state: &AppState,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    let auth_cookie = match generate_auth_cookie(email) {
-        Ok(cookie) => cookie,
-        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
-    };
+    let amr = vec![AuthenticationMethod::Password];
+    let (auth_cookie, refresh_cookie) =
+        match start_session(state, user, token_version, amr, client).await {
+            Ok(cookies) => cookies,
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+        };
 
-    let updated_jar = jar.add(auth_cookie);
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
 
     (
         updated_jar,
@@ -118,4 +213,7 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    // Tells the client where the user finds the code
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: TwoFAMethod,
 }
@@ -4,25 +4,27 @@ use secrecy::Secret;
 
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{
+        AuthAPIError, RefreshToken, RefreshTokenFamilyId, RefreshTokenStoreError, SessionStoreError,
+    },
+    utils::{
+        authenticated_user::{AuthenticatedSession, AuthenticatedUser, UserCredential},
+        constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    },
 };
 
+// Log out the session of the JWT, whether it was sent as a bearer token or in the auth cookie
 #[tracing::instrument(name = "Logout", skip_all)]
 pub async fn logout(
     State(state): State<AppState>,
+    AuthenticatedUser {
+        token, credential, ..
+    }: AuthenticatedUser,
     jar: CookieJar,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
-    let cookie = match jar.get(JWT_COOKIE_NAME) {
-        Some(cookie) => cookie,
-        None => return (jar, Err(AuthAPIError::MissingToken)),
-    };
-
-    let token = Secret::new(cookie.value().to_owned());
-
-    let _ = match validate_token(&token, state.banned_token_store.clone()).await {
-        Ok(claims) => claims,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    // Personal access tokens are revoked through routes::personal_access_tokens
+    let UserCredential::Jwt(claims) = credential else {
+        return (jar, Err(AuthAPIError::InvalidToken));
     };
 
     if let Err(e) = state
@@ -35,7 +37,96 @@ pub async fn logout(
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
-    let jar = jar.remove(cookie::Cookie::from(JWT_COOKIE_NAME));
+    if let Err(e) = revoke_refresh_token(&state, &jar).await {
+        return (jar, Err(e));
+    }
+
+    if let Err(e) = remove_session(&state, &claims.sid).await {
+        return (jar, Err(e));
+    }
+
+    let jar = jar
+        .remove(cookie::Cookie::from(JWT_COOKIE_NAME))
+        .remove(cookie::Cookie::from(REFRESH_COOKIE_NAME));
+
+    (jar, Ok(StatusCode::OK))
+}
+
+// Log the user out on every device by revoking all JWTs and refresh tokens issued so far
+#[tracing::instrument(name = "Logout all", skip_all)]
+pub async fn logout_all(
+    State(state): State<AppState>,
+    AuthenticatedSession { user, .. }: AuthenticatedSession,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = user.email;
+
+    if let Err(e) = state
+        .user_store
+        .write()
+        .await
+        .increment_token_version(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    if let Err(e) = state
+        .session_store
+        .write()
+        .await
+        .remove_sessions(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    let jar = jar
+        .remove(cookie::Cookie::from(JWT_COOKIE_NAME))
+        .remove(cookie::Cookie::from(REFRESH_COOKIE_NAME));
 
     (jar, Ok(StatusCode::OK))
 }
+
+// Remove the session the JWT was issued to
+#[tracing::instrument(name = "Remove session", skip_all)]
+async fn remove_session(state: &AppState, session_id: &str) -> Result<(), AuthAPIError> {
+    let session_id = RefreshTokenFamilyId::parse(session_id.to_owned())
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    match state
+        .session_store
+        .write()
+        .await
+        .remove_session(&session_id)
+        .await
+    {
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => Ok(()),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Revoke the family of the refresh token sent along with the JWT, if there is one
+#[tracing::instrument(name = "Revoke refresh token", skip_all)]
+async fn revoke_refresh_token(state: &AppState, jar: &CookieJar) -> Result<(), AuthAPIError> {
+    let token = match jar
+        .get(REFRESH_COOKIE_NAME)
+        .and_then(|cookie| RefreshToken::parse(Secret::new(cookie.value().to_owned())).ok())
+    {
+        Some(token) => token,
+        None => return Ok(()),
+    };
+
+    let mut refresh_token_store = state.refresh_token_store.write().await;
+
+    let record = match refresh_token_store.get_token(&token).await {
+        Ok(record) => record,
+        Err(RefreshTokenStoreError::TokenNotFound) => return Ok(()),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    refresh_token_store
+        .revoke_family(&record.family_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
@@ -1,12 +1,56 @@
+mod authorize;
+mod change_email;
+mod change_password;
+mod delete_account;
+mod device_authorization;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod oauth_clients;
+mod openid_configuration;
+mod password_reset;
+mod personal_access_tokens;
+mod recovery_codes;
+mod refresh;
+mod revoke;
+mod roles;
+mod rotate_signing_key;
+mod sessions;
 mod signup;
+mod token;
+mod totp;
+mod unlock_account;
+mod userinfo;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
 
 // re-export items from sub-modules
+pub use authorize::*;
+pub use change_email::*;
+pub use change_password::*;
+pub use delete_account::*;
+pub use device_authorization::*;
+pub use introspect::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use oauth_clients::*;
+pub use openid_configuration::*;
+pub use password_reset::*;
+pub use personal_access_tokens::*;
+pub use recovery_codes::*;
+pub use refresh::*;
+pub use revoke::*;
+pub use roles::*;
+pub use rotate_signing_key::*;
+pub use sessions::*;
 pub use signup::*;
+pub use token::*;
+pub use totp::*;
+pub use unlock_account::*;
+pub use userinfo::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
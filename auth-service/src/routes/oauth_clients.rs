@@ -0,0 +1,62 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, OAuthClient},
+};
+
+// Register an application that logs its users in through /authorize and /token, or a machine
+// client that gets tokens of its own from /token. The secret of a confidential client is only
+// ever shown in this response. Only reached by admins who have the clients:write permission.
+#[tracing::instrument(name = "Register OAuth client", skip_all)]
+pub async fn register_oauth_client(
+    State(state): State<AppState>,
+    Json(request): Json<RegisterOAuthClientRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (client, client_secret) = OAuthClient::new(
+        request.name,
+        request.redirect_uris,
+        request.scopes,
+        request.confidential,
+    )
+    .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let response = RegisterOAuthClientResponse {
+        client_id: client.id.clone(),
+        client_secret: client_secret.map(|secret| secret.expose_secret().to_owned()),
+    };
+
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .add_client(client)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    tracing::info!("registered OAuth client {}", response.client_id);
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[derive(Deserialize)]
+pub struct RegisterOAuthClientRequest {
+    pub name: String,
+    #[serde(rename = "redirectUris", default)]
+    pub redirect_uris: Vec<String>,
+    // Scopes the client may request from its users, or for itself as a machine client
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // Whether the client can keep a secret, i.e. has a backend
+    pub confidential: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterOAuthClientResponse {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[serde(rename = "clientSecret", skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+}
@@ -0,0 +1,86 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use jsonwebtoken::Algorithm;
+use serde::{Deserialize, Serialize};
+
+use super::token::DEVICE_CODE_GRANT;
+use crate::{
+    app_state::AppState,
+    utils::constants::{EMAIL_SCOPE, OPENID_SCOPE},
+};
+
+// OpenID Connect Discovery 1.0 provider metadata, which OpenID Connect client libraries configure
+// themselves with. The endpoints are listed under the issuer, so JWT_ISSUER has to be the public
+// URL of this service for them to work.
+#[tracing::instrument(name = "OpenID configuration", skip_all)]
+pub async fn openid_configuration(State(state): State<AppState>) -> impl IntoResponse {
+    let issuer = state.jwt_config.issuer.clone();
+    let base_url = issuer.trim_end_matches('/');
+
+    // ID tokens signed with a retired key stay valid until they expire
+    let mut signing_algorithms = Vec::new();
+    for key in state.keyring.read().await.keys() {
+        if !signing_algorithms.contains(&key.algorithm()) {
+            signing_algorithms.push(key.algorithm());
+        }
+    }
+
+    Json(OpenIdConfiguration {
+        authorization_endpoint: format!("{}/authorize", base_url),
+        token_endpoint: format!("{}/token", base_url),
+        userinfo_endpoint: format!("{}/userinfo", base_url),
+        device_authorization_endpoint: format!("{}/device/code", base_url),
+        jwks_uri: format!("{}/.well-known/jwks.json", base_url),
+        issuer,
+        scopes_supported: vec![OPENID_SCOPE.to_owned(), EMAIL_SCOPE.to_owned()],
+        response_types_supported: vec!["code".to_owned()],
+        grant_types_supported: vec![
+            "authorization_code".to_owned(),
+            "refresh_token".to_owned(),
+            "client_credentials".to_owned(),
+            DEVICE_CODE_GRANT.to_owned(),
+        ],
+        subject_types_supported: vec!["public".to_owned()],
+        id_token_signing_alg_values_supported: signing_algorithms,
+        token_endpoint_auth_methods_supported: vec![
+            "client_secret_basic".to_owned(),
+            "client_secret_post".to_owned(),
+            "none".to_owned(),
+        ],
+        code_challenge_methods_supported: vec!["S256".to_owned()],
+        claims_supported: [
+            "iss",
+            "sub",
+            "aud",
+            "exp",
+            "iat",
+            "auth_time",
+            "nonce",
+            "amr",
+            "email",
+            "email_verified",
+        ]
+        .map(str::to_owned)
+        .to_vec(),
+    })
+}
+
+// See OpenID Connect Discovery 1.0 section 3
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    // See RFC 8628 section 4
+    pub device_authorization_endpoint: String,
+    pub jwks_uri: String,
+    pub scopes_supported: Vec<String>,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<Algorithm>,
+    // Public clients send no secret at all
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
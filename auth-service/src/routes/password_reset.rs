@@ -0,0 +1,132 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use tracing::Instrument;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, Password, PasswordResetToken, PasswordResetTokenStoreError,
+        UserStoreError,
+    },
+    utils::auth::PASSWORD_RESET_TOKEN_TTL_SECONDS,
+};
+
+#[tracing::instrument(name = "Request password reset", skip_all)]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // The response must not reveal whether the account exists, not even through its timing,
+    // so the lookup and the email are handled in the background.
+    tokio::spawn(send_password_reset_email(state, email).in_current_span());
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[tracing::instrument(name = "Send password reset email", skip_all)]
+async fn send_password_reset_email(state: AppState, email: Email) {
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return,
+        Err(e) => {
+            tracing::error!("failed to look up user for password reset: {:?}", e);
+            return;
+        }
+    }
+
+    let token = PasswordResetToken::default();
+
+    if let Err(e) = state
+        .password_reset_token_store
+        .write()
+        .await
+        .add_token(token.clone(), email.clone())
+        .await
+    {
+        tracing::error!("failed to store password reset token: {:?}", e);
+        return;
+    }
+
+    let content = format!(
+        "Use this token to reset your password within the next {} minutes: {}",
+        PASSWORD_RESET_TOKEN_TTL_SECONDS / 60,
+        token.as_ref().expose_secret()
+    );
+
+    if let Err(e) = state
+        .email_client
+        .send_email(&email, "Password reset", &content)
+        .await
+    {
+        tracing::error!("failed to send password reset email: {:?}", e);
+    }
+}
+
+#[tracing::instrument(name = "Confirm password reset", skip_all)]
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = PasswordResetToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Check the new password before using up the token, so a typo doesn't cost the user a new email
+    let password =
+        Password::parse(request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let email = match state
+        .password_reset_token_store
+        .write()
+        .await
+        .take_token(&token)
+        .await
+    {
+        Ok(email) => email,
+        Err(PasswordResetTokenStoreError::TokenNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let mut user_store = state.user_store.write().await;
+
+    match user_store.update_password(&email, password).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // The token was delivered to the address, which proves the user owns it
+    if let Err(e) = user_store.verify_email(&email).await {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    // Whoever made the reset necessary may still hold a session, so revoke them all
+    if let Err(e) = user_store.increment_token_version(&email).await {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    if let Err(e) = state
+        .session_store
+        .write()
+        .await
+        .remove_sessions(&email)
+        .await
+    {
+        return Err(AuthAPIError::UnexpectedError(e.into()));
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub token: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
@@ -0,0 +1,135 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, PersonalAccessToken, PersonalAccessTokenStoreError},
+    utils::authenticated_user::AuthenticatedSession,
+};
+
+// Create a long-lived token for the logged in user's scripts, which send it as a bearer token
+// instead of the auth cookie. The token is only ever shown in this response.
+#[tracing::instrument(name = "Create personal access token", skip_all)]
+pub async fn create_personal_access_token(
+    State(state): State<AppState>,
+    AuthenticatedSession { user, .. }: AuthenticatedSession,
+    Json(request): Json<CreatePersonalAccessTokenRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let expires_in_seconds = request
+        .expires_in_days
+        .map(|days| i64::from(days) * SECONDS_PER_DAY);
+
+    let (token, secret) =
+        PersonalAccessToken::new(user.id, request.name, request.scopes, expires_in_seconds)
+            .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let response = CreatePersonalAccessTokenResponse {
+        token: secret.expose_secret().to_owned(),
+        details: PersonalAccessTokenResponse::from(&token),
+    };
+
+    state
+        .personal_access_token_store
+        .write()
+        .await
+        .add_token(token)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+// The logged in user's tokens, newest first. Expired tokens are listed too.
+#[tracing::instrument(name = "List personal access tokens", skip_all)]
+pub async fn list_personal_access_tokens(
+    State(state): State<AppState>,
+    AuthenticatedSession { user, .. }: AuthenticatedSession,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let tokens = state
+        .personal_access_token_store
+        .read()
+        .await
+        .get_tokens(&user.id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .iter()
+        .map(PersonalAccessTokenResponse::from)
+        .collect::<Vec<_>>();
+
+    Ok((StatusCode::OK, Json(tokens)))
+}
+
+// Revoke one of the logged in user's tokens. It stops working right away.
+#[tracing::instrument(name = "Revoke personal access token", skip_all)]
+pub async fn revoke_personal_access_token(
+    State(state): State<AppState>,
+    AuthenticatedSession { user, .. }: AuthenticatedSession,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    // Tokens of other users are reported as missing, so their IDs can't be probed
+    match state
+        .personal_access_token_store
+        .write()
+        .await
+        .remove_token(&user.id, &id)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(PersonalAccessTokenStoreError::TokenNotFound) => {
+            Err(AuthAPIError::PersonalAccessTokenNotFound)
+        }
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreatePersonalAccessTokenRequest {
+    // Reminds the user what the token is for
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // Tokens without it don't expire
+    #[serde(rename = "expiresInDays")]
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatePersonalAccessTokenResponse {
+    pub token: String,
+    #[serde(flatten)]
+    pub details: PersonalAccessTokenResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PersonalAccessTokenResponse {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: Option<i64>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: Option<i64>,
+}
+
+impl From<&PersonalAccessToken> for PersonalAccessTokenResponse {
+    fn from(token: &PersonalAccessToken) -> Self {
+        Self {
+            id: token.id.clone(),
+            name: token.name.clone(),
+            scopes: token.scopes.clone(),
+            created_at: token.created_at,
+            last_used_at: token.last_used_at,
+            expires_at: token.expires_at,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, RecoveryCode},
+    utils::authenticated_user::{AuthenticatedSession, AuthenticatedUser},
+};
+
+// Replace the recovery codes of the logged-in user, for when they used up or lost the old ones
+#[tracing::instrument(name = "Regenerate recovery codes", skip_all)]
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    AuthenticatedSession { user, .. }: AuthenticatedSession,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = user.email;
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if !user.requires_2fa {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    let recovery_codes = issue_recovery_codes(&state, &email).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesResponse { recovery_codes }),
+    ))
+}
+
+#[tracing::instrument(name = "Count recovery codes", skip_all)]
+pub async fn count_recovery_codes(
+    State(state): State<AppState>,
+    AuthenticatedUser { user, .. }: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = user.email;
+
+    let remaining = state
+        .user_store
+        .read()
+        .await
+        .count_recovery_codes(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesCountResponse { remaining }),
+    ))
+}
+
+// Store a new set of recovery codes for the user. The plain codes are returned so they
+// can be shown once; only their hashes are kept.
+pub(crate) async fn issue_recovery_codes(
+    state: &AppState,
+    email: &Email,
+) -> Result<Vec<String>, AuthAPIError> {
+    let codes = RecoveryCode::generate_set();
+
+    let plain_codes = codes
+        .iter()
+        .map(|code| code.as_ref().expose_secret().to_owned())
+        .collect();
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_recovery_codes(email, codes)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(plain_codes)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesCountResponse {
+    pub remaining: usize,
+}
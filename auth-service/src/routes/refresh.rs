@@ -0,0 +1,185 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use secrecy::Secret;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, RefreshToken, RefreshTokenFamilyId, RefreshTokenRecord, RefreshTokenStore,
+        RefreshTokenStoreError, SessionStoreError, UserRoles, UserStoreError,
+    },
+    utils::{
+        auth::{create_auth_cookie, create_refresh_cookie, generate_access_token},
+        constants::REFRESH_COOKIE_NAME,
+    },
+};
+
+#[tracing::instrument(name = "Refresh", skip_all)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let cookie = match jar.get(REFRESH_COOKIE_NAME) {
+        Some(cookie) => cookie,
+        None => return (jar, Err(AuthAPIError::MissingToken)),
+    };
+
+    let token = match RefreshToken::parse(Secret::new(cookie.value().to_owned())) {
+        Ok(token) => token,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // Tokens issued to OAuth clients are refreshed at /token instead
+    let (auth_token, new_token, _) = match rotate_refresh_token(&state, &token, None).await {
+        Ok(rotated) => rotated,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let updated_jar = jar
+        .add(create_auth_cookie(auth_token))
+        .add(create_refresh_cookie(&new_token));
+
+    (updated_jar, Ok(StatusCode::OK))
+}
+
+// Replace the refresh token with the next one of its family, and issue a new JWT auth token
+// for the session. The token has to have been issued to the given OAuth client, or on login
+// when there is none. Returns both tokens along with the record of the family.
+#[tracing::instrument(name = "Rotate refresh token", skip_all)]
+pub(crate) async fn rotate_refresh_token(
+    state: &AppState,
+    token: &RefreshToken,
+    client_id: Option<&str>,
+) -> Result<(Secret<String>, RefreshToken, RefreshTokenRecord), AuthAPIError> {
+    // Hold the write lock for the whole rotation so the same token can't be rotated twice
+    let mut refresh_token_store = state.refresh_token_store.write().await;
+
+    let record = match refresh_token_store.get_token(token).await {
+        Ok(record) => record,
+        Err(RefreshTokenStoreError::TokenNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if record
+        .client_grant
+        .as_ref()
+        .map(|grant| grant.client_id.as_str())
+        != client_id
+    {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    let family_head = match refresh_token_store.get_family_head(&record.family_id).await {
+        Ok(family_head) => family_head,
+        Err(RefreshTokenStoreError::TokenNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if &family_head != token {
+        // An already rotated token came back, so someone else may hold a copy of it.
+        // Revoke the whole family, which logs out both the attacker and the legitimate user.
+        tracing::warn!("Refresh token reuse detected, revoking token family");
+
+        end_session(state, &mut *refresh_token_store, &record.family_id).await?;
+
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    let (user, token_version) = {
+        let user_store = state.user_store.read().await;
+
+        let result = match user_store.get_user(&record.email).await {
+            Ok(user) => user_store
+                .get_token_version(&record.email)
+                .await
+                .map(|token_version| (user, token_version)),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(result) => result,
+            Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+            Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+        }
+    };
+
+    // The user revoked all their tokens (e.g. by resetting the password) after this family started
+    if record.token_version != token_version {
+        end_session(state, &mut *refresh_token_store, &record.family_id).await?;
+
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    // The session was revoked, e.g. from another device
+    match state
+        .session_store
+        .write()
+        .await
+        .touch_session(&record.family_id)
+        .await
+    {
+        Ok(_) => {}
+        Err(SessionStoreError::SessionNotFound) => {
+            end_session(state, &mut *refresh_token_store, &record.family_id).await?;
+
+            return Err(AuthAPIError::InvalidToken);
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // Tokens issued to clients don't carry roles, see generate_access_token
+    let roles = match record.client_grant {
+        Some(_) => UserRoles::default(),
+        None => state
+            .role_store
+            .read()
+            .await
+            .get_user_roles(&user.id)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?,
+    };
+
+    let auth_token = generate_access_token(
+        &user,
+        &roles,
+        token_version,
+        &record.family_id,
+        record.client_grant.as_ref(),
+        &state.jwt_config,
+        state.keyring.read().await.active(),
+    )
+    .map_err(AuthAPIError::UnexpectedError)?;
+
+    let new_token = RefreshToken::default();
+
+    refresh_token_store
+        .add_token(new_token.clone(), record.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((auth_token, new_token, record))
+}
+
+// Revoke the token family along with the session it belongs to
+#[tracing::instrument(name = "End session", skip_all)]
+pub(crate) async fn end_session(
+    state: &AppState,
+    refresh_token_store: &mut (dyn RefreshTokenStore + Send + Sync),
+    family_id: &RefreshTokenFamilyId,
+) -> Result<(), AuthAPIError> {
+    refresh_token_store
+        .revoke_family(family_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    match state
+        .session_store
+        .write()
+        .await
+        .remove_session(family_id)
+        .await
+    {
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => Ok(()),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
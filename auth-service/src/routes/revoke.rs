@@ -0,0 +1,139 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Form,
+};
+use secrecy::Secret;
+use serde::Deserialize;
+
+use super::refresh::end_session;
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, PersonalAccessTokenStoreError, RefreshToken, RefreshTokenStoreError, User,
+    },
+    utils::auth::{authenticate_client, validate_any_token, AuthenticatedClient, ValidatedToken},
+};
+
+// OAuth 2.0 token revocation (RFC 7009) for clients that can't use the cookie based /logout.
+// Clients can only revoke the tokens issued to them. Succeeds for tokens that are invalid,
+// already revoked or another client's too, so clients have nothing to handle and learn nothing
+// about the token.
+#[tracing::instrument(name = "Revoke", skip_all)]
+pub async fn revoke(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<RevokeRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client = authenticate_client(&state, &headers).await?;
+
+    // The hint only decides which kind of token is looked for first
+    match request.token_type_hint.as_deref() {
+        Some(REFRESH_TOKEN_HINT) => {
+            if !revoke_refresh_token(&state, &client, &request.token).await? {
+                revoke_access_token(&state, &client, &request.token).await?;
+            }
+        }
+        _ => {
+            if !revoke_access_token(&state, &client, &request.token).await? {
+                revoke_refresh_token(&state, &client, &request.token).await?;
+            }
+        }
+    }
+
+    Ok(StatusCode::OK)
+}
+
+const REFRESH_TOKEN_HINT: &str = "refresh_token";
+
+// Ban the JWT auth token. Only valid tokens are banned, so the store can't be filled with
+// junk. Personal access tokens are removed instead, they live until they are. Returns whether
+// the token was one.
+#[tracing::instrument(name = "Revoke access token", skip_all)]
+async fn revoke_access_token(
+    state: &AppState,
+    client: &AuthenticatedClient,
+    token: &Secret<String>,
+) -> Result<bool, AuthAPIError> {
+    let validated = match validate_any_token(token, state).await {
+        Ok(validated) => validated,
+        Err(_) => return Ok(false),
+    };
+
+    // Tokens of other clients are left alone, see RFC 7009 section 2.1
+    if !client.owns(validated.client_id()) {
+        return Ok(true);
+    }
+
+    if let ValidatedToken::PersonalAccessToken(token, user) = validated {
+        return remove_personal_access_token(state, &user, &token.id).await;
+    }
+
+    state
+        .banned_token_store
+        .write()
+        .await
+        .add_token(token.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(true)
+}
+
+async fn remove_personal_access_token(
+    state: &AppState,
+    user: &User,
+    id: &str,
+) -> Result<bool, AuthAPIError> {
+    match state
+        .personal_access_token_store
+        .write()
+        .await
+        .remove_token(&user.id, id)
+        .await
+    {
+        // Removed by a concurrent request in the meantime
+        Ok(()) | Err(PersonalAccessTokenStoreError::TokenNotFound) => Ok(true),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// End the session of the refresh token, which revokes the JWT auth tokens issued to it as
+// well. Returns whether the token was a known refresh token.
+#[tracing::instrument(name = "Revoke refresh token", skip_all)]
+async fn revoke_refresh_token(
+    state: &AppState,
+    client: &AuthenticatedClient,
+    token: &Secret<String>,
+) -> Result<bool, AuthAPIError> {
+    let Ok(token) = RefreshToken::parse(token.clone()) else {
+        return Ok(false);
+    };
+
+    let mut refresh_token_store = state.refresh_token_store.write().await;
+
+    let record = match refresh_token_store.get_token(&token).await {
+        Ok(record) => record,
+        Err(RefreshTokenStoreError::TokenNotFound) => return Ok(false),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let grant_client_id = record
+        .client_grant
+        .as_ref()
+        .map(|grant| grant.client_id.as_str());
+    if !client.owns(grant_client_id) {
+        return Ok(true);
+    }
+
+    end_session(state, &mut *refresh_token_store, &record.family_id).await?;
+
+    Ok(true)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeRequest {
+    pub token: Secret<String>,
+    pub token_type_hint: Option<String>,
+}
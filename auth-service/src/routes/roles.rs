@@ -0,0 +1,103 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Role, RoleStoreError, UserId, UserStoreError},
+};
+
+// Create a role, or replace the permissions of an existing one. Users who have it get the new
+// permissions with their next JWT auth token. Like the other role routes, only for users with
+// the roles:write permission, see RequirePermission.
+#[tracing::instrument(name = "Set role", skip_all)]
+pub async fn set_role(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(request): Json<SetRoleRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let role =
+        Role::new(name, request.permissions).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let name = role.name.clone();
+
+    state
+        .role_store
+        .write()
+        .await
+        .set_role(role)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    tracing::info!("set role {}", name);
+
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Assign role", skip_all)]
+pub async fn assign_role(
+    State(state): State<AppState>,
+    Path((user_id, role)): Path<(String, String)>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user_id = find_user(&state, user_id).await?;
+
+    match state
+        .role_store
+        .write()
+        .await
+        .assign_role(&user_id, &role)
+        .await
+    {
+        Ok(()) => {}
+        Err(RoleStoreError::RoleNotFound) => return Err(AuthAPIError::RoleNotFound),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    tracing::info!("assigned role {}", role);
+
+    Ok(StatusCode::OK)
+}
+
+// RoleNotFound when the user doesn't have the role
+#[tracing::instrument(name = "Unassign role", skip_all)]
+pub async fn unassign_role(
+    State(state): State<AppState>,
+    Path((user_id, role)): Path<(String, String)>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user_id = find_user(&state, user_id).await?;
+
+    match state
+        .role_store
+        .write()
+        .await
+        .unassign_role(&user_id, &role)
+        .await
+    {
+        Ok(()) => {}
+        Err(RoleStoreError::RoleNotFound) => return Err(AuthAPIError::RoleNotFound),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    tracing::info!("unassigned role {}", role);
+
+    Ok(StatusCode::OK)
+}
+
+// The ID of the user, as returned by /verify-token, if there is such a user
+async fn find_user(state: &AppState, user_id: String) -> Result<UserId, AuthAPIError> {
+    let user_id = UserId::parse(user_id).map_err(|_| AuthAPIError::UserNotFound)?;
+
+    match state.user_store.read().await.get_user_by_id(&user_id).await {
+        Ok(_) => Ok(user_id),
+        Err(UserStoreError::UserNotFound) => Err(AuthAPIError::UserNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetRoleRequest {
+    pub permissions: Vec<String>,
+}
@@ -0,0 +1,102 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{Duration, Utc};
+use jsonwebtoken::Algorithm;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, SigningKey, SigningKeyStoreError},
+    utils::auth::{EMAIL_VERIFICATION_TOKEN_TTL_SECONDS, SIGNING_KEY_ACTIVATION_DELAY_SECONDS},
+};
+
+// Make a new key the signing key of every instance, only reached by admins who have the
+// signing_keys:rotate permission. The key is published right away and signs tokens once every
+// instance has picked it up. The previous key keeps verifying the tokens it signed until they
+// expire.
+#[tracing::instrument(name = "Rotate signing key", skip_all)]
+pub async fn rotate_signing_key(
+    State(state): State<AppState>,
+    Json(request): Json<RotateSigningKeyRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let algorithm = match request.algorithm {
+        Some(algorithm) => algorithm,
+        None => state.keyring.read().await.active().algorithm(),
+    };
+
+    let key = match (algorithm, request.key) {
+        (Algorithm::EdDSA, Some(pem)) => {
+            SigningKey::from_ed25519_pem(&pem).map_err(|_| AuthAPIError::InvalidSigningKey)?
+        }
+        (Algorithm::EdDSA, None) => {
+            SigningKey::generate_ed25519().map_err(AuthAPIError::UnexpectedError)?
+        }
+        // Every verifier has to be given the new secret, so it can not be generated here
+        (Algorithm::HS256, Some(secret)) if !secret.expose_secret().is_empty() => {
+            SigningKey::from_secret(&secret)
+        }
+        _ => return Err(AuthAPIError::InvalidSigningKey),
+    };
+
+    let kid = key.kid().to_owned();
+    let now = Utc::now();
+    let activates_at = now + Duration::seconds(SIGNING_KEY_ACTIVATION_DELAY_SECONDS);
+
+    match state
+        .signing_key_store
+        .write()
+        .await
+        .rotate(key, activates_at)
+        .await
+    {
+        Ok(()) => {}
+        Err(SigningKeyStoreError::KeyAlreadyExists) => {
+            tracing::warn!("signing key rotation rejected: key {} already exists", kid);
+            return Err(AuthAPIError::InvalidSigningKey);
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // The other instances pick the key up when they reload the keyring, this one right away
+    let retired_after = now - Duration::seconds(EMAIL_VERIFICATION_TOKEN_TTL_SECONDS);
+    let entries = state
+        .signing_key_store
+        .read()
+        .await
+        .get_keys(retired_after)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state
+        .keyring
+        .write()
+        .await
+        .replace_entries(entries)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    tracing::info!("rotated signing key, new key id: {}", kid);
+
+    Ok((
+        StatusCode::OK,
+        Json(RotateSigningKeyResponse {
+            kid,
+            activates_at: activates_at.timestamp(),
+        }),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct RotateSigningKeyRequest {
+    // Defaults to the algorithm of the active key
+    pub algorithm: Option<Algorithm>,
+    // PKCS#8 PEM for EdDSA (generated when omitted), the shared secret for HS256
+    pub key: Option<Secret<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RotateSigningKeyResponse {
+    pub kid: String,
+    // When the key starts signing tokens, as a Unix timestamp
+    #[serde(rename = "activatesAt")]
+    pub activates_at: i64,
+}
@@ -0,0 +1,106 @@
+use std::net::IpAddr;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, RefreshTokenFamilyId, Session, SessionStoreError},
+    utils::authenticated_user::AuthenticatedSession,
+};
+
+// Where the logged in user is logged in, most recently used first
+#[tracing::instrument(name = "List sessions", skip_all)]
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    AuthenticatedSession {
+        session: current_session,
+        ..
+    }: AuthenticatedSession,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(&current_session.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(|session| SessionResponse::new(session, &current_session.id))
+        .collect::<Vec<_>>();
+
+    Ok((StatusCode::OK, Json(sessions)))
+}
+
+// Log out one device of the logged in user. Its JWT stops working right away
+// and its refresh token can't be used anymore.
+#[tracing::instrument(name = "Revoke session", skip_all)]
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    AuthenticatedSession {
+        session: current_session,
+        ..
+    }: AuthenticatedSession,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let id = RefreshTokenFamilyId::parse(id).map_err(|_| AuthAPIError::SessionNotFound)?;
+
+    let mut session_store = state.session_store.write().await;
+
+    // Sessions of other users are reported as missing, so their IDs can't be probed
+    match session_store.get_session(&id).await {
+        Ok(session) if session.email == current_session.email => {}
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => {
+            return Err(AuthAPIError::SessionNotFound)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_family(&id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    session_store
+        .remove_session(&id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "lastSeenAt")]
+    pub last_seen_at: i64,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "ipAddress")]
+    pub ip_address: IpAddr,
+    // Whether this is the session the request was made with
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current_session_id: &RefreshTokenFamilyId) -> Self {
+        Self {
+            current: session.id == *current_session_id,
+            id: session.id.as_ref().to_owned(),
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+        }
+    }
+}
@@ -4,9 +4,11 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, Password, User},
+    domain::{AuthAPIError, Email, Password, User, UserStoreError},
 };
 
+use super::{recovery_codes::issue_recovery_codes, verify_email::send_verification_email};
+
 #[tracing::instrument(name = "Signup", skip_all)]
 pub async fn signup(
     State(state): State<AppState>,
@@ -19,21 +21,35 @@ pub async fn signup(
     let password =
         Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
 
-    let user = User::new(email, password, request.requires_2fa);
+    let user = User::new(email.clone(), password, request.requires_2fa);
+
+    {
+        let mut user_store = state.user_store.write().await;
 
-    let mut user_store = state.user_store.write().await;
+        if user_store.get_user(&user.email).await.is_ok() {
+            return Err(AuthAPIError::UserAlreadyExists);
+        }
 
-    if user_store.get_user(&user.email).await.is_ok() {
-        return Err(AuthAPIError::UserAlreadyExists);
+        user_store.add_user(user).await.map_err(|e| match e {
+            UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
     }
 
-    user_store
-        .add_user(user)
-        .await
-        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    // Without recovery codes, losing access to the mailbox would lock a 2FA user out for good
+    let recovery_codes = match request.requires_2fa {
+        true => Some(issue_recovery_codes(&state, &email).await?),
+        false => None,
+    };
+
+    // The account exists now, so a failed email must not fail the signup; it can be resent
+    if let Err(e) = send_verification_email(&state, &email).await {
+        tracing::error!("failed to send verification email: {:?}", e);
+    }
 
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
+        recovery_codes,
     });
 
     Ok((StatusCode::CREATED, response))
@@ -50,4 +66,11 @@ pub struct SignupRequest {
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SignupResponse {
     pub message: String,
+    // Only for users who require 2FA
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recovery_codes: Option<Vec<String>>,
 }
@@ -0,0 +1,406 @@
+use axum::{
+    extract::State,
+    http::{
+        header::{CACHE_CONTROL, PRAGMA},
+        HeaderMap,
+    },
+    response::IntoResponse,
+    Form, Json,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use super::refresh::rotate_refresh_token;
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, AuthorizationCode, AuthorizationCodeStoreError, DeviceAuthorizationStatus,
+        DeviceCode, DeviceCodeStoreError, OAuthClient, OAuthClientStoreError, OAuthError,
+        RefreshToken, UserStoreError,
+    },
+    utils::{
+        auth::{
+            basic_auth_credentials, generate_client_token, generate_id_token, start_client_session,
+            TOKEN_TTL_SECONDS,
+        },
+        client_info::ClientInfo,
+        constants::OPENID_SCOPE,
+    },
+};
+
+// OAuth 2.0 token endpoint (RFC 6749 section 3.2). Exchanges the codes handed out by
+// /authorize and /device/code for tokens, refreshes those tokens, and issues machine clients
+// tokens of their own.
+#[tracing::instrument(name = "Token", skip_all)]
+pub async fn token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<TokenRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let client = authenticate_oauth_client(
+        &state,
+        &headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_ref(),
+    )
+    .await?;
+
+    let response = match request.grant_type.as_deref() {
+        Some(AUTHORIZATION_CODE_GRANT) => exchange_code(&state, &client, request).await?,
+        Some(REFRESH_TOKEN_GRANT) => refresh_tokens(&state, &client, request).await?,
+        Some(CLIENT_CREDENTIALS_GRANT) => issue_client_token(&state, &client, request).await?,
+        Some(DEVICE_CODE_GRANT) => exchange_device_code(&state, &client, request).await?,
+        Some(_) => return Err(OAuthError::UnsupportedGrantType),
+        None => return Err(OAuthError::InvalidRequest("Missing grant_type")),
+    };
+
+    // Tokens must not be cached on the way back to the client
+    Ok((
+        [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")],
+        Json(response),
+    ))
+}
+
+const AUTHORIZATION_CODE_GRANT: &str = "authorization_code";
+const REFRESH_TOKEN_GRANT: &str = "refresh_token";
+const CLIENT_CREDENTIALS_GRANT: &str = "client_credentials";
+pub const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+// Confidential clients authenticate with their secret, either with HTTP Basic auth or in the
+// form. Public clients only name themselves.
+#[tracing::instrument(name = "Authenticate OAuth client", skip_all)]
+pub(crate) async fn authenticate_oauth_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&Secret<String>>,
+) -> Result<OAuthClient, OAuthError> {
+    let (client_id, client_secret) = match basic_auth_credentials(headers) {
+        Some((client_id, client_secret)) => (client_id, Some(client_secret)),
+        None => (
+            client_id.ok_or(OAuthError::InvalidClient)?.to_owned(),
+            client_secret.map(|secret| secret.expose_secret().to_owned()),
+        ),
+    };
+
+    let client = match state
+        .oauth_client_store
+        .read()
+        .await
+        .get_client(&client_id)
+        .await
+    {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => return Err(OAuthError::InvalidClient),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    if client.is_confidential() && !client.verify_secret(&client_secret.unwrap_or_default()) {
+        return Err(OAuthError::InvalidClient);
+    }
+
+    Ok(client)
+}
+
+// Authorization code grant (RFC 6749 section 4.1.3). The code has to be exchanged by the client
+// it was issued to, with the same redirect URI and the verifier of its PKCE challenge.
+#[tracing::instrument(name = "Exchange authorization code", skip_all)]
+async fn exchange_code(
+    state: &AppState,
+    client: &OAuthClient,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    let code = request
+        .code
+        .ok_or(OAuthError::InvalidRequest("Missing code"))?;
+    let code = AuthorizationCode::parse(code)
+        .map_err(|_| OAuthError::InvalidGrant("Invalid authorization code"))?;
+
+    let code_verifier = request
+        .code_verifier
+        .ok_or(OAuthError::InvalidRequest("Missing code_verifier"))?;
+
+    // Taking the code uses it up, so a code sent along with a wrong verifier can't be retried
+    let grant = match state
+        .authorization_code_store
+        .write()
+        .await
+        .take_code(&code)
+        .await
+    {
+        Ok(grant) => grant,
+        Err(AuthorizationCodeStoreError::CodeNotFound) => {
+            return Err(OAuthError::InvalidGrant("Invalid authorization code"))
+        }
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    if grant.client_grant.client_id != client.id {
+        return Err(OAuthError::InvalidGrant("Invalid authorization code"));
+    }
+
+    if request.redirect_uri.as_deref() != Some(grant.redirect_uri.as_str()) {
+        return Err(OAuthError::InvalidGrant(
+            "redirect_uri does not match the authorization request",
+        ));
+    }
+
+    if !grant.code_challenge.verify(code_verifier.expose_secret()) {
+        return Err(OAuthError::InvalidGrant("Invalid code_verifier"));
+    }
+
+    let (user, token_version) = {
+        let user_store = state.user_store.read().await;
+
+        let result = match user_store.get_user_by_id(&grant.user_id).await {
+            Ok(user) => user_store
+                .get_token_version(&user.email)
+                .await
+                .map(|token_version| (user, token_version)),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(result) => result,
+            // The account was deleted since the code was issued
+            Err(UserStoreError::UserNotFound) => {
+                return Err(OAuthError::InvalidGrant("Invalid authorization code"))
+            }
+            Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+        }
+    };
+
+    // OpenID Connect authentication requests get an ID token along with the other tokens
+    let id_token = match grant.client_grant.has_scope(OPENID_SCOPE) {
+        true => Some(
+            generate_id_token(
+                &user,
+                &grant,
+                &state.jwt_config,
+                state.keyring.read().await.active(),
+            )
+            .map_err(OAuthError::UnexpectedError)?,
+        ),
+        false => None,
+    };
+
+    let scope = grant.client_grant.scope.clone();
+    let device = ClientInfo {
+        user_agent: grant.user_agent,
+        ip_address: grant.ip_address,
+    };
+
+    let (access_token, refresh_token) =
+        start_client_session(state, &user, token_version, grant.client_grant, device)
+            .await
+            .map_err(OAuthError::UnexpectedError)?;
+
+    Ok(TokenResponse {
+        id_token: id_token.map(|token| token.expose_secret().to_owned()),
+        ..TokenResponse::new(access_token, &refresh_token, scope)
+    })
+}
+
+// Refresh token grant (RFC 6749 section 6). Rotates the refresh token just like /refresh does
+// for the cookies, including the reuse detection.
+#[tracing::instrument(name = "Refresh tokens", skip_all)]
+async fn refresh_tokens(
+    state: &AppState,
+    client: &OAuthClient,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    let token = request
+        .refresh_token
+        .ok_or(OAuthError::InvalidRequest("Missing refresh_token"))?;
+    let token = RefreshToken::parse(token)
+        .map_err(|_| OAuthError::InvalidGrant("Invalid refresh token"))?;
+
+    let (access_token, refresh_token, record) =
+        match rotate_refresh_token(state, &token, Some(&client.id)).await {
+            Ok(rotated) => rotated,
+            Err(AuthAPIError::InvalidToken) => {
+                return Err(OAuthError::InvalidGrant("Invalid refresh token"))
+            }
+            Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+        };
+
+    let scope = record.client_grant.and_then(|grant| grant.scope);
+
+    Ok(TokenResponse::new(access_token, &refresh_token, scope))
+}
+
+// Client credentials grant (RFC 6749 section 4.4). Machine clients get a token of their own,
+// limited to the scopes they were registered with. There is no user who could have to log in
+// again, so no refresh token comes with it.
+#[tracing::instrument(name = "Issue client token", skip_all)]
+async fn issue_client_token(
+    state: &AppState,
+    client: &OAuthClient,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    if !client.is_machine_client() {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+
+    let scope = client
+        .grant_scope(request.scope.as_deref())
+        .ok_or(OAuthError::InvalidScope)?;
+
+    let access_token = generate_client_token(
+        client,
+        scope.clone(),
+        &state.jwt_config,
+        state.keyring.read().await.active(),
+    )
+    .map_err(OAuthError::UnexpectedError)?;
+
+    Ok(TokenResponse {
+        access_token: access_token.expose_secret().to_owned(),
+        token_type: "Bearer".to_owned(),
+        expires_in: TOKEN_TTL_SECONDS,
+        refresh_token: None,
+        scope: Some(scope),
+        id_token: None,
+    })
+}
+
+// Device authorization grant (RFC 8628 section 3.4). The device polls with the code it got from
+// /device/code until its user approved or denied it on the device page.
+#[tracing::instrument(name = "Exchange device code", skip_all)]
+async fn exchange_device_code(
+    state: &AppState,
+    client: &OAuthClient,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    let device_code = request
+        .device_code
+        .ok_or(OAuthError::InvalidRequest("Missing device_code"))?;
+    let device_code = DeviceCode::parse(device_code)
+        .map_err(|_| OAuthError::InvalidGrant("Invalid device code"))?;
+
+    let mut device_code_store = state.device_code_store.write().await;
+
+    // Codes that are gone have expired, or were already used up by an earlier poll
+    let authorization = match device_code_store.get_code(&device_code).await {
+        Ok(authorization) => authorization,
+        Err(DeviceCodeStoreError::CodeNotFound) => return Err(OAuthError::ExpiredToken),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    // Checked before the code is taken, so other clients can't use up the grant
+    if authorization.client_grant.client_id != client.id {
+        return Err(OAuthError::InvalidGrant("Invalid device code"));
+    }
+
+    if authorization.status == DeviceAuthorizationStatus::Pending {
+        let is_on_time = device_code_store
+            .record_poll(&device_code)
+            .await
+            .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+        return Err(match is_on_time {
+            true => OAuthError::AuthorizationPending,
+            false => OAuthError::SlowDown,
+        });
+    }
+
+    // Of concurrent polls only the one that takes the code gets tokens
+    let authorization = match device_code_store.take_code(&device_code).await {
+        Ok(authorization) => authorization,
+        Err(DeviceCodeStoreError::CodeNotFound) => return Err(OAuthError::ExpiredToken),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+    drop(device_code_store);
+
+    let user_id = match authorization.status {
+        DeviceAuthorizationStatus::Approved(user_id) => user_id,
+        // Resolved requests don't go back to pending
+        DeviceAuthorizationStatus::Denied | DeviceAuthorizationStatus::Pending => {
+            return Err(OAuthError::AccessDenied)
+        }
+    };
+
+    let (user, token_version) = {
+        let user_store = state.user_store.read().await;
+
+        let result = match user_store.get_user_by_id(&user_id).await {
+            Ok(user) => user_store
+                .get_token_version(&user.email)
+                .await
+                .map(|token_version| (user, token_version)),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(result) => result,
+            // The account was deleted since the device was approved
+            Err(UserStoreError::UserNotFound) => return Err(OAuthError::AccessDenied),
+            Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+        }
+    };
+
+    let scope = authorization.client_grant.scope.clone();
+    let device = ClientInfo {
+        user_agent: authorization.user_agent,
+        ip_address: authorization.ip_address,
+    };
+
+    let (access_token, refresh_token) = start_client_session(
+        state,
+        &user,
+        token_version,
+        authorization.client_grant,
+        device,
+    )
+    .await
+    .map_err(OAuthError::UnexpectedError)?;
+
+    Ok(TokenResponse::new(access_token, &refresh_token, scope))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: Option<String>,
+    pub code: Option<Secret<String>>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<Secret<String>>,
+    pub refresh_token: Option<Secret<String>>,
+    pub device_code: Option<Secret<String>>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<Secret<String>>,
+    // Only for the client credentials grant, the other grants keep the scope the user granted
+    pub scope: Option<String>,
+}
+
+// Successful response, see RFC 6749 section 5.1
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    // Not issued to machine clients
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // Only for OpenID Connect authentication requests, refreshing tokens doesn't issue a new one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+impl TokenResponse {
+    fn new(
+        access_token: Secret<String>,
+        refresh_token: &RefreshToken,
+        scope: Option<String>,
+    ) -> Self {
+        Self {
+            access_token: access_token.expose_secret().to_owned(),
+            token_type: "Bearer".to_owned(),
+            expires_in: TOKEN_TTL_SECONDS,
+            refresh_token: Some(refresh_token.as_ref().expose_secret().to_owned()),
+            scope,
+            id_token: None,
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use super::recovery_codes::{issue_recovery_codes, RecoveryCodesResponse};
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, TotpSecret, TwoFAAttemptKey, TwoFACode, TwoFACodeStoreError},
+    utils::{authenticated_user::AuthenticatedSession, constants::TOTP_ISSUER},
+};
+
+// Hand out a new authenticator app secret. It only replaces the email 2FA once
+// the user proves their app generates the right codes, see confirm_totp.
+#[tracing::instrument(name = "Enroll TOTP", skip_all)]
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    AuthenticatedSession { user, .. }: AuthenticatedSession,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = user.email;
+
+    let secret = TotpSecret::generate().map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = EnrollTotpResponse {
+        secret: secret.to_base32().expose_secret().to_owned(),
+        otpauth_uri: secret
+            .otpauth_uri(TOTP_ISSUER, &email)
+            .expose_secret()
+            .to_owned(),
+    };
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_pending_totp_secret(&email, secret)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[tracing::instrument(name = "Confirm TOTP", skip_all)]
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    AuthenticatedSession { user, .. }: AuthenticatedSession,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = user.email;
+
+    let code = TwoFACode::parse(request.code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Limited like the codes of a login, so the secret can't be guessed from a stolen session
+    match state
+        .two_fa_code_store
+        .write()
+        .await
+        .record_attempt(&TwoFAAttemptKey::TotpEnrollment(email.clone()))
+        .await
+    {
+        Ok(_) => {}
+        Err(TwoFACodeStoreError::TooManyAttempts) => {
+            return Err(AuthAPIError::TotpAttemptsExceeded)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    {
+        let mut user_store = state.user_store.write().await;
+
+        let secret = match user_store.get_pending_totp_secret(&email).await {
+            Ok(Some(secret)) => secret,
+            Ok(None) => return Err(AuthAPIError::IncorrectCredentials),
+            Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+        };
+
+        let Some(time_step) = secret.verify(&code) else {
+            return Err(AuthAPIError::IncorrectCredentials);
+        };
+
+        user_store
+            .enable_totp(&email, secret, time_step)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    }
+
+    // Codes handed out for the email 2FA are replaced too, so there is only ever one set
+    let recovery_codes = issue_recovery_codes(&state, &email).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesResponse { recovery_codes }),
+    ))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnrollTotpResponse {
+    // Base32 encoded, for users who type it into their app
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTotpRequest {
+    pub code: Secret<String>,
+}
@@ -0,0 +1,94 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use color_eyre::eyre::Result;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, UserStoreError, ACCOUNT_LOCKOUT_SECONDS},
+    utils::auth::{generate_account_unlock_token, validate_account_unlock_token},
+};
+
+// Lift the lockout with the token emailed when the account got locked
+#[tracing::instrument(name = "Unlock account", skip_all)]
+pub async fn unlock_account(
+    State(state): State<AppState>,
+    Json(request): Json<UnlockAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = validate_account_unlock_token(&request.token, state.keyring.clone())
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    clear_lockout(&state, &email).await?;
+
+    Ok(StatusCode::OK)
+}
+
+// Lift the lockout for a user who can't get to the email, only reached by admins who have the
+// accounts:unlock permission
+#[tracing::instrument(name = "Admin unlock account", skip_all)]
+pub async fn admin_unlock_account(
+    State(state): State<AppState>,
+    Json(request): Json<AdminUnlockAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    clear_lockout(&state, &email).await?;
+
+    tracing::info!("unlocked account");
+
+    Ok(StatusCode::OK)
+}
+
+async fn clear_lockout(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    state
+        .login_attempt_store
+        .write()
+        .await
+        .clear_failures(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+// Tell the owner of a just locked account, unless there is no such account
+pub(crate) async fn notify_account_locked(state: AppState, email: Email) {
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return,
+        Err(e) => {
+            tracing::error!("failed to look up user for account lockout email: {:?}", e);
+            return;
+        }
+    }
+
+    if let Err(e) = send_unlock_email(&state, &email).await {
+        tracing::error!("failed to send account lockout email: {:?}", e);
+    }
+}
+
+#[tracing::instrument(name = "Send unlock email", skip_all)]
+async fn send_unlock_email(state: &AppState, email: &Email) -> Result<()> {
+    let token = generate_account_unlock_token(email, state.keyring.read().await.active())?;
+
+    let content = format!(
+        "Your account was locked for {} minutes after too many failed login attempts. \
+         If that was you, use this token to unlock it right away: {}",
+        ACCOUNT_LOCKOUT_SECONDS / 60,
+        token.expose_secret()
+    );
+
+    state
+        .email_client
+        .send_email(email, "Your account has been locked", &content)
+        .await
+}
+
+#[derive(Deserialize)]
+pub struct UnlockAccountRequest {
+    pub token: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AdminUnlockAccountRequest {
+    pub email: Secret<String>,
+}
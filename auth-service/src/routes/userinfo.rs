@@ -0,0 +1,63 @@
+use axum::{extract::State, http::HeaderMap, response::IntoResponse, Json};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, ClientGrant},
+    utils::{
+        auth::{bearer_token, validate_token},
+        constants::{EMAIL_SCOPE, OPENID_SCOPE},
+    },
+};
+
+// OpenID Connect UserInfo endpoint (OpenID Connect Core section 5.3). Tells an OAuth client
+// about the user its access token was issued for, as far as the granted scope allows.
+#[tracing::instrument(name = "UserInfo", skip_all)]
+pub async fn userinfo(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = bearer_token(&headers).ok_or(AuthAPIError::MissingToken)?;
+
+    let (claims, user) = validate_token(
+        &token,
+        state.banned_token_store.clone(),
+        state.keyring.clone(),
+        state.user_store.clone(),
+        state.session_store.clone(),
+        &state.jwt_config,
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Only the access tokens of clients the user logged in to with OpenID Connect are accepted
+    let grant = ClientGrant {
+        client_id: claims.client_id.ok_or(AuthAPIError::InvalidToken)?,
+        scope: claims.scope,
+    };
+
+    if !grant.has_scope(OPENID_SCOPE) {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    let email_granted = grant.has_scope(EMAIL_SCOPE);
+
+    Ok(Json(UserInfoResponse {
+        sub: user.id.as_ref().to_owned(),
+        email: email_granted.then(|| user.email.as_ref().expose_secret().to_owned()),
+        email_verified: email_granted.then_some(user.email_verified),
+    }))
+}
+
+// Standard claims, see OpenID Connect Core section 5.1
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserInfoResponse {
+    // ID of the user, the same as in ID tokens
+    pub sub: String,
+    // Only with the email scope
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+}
@@ -1,17 +1,24 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
+use color_eyre::eyre::eyre;
 use secrecy::Secret;
 use serde::Deserialize;
+use tracing::Instrument;
 
+use super::unlock_account::notify_account_locked;
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, AuthenticationMethod, Email, LoginAttemptId, LoginAttemptStoreError,
+        RecoveryCode, TwoFAAttemptKey, TwoFACode, TwoFACodeStoreError, TwoFAMethod, UserStoreError,
+    },
+    utils::{auth::start_session, client_info::ClientInfo},
 };
 
 #[tracing::instrument(name = "Verify 2FA", skip_all)]
 pub async fn verify_2fa(
     State(state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
     Json(request): Json<Verify2FARequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -25,13 +32,35 @@ pub async fn verify_2fa(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    let two_fa_code = match TwoFACode::parse(request.two_fa_code) {
-        Ok(two_fa_code) => two_fa_code,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    let second_factor = match SecondFactor::parse(request.two_fa_code) {
+        Some(second_factor) => second_factor,
+        None => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
+    // Failed codes count towards the login lockout, so the lockout applies here too
+    match state
+        .login_attempt_store
+        .read()
+        .await
+        .check_lockout(&email)
+        .await
     {
-        let two_fa_code_store = state.two_fa_code_store.read().await;
+        Ok(_) => {}
+        Err(LoginAttemptStoreError::AccountLocked {
+            retry_after_seconds,
+        }) => {
+            return (
+                jar,
+                Err(AuthAPIError::TooManyLoginAttempts {
+                    retry_after_seconds,
+                }),
+            )
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    let (stored_two_fa_code, incorrect_code_error) = {
+        let mut two_fa_code_store = state.two_fa_code_store.write().await;
 
         let (stored_login_attempt_id, stored_two_fa_code) =
             match two_fa_code_store.get_code(&email).await {
@@ -39,35 +68,248 @@ pub async fn verify_2fa(
                 Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
             };
 
-        if stored_login_attempt_id != login_attempt_id || stored_two_fa_code != two_fa_code {
+        // Only attempts at the code of the current login attempt are counted, so nobody can
+        // use up the attempts of a login they didn't start
+        if stored_login_attempt_id != login_attempt_id {
             return (jar, Err(AuthAPIError::IncorrectCredentials));
         }
+
+        let attempts_left = match two_fa_code_store
+            .record_attempt(&TwoFAAttemptKey::Login(login_attempt_id.clone()))
+            .await
+        {
+            Ok(attempts_left) => attempts_left,
+            Err(TwoFACodeStoreError::TooManyAttempts) => {
+                return (jar, Err(AuthAPIError::TwoFAAttemptsExceeded))
+            }
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        };
+
+        // After a failed last attempt the user has to log in again, so tell them right away
+        let incorrect_code_error = match attempts_left {
+            0 => AuthAPIError::TwoFAAttemptsExceeded,
+            _ => AuthAPIError::IncorrectCredentials,
+        };
+
+        (stored_two_fa_code, incorrect_code_error)
+    };
+
+    let verified = match &second_factor {
+        SecondFactor::Code(two_fa_code) => {
+            check_two_fa_code(&state, &email, two_fa_code, Some(&stored_two_fa_code)).await
+        }
+        SecondFactor::RecoveryCode(recovery_code) => {
+            use_recovery_code(&state, &email, recovery_code).await
+        }
+    };
+
+    match verified {
+        Ok(true) => {}
+        Ok(false) => {
+            match state
+                .login_attempt_store
+                .write()
+                .await
+                .record_failure(&email)
+                .await
+            {
+                Ok(true) => {
+                    tokio::spawn(notify_account_locked(state.clone(), email).in_current_span());
+                }
+                Ok(false) => {}
+                Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+            }
+
+            return (jar, Err(incorrect_code_error));
+        }
+        Err(e) => return (jar, Err(e)),
     }
 
-    let auth_cookie = match generate_auth_cookie(&email) {
-        Ok(cookie) => cookie,
+    // Taking the code before the session is started makes sure that of concurrent requests with
+    // the same login attempt only one logs the user in
+    match state
+        .two_fa_code_store
+        .write()
+        .await
+        .take_code(&email)
+        .await
+    {
+        Ok((taken_login_attempt_id, taken_two_fa_code))
+            if taken_login_attempt_id == login_attempt_id
+                && taken_two_fa_code == stored_two_fa_code => {}
+        Ok(_) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return (jar, Err(AuthAPIError::IncorrectCredentials))
+        }
         Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    // The login only succeeds now, so this is where its failures are forgotten
+    if let Err(e) = state
+        .login_attempt_store
+        .write()
+        .await
+        .clear_failures(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    // Recorded with the session, and reported in the ID tokens issued from it
+    let amr = match second_factor {
+        SecondFactor::Code(_) => vec![
+            AuthenticationMethod::Password,
+            AuthenticationMethod::OneTimePassword,
+            AuthenticationMethod::MultiFactor,
+        ],
+        SecondFactor::RecoveryCode(_) => vec![
+            AuthenticationMethod::Password,
+            AuthenticationMethod::MultiFactor,
+        ],
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    let (user, token_version) = {
+        let user_store = state.user_store.read().await;
 
-    {
-        let mut two_fa_code_store = state.two_fa_code_store.write().await;
+        let user = match user_store.get_user(&email).await {
+            Ok(user) => user,
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        };
+
+        match user_store.get_token_version(&email).await {
+            Ok(token_version) => (user, token_version),
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        }
+    };
 
-        match two_fa_code_store.remove_code(&email).await {
-            Ok(_) => {}
-            Err(_) => return (updated_jar, Err(AuthAPIError::IncorrectCredentials)),
+    let (auth_cookie, refresh_cookie) =
+        match start_session(&state, &user, token_version, amr, client).await {
+            Ok(cookies) => cookies,
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
         };
-    }
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
 
     (updated_jar, Ok(StatusCode::OK.into_response()))
 }
 
+// Compare the code against the emailed one, or the one the user's authenticator app shows.
+// Without an emailed code only users of an authenticator app can pass.
+#[tracing::instrument(name = "Check 2FA code", skip_all)]
+pub(crate) async fn check_two_fa_code(
+    state: &AppState,
+    email: &Email,
+    two_fa_code: &TwoFACode,
+    emailed_two_fa_code: Option<&TwoFACode>,
+) -> Result<bool, AuthAPIError> {
+    let secret = {
+        let user_store = state.user_store.read().await;
+
+        let user = match user_store.get_user(email).await {
+            Ok(user) => user,
+            Err(UserStoreError::UserNotFound) => return Ok(false),
+            Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+        };
+
+        match user.two_fa_method {
+            TwoFAMethod::Email => return Ok(emailed_two_fa_code == Some(two_fa_code)),
+            TwoFAMethod::AuthenticatorApp => match user_store.get_totp_secret(email).await {
+                Ok(Some(secret)) => secret,
+                Ok(None) => {
+                    return Err(AuthAPIError::UnexpectedError(eyre!(
+                        "authenticator app 2FA is enabled without a TOTP secret"
+                    )))
+                }
+                Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+            },
+        }
+    };
+
+    let Some(time_step) = secret.verify(two_fa_code) else {
+        return Ok(false);
+    };
+
+    // A code that was accepted before may have been seen by someone else
+    match state
+        .user_store
+        .write()
+        .await
+        .use_totp_time_step(email, time_step)
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(UserStoreError::InvalidCredentials) => Ok(false),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Use up the recovery code if the user has it. The codes are hashed like passwords, so they
+// are checked without holding on to the user store.
+#[tracing::instrument(name = "Use recovery code", skip_all)]
+pub(crate) async fn use_recovery_code(
+    state: &AppState,
+    email: &Email,
+    recovery_code: &RecoveryCode,
+) -> Result<bool, AuthAPIError> {
+    let stored_codes = match state
+        .user_store
+        .read()
+        .await
+        .get_recovery_codes(email)
+        .await
+    {
+        Ok(stored_codes) => stored_codes,
+        Err(UserStoreError::UserNotFound) => return Ok(false),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let mut matching_code = None;
+    for stored_code in stored_codes {
+        if stored_code.matches(recovery_code).await {
+            matching_code = Some(stored_code);
+            break;
+        }
+    }
+
+    let Some(stored_code) = matching_code else {
+        return Ok(false);
+    };
+
+    // Removing the code fails if a concurrent request used it first
+    match state
+        .user_store
+        .write()
+        .await
+        .remove_recovery_code(email, stored_code.id)
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(UserStoreError::InvalidCredentials) => Ok(false),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// What the user entered in place of the 2FA code
+pub(crate) enum SecondFactor {
+    Code(TwoFACode),
+    // For users who can't get to their code
+    RecoveryCode(RecoveryCode),
+}
+
+impl SecondFactor {
+    pub(crate) fn parse(value: Secret<String>) -> Option<Self> {
+        TwoFACode::parse(value.clone())
+            .map(Self::Code)
+            .or_else(|_| RecoveryCode::parse(value).map(Self::RecoveryCode))
+            .ok()
+    }
+}
+
 #[derive(Deserialize)]
 pub struct Verify2FARequest {
     pub email: Secret<String>,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: Secret<String>,
+    // Either the 2FA code or one of the user's recovery codes
     #[serde(rename = "2FACode")]
     pub two_fa_code: Secret<String>,
 }
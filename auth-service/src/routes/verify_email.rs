@@ -0,0 +1,88 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use color_eyre::eyre::Result;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use tracing::Instrument;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, UserStoreError},
+    utils::auth::{
+        generate_email_verification_token, validate_email_verification_token,
+        EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+    },
+};
+
+#[tracing::instrument(name = "Verify email", skip_all)]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = validate_email_verification_token(&request.token, state.keyring.clone())
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    match state.user_store.write().await.verify_email(&email).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(UserStoreError::UserNotFound) => Err(AuthAPIError::InvalidToken),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[tracing::instrument(name = "Resend verification email", skip_all)]
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Like the password reset request, the response doesn't tell whether the account exists
+    tokio::spawn(resend_if_unverified(state, email).in_current_span());
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn resend_if_unverified(state: AppState, email: Email) {
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return,
+        Err(e) => {
+            tracing::error!("failed to look up user for verification email: {:?}", e);
+            return;
+        }
+    };
+
+    if user.email_verified {
+        return;
+    }
+
+    if let Err(e) = send_verification_email(&state, &email).await {
+        tracing::error!("failed to send verification email: {:?}", e);
+    }
+}
+
+#[tracing::instrument(name = "Send verification email", skip_all)]
+pub(crate) async fn send_verification_email(state: &AppState, email: &Email) -> Result<()> {
+    let token = generate_email_verification_token(email, state.keyring.read().await.active())?;
+
+    let content = format!(
+        "Use this token to verify your email address within the next {} hours: {}",
+        EMAIL_VERIFICATION_TOKEN_TTL_SECONDS / 3600,
+        token.expose_secret()
+    );
+
+    state
+        .email_client
+        .send_email(email, "Verify your email address", &content)
+        .await
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: Secret<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: Secret<String>,
+}
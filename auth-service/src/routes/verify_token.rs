@@ -1,21 +1,91 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::{rejection::JsonRejection, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Json,
+};
 use secrecy::Secret;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::validate_token};
+use crate::{
+    app_state::AppState,
+    domain::AuthAPIError,
+    utils::auth::{bearer_token, validate_any_token, ValidatedToken},
+};
 
+// The token is sent either in the body or as `Authorization: Bearer`, which scripts with a
+// personal access token tend to use. The header wins when both are sent.
 #[tracing::instrument(name = "Verify token", skip_all)]
 pub async fn verify_token(
     State(state): State<AppState>,
-    Json(request): Json<VerifyTokenRequest>,
-) -> Result<impl IntoResponse, AuthAPIError> {
-    match validate_token(&request.token, state.banned_token_store.clone()).await {
-        Ok(_) => Ok(StatusCode::OK),
-        Err(_) => Err(AuthAPIError::InvalidToken),
-    }
+    headers: HeaderMap,
+    request: Result<Json<VerifyTokenRequest>, JsonRejection>,
+) -> Result<Response, AuthAPIError> {
+    let token = match (bearer_token(&headers), request) {
+        (Some(token), _) => token,
+        (None, Ok(Json(request))) => request.token,
+        (None, Err(rejection)) => return Ok(rejection.into_response()),
+    };
+
+    let response = match validate_any_token(&token, &state).await {
+        Ok(ValidatedToken::User(claims, user)) => VerifyTokenResponse {
+            user_id: Some(user.id.as_ref().to_owned()),
+            // Only set for tokens issued to OAuth clients through /token
+            client_id: claims.client_id,
+            scope: claims.scope,
+            // As of when the token was issued, and empty for tokens issued to OAuth clients
+            roles: claims.roles,
+            permissions: claims.permissions,
+        },
+        Ok(ValidatedToken::Client(claims, client)) => VerifyTokenResponse {
+            user_id: None,
+            client_id: Some(client.id),
+            scope: Some(claims.scope),
+            roles: Vec::new(),
+            permissions: Vec::new(),
+        },
+        Ok(ValidatedToken::PersonalAccessToken(token, user)) => {
+            // Personal access tokens live long, so the roles are looked up every time
+            let roles = state
+                .role_store
+                .read()
+                .await
+                .get_user_roles(&user.id)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+            VerifyTokenResponse {
+                user_id: Some(user.id.as_ref().to_owned()),
+                client_id: None,
+                scope: token.scope(),
+                permissions: token.permissions(&roles),
+                roles: roles.roles,
+            }
+        }
+        Err(_) => return Err(AuthAPIError::InvalidToken),
+    };
+
+    Ok(Json(response).into_response())
 }
 
 #[derive(Debug, Deserialize)]
 pub struct VerifyTokenRequest {
     pub token: Secret<String>,
 }
+
+// Identifies who the token was issued to, so other services can key their data on it, and what
+// they may do there. User tokens have a userId, machine client tokens only a clientId.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyTokenResponse {
+    #[serde(rename = "userId", skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(rename = "clientId", skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // Roles of the user and the permissions the token grants through them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permissions: Vec<String>,
+}
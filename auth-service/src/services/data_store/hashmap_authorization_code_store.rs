@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use secrecy::ExposeSecret;
+
+use crate::domain::{
+    AuthorizationCode, AuthorizationCodeStore, AuthorizationCodeStoreError, AuthorizationGrant,
+};
+
+#[derive(Default)]
+pub struct HashmapAuthorizationCodeStore {
+    codes: HashMap<String, AuthorizationGrant>,
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for HashmapAuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        self.codes
+            .insert(code.as_ref().expose_secret().to_owned(), grant);
+        Ok(())
+    }
+
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        self.codes
+            .remove(code.as_ref().expose_secret())
+            .ok_or(AuthorizationCodeStoreError::CodeNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+    use crate::domain::{AuthenticationMethod, ClientGrant, CodeChallenge, UserId};
+
+    fn grant() -> AuthorizationGrant {
+        AuthorizationGrant {
+            client_grant: ClientGrant {
+                client_id: "client".to_owned(),
+                scope: Some("profile".to_owned()),
+            },
+            redirect_uri: "https://app.example.com/callback".to_owned(),
+            code_challenge: CodeChallenge::parse("a".repeat(43)).unwrap(),
+            user_id: UserId::default(),
+            user_agent: None,
+            ip_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            nonce: None,
+            auth_time: 0,
+            amr: vec![AuthenticationMethod::Password],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_take_code() {
+        let mut store = HashmapAuthorizationCodeStore::default();
+        let code = AuthorizationCode::default();
+
+        let grant = grant();
+
+        store.add_code(code.clone(), grant.clone()).await.unwrap();
+
+        assert_eq!(store.take_code(&code).await.unwrap(), grant);
+    }
+
+    #[tokio::test]
+    async fn test_code_can_only_be_taken_once() {
+        let mut store = HashmapAuthorizationCodeStore::default();
+        let code = AuthorizationCode::default();
+
+        store.add_code(code.clone(), grant()).await.unwrap();
+        store.take_code(&code).await.unwrap();
+
+        assert_eq!(
+            store.take_code(&code).await.unwrap_err(),
+            AuthorizationCodeStoreError::CodeNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_take_unknown_code() {
+        let mut store = HashmapAuthorizationCodeStore::default();
+
+        let result = store.take_code(&AuthorizationCode::default()).await;
+
+        assert_eq!(
+            result.unwrap_err(),
+            AuthorizationCodeStoreError::CodeNotFound
+        );
+    }
+}
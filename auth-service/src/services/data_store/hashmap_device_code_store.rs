@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use secrecy::ExposeSecret;
+
+use crate::{
+    domain::{
+        DeviceAuthorization, DeviceAuthorizationStatus, DeviceCode, DeviceCodeStore,
+        DeviceCodeStoreError, UserCode,
+    },
+    utils::auth::DEVICE_CODE_POLL_INTERVAL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashmapDeviceCodeStore {
+    authorizations: HashMap<String, DeviceAuthorization>,
+    // Device code of each user code that hasn't been entered yet
+    user_codes: HashMap<String, String>,
+    last_polls: HashMap<String, Instant>,
+}
+
+#[async_trait::async_trait]
+impl DeviceCodeStore for HashmapDeviceCodeStore {
+    async fn add_code(
+        &mut self,
+        device_code: DeviceCode,
+        authorization: DeviceAuthorization,
+    ) -> Result<(), DeviceCodeStoreError> {
+        let device_code = device_code.as_ref().expose_secret().to_owned();
+
+        self.user_codes.insert(
+            authorization.user_code.as_ref().to_owned(),
+            device_code.clone(),
+        );
+        self.authorizations.insert(device_code, authorization);
+        Ok(())
+    }
+
+    async fn resolve(
+        &mut self,
+        user_code: &UserCode,
+        status: DeviceAuthorizationStatus,
+    ) -> Result<(), DeviceCodeStoreError> {
+        let authorization = self
+            .user_codes
+            .remove(user_code.as_ref())
+            .and_then(|device_code| self.authorizations.get_mut(&device_code))
+            .ok_or(DeviceCodeStoreError::CodeNotFound)?;
+
+        authorization.status = status;
+        Ok(())
+    }
+
+    async fn get_code(
+        &self,
+        device_code: &DeviceCode,
+    ) -> Result<DeviceAuthorization, DeviceCodeStoreError> {
+        self.authorizations
+            .get(device_code.as_ref().expose_secret())
+            .cloned()
+            .ok_or(DeviceCodeStoreError::CodeNotFound)
+    }
+
+    async fn take_code(
+        &mut self,
+        device_code: &DeviceCode,
+    ) -> Result<DeviceAuthorization, DeviceCodeStoreError> {
+        let device_code = device_code.as_ref().expose_secret();
+
+        self.last_polls.remove(device_code);
+        self.authorizations
+            .remove(device_code)
+            .ok_or(DeviceCodeStoreError::CodeNotFound)
+    }
+
+    async fn record_poll(
+        &mut self,
+        device_code: &DeviceCode,
+    ) -> Result<bool, DeviceCodeStoreError> {
+        let now = Instant::now();
+        let interval = Duration::from_secs(DEVICE_CODE_POLL_INTERVAL_SECONDS);
+
+        let last_poll = self
+            .last_polls
+            .insert(device_code.as_ref().expose_secret().to_owned(), now);
+
+        Ok(last_poll.is_none_or(|last_poll| now.duration_since(last_poll) >= interval))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+    use crate::domain::{ClientGrant, UserId};
+
+    fn authorization(user_code: UserCode) -> DeviceAuthorization {
+        DeviceAuthorization {
+            client_grant: ClientGrant {
+                client_id: "client".to_owned(),
+                scope: Some("profile".to_owned()),
+            },
+            user_code,
+            user_agent: None,
+            ip_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            status: DeviceAuthorizationStatus::Pending,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_code() {
+        let mut store = HashmapDeviceCodeStore::default();
+        let device_code = DeviceCode::default();
+        let authorization = authorization(UserCode::default());
+
+        store
+            .add_code(device_code.clone(), authorization.clone())
+            .await
+            .unwrap();
+
+        // Requests stay until they are taken
+        assert_eq!(store.get_code(&device_code).await.unwrap(), authorization);
+        assert_eq!(store.get_code(&device_code).await.unwrap(), authorization);
+    }
+
+    #[tokio::test]
+    async fn test_resolved_code_can_only_be_taken_once() {
+        let mut store = HashmapDeviceCodeStore::default();
+        let device_code = DeviceCode::default();
+        let user_code = UserCode::default();
+        let user_id = UserId::default();
+
+        store
+            .add_code(device_code.clone(), authorization(user_code.clone()))
+            .await
+            .unwrap();
+        store
+            .resolve(
+                &user_code,
+                DeviceAuthorizationStatus::Approved(user_id.clone()),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.take_code(&device_code).await.unwrap().status,
+            DeviceAuthorizationStatus::Approved(user_id)
+        );
+        assert_eq!(
+            store.take_code(&device_code).await.unwrap_err(),
+            DeviceCodeStoreError::CodeNotFound
+        );
+        assert_eq!(
+            store.get_code(&device_code).await.unwrap_err(),
+            DeviceCodeStoreError::CodeNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_user_code_can_only_be_entered_once() {
+        let mut store = HashmapDeviceCodeStore::default();
+        let user_code = UserCode::default();
+
+        store
+            .add_code(DeviceCode::default(), authorization(user_code.clone()))
+            .await
+            .unwrap();
+        store
+            .resolve(&user_code, DeviceAuthorizationStatus::Denied)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store
+                .resolve(&user_code, DeviceAuthorizationStatus::Denied)
+                .await
+                .unwrap_err(),
+            DeviceCodeStoreError::CodeNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_poll() {
+        let mut store = HashmapDeviceCodeStore::default();
+        let device_code = DeviceCode::default();
+
+        assert!(store.record_poll(&device_code).await.unwrap());
+        assert!(!store.record_poll(&device_code).await.unwrap());
+        assert!(store.record_poll(&DeviceCode::default()).await.unwrap());
+    }
+}
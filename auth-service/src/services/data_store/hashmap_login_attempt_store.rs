@@ -0,0 +1,148 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::domain::{
+    Email, LoginAttemptStore, LoginAttemptStoreError, RateLimitKey, ACCOUNT_LOCKOUT_SECONDS,
+    LOGIN_ATTEMPT_WINDOW_SECONDS, MAX_FAILED_LOGINS,
+};
+
+#[derive(Default)]
+pub struct HashmapLoginAttemptStore {
+    // Times of the attempts within the window, oldest first
+    attempts: HashMap<RateLimitKey, VecDeque<Instant>>,
+    failures: HashMap<Email, u32>,
+    // End of the lockout of each locked account
+    lockouts: HashMap<Email, Instant>,
+}
+
+#[async_trait::async_trait]
+impl LoginAttemptStore for HashmapLoginAttemptStore {
+    async fn record_attempt(&mut self, key: &RateLimitKey) -> Result<(), LoginAttemptStoreError> {
+        let now = Instant::now();
+        let window = Duration::from_secs(LOGIN_ATTEMPT_WINDOW_SECONDS);
+        let attempts = self.attempts.entry(key.clone()).or_default();
+
+        while attempts
+            .front()
+            .is_some_and(|attempt| now.duration_since(*attempt) >= window)
+        {
+            attempts.pop_front();
+        }
+
+        if attempts.len() >= key.max_attempts() as usize {
+            let oldest = attempts[0];
+            return Err(LoginAttemptStoreError::RateLimited {
+                retry_after_seconds: seconds_until(oldest + window, now),
+            });
+        }
+
+        attempts.push_back(now);
+        Ok(())
+    }
+
+    async fn check_lockout(&self, email: &Email) -> Result<(), LoginAttemptStoreError> {
+        let now = Instant::now();
+
+        match self.lockouts.get(email) {
+            Some(until) if *until > now => Err(LoginAttemptStoreError::AccountLocked {
+                retry_after_seconds: seconds_until(*until, now),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    async fn record_failure(&mut self, email: &Email) -> Result<bool, LoginAttemptStoreError> {
+        let failures = self.failures.entry(email.clone()).or_default();
+        *failures += 1;
+
+        if *failures < MAX_FAILED_LOGINS {
+            return Ok(false);
+        }
+
+        self.failures.remove(email);
+        self.lockouts.insert(
+            email.clone(),
+            Instant::now() + Duration::from_secs(ACCOUNT_LOCKOUT_SECONDS),
+        );
+        Ok(true)
+    }
+
+    async fn clear_failures(&mut self, email: &Email) -> Result<(), LoginAttemptStoreError> {
+        self.failures.remove(email);
+        self.lockouts.remove(email);
+        Ok(())
+    }
+}
+
+// Whole seconds until the instant, rounded up so clients don't retry too early
+fn seconds_until(instant: Instant, now: Instant) -> u64 {
+    let remaining = instant.saturating_duration_since(now);
+    remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use secrecy::Secret;
+
+    use super::*;
+    use crate::domain::{MAX_LOGIN_ATTEMPTS_PER_ACCOUNT, MAX_LOGIN_ATTEMPTS_PER_IP};
+
+    fn email(address: &str) -> Email {
+        Email::parse(Secret::new(address.to_owned())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_record_attempt() {
+        let mut store = HashmapLoginAttemptStore::default();
+        let account = RateLimitKey::Account(email("user1@a.com"));
+        let ip = RateLimitKey::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        for _ in 0..MAX_LOGIN_ATTEMPTS_PER_ACCOUNT {
+            assert_eq!(store.record_attempt(&account).await, Ok(()));
+        }
+
+        match store.record_attempt(&account).await {
+            Err(LoginAttemptStoreError::RateLimited {
+                retry_after_seconds,
+            }) => assert_eq!(retry_after_seconds, LOGIN_ATTEMPT_WINDOW_SECONDS),
+            result => panic!("Expected RateLimited, got {:?}", result),
+        }
+
+        // Other keys have their own window and limit
+        let other_account = RateLimitKey::Account(email("user2@a.com"));
+        assert_eq!(store.record_attempt(&other_account).await, Ok(()));
+
+        for _ in 0..MAX_LOGIN_ATTEMPTS_PER_IP {
+            assert_eq!(store.record_attempt(&ip).await, Ok(()));
+        }
+        assert!(store.record_attempt(&ip).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_record_failure() {
+        let mut store = HashmapLoginAttemptStore::default();
+        let email = email("user1@a.com");
+
+        for _ in 1..MAX_FAILED_LOGINS {
+            assert_eq!(store.record_failure(&email).await, Ok(false));
+            assert_eq!(store.check_lockout(&email).await, Ok(()));
+        }
+
+        assert_eq!(store.record_failure(&email).await, Ok(true));
+
+        match store.check_lockout(&email).await {
+            Err(LoginAttemptStoreError::AccountLocked {
+                retry_after_seconds,
+            }) => assert_eq!(retry_after_seconds, ACCOUNT_LOCKOUT_SECONDS),
+            result => panic!("Expected AccountLocked, got {:?}", result),
+        }
+
+        assert_eq!(store.clear_failures(&email).await, Ok(()));
+        assert_eq!(store.check_lockout(&email).await, Ok(()));
+        assert_eq!(store.record_failure(&email).await, Ok(false));
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use crate::domain::{OAuthClient, OAuthClientStore, OAuthClientStoreError};
+
+#[derive(Default)]
+pub struct HashmapOAuthClientStore {
+    clients: HashMap<String, OAuthClient>,
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for HashmapOAuthClientStore {
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError> {
+        self.clients.insert(client.id.clone(), client);
+        Ok(())
+    }
+
+    async fn get_client(&self, id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        self.clients
+            .get(id)
+            .cloned()
+            .ok_or(OAuthClientStoreError::ClientNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_and_get_client() {
+        let mut store = HashmapOAuthClientStore::default();
+        let (client, _) = OAuthClient::new(
+            "App".to_owned(),
+            vec!["https://app.example.com/callback".to_owned()],
+            vec![],
+            true,
+        )
+        .unwrap();
+
+        store.add_client(client.clone()).await.unwrap();
+
+        assert_eq!(store.get_client(&client.id).await.unwrap(), client);
+    }
+
+    #[tokio::test]
+    async fn test_get_unknown_client() {
+        let store = HashmapOAuthClientStore::default();
+
+        assert_eq!(
+            store.get_client("unknown").await.unwrap_err(),
+            OAuthClientStoreError::ClientNotFound
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use secrecy::ExposeSecret;
+
+use crate::domain::{
+    Email, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError,
+};
+
+#[derive(Default)]
+pub struct HashmapPasswordResetTokenStore {
+    tokens: HashMap<String, Email>,
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for HashmapPasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        token: PasswordResetToken,
+        email: Email,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        self.tokens.retain(|_, existing| *existing != email);
+        self.tokens
+            .insert(token.as_ref().expose_secret().to_owned(), email);
+        Ok(())
+    }
+
+    async fn take_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        self.tokens
+            .remove(token.as_ref().expose_secret())
+            .ok_or(PasswordResetTokenStoreError::TokenNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_and_take_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let token = PasswordResetToken::default();
+        let email = Email::parse(Secret::new("user1@a.com".to_string())).unwrap();
+
+        store.add_token(token.clone(), email.clone()).await.unwrap();
+
+        assert_eq!(store.take_token(&token).await.unwrap(), email);
+    }
+
+    #[tokio::test]
+    async fn test_token_can_only_be_taken_once() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let token = PasswordResetToken::default();
+        let email = Email::parse(Secret::new("user1@a.com".to_string())).unwrap();
+
+        store.add_token(token.clone(), email).await.unwrap();
+        store.take_token(&token).await.unwrap();
+
+        assert_eq!(
+            store.take_token(&token).await.unwrap_err(),
+            PasswordResetTokenStoreError::TokenNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_token_replaces_earlier_one() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let first = PasswordResetToken::default();
+        let second = PasswordResetToken::default();
+        let email = Email::parse(Secret::new("user1@a.com".to_string())).unwrap();
+
+        store.add_token(first.clone(), email.clone()).await.unwrap();
+        store
+            .add_token(second.clone(), email.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.take_token(&first).await.unwrap_err(),
+            PasswordResetTokenStoreError::TokenNotFound
+        );
+        assert_eq!(store.take_token(&second).await.unwrap(), email);
+    }
+
+    #[tokio::test]
+    async fn test_take_unknown_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+
+        let result = store.take_token(&PasswordResetToken::default()).await;
+
+        assert_eq!(
+            result.unwrap_err(),
+            PasswordResetTokenStoreError::TokenNotFound
+        );
+    }
+}
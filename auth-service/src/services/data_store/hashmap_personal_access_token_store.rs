@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{
+    PersonalAccessToken, PersonalAccessTokenStore, PersonalAccessTokenStoreError, UserId,
+};
+
+#[derive(Default)]
+pub struct HashmapPersonalAccessTokenStore {
+    tokens: HashMap<String, PersonalAccessToken>,
+}
+
+#[async_trait::async_trait]
+impl PersonalAccessTokenStore for HashmapPersonalAccessTokenStore {
+    async fn add_token(
+        &mut self,
+        token: PersonalAccessToken,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        self.tokens.insert(token.id.clone(), token);
+        Ok(())
+    }
+
+    async fn get_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<PersonalAccessToken, PersonalAccessTokenStoreError> {
+        self.tokens
+            .values()
+            .find(|token| token.token_hash == token_hash)
+            .cloned()
+            .ok_or(PersonalAccessTokenStoreError::TokenNotFound)
+    }
+
+    async fn get_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Vec<PersonalAccessToken>, PersonalAccessTokenStoreError> {
+        let mut tokens: Vec<PersonalAccessToken> = self
+            .tokens
+            .values()
+            .filter(|token| token.user_id == *user_id)
+            .cloned()
+            .collect();
+
+        tokens.sort_by_key(|token| std::cmp::Reverse(token.created_at));
+        Ok(tokens)
+    }
+
+    async fn touch_token(&mut self, id: &str) -> Result<(), PersonalAccessTokenStoreError> {
+        let token = self
+            .tokens
+            .get_mut(id)
+            .ok_or(PersonalAccessTokenStoreError::TokenNotFound)?;
+
+        token.last_used_at = Some(Utc::now().timestamp());
+        Ok(())
+    }
+
+    async fn remove_token(
+        &mut self,
+        user_id: &UserId,
+        id: &str,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        match self.tokens.get(id) {
+            Some(token) if token.user_id == *user_id => {
+                self.tokens.remove(id);
+                Ok(())
+            }
+            _ => Err(PersonalAccessTokenStoreError::TokenNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(user_id: &UserId) -> PersonalAccessToken {
+        let (token, _) =
+            PersonalAccessToken::new(user_id.clone(), "CI".to_owned(), vec![], None).unwrap();
+        token
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_token() {
+        let mut store = HashmapPersonalAccessTokenStore::default();
+        let user_id = UserId::default();
+        let mut first = token(&user_id);
+        first.created_at -= 60;
+        let second = token(&user_id);
+
+        store.add_token(first.clone()).await.unwrap();
+        store.add_token(second.clone()).await.unwrap();
+        store.add_token(token(&UserId::default())).await.unwrap();
+
+        assert_eq!(
+            store.get_token_by_hash(&first.token_hash).await,
+            Ok(first.clone())
+        );
+        assert_eq!(
+            store.get_token_by_hash("unknown").await,
+            Err(PersonalAccessTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(store.get_tokens(&user_id).await, Ok(vec![second, first]));
+    }
+
+    #[tokio::test]
+    async fn test_touch_token() {
+        let mut store = HashmapPersonalAccessTokenStore::default();
+        let token = token(&UserId::default());
+
+        store.add_token(token.clone()).await.unwrap();
+        store.touch_token(&token.id).await.unwrap();
+
+        let touched = store.get_token_by_hash(&token.token_hash).await.unwrap();
+        assert!(touched.last_used_at.is_some());
+        assert_eq!(
+            store.touch_token("unknown").await,
+            Err(PersonalAccessTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_token() {
+        let mut store = HashmapPersonalAccessTokenStore::default();
+        let user_id = UserId::default();
+        let token = token(&user_id);
+
+        store.add_token(token.clone()).await.unwrap();
+
+        // Users can only remove their own tokens
+        assert_eq!(
+            store.remove_token(&UserId::default(), &token.id).await,
+            Err(PersonalAccessTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(store.remove_token(&user_id, &token.id).await, Ok(()));
+        assert_eq!(
+            store.remove_token(&user_id, &token.id).await,
+            Err(PersonalAccessTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(store.get_tokens(&user_id).await, Ok(vec![]));
+    }
+}
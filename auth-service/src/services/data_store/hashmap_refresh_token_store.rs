@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use secrecy::ExposeSecret;
+
+use crate::domain::{
+    RefreshToken, RefreshTokenFamilyId, RefreshTokenRecord, RefreshTokenStore,
+    RefreshTokenStoreError,
+};
+
+#[derive(Default)]
+pub struct HashmapRefreshTokenStore {
+    tokens: HashMap<String, RefreshTokenRecord>,
+    families: HashMap<RefreshTokenFamilyId, RefreshToken>,
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.families
+            .insert(record.family_id.clone(), token.clone());
+        self.tokens
+            .insert(token.as_ref().expose_secret().to_owned(), record);
+        Ok(())
+    }
+
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        match self.tokens.get(token.as_ref().expose_secret()) {
+            Some(record) => Ok(record.clone()),
+            None => Err(RefreshTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn get_family_head(
+        &self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<RefreshToken, RefreshTokenStoreError> {
+        match self.families.get(family_id) {
+            Some(token) => Ok(token.clone()),
+            None => Err(RefreshTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.families.remove(family_id);
+        self.tokens
+            .retain(|_, record| &record.family_id != family_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+    use crate::domain::Email;
+
+    fn record() -> RefreshTokenRecord {
+        RefreshTokenRecord::new(
+            Email::parse(Secret::new("user1@a.com".to_string())).unwrap(),
+            RefreshTokenFamilyId::default(),
+            0,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        let record = record();
+
+        store
+            .add_token(token.clone(), record.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_token(&token).await.unwrap(), record);
+        assert_eq!(
+            store.get_family_head(&record.family_id).await.unwrap(),
+            token
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_token_not_found() {
+        let store = HashmapRefreshTokenStore::default();
+
+        let result = store.get_token(&RefreshToken::default()).await;
+
+        assert_eq!(result.unwrap_err(), RefreshTokenStoreError::TokenNotFound);
+    }
+
+    #[tokio::test]
+    async fn test_rotation_moves_family_head() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let record = record();
+        let first_token = RefreshToken::default();
+        let second_token = RefreshToken::default();
+
+        store
+            .add_token(first_token.clone(), record.clone())
+            .await
+            .unwrap();
+        store
+            .add_token(second_token.clone(), record.clone())
+            .await
+            .unwrap();
+
+        assert!(store.get_token(&first_token).await.is_ok());
+        assert_eq!(
+            store.get_family_head(&record.family_id).await.unwrap(),
+            second_token
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_family() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let record = record();
+        let token = RefreshToken::default();
+        store
+            .add_token(token.clone(), record.clone())
+            .await
+            .unwrap();
+
+        store.revoke_family(&record.family_id).await.unwrap();
+
+        assert_eq!(
+            store.get_family_head(&record.family_id).await.unwrap_err(),
+            RefreshTokenStoreError::TokenNotFound
+        );
+        assert_eq!(
+            store.get_token(&token).await.unwrap_err(),
+            RefreshTokenStoreError::TokenNotFound
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::domain::{Role, RoleStore, RoleStoreError, UserId, UserRoles};
+
+#[derive(Default)]
+pub struct HashmapRoleStore {
+    roles: HashMap<String, Role>,
+    user_roles: HashMap<UserId, HashSet<String>>,
+}
+
+#[async_trait::async_trait]
+impl RoleStore for HashmapRoleStore {
+    async fn set_role(&mut self, role: Role) -> Result<(), RoleStoreError> {
+        self.roles.insert(role.name.clone(), role);
+        Ok(())
+    }
+
+    async fn assign_role(&mut self, user_id: &UserId, role: &str) -> Result<(), RoleStoreError> {
+        if !self.roles.contains_key(role) {
+            return Err(RoleStoreError::RoleNotFound);
+        }
+
+        self.user_roles
+            .entry(user_id.clone())
+            .or_default()
+            .insert(role.to_owned());
+        Ok(())
+    }
+
+    async fn unassign_role(&mut self, user_id: &UserId, role: &str) -> Result<(), RoleStoreError> {
+        let removed = self
+            .user_roles
+            .get_mut(user_id)
+            .is_some_and(|roles| roles.remove(role));
+
+        if !removed {
+            return Err(RoleStoreError::RoleNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn get_user_roles(&self, user_id: &UserId) -> Result<UserRoles, RoleStoreError> {
+        let roles = self
+            .user_roles
+            .get(user_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|name| self.roles.get(name).cloned());
+
+        Ok(UserRoles::new(roles))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(name: &str, permissions: &[&str]) -> Role {
+        let permissions = permissions.iter().map(|p| p.to_string()).collect();
+        Role::new(name.to_owned(), permissions).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_assign_role() {
+        let mut store = HashmapRoleStore::default();
+        let user_id = UserId::default();
+
+        assert_eq!(
+            store.assign_role(&user_id, "analyst").await,
+            Err(RoleStoreError::RoleNotFound)
+        );
+
+        store
+            .set_role(role("analyst", &["reports:read"]))
+            .await
+            .unwrap();
+        store.assign_role(&user_id, "analyst").await.unwrap();
+        store.assign_role(&user_id, "analyst").await.unwrap();
+
+        let roles = store.get_user_roles(&user_id).await.unwrap();
+        assert_eq!(roles.roles, vec!["analyst"]);
+        assert_eq!(roles.permissions, vec!["reports:read"]);
+        assert_eq!(
+            store.get_user_roles(&UserId::default()).await,
+            Ok(UserRoles::default())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_role_replaces_permissions() {
+        let mut store = HashmapRoleStore::default();
+        let user_id = UserId::default();
+
+        store
+            .set_role(role("analyst", &["reports:read"]))
+            .await
+            .unwrap();
+        store.assign_role(&user_id, "analyst").await.unwrap();
+        store
+            .set_role(role("analyst", &["reports:write"]))
+            .await
+            .unwrap();
+
+        let roles = store.get_user_roles(&user_id).await.unwrap();
+        assert_eq!(roles.permissions, vec!["reports:write"]);
+    }
+
+    #[tokio::test]
+    async fn test_unassign_role() {
+        let mut store = HashmapRoleStore::default();
+        let user_id = UserId::default();
+
+        store
+            .set_role(role("analyst", &["reports:read"]))
+            .await
+            .unwrap();
+        store.assign_role(&user_id, "analyst").await.unwrap();
+
+        assert_eq!(store.unassign_role(&user_id, "analyst").await, Ok(()));
+        assert_eq!(
+            store.unassign_role(&user_id, "analyst").await,
+            Err(RoleStoreError::RoleNotFound)
+        );
+        assert_eq!(
+            store.get_user_roles(&user_id).await,
+            Ok(UserRoles::default())
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{Email, RefreshTokenFamilyId, Session, SessionStore, SessionStoreError};
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    sessions: HashMap<RefreshTokenFamilyId, Session>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &RefreshTokenFamilyId) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .get(id)
+            .cloned()
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .values()
+            .filter(|session| session.email == *email)
+            .cloned()
+            .collect();
+
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+        Ok(sessions)
+    }
+
+    async fn touch_session(&mut self, id: &RefreshTokenFamilyId) -> Result<(), SessionStoreError> {
+        let session = self
+            .sessions
+            .get_mut(id)
+            .ok_or(SessionStoreError::SessionNotFound)?;
+
+        session.last_seen_at = Utc::now().timestamp();
+        Ok(())
+    }
+
+    async fn remove_session(&mut self, id: &RefreshTokenFamilyId) -> Result<(), SessionStoreError> {
+        self.sessions
+            .remove(id)
+            .map(|_| ())
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, session| session.email != *email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use secrecy::Secret;
+
+    use super::*;
+
+    fn session(email: &str) -> Session {
+        Session::new(
+            Email::parse(Secret::new(email.to_owned())).unwrap(),
+            Some("test-agent".to_owned()),
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_session() {
+        let mut store = HashmapSessionStore::default();
+        let session = session("user1@a.com");
+
+        store.add_session(session.clone()).await.unwrap();
+
+        assert_eq!(store.get_session(&session.id).await, Ok(session.clone()));
+        assert_eq!(
+            store.get_sessions(&session.email).await,
+            Ok(vec![session.clone()])
+        );
+        assert_eq!(
+            store
+                .get_session(&RefreshTokenFamilyId::default())
+                .await
+                .unwrap_err(),
+            SessionStoreError::SessionNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_touch_session() {
+        let mut store = HashmapSessionStore::default();
+        let mut first = session("user1@a.com");
+        first.last_seen_at -= 60;
+        let mut second = session("user1@a.com");
+        second.last_seen_at -= 30;
+
+        store.add_session(first.clone()).await.unwrap();
+        store.add_session(second.clone()).await.unwrap();
+
+        let ids = |sessions: Vec<Session>| -> Vec<RefreshTokenFamilyId> {
+            sessions.into_iter().map(|session| session.id).collect()
+        };
+
+        let sessions = store.get_sessions(&first.email).await.unwrap();
+        assert_eq!(ids(sessions), vec![second.id.clone(), first.id.clone()]);
+
+        store.touch_session(&first.id).await.unwrap();
+
+        let sessions = store.get_sessions(&first.email).await.unwrap();
+        assert_eq!(ids(sessions), vec![first.id.clone(), second.id.clone()]);
+    }
+
+    #[tokio::test]
+    async fn test_remove_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let first = session("user1@a.com");
+        let second = session("user1@a.com");
+        let other = session("user2@a.com");
+
+        for session in [&first, &second, &other] {
+            store.add_session(session.clone()).await.unwrap();
+        }
+
+        assert_eq!(store.remove_session(&first.id).await, Ok(()));
+        assert_eq!(
+            store.remove_session(&first.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert_eq!(
+            store.get_sessions(&first.email).await,
+            Ok(vec![second.clone()])
+        );
+
+        assert_eq!(store.remove_sessions(&first.email).await, Ok(()));
+        assert_eq!(store.get_sessions(&first.email).await, Ok(vec![]));
+        assert_eq!(store.get_sessions(&other.email).await, Ok(vec![other]));
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::domain::{KeyringEntry, SigningKey, SigningKeyStore, SigningKeyStoreError};
+
+#[derive(Default)]
+pub struct HashmapSigningKeyStore {
+    entries: HashMap<String, KeyringEntry>,
+}
+
+#[async_trait::async_trait]
+impl SigningKeyStore for HashmapSigningKeyStore {
+    async fn get_keys(
+        &self,
+        retired_after: DateTime<Utc>,
+    ) -> Result<Vec<KeyringEntry>, SigningKeyStoreError> {
+        Ok(self
+            .entries
+            .values()
+            .filter(|entry| entry.retired_at.is_none_or(|at| at > retired_after))
+            .cloned()
+            .collect())
+    }
+
+    async fn add_retired_key(
+        &mut self,
+        key: SigningKey,
+        retired_at: DateTime<Utc>,
+    ) -> Result<(), SigningKeyStoreError> {
+        self.entries
+            .entry(key.kid().to_owned())
+            .or_insert(KeyringEntry {
+                key,
+                activated_at: retired_at,
+                retired_at: Some(retired_at),
+            });
+        Ok(())
+    }
+
+    async fn rotate(
+        &mut self,
+        key: SigningKey,
+        activates_at: DateTime<Utc>,
+    ) -> Result<(), SigningKeyStoreError> {
+        if self.entries.contains_key(key.kid()) {
+            return Err(SigningKeyStoreError::KeyAlreadyExists);
+        }
+
+        for entry in self.entries.values_mut() {
+            if entry.retired_at.is_none() {
+                entry.retired_at = Some(activates_at);
+            }
+        }
+
+        self.entries.insert(
+            key.kid().to_owned(),
+            KeyringEntry {
+                key,
+                activated_at: activates_at,
+                retired_at: None,
+            },
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use secrecy::Secret;
+
+    use super::*;
+
+    fn key(secret: &str) -> SigningKey {
+        SigningKey::from_secret(&Secret::new(secret.to_owned()))
+    }
+
+    #[tokio::test]
+    async fn test_rotate_retires_current_key() {
+        let mut store = HashmapSigningKeyStore::default();
+        let now = Utc::now();
+
+        store.rotate(key("first"), now).await.unwrap();
+        store
+            .rotate(key("second"), now + Duration::minutes(2))
+            .await
+            .unwrap();
+
+        let entries = store.get_keys(now - Duration::hours(1)).await.unwrap();
+        let first = entries
+            .iter()
+            .find(|entry| entry.key.kid() == key("first").kid())
+            .unwrap();
+        let second = entries
+            .iter()
+            .find(|entry| entry.key.kid() == key("second").kid())
+            .unwrap();
+
+        assert_eq!(first.retired_at, Some(now + Duration::minutes(2)));
+        assert_eq!(second.activated_at, now + Duration::minutes(2));
+        assert_eq!(second.retired_at, None);
+    }
+
+    #[tokio::test]
+    async fn test_rotate_to_known_key() {
+        let mut store = HashmapSigningKeyStore::default();
+
+        store.rotate(key("first"), Utc::now()).await.unwrap();
+
+        assert_eq!(
+            store.rotate(key("first"), Utc::now()).await,
+            Err(SigningKeyStoreError::KeyAlreadyExists)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_retired_key_keeps_stored_key() {
+        let mut store = HashmapSigningKeyStore::default();
+        let now = Utc::now();
+
+        store.rotate(key("first"), now).await.unwrap();
+        store.add_retired_key(key("first"), now).await.unwrap();
+        store
+            .add_retired_key(key("previous"), now - Duration::hours(1))
+            .await
+            .unwrap();
+
+        let entries = store.get_keys(now - Duration::hours(2)).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries
+            .iter()
+            .any(|entry| entry.key.kid() == key("first").kid() && entry.retired_at.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_get_keys_skips_keys_retired_before() {
+        let mut store = HashmapSigningKeyStore::default();
+        let now = Utc::now();
+
+        store
+            .add_retired_key(key("previous"), now - Duration::hours(2))
+            .await
+            .unwrap();
+        store.rotate(key("first"), now).await.unwrap();
+
+        let entries = store.get_keys(now - Duration::hours(1)).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key.kid(), key("first").kid());
+    }
+}
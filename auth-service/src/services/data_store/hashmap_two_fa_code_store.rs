@@ -1,10 +1,15 @@
-use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use crate::domain::{
+    Email, LoginAttemptId, TwoFAAttemptKey, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+    MAX_2FA_ATTEMPTS,
+};
 use color_eyre::eyre::{eyre, Result};
+use secrecy::ExposeSecret;
 use std::collections::HashMap;
 
 #[derive(Default)]
 pub struct HashmapTwoFACodeStore {
     codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    attempts: HashMap<String, u32>,
 }
 
 #[async_trait::async_trait]
@@ -20,12 +25,16 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
     }
 
     async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        if self.codes.remove(email).is_none() {
-            return Err(TwoFACodeStoreError::UnexpectedError(eyre!(
+        match self.codes.remove(email) {
+            Some((login_attempt_id, _)) => {
+                self.attempts
+                    .remove(&get_attempts_key(&TwoFAAttemptKey::Login(login_attempt_id)));
+                Ok(())
+            }
+            None => Err(TwoFACodeStoreError::UnexpectedError(eyre!(
                 "code not found"
-            )));
+            ))),
         }
-        Ok(())
     }
     async fn get_code(
         &self,
@@ -36,6 +45,35 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
+
+    async fn take_code(
+        &mut self,
+        email: &Email,
+    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        self.codes
+            .remove(email)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    async fn record_attempt(&mut self, key: &TwoFAAttemptKey) -> Result<u32, TwoFACodeStoreError> {
+        let attempts = self.attempts.entry(get_attempts_key(key)).or_default();
+        *attempts += 1;
+        if *attempts > MAX_2FA_ATTEMPTS {
+            return Err(TwoFACodeStoreError::TooManyAttempts);
+        }
+        Ok(MAX_2FA_ATTEMPTS - *attempts)
+    }
+}
+
+fn get_attempts_key(key: &TwoFAAttemptKey) -> String {
+    match key {
+        TwoFAAttemptKey::Login(login_attempt_id) => {
+            format!("login:{}", login_attempt_id.as_ref().expose_secret())
+        }
+        TwoFAAttemptKey::TotpEnrollment(email) => {
+            format!("totp_enrollment:{}", email.as_ref().expose_secret())
+        }
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -135,4 +173,79 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_take_code() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse(Secret::new("user1@a.com".to_string())).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::default();
+        store
+            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+            .await
+            .unwrap();
+
+        let (taken_login_attempt_id, taken_code) = store.take_code(&email).await.unwrap();
+
+        assert_eq!(taken_login_attempt_id, login_attempt_id);
+        assert_eq!(taken_code, code);
+
+        assert_eq!(
+            store.take_code(&email).await.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_attempt() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse(Secret::new("user1@a.com".to_string())).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let key = TwoFAAttemptKey::Login(login_attempt_id.clone());
+        store
+            .add_code(
+                email.clone(),
+                login_attempt_id.clone(),
+                TwoFACode::default(),
+            )
+            .await
+            .unwrap();
+
+        for attempts_left in (0..MAX_2FA_ATTEMPTS).rev() {
+            assert_eq!(store.record_attempt(&key).await.unwrap(), attempts_left);
+        }
+
+        assert_eq!(
+            store.record_attempt(&key).await.unwrap_err(),
+            TwoFACodeStoreError::TooManyAttempts
+        );
+
+        // Storing the code again doesn't reset the count, only a new login attempt has its own
+        store
+            .add_code(
+                email.clone(),
+                login_attempt_id.clone(),
+                TwoFACode::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            store.record_attempt(&key).await.unwrap_err(),
+            TwoFACodeStoreError::TooManyAttempts
+        );
+        assert_eq!(
+            store
+                .record_attempt(&TwoFAAttemptKey::Login(LoginAttemptId::default()))
+                .await
+                .unwrap(),
+            MAX_2FA_ATTEMPTS - 1
+        );
+        assert_eq!(
+            store
+                .record_attempt(&TwoFAAttemptKey::TotpEnrollment(email))
+                .await
+                .unwrap(),
+            MAX_2FA_ATTEMPTS - 1
+        );
+    }
 }
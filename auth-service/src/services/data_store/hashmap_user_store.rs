@@ -1,10 +1,23 @@
 use std::collections::HashMap;
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use chrono::Utc;
+
+use crate::domain::{
+    compute_password_hash, Email, Password, RecoveryCode, StoredRecoveryCode, TotpSecret,
+    TwoFAMethod, User, UserId, UserStore, UserStoreError,
+};
 
 #[derive(Default, Clone)]
 pub struct HashmapUserStore {
     users: HashMap<Email, User>,
+    token_versions: HashMap<Email, i32>,
+    pending_totp_secrets: HashMap<Email, TotpSecret>,
+    totp_secrets: HashMap<Email, TotpSecret>,
+    totp_time_steps: HashMap<Email, u64>,
+    recovery_codes: HashMap<Email, Vec<StoredRecoveryCode>>,
+    next_recovery_code_id: i32,
+    // When the users scheduled for deletion are purged
+    purge_times: HashMap<Email, i64>,
 }
 
 #[async_trait::async_trait]
@@ -18,23 +31,274 @@ impl UserStore for HashmapUserStore {
     }
 
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        match self.users.get(&email) {
+        match self.users.get(email) {
+            Some(_) if self.purge_times.contains_key(email) => Err(UserStoreError::UserNotFound),
             Some(user) => Ok(user.clone()),
             None => Err(UserStoreError::UserNotFound),
         }
     }
 
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        self.users
+            .values()
+            .find(|user| &user.id == id && !self.purge_times.contains_key(&user.email))
+            .cloned()
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
     async fn validate_user(
         &self,
         email: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
-        match self.users.get(&email) {
+        match self.users.get(email) {
+            Some(_) if self.purge_times.contains_key(email) => Err(UserStoreError::UserNotFound),
             Some(user) if user.password.eq(password) => Ok(()),
             Some(_) => Err(UserStoreError::InvalidCredentials),
             None => Err(UserStoreError::UserNotFound),
         }
     }
+
+    async fn verify_email(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.email_verified = true;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+        token_version: i32,
+    ) -> Result<(), UserStoreError> {
+        if self.users.contains_key(new_email) {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+        if self.purge_times.contains_key(email)
+            || self.token_versions.get(email).copied().unwrap_or_default() != token_version
+        {
+            return Err(UserStoreError::UserNotFound);
+        }
+        let mut user = self
+            .users
+            .remove(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.email = new_email.clone();
+        user.email_verified = true;
+        self.users.insert(new_email.clone(), user);
+
+        let version = self.token_versions.remove(email).unwrap_or_default();
+        self.token_versions.insert(new_email.clone(), version + 1);
+        if let Some(secret) = self.pending_totp_secrets.remove(email) {
+            self.pending_totp_secrets.insert(new_email.clone(), secret);
+        }
+        if let Some(secret) = self.totp_secrets.remove(email) {
+            self.totp_secrets.insert(new_email.clone(), secret);
+        }
+        if let Some(time_step) = self.totp_time_steps.remove(email) {
+            self.totp_time_steps.insert(new_email.clone(), time_step);
+        }
+        if let Some(codes) = self.recovery_codes.remove(email) {
+            self.recovery_codes.insert(new_email.clone(), codes);
+        }
+        Ok(())
+    }
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.password = password;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn get_token_version(&self, email: &Email) -> Result<i32, UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(self.token_versions.get(email).copied().unwrap_or_default())
+    }
+
+    async fn increment_token_version(&mut self, email: &Email) -> Result<i32, UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        let version = self.token_versions.entry(email.clone()).or_default();
+        *version += 1;
+        Ok(*version)
+    }
+
+    async fn set_pending_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        self.pending_totp_secrets.insert(email.clone(), secret);
+        Ok(())
+    }
+
+    async fn get_pending_totp_secret(
+        &self,
+        email: &Email,
+    ) -> Result<Option<TotpSecret>, UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(self.pending_totp_secrets.get(email).cloned())
+    }
+
+    async fn enable_totp(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+        time_step: u64,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.requires_2fa = true;
+                user.two_fa_method = TwoFAMethod::AuthenticatorApp;
+                self.pending_totp_secrets.remove(email);
+                self.totp_secrets.insert(email.clone(), secret);
+                self.totp_time_steps.insert(email.clone(), time_step);
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn get_totp_secret(&self, email: &Email) -> Result<Option<TotpSecret>, UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(self.totp_secrets.get(email).cloned())
+    }
+
+    async fn use_totp_time_step(
+        &mut self,
+        email: &Email,
+        time_step: u64,
+    ) -> Result<(), UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        if self
+            .totp_time_steps
+            .get(email)
+            .is_some_and(|last| time_step <= *last)
+        {
+            return Err(UserStoreError::InvalidCredentials);
+        }
+        self.totp_time_steps.insert(email.clone(), time_step);
+        Ok(())
+    }
+
+    async fn set_recovery_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        let mut stored_codes = Vec::with_capacity(codes.len());
+        for code in codes {
+            let code_hash = compute_password_hash(code.as_ref().to_owned())
+                .await
+                .map_err(UserStoreError::UnexpectedError)?;
+            self.next_recovery_code_id += 1;
+            stored_codes.push(StoredRecoveryCode {
+                id: self.next_recovery_code_id,
+                code_hash,
+            });
+        }
+        self.recovery_codes.insert(email.clone(), stored_codes);
+        Ok(())
+    }
+
+    async fn get_recovery_codes(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<StoredRecoveryCode>, UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(self.recovery_codes.get(email).cloned().unwrap_or_default())
+    }
+
+    async fn remove_recovery_code(&mut self, email: &Email, id: i32) -> Result<(), UserStoreError> {
+        let codes = self
+            .recovery_codes
+            .get_mut(email)
+            .ok_or(UserStoreError::InvalidCredentials)?;
+        match codes.iter().position(|stored_code| stored_code.id == id) {
+            Some(index) => {
+                codes.remove(index);
+                Ok(())
+            }
+            None => Err(UserStoreError::InvalidCredentials),
+        }
+    }
+
+    async fn count_recovery_codes(&self, email: &Email) -> Result<usize, UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(self.recovery_codes.get(email).map_or(0, Vec::len))
+    }
+
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        if self.users.remove(email).is_none() {
+            return Err(UserStoreError::UserNotFound);
+        }
+        self.token_versions.remove(email);
+        self.pending_totp_secrets.remove(email);
+        self.totp_secrets.remove(email);
+        self.totp_time_steps.remove(email);
+        self.recovery_codes.remove(email);
+        self.purge_times.remove(email);
+        Ok(())
+    }
+
+    async fn schedule_deletion(
+        &mut self,
+        email: &Email,
+        purge_at: i64,
+    ) -> Result<(), UserStoreError> {
+        if !self.users.contains_key(email) || self.purge_times.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        self.purge_times.insert(email.clone(), purge_at);
+        Ok(())
+    }
+
+    async fn purge_deleted_users(&mut self) -> Result<Vec<Email>, UserStoreError> {
+        let now = Utc::now().timestamp();
+        let due: Vec<Email> = self
+            .purge_times
+            .iter()
+            .filter(|(_, purge_at)| **purge_at <= now)
+            .map(|(email, _)| email.clone())
+            .collect();
+
+        for email in &due {
+            self.delete_user(email).await?;
+        }
+
+        Ok(due)
+    }
 }
 
 #[cfg(test)]
@@ -87,6 +351,29 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_get_user_by_id() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("user1@a.com".to_string())).unwrap(),
+            Password::parse(Secret::new("password123".to_owned())).unwrap(),
+            true,
+        );
+        store.add_user(user.clone()).await.unwrap();
+
+        assert_eq!(store.get_user_by_id(&user.id).await.unwrap(), user);
+        assert_eq!(
+            store.get_user_by_id(&UserId::default()).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        store.schedule_deletion(&user.email, 0).await.unwrap();
+        assert_eq!(
+            store.get_user_by_id(&user.id).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
     #[tokio::test]
     async fn test_validate_user() {
         let mut store = HashmapUserStore::default();
@@ -114,4 +401,317 @@ mod tests {
         assert!(result_not_found.is_err());
         assert_eq!(result_not_found.unwrap_err(), UserStoreError::UserNotFound);
     }
+
+    #[tokio::test]
+    async fn test_verify_email() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("user1@a.com".to_string())).unwrap(),
+            Password::parse(Secret::new("password123".to_string())).unwrap(),
+            true,
+        );
+        store.add_user(user.clone()).await.unwrap();
+
+        assert!(!store.get_user(&user.email).await.unwrap().email_verified);
+        store.verify_email(&user.email).await.unwrap();
+        assert!(store.get_user(&user.email).await.unwrap().email_verified);
+
+        let email2 = Email::parse(Secret::new("user2@a.com".to_string())).unwrap();
+        assert_eq!(
+            store.verify_email(&email2).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_change_email() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("user1@a.com".to_string())).unwrap(),
+            Password::parse(Secret::new("password123".to_string())).unwrap(),
+            true,
+        );
+        let other_user = User::new(
+            Email::parse(Secret::new("user2@a.com".to_string())).unwrap(),
+            Password::parse(Secret::new("password123".to_string())).unwrap(),
+            false,
+        );
+        store.add_user(user.clone()).await.unwrap();
+        store.add_user(other_user.clone()).await.unwrap();
+        store.increment_token_version(&user.email).await.unwrap();
+
+        assert_eq!(
+            store.change_email(&user.email, &other_user.email, 1).await,
+            Err(UserStoreError::UserAlreadyExists)
+        );
+
+        let new_email = Email::parse(Secret::new("user3@a.com".to_string())).unwrap();
+        assert_eq!(
+            store.change_email(&user.email, &new_email, 0).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        store
+            .change_email(&user.email, &new_email, 1)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_user(&user.email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        let changed_user = store.get_user(&new_email).await.unwrap();
+        assert_eq!(changed_user.id, user.id);
+        assert!(changed_user.email_verified);
+        assert_eq!(store.get_token_version(&new_email).await, Ok(2));
+
+        assert_eq!(
+            store.change_email(&user.email, &user.email, 2).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("user1@a.com".to_string())).unwrap(),
+            Password::parse(Secret::new("password123".to_string())).unwrap(),
+            true,
+        );
+        store.add_user(user.clone()).await.unwrap();
+
+        let password2 = Password::parse(Secret::new("password234".to_string())).unwrap();
+        store
+            .update_password(&user.email, password2.clone())
+            .await
+            .unwrap();
+
+        assert!(store.validate_user(&user.email, &password2).await.is_ok());
+        assert_eq!(
+            store
+                .validate_user(&user.email, &user.password)
+                .await
+                .unwrap_err(),
+            UserStoreError::InvalidCredentials
+        );
+
+        let email2 = Email::parse(Secret::new("user2@a.com".to_string())).unwrap();
+        assert_eq!(
+            store.update_password(&email2, password2).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_increment_token_version() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("user1@a.com".to_string())).unwrap(),
+            Password::parse(Secret::new("password123".to_string())).unwrap(),
+            true,
+        );
+        store.add_user(user.clone()).await.unwrap();
+
+        assert_eq!(store.get_token_version(&user.email).await.unwrap(), 0);
+        assert_eq!(store.increment_token_version(&user.email).await.unwrap(), 1);
+        assert_eq!(store.get_token_version(&user.email).await.unwrap(), 1);
+
+        let email2 = Email::parse(Secret::new("user2@a.com".to_string())).unwrap();
+        assert_eq!(
+            store.get_token_version(&email2).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enable_totp() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("user1@a.com".to_string())).unwrap(),
+            Password::parse(Secret::new("password123".to_string())).unwrap(),
+            false,
+        );
+        store.add_user(user.clone()).await.unwrap();
+
+        let secret = TotpSecret::generate().unwrap();
+        store
+            .set_pending_totp_secret(&user.email, secret.clone())
+            .await
+            .unwrap();
+        assert!(store
+            .get_pending_totp_secret(&user.email)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(store.get_totp_secret(&user.email).await.unwrap().is_none());
+
+        store.enable_totp(&user.email, secret, 100).await.unwrap();
+
+        let stored_user = store.get_user(&user.email).await.unwrap();
+        assert!(stored_user.requires_2fa);
+        assert_eq!(stored_user.two_fa_method, TwoFAMethod::AuthenticatorApp);
+        assert!(store
+            .get_pending_totp_secret(&user.email)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(store.get_totp_secret(&user.email).await.unwrap().is_some());
+
+        let email2 = Email::parse(Secret::new("user2@a.com".to_string())).unwrap();
+        assert_eq!(
+            store
+                .enable_totp(&email2, TotpSecret::generate().unwrap(), 100)
+                .await
+                .unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_use_totp_time_step() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("user1@a.com".to_string())).unwrap(),
+            Password::parse(Secret::new("password123".to_string())).unwrap(),
+            false,
+        );
+        store.add_user(user.clone()).await.unwrap();
+        store
+            .enable_totp(&user.email, TotpSecret::generate().unwrap(), 100)
+            .await
+            .unwrap();
+
+        // The code that confirmed the secret can't be used again
+        for time_step in [99, 100] {
+            assert_eq!(
+                store.use_totp_time_step(&user.email, time_step).await,
+                Err(UserStoreError::InvalidCredentials)
+            );
+        }
+
+        assert_eq!(store.use_totp_time_step(&user.email, 101).await, Ok(()));
+        assert_eq!(
+            store.use_totp_time_step(&user.email, 101).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_recovery_code() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(
+            Email::parse(Secret::new("user1@a.com".to_string())).unwrap(),
+            Password::parse(Secret::new("password123".to_string())).unwrap(),
+            true,
+        );
+        store.add_user(user.clone()).await.unwrap();
+
+        let codes = RecoveryCode::generate_set();
+        store
+            .set_recovery_codes(&user.email, codes.clone())
+            .await
+            .unwrap();
+
+        let stored_codes = store.get_recovery_codes(&user.email).await.unwrap();
+        assert_eq!(stored_codes.len(), codes.len());
+        assert!(stored_codes[0].matches(&codes[0]).await);
+        assert!(!stored_codes[0].matches(&codes[1]).await);
+
+        store
+            .remove_recovery_code(&user.email, stored_codes[0].id)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.count_recovery_codes(&user.email).await.unwrap(),
+            codes.len() - 1
+        );
+
+        assert_eq!(
+            store
+                .remove_recovery_code(&user.email, stored_codes[0].id)
+                .await
+                .unwrap_err(),
+            UserStoreError::InvalidCredentials
+        );
+
+        // Codes of a replaced set can't be removed anymore either
+        store
+            .set_recovery_codes(&user.email, RecoveryCode::generate_set())
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .remove_recovery_code(&user.email, stored_codes[1].id)
+                .await
+                .unwrap_err(),
+            UserStoreError::InvalidCredentials
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse(Secret::new("user1@a.com".to_string())).unwrap();
+        let user = User::new(
+            email.clone(),
+            Password::parse(Secret::new("password123".to_owned())).unwrap(),
+            false,
+        );
+        store.add_user(user.clone()).await.unwrap();
+        store.increment_token_version(&email).await.unwrap();
+
+        assert_eq!(store.delete_user(&email).await, Ok(()));
+        assert_eq!(
+            store.get_user(&email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        assert_eq!(
+            store.delete_user(&email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        // Nothing of the old account is left over
+        store.add_user(user).await.unwrap();
+        assert_eq!(store.get_token_version(&email).await, Ok(0));
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_deletion() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse(Secret::new("user1@a.com".to_string())).unwrap();
+        let other_email = Email::parse(Secret::new("user2@a.com".to_string())).unwrap();
+        let password = Password::parse(Secret::new("password123".to_owned())).unwrap();
+
+        for email in [&email, &other_email] {
+            store
+                .add_user(User::new(email.clone(), password.clone(), false))
+                .await
+                .unwrap();
+        }
+
+        let now = Utc::now().timestamp();
+        store.schedule_deletion(&email, now).await.unwrap();
+        store
+            .schedule_deletion(&other_email, now + 3600)
+            .await
+            .unwrap();
+
+        // Both are hidden right away
+        for email in [&email, &other_email] {
+            assert_eq!(
+                store.get_user(email).await,
+                Err(UserStoreError::UserNotFound)
+            );
+            assert_eq!(
+                store.validate_user(email, &password).await,
+                Err(UserStoreError::UserNotFound)
+            );
+        }
+
+        // Only the due one is purged
+        assert_eq!(store.purge_deleted_users().await, Ok(vec![email.clone()]));
+        assert_eq!(store.purge_deleted_users().await, Ok(vec![]));
+        assert!(!store.users.contains_key(&email));
+        assert!(store.users.contains_key(&other_email));
+    }
 }
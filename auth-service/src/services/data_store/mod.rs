@@ -1,13 +1,55 @@
+pub mod hashmap_authorization_code_store;
+pub mod hashmap_device_code_store;
+pub mod hashmap_login_attempt_store;
+pub mod hashmap_oauth_client_store;
+pub mod hashmap_password_reset_token_store;
+pub mod hashmap_personal_access_token_store;
+pub mod hashmap_refresh_token_store;
+pub mod hashmap_role_store;
+pub mod hashmap_session_store;
+pub mod hashmap_signing_key_store;
 pub mod hashmap_two_fa_code_store;
 pub mod hashmap_user_store;
 pub mod hashset_banned_token_store;
+pub mod postgres_oauth_client_store;
+pub mod postgres_personal_access_token_store;
+pub mod postgres_role_store;
+pub mod postgres_session_store;
+pub mod postgres_signing_key_store;
 pub mod postgres_user_store;
+pub mod redis_authorization_code_store;
 pub mod redis_banned_token_store;
+pub mod redis_device_code_store;
+pub mod redis_login_attempt_store;
+pub mod redis_password_reset_token_store;
+pub mod redis_refresh_token_store;
+pub mod redis_session_store;
 pub mod redis_two_fa_code_store;
 
+pub use hashmap_authorization_code_store::*;
+pub use hashmap_device_code_store::*;
+pub use hashmap_login_attempt_store::*;
+pub use hashmap_oauth_client_store::*;
+pub use hashmap_password_reset_token_store::*;
+pub use hashmap_personal_access_token_store::*;
+pub use hashmap_refresh_token_store::*;
+pub use hashmap_role_store::*;
+pub use hashmap_session_store::*;
+pub use hashmap_signing_key_store::*;
 pub use hashmap_two_fa_code_store::*;
 pub use hashmap_user_store::*;
 pub use hashset_banned_token_store::*;
+pub use postgres_oauth_client_store::*;
+pub use postgres_personal_access_token_store::*;
+pub use postgres_role_store::*;
+pub use postgres_session_store::*;
+pub use postgres_signing_key_store::*;
 pub use postgres_user_store::*;
+pub use redis_authorization_code_store::*;
 pub use redis_banned_token_store::*;
+pub use redis_device_code_store::*;
+pub use redis_login_attempt_store::*;
+pub use redis_password_reset_token_store::*;
+pub use redis_refresh_token_store::*;
+pub use redis_session_store::*;
 pub use redis_two_fa_code_store::*;
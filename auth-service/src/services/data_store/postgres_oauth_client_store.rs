@@ -0,0 +1,53 @@
+use sqlx::PgPool;
+
+use crate::domain::{OAuthClient, OAuthClientStore, OAuthClientStoreError};
+
+pub struct PostgresOAuthClientStore {
+    pool: PgPool,
+}
+
+impl PostgresOAuthClientStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for PostgresOAuthClientStore {
+    #[tracing::instrument(name = "Adding OAuth client to PostgreSQL", skip_all)]
+    async fn add_client(&mut self, client: OAuthClient) -> Result<(), OAuthClientStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO oauth_clients (id, name, secret_hash, redirect_uris, scopes)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            client.id,
+            client.name,
+            client.secret_hash,
+            &client.redirect_uris,
+            &client.scopes,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving OAuth client from PostgreSQL", skip_all)]
+    async fn get_client(&self, id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        sqlx::query_as!(
+            OAuthClient,
+            r#"
+            SELECT id, name, secret_hash, redirect_uris, scopes
+            FROM oauth_clients
+            WHERE id = $1
+            "#,
+            id,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?
+        .ok_or(OAuthClientStoreError::ClientNotFound)
+    }
+}
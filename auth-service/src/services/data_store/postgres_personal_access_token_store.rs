@@ -0,0 +1,171 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    PersonalAccessToken, PersonalAccessTokenStore, PersonalAccessTokenStoreError, UserId,
+};
+
+pub struct PostgresPersonalAccessTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresPersonalAccessTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl PersonalAccessTokenStore for PostgresPersonalAccessTokenStore {
+    #[tracing::instrument(name = "Adding personal access token to PostgreSQL", skip_all)]
+    async fn add_token(
+        &mut self,
+        token: PersonalAccessToken,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO personal_access_tokens
+                (id, user_id, name, token_hash, scopes, created_at, last_used_at, expires_at)
+            VALUES ($1, $2::TEXT::UUID, $3, $4, $5, to_timestamp($6::BIGINT),
+                to_timestamp($7::BIGINT), to_timestamp($8::BIGINT))
+            "#,
+            token.id,
+            token.user_id.as_ref(),
+            token.name,
+            token.token_hash,
+            &token.scopes,
+            token.created_at,
+            token.last_used_at,
+            token.expires_at,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving personal access token from PostgreSQL", skip_all)]
+    async fn get_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<PersonalAccessToken, PersonalAccessTokenStoreError> {
+        sqlx::query_as!(
+            PersonalAccessTokenRow,
+            r#"
+            SELECT id, user_id::TEXT AS "user_id!", name, token_hash, scopes,
+                EXTRACT(EPOCH FROM created_at)::BIGINT AS "created_at!",
+                EXTRACT(EPOCH FROM last_used_at)::BIGINT AS last_used_at,
+                EXTRACT(EPOCH FROM expires_at)::BIGINT AS expires_at
+            FROM personal_access_tokens
+            WHERE token_hash = $1
+            "#,
+            token_hash,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?
+        .ok_or(PersonalAccessTokenStoreError::TokenNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Retrieving personal access tokens from PostgreSQL", skip_all)]
+    async fn get_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Vec<PersonalAccessToken>, PersonalAccessTokenStoreError> {
+        sqlx::query_as!(
+            PersonalAccessTokenRow,
+            r#"
+            SELECT id, user_id::TEXT AS "user_id!", name, token_hash, scopes,
+                EXTRACT(EPOCH FROM created_at)::BIGINT AS "created_at!",
+                EXTRACT(EPOCH FROM last_used_at)::BIGINT AS last_used_at,
+                EXTRACT(EPOCH FROM expires_at)::BIGINT AS expires_at
+            FROM personal_access_tokens
+            WHERE user_id = $1::TEXT::UUID
+            ORDER BY created_at DESC
+            "#,
+            user_id.as_ref(),
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(PersonalAccessToken::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Touching personal access token in PostgreSQL", skip_all)]
+    async fn touch_token(&mut self, id: &str) -> Result<(), PersonalAccessTokenStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE personal_access_tokens
+            SET last_used_at = now()
+            WHERE id = $1
+            "#,
+            id,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PersonalAccessTokenStoreError::TokenNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing personal access token from PostgreSQL", skip_all)]
+    async fn remove_token(
+        &mut self,
+        user_id: &UserId,
+        id: &str,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM personal_access_tokens
+            WHERE id = $1 AND user_id = $2::TEXT::UUID
+            "#,
+            id,
+            user_id.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PersonalAccessTokenStoreError::TokenNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+struct PersonalAccessTokenRow {
+    id: String,
+    user_id: String,
+    name: String,
+    token_hash: String,
+    scopes: Vec<String>,
+    created_at: i64,
+    last_used_at: Option<i64>,
+    expires_at: Option<i64>,
+}
+
+impl TryFrom<PersonalAccessTokenRow> for PersonalAccessToken {
+    type Error = PersonalAccessTokenStoreError;
+
+    fn try_from(row: PersonalAccessTokenRow) -> Result<Self, Self::Error> {
+        Ok(PersonalAccessToken {
+            id: row.id,
+            user_id: UserId::parse(row.user_id)
+                .map_err(PersonalAccessTokenStoreError::UnexpectedError)?,
+            name: row.name,
+            token_hash: row.token_hash,
+            scopes: row.scopes,
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+            expires_at: row.expires_at,
+        })
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use sqlx::PgPool;
+
+use crate::domain::{Role, RoleStore, RoleStoreError, UserId, UserRoles};
+
+pub struct PostgresRoleStore {
+    pool: PgPool,
+}
+
+impl PostgresRoleStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RoleStore for PostgresRoleStore {
+    #[tracing::instrument(name = "Setting role in PostgreSQL", skip_all)]
+    async fn set_role(&mut self, role: Role) -> Result<(), RoleStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| RoleStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO roles (name)
+            VALUES ($1)
+            ON CONFLICT (name) DO NOTHING
+            "#,
+            role.name,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RoleStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO permissions (name)
+            SELECT permission FROM UNNEST($1::TEXT[]) AS permission
+            ON CONFLICT (name) DO NOTHING
+            "#,
+            &role.permissions,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RoleStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM role_permissions
+            WHERE role = $1
+            "#,
+            role.name,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RoleStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO role_permissions (role, permission)
+            SELECT $1, permission FROM UNNEST($2::TEXT[]) AS permission
+            "#,
+            role.name,
+            &role.permissions,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| RoleStoreError::UnexpectedError(e.into()))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| RoleStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Assigning role in PostgreSQL", skip_all)]
+    async fn assign_role(&mut self, user_id: &UserId, role: &str) -> Result<(), RoleStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO user_roles (user_id, role)
+            VALUES ($1::TEXT::UUID, $2)
+            ON CONFLICT (user_id, role) DO NOTHING
+            "#,
+            user_id.as_ref(),
+            role,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.constraint() == Some("user_roles_role_fkey") => {
+                RoleStoreError::RoleNotFound
+            }
+            e => RoleStoreError::UnexpectedError(e.into()),
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Unassigning role in PostgreSQL", skip_all)]
+    async fn unassign_role(&mut self, user_id: &UserId, role: &str) -> Result<(), RoleStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM user_roles
+            WHERE user_id = $1::TEXT::UUID AND role = $2
+            "#,
+            user_id.as_ref(),
+            role,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RoleStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(RoleStoreError::RoleNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving user roles from PostgreSQL", skip_all)]
+    async fn get_user_roles(&self, user_id: &UserId) -> Result<UserRoles, RoleStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT user_roles.role, role_permissions.permission AS "permission?"
+            FROM user_roles
+            LEFT JOIN role_permissions ON role_permissions.role = user_roles.role
+            WHERE user_roles.user_id = $1::TEXT::UUID
+            "#,
+            user_id.as_ref(),
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| RoleStoreError::UnexpectedError(e.into()))?;
+
+        // One row per permission of each role, and one without a permission for empty roles
+        let mut roles: HashMap<String, Vec<String>> = HashMap::new();
+        for row in rows {
+            let permissions = roles.entry(row.role).or_default();
+            permissions.extend(row.permission);
+        }
+
+        let roles = roles
+            .into_iter()
+            .map(|(name, permissions)| Role { name, permissions });
+
+        Ok(UserRoles::new(roles))
+    }
+}
@@ -0,0 +1,188 @@
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        AuthenticationMethod, Email, RefreshTokenFamilyId, Session, SessionStore, SessionStoreError,
+    },
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+// Sessions that weren't used for as long as a refresh token lives can't be used anymore,
+// so they are left out. Their rows are removed with the other sessions of the user.
+pub struct PostgresSessionStore {
+    pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for PostgresSessionStore {
+    #[tracing::instrument(name = "Adding session to PostgreSQL", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions (id, email, created_at, last_seen_at, user_agent, ip_address, amr)
+            VALUES ($1, $2, to_timestamp($3::BIGINT), to_timestamp($4::BIGINT), $5, $6, $7)
+            "#,
+            session.id.as_ref(),
+            session.email.as_ref().expose_secret(),
+            session.created_at,
+            session.last_seen_at,
+            session.user_agent,
+            session.ip_address.to_string(),
+            &session
+                .amr
+                .iter()
+                .map(|method| method.as_ref().to_owned())
+                .collect::<Vec<_>>(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving session from PostgreSQL", skip_all)]
+    async fn get_session(&self, id: &RefreshTokenFamilyId) -> Result<Session, SessionStoreError> {
+        sqlx::query_as!(
+            SessionRow,
+            r#"
+            SELECT id, email, user_agent, ip_address, amr,
+                EXTRACT(EPOCH FROM created_at)::BIGINT AS "created_at!",
+                EXTRACT(EPOCH FROM last_seen_at)::BIGINT AS "last_seen_at!"
+            FROM sessions
+            WHERE id = $1 AND last_seen_at > now() - make_interval(secs => $2)
+            "#,
+            id.as_ref(),
+            REFRESH_TOKEN_TTL_SECONDS as f64,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?
+        .ok_or(SessionStoreError::SessionNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Retrieving sessions from PostgreSQL", skip_all)]
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        sqlx::query_as!(
+            SessionRow,
+            r#"
+            SELECT id, email, user_agent, ip_address, amr,
+                EXTRACT(EPOCH FROM created_at)::BIGINT AS "created_at!",
+                EXTRACT(EPOCH FROM last_seen_at)::BIGINT AS "last_seen_at!"
+            FROM sessions
+            WHERE email = $1 AND last_seen_at > now() - make_interval(secs => $2)
+            ORDER BY last_seen_at DESC
+            "#,
+            email.as_ref().expose_secret(),
+            REFRESH_TOKEN_TTL_SECONDS as f64,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(Session::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Touching session in PostgreSQL", skip_all)]
+    async fn touch_session(&mut self, id: &RefreshTokenFamilyId) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET last_seen_at = now()
+            WHERE id = $1
+            "#,
+            id.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing session from PostgreSQL", skip_all)]
+    async fn remove_session(&mut self, id: &RefreshTokenFamilyId) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE id = $1
+            "#,
+            id.as_ref(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing sessions from PostgreSQL", skip_all)]
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
+
+struct SessionRow {
+    id: String,
+    email: String,
+    user_agent: Option<String>,
+    ip_address: String,
+    amr: Vec<String>,
+    created_at: i64,
+    last_seen_at: i64,
+}
+
+impl TryFrom<SessionRow> for Session {
+    type Error = SessionStoreError;
+
+    fn try_from(row: SessionRow) -> Result<Self, Self::Error> {
+        Ok(Session {
+            id: RefreshTokenFamilyId::parse(row.id).map_err(SessionStoreError::UnexpectedError)?,
+            email: Email::parse(Secret::new(row.email))
+                .map_err(SessionStoreError::UnexpectedError)?,
+            created_at: row.created_at,
+            last_seen_at: row.last_seen_at,
+            user_agent: row.user_agent,
+            ip_address: row
+                .ip_address
+                .parse()
+                .map_err(|e: std::net::AddrParseError| {
+                    SessionStoreError::UnexpectedError(e.into())
+                })?,
+            amr: row
+                .amr
+                .iter()
+                .map(|method| AuthenticationMethod::parse(method))
+                .collect::<Result<_, _>>()
+                .map_err(SessionStoreError::UnexpectedError)?,
+        })
+    }
+}
@@ -0,0 +1,162 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use jsonwebtoken::Algorithm;
+use sqlx::PgPool;
+
+use crate::domain::{
+    KeyringEntry, SecretCipher, SigningKey, SigningKeyStore, SigningKeyStoreError,
+};
+
+pub struct PostgresSigningKeyStore {
+    pool: PgPool,
+    cipher: SecretCipher,
+}
+
+impl PostgresSigningKeyStore {
+    pub fn new(pool: PgPool, cipher: SecretCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    fn parse_key(&self, algorithm: &str, private_key: &[u8]) -> Result<SigningKey> {
+        let algorithm = Algorithm::from_str(algorithm).wrap_err("Invalid signing key algorithm")?;
+        let private_key = self.cipher.decrypt(private_key)?;
+        SigningKey::from_private_key(algorithm, &private_key)
+    }
+}
+
+#[async_trait::async_trait]
+impl SigningKeyStore for PostgresSigningKeyStore {
+    #[tracing::instrument(name = "Retrieving signing keys from PostgreSQL", skip_all)]
+    async fn get_keys(
+        &self,
+        retired_after: DateTime<Utc>,
+    ) -> Result<Vec<KeyringEntry>, SigningKeyStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT
+                algorithm,
+                private_key,
+                EXTRACT(EPOCH FROM activated_at)::BIGINT AS "activated_at!",
+                EXTRACT(EPOCH FROM retired_at)::BIGINT AS retired_at
+            FROM signing_keys
+            WHERE retired_at IS NULL OR retired_at > to_timestamp($1::BIGINT)
+            "#,
+            retired_after.timestamp(),
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(KeyringEntry {
+                    key: self
+                        .parse_key(&row.algorithm, &row.private_key)
+                        .map_err(SigningKeyStoreError::UnexpectedError)?,
+                    activated_at: parse_timestamp(row.activated_at)?,
+                    retired_at: row.retired_at.map(parse_timestamp).transpose()?,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Adding retired signing key to PostgreSQL", skip_all)]
+    async fn add_retired_key(
+        &mut self,
+        key: SigningKey,
+        retired_at: DateTime<Utc>,
+    ) -> Result<(), SigningKeyStoreError> {
+        let private_key = self
+            .cipher
+            .encrypt(key.private_key())
+            .map_err(SigningKeyStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO signing_keys (kid, algorithm, private_key, activated_at, retired_at)
+            VALUES ($1, $2, $3, to_timestamp($4::BIGINT), to_timestamp($4::BIGINT))
+            ON CONFLICT (kid) DO NOTHING
+            "#,
+            key.kid(),
+            format!("{:?}", key.algorithm()),
+            private_key,
+            retired_at.timestamp(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Rotating signing key in PostgreSQL", skip_all)]
+    async fn rotate(
+        &mut self,
+        key: SigningKey,
+        activates_at: DateTime<Utc>,
+    ) -> Result<(), SigningKeyStoreError> {
+        let private_key = self
+            .cipher
+            .encrypt(key.private_key())
+            .map_err(SigningKeyStoreError::UnexpectedError)?;
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+
+        // Concurrent rotations would otherwise both leave their key unretired
+        sqlx::query!("LOCK TABLE signing_keys IN SHARE ROW EXCLUSIVE MODE")
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            UPDATE signing_keys
+            SET retired_at = to_timestamp($1::BIGINT)
+            WHERE retired_at IS NULL
+            "#,
+            activates_at.timestamp(),
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO signing_keys (kid, algorithm, private_key, activated_at)
+            VALUES ($1, $2, $3, to_timestamp($4::BIGINT))
+            ON CONFLICT (kid) DO NOTHING
+            "#,
+            key.kid(),
+            format!("{:?}", key.algorithm()),
+            private_key,
+            activates_at.timestamp(),
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?
+        .rows_affected();
+
+        // Dropping the transaction rolls back the retirement of the current key
+        if inserted == 0 {
+            return Err(SigningKeyStoreError::KeyAlreadyExists);
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))
+    }
+}
+
+fn parse_timestamp(seconds: i64) -> Result<DateTime<Utc>, SigningKeyStoreError> {
+    DateTime::from_timestamp(seconds, 0).ok_or(SigningKeyStoreError::UnexpectedError(eyre!(
+        "invalid timestamp: {}",
+        seconds
+    )))
+}
@@ -1,24 +1,32 @@
-use argon2::{
-    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
-    PasswordVerifier, Version,
-};
-
-use color_eyre::eyre::{Context, Result};
+use color_eyre::eyre::Result;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 
 use crate::domain::{
+    compute_password_hash,
     data_stores::{UserStore, UserStoreError},
-    Email, Password, User,
+    verify_password_hash, Email, Password, RecoveryCode, StoredRecoveryCode, TotpSecret,
+    TotpSecretCipher, TwoFAMethod, User, UserId,
 };
 
 pub struct PostgresUserStore {
     pool: PgPool,
+    totp_cipher: TotpSecretCipher,
 }
 
 impl PostgresUserStore {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, totp_cipher: TotpSecretCipher) -> Self {
+        Self { pool, totp_cipher }
+    }
+
+    fn decrypt_totp_secret(
+        &self,
+        encrypted: Option<Vec<u8>>,
+    ) -> Result<Option<TotpSecret>, UserStoreError> {
+        encrypted
+            .map(|encrypted| self.totp_cipher.decrypt(&encrypted))
+            .transpose()
+            .map_err(UserStoreError::UnexpectedError)
     }
 }
 
@@ -32,16 +40,25 @@ impl UserStore for PostgresUserStore {
 
         sqlx::query!(
             r#"
-            INSERT INTO users (email, password_hash, requires_2fa) 
-            VALUES ($1, $2, $3)
+            INSERT INTO users (id, email, password_hash, requires_2fa, two_fa_method, email_verified) 
+            VALUES ($1::TEXT::UUID, $2, $3, $4, $5, $6)
             "#,
+            user.id.as_ref(),
             user.email.as_ref().expose_secret(),
             password_hash.expose_secret(),
-            user.requires_2fa
+            user.requires_2fa,
+            user.two_fa_method.as_ref(),
+            user.email_verified
         )
         .execute(&self.pool)
         .await
-        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        .map_err(|e| match e {
+            // Also users scheduled for deletion keep their address until they are purged
+            sqlx::Error::Database(e) if e.is_unique_violation() => {
+                UserStoreError::UserAlreadyExists
+            }
+            e => UserStoreError::UnexpectedError(e.into()),
+        })?;
 
         Ok(())
     }
@@ -50,9 +67,9 @@ impl UserStore for PostgresUserStore {
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         sqlx::query!(
             r#"
-            SELECT email, password_hash, requires_2fa 
+            SELECT id::TEXT AS "id!", email, password_hash, requires_2fa, two_fa_method, email_verified 
             FROM users 
-            WHERE email = $1
+            WHERE email = $1 AND purge_at IS NULL
             "#,
             email.as_ref().expose_secret(),
         )
@@ -60,12 +77,46 @@ impl UserStore for PostgresUserStore {
         .await
         .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
         .map(|row| {
-            Ok(User::new(
-                Email::parse(Secret::new(row.email)).map_err(UserStoreError::UnexpectedError)?,
-                Password::parse(Secret::new(row.password_hash))
+            Ok(User {
+                id: UserId::parse(row.id).map_err(UserStoreError::UnexpectedError)?,
+                email: Email::parse(Secret::new(row.email))
+                    .map_err(UserStoreError::UnexpectedError)?,
+                password: Password::parse(Secret::new(row.password_hash))
+                    .map_err(UserStoreError::UnexpectedError)?,
+                requires_2fa: row.requires_2fa,
+                two_fa_method: TwoFAMethod::parse(&row.two_fa_method)
+                    .map_err(UserStoreError::UnexpectedError)?,
+                email_verified: row.email_verified,
+            })
+        })
+        .ok_or(UserStoreError::UserNotFound)?
+    }
+
+    #[tracing::instrument(name = "Retrieving user by id from PostgreSQL", skip_all)]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        sqlx::query!(
+            r#"
+            SELECT id::TEXT AS "id!", email, password_hash, requires_2fa, two_fa_method, email_verified 
+            FROM users 
+            WHERE id = $1::TEXT::UUID AND purge_at IS NULL
+            "#,
+            id.as_ref(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .map(|row| {
+            Ok(User {
+                id: UserId::parse(row.id).map_err(UserStoreError::UnexpectedError)?,
+                email: Email::parse(Secret::new(row.email))
+                    .map_err(UserStoreError::UnexpectedError)?,
+                password: Password::parse(Secret::new(row.password_hash))
                     .map_err(UserStoreError::UnexpectedError)?,
-                row.requires_2fa,
-            ))
+                requires_2fa: row.requires_2fa,
+                two_fa_method: TwoFAMethod::parse(&row.two_fa_method)
+                    .map_err(UserStoreError::UnexpectedError)?,
+                email_verified: row.email_verified,
+            })
         })
         .ok_or(UserStoreError::UserNotFound)?
     }
@@ -87,58 +138,441 @@ impl UserStore for PostgresUserStore {
 
         Ok(())
     }
-}
 
-#[tracing::instrument(name = "Verify password hash", skip_all)]
-async fn verify_password_hash(
-    expected_password_hash: Secret<String>,
-    password_candidate: Secret<String>,
-) -> Result<()> {
-    let current_span: tracing::Span = tracing::Span::current();
-
-    let result = tokio::task::spawn_blocking(move || {
-        // This code block ensures that the operations within the closure are executed within the context of the current span.
-        // This is especially useful for tracing operations that are performed in a different thread or task, such as within tokio::task::spawn_blocking.
-        current_span.in_scope(|| {
-            let expected_password_hash: PasswordHash<'_> =
-                PasswordHash::new(expected_password_hash.expose_secret())?;
-
-            Argon2::default()
-                .verify_password(
-                    password_candidate.expose_secret().as_bytes(),
-                    &expected_password_hash,
-                )
-                .wrap_err("failed to verify password hash")
-        })
-    })
-    .await;
+    #[tracing::instrument(name = "Marking user email as verified in PostgreSQL", skip_all)]
+    async fn verify_email(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET email_verified = TRUE
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
 
-    result?
-}
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
 
-#[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
-    let current_span: tracing::Span = tracing::Span::current();
-
-    let result = tokio::task::spawn_blocking(move || {
-        // This code block ensures that the operations within the closure are executed within the context of the current span.
-        // This is especially useful for tracing operations that are performed in a different thread or task, such as within tokio::task::spawn_blocking.
-        current_span.in_scope(|| {
-            let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
-
-            let password_hash = Argon2::new(
-                Algorithm::Argon2id,
-                Version::V0x13,
-                Params::new(15000, 2, 1, None)?,
-            )
-            .hash_password(password.expose_secret().as_bytes(), &salt)?
-            .to_string();
-
-            Ok(Secret::new(password_hash))
-            //Err(eyre!("oh no!"))
-        })
-    })
-    .await;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Changing user email in PostgreSQL", skip_all)]
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: &Email,
+        token_version: i32,
+    ) -> Result<(), UserStoreError> {
+        // Recovery codes and sessions follow the address by their foreign keys
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET email = $2, email_verified = TRUE, token_version = token_version + 1
+            WHERE email = $1 AND token_version = $3 AND purge_at IS NULL
+            "#,
+            email.as_ref().expose_secret(),
+            new_email.as_ref().expose_secret(),
+            token_version,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => {
+                UserStoreError::UserAlreadyExists
+            }
+            e => UserStoreError::UnexpectedError(e.into()),
+        })?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating user password in PostgreSQL", skip_all)]
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(password.as_ref().to_owned())
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $1
+            WHERE email = $2
+            "#,
+            password_hash.expose_secret(),
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving token version from PostgreSQL", skip_all)]
+    async fn get_token_version(&self, email: &Email) -> Result<i32, UserStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT token_version
+            FROM users
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)
+    }
+
+    #[tracing::instrument(name = "Incrementing token version in PostgreSQL", skip_all)]
+    async fn increment_token_version(&mut self, email: &Email) -> Result<i32, UserStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            UPDATE users
+            SET token_version = token_version + 1
+            WHERE email = $1
+            RETURNING token_version
+            "#,
+            email.as_ref().expose_secret(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)
+    }
+
+    #[tracing::instrument(name = "Storing pending TOTP secret in PostgreSQL", skip_all)]
+    async fn set_pending_totp_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        let encrypted = self
+            .totp_cipher
+            .encrypt(&secret)
+            .map_err(UserStoreError::UnexpectedError)?;
 
-    result?
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET pending_totp_secret = $1
+            WHERE email = $2
+            "#,
+            encrypted,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving pending TOTP secret from PostgreSQL", skip_all)]
+    async fn get_pending_totp_secret(
+        &self,
+        email: &Email,
+    ) -> Result<Option<TotpSecret>, UserStoreError> {
+        let encrypted = sqlx::query_scalar!(
+            r#"
+            SELECT pending_totp_secret
+            FROM users
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        self.decrypt_totp_secret(encrypted)
+    }
+
+    #[tracing::instrument(name = "Enabling TOTP in PostgreSQL", skip_all)]
+    async fn enable_totp(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+        time_step: u64,
+    ) -> Result<(), UserStoreError> {
+        let encrypted = self
+            .totp_cipher
+            .encrypt(&secret)
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET requires_2fa = TRUE,
+                two_fa_method = $1,
+                totp_secret = $2,
+                pending_totp_secret = NULL,
+                totp_time_step = $3
+            WHERE email = $4
+            "#,
+            TwoFAMethod::AuthenticatorApp.as_ref(),
+            encrypted,
+            time_step as i64,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving TOTP secret from PostgreSQL", skip_all)]
+    async fn get_totp_secret(&self, email: &Email) -> Result<Option<TotpSecret>, UserStoreError> {
+        let encrypted = sqlx::query_scalar!(
+            r#"
+            SELECT totp_secret
+            FROM users
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        self.decrypt_totp_secret(encrypted)
+    }
+
+    #[tracing::instrument(name = "Using TOTP time step in PostgreSQL", skip_all)]
+    async fn use_totp_time_step(
+        &mut self,
+        email: &Email,
+        time_step: u64,
+    ) -> Result<(), UserStoreError> {
+        // The comparison is part of the update, so concurrent requests can't both use the step
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_time_step = $1
+            WHERE email = $2 AND (totp_time_step IS NULL OR totp_time_step < $1)
+            "#,
+            time_step as i64,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::InvalidCredentials);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Storing recovery codes in PostgreSQL", skip_all)]
+    async fn set_recovery_codes(
+        &mut self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), UserStoreError> {
+        // Recovery codes are hashed just like passwords
+        let mut code_hashes = Vec::with_capacity(codes.len());
+        for code in codes {
+            let code_hash = compute_password_hash(code.as_ref().to_owned())
+                .await
+                .map_err(UserStoreError::UnexpectedError)?;
+            code_hashes.push(code_hash.expose_secret().to_owned());
+        }
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (email, code_hash)
+            SELECT $1, code_hash FROM UNNEST($2::TEXT[]) AS code_hash
+            "#,
+            email.as_ref().expose_secret(),
+            &code_hashes,
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_foreign_key_violation() => {
+                UserStoreError::UserNotFound
+            }
+            e => UserStoreError::UnexpectedError(e.into()),
+        })?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Retrieving recovery codes from PostgreSQL", skip_all)]
+    async fn get_recovery_codes(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<StoredRecoveryCode>, UserStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, code_hash
+            FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| StoredRecoveryCode {
+                id: row.id,
+                code_hash: Secret::new(row.code_hash),
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(name = "Removing recovery code from PostgreSQL", skip_all)]
+    async fn remove_recovery_code(&mut self, email: &Email, id: i32) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE id = $1 AND email = $2
+            "#,
+            id,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        // Somebody else used the code in the meantime
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::InvalidCredentials);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Counting recovery codes in PostgreSQL", skip_all)]
+    async fn count_recovery_codes(&self, email: &Email) -> Result<usize, UserStoreError> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        usize::try_from(count).map_err(|e| UserStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Deleting user from PostgreSQL", skip_all)]
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        // Recovery codes and sessions are deleted along with the user by their foreign keys
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM users
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Scheduling user deletion in PostgreSQL", skip_all)]
+    async fn schedule_deletion(
+        &mut self,
+        email: &Email,
+        purge_at: i64,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET purge_at = to_timestamp($2::BIGINT)
+            WHERE email = $1 AND purge_at IS NULL
+            "#,
+            email.as_ref().expose_secret(),
+            purge_at,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Purging deleted users from PostgreSQL", skip_all)]
+    async fn purge_deleted_users(&mut self) -> Result<Vec<Email>, UserStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            DELETE FROM users
+            WHERE purge_at <= now()
+            RETURNING email
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .into_iter()
+        .map(|email| Email::parse(Secret::new(email)).map_err(UserStoreError::UnexpectedError))
+        .collect()
+    }
 }
@@ -0,0 +1,125 @@
+use std::{net::IpAddr, sync::Arc};
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{
+            AuthorizationCode, AuthorizationCodeStore, AuthorizationCodeStoreError,
+            AuthorizationGrant, ClientGrant,
+        },
+        AuthenticationMethod, CodeChallenge, UserId,
+    },
+    utils::auth::AUTHORIZATION_CODE_TTL_SECONDS,
+};
+
+pub struct RedisAuthorizationCodeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisAuthorizationCodeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for RedisAuthorizationCodeStore {
+    #[tracing::instrument(name = "Add authorization code", skip_all)]
+    async fn add_code(
+        &mut self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        let record = AuthorizationGrantRecord {
+            client_grant: grant.client_grant,
+            redirect_uri: grant.redirect_uri,
+            code_challenge: grant.code_challenge.as_ref().to_owned(),
+            user_id: grant.user_id.as_ref().to_owned(),
+            user_agent: grant.user_agent,
+            ip_address: grant.ip_address,
+            nonce: grant.nonce,
+            auth_time: grant.auth_time,
+            amr: grant.amr,
+        };
+        let record_json = serde_json::to_string(&record)
+            .wrap_err("Failed to serialize authorization grant")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(
+                get_key(&code).expose_secret(),
+                record_json,
+                AUTHORIZATION_CODE_TTL_SECONDS,
+            )
+            .wrap_err("Failed to set authorization code in Redis")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Take authorization code", skip_all)]
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        // GETDEL makes sure that concurrent requests can't both exchange the code
+        let value: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_key(code).expose_secret())
+            .wrap_err("Failed to take authorization code from Redis")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(AuthorizationCodeStoreError::CodeNotFound)?;
+
+        let record: AuthorizationGrantRecord = serde_json::from_str(&value)
+            .wrap_err("failed to deserialize authorization grant")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        Ok(AuthorizationGrant {
+            client_grant: record.client_grant,
+            redirect_uri: record.redirect_uri,
+            code_challenge: CodeChallenge::parse(record.code_challenge)
+                .map_err(AuthorizationCodeStoreError::UnexpectedError)?,
+            user_id: UserId::parse(record.user_id)
+                .map_err(AuthorizationCodeStoreError::UnexpectedError)?,
+            user_agent: record.user_agent,
+            ip_address: record.ip_address,
+            nonce: record.nonce,
+            auth_time: record.auth_time,
+            amr: record.amr,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthorizationGrantRecord {
+    client_grant: ClientGrant,
+    redirect_uri: String,
+    code_challenge: String,
+    user_id: String,
+    user_agent: Option<String>,
+    ip_address: IpAddr,
+    nonce: Option<String>,
+    auth_time: i64,
+    amr: Vec<AuthenticationMethod>,
+}
+
+const AUTHORIZATION_CODE_KEY_PREFIX: &str = "authorization_code:";
+
+fn get_key(code: &AuthorizationCode) -> Secret<String> {
+    Secret::new(format!(
+        "{}{}",
+        AUTHORIZATION_CODE_KEY_PREFIX,
+        code.as_ref().expose_secret()
+    ))
+}
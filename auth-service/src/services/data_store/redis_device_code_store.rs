@@ -0,0 +1,270 @@
+use std::{net::IpAddr, sync::Arc};
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        ClientGrant, DeviceAuthorization, DeviceAuthorizationStatus, DeviceCode, DeviceCodeStore,
+        DeviceCodeStoreError, UserCode, UserId,
+    },
+    utils::auth::{DEVICE_CODE_POLL_INTERVAL_SECONDS, DEVICE_CODE_TTL_SECONDS},
+};
+
+pub struct RedisDeviceCodeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisDeviceCodeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl DeviceCodeStore for RedisDeviceCodeStore {
+    #[tracing::instrument(name = "Add device code", skip_all)]
+    async fn add_code(
+        &mut self,
+        device_code: DeviceCode,
+        authorization: DeviceAuthorization,
+    ) -> Result<(), DeviceCodeStoreError> {
+        let user_code_key = get_user_code_key(&authorization.user_code);
+        let record_json = serialize(authorization.into())?;
+
+        let mut conn = self.conn.write().await;
+
+        let _: () = conn
+            .set_ex(
+                get_key(&device_code).expose_secret(),
+                record_json,
+                DEVICE_CODE_TTL_SECONDS,
+            )
+            .wrap_err("Failed to set device code in Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .set_ex(
+                user_code_key,
+                device_code.as_ref().expose_secret(),
+                DEVICE_CODE_TTL_SECONDS,
+            )
+            .wrap_err("Failed to set user code in Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Resolve device code", skip_all)]
+    async fn resolve(
+        &mut self,
+        user_code: &UserCode,
+        status: DeviceAuthorizationStatus,
+    ) -> Result<(), DeviceCodeStoreError> {
+        let mut conn = self.conn.write().await;
+
+        // GETDEL makes sure that the code can't be entered twice
+        let device_code: Option<String> = conn
+            .get_del(get_user_code_key(user_code))
+            .wrap_err("Failed to take user code from Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        let device_code = device_code.ok_or(DeviceCodeStoreError::CodeNotFound)?;
+        let key = get_key(
+            &DeviceCode::parse(Secret::new(device_code))
+                .map_err(DeviceCodeStoreError::UnexpectedError)?,
+        );
+
+        let value: Option<String> = conn
+            .get(key.expose_secret())
+            .wrap_err("Failed to get device code from Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        // TTL is negative when the key doesn't exist
+        let ttl: i64 = conn
+            .ttl(key.expose_secret())
+            .wrap_err("Failed to get expiry of device code from Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        let value = value
+            .filter(|_| ttl > 0)
+            .ok_or(DeviceCodeStoreError::CodeNotFound)?;
+
+        let mut record = deserialize(&value)?;
+        record.status = status.into();
+
+        // The device keeps polling until the code expires as it was issued
+        let _: () = conn
+            .set_ex(key.expose_secret(), serialize(record)?, ttl as u64)
+            .wrap_err("Failed to set device code in Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Get device code", skip_all)]
+    async fn get_code(
+        &self,
+        device_code: &DeviceCode,
+    ) -> Result<DeviceAuthorization, DeviceCodeStoreError> {
+        let value: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get(get_key(device_code).expose_secret())
+            .wrap_err("Failed to get device code from Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(DeviceCodeStoreError::CodeNotFound)?;
+        deserialize(&value)?.try_into()
+    }
+
+    #[tracing::instrument(name = "Take device code", skip_all)]
+    async fn take_code(
+        &mut self,
+        device_code: &DeviceCode,
+    ) -> Result<DeviceAuthorization, DeviceCodeStoreError> {
+        // GETDEL makes sure that concurrent polls can't both get tokens
+        let value: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_key(device_code).expose_secret())
+            .wrap_err("Failed to take device code from Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(DeviceCodeStoreError::CodeNotFound)?;
+        deserialize(&value)?.try_into()
+    }
+
+    #[tracing::instrument(name = "Record device poll", skip_all)]
+    async fn record_poll(
+        &mut self,
+        device_code: &DeviceCode,
+    ) -> Result<bool, DeviceCodeStoreError> {
+        let key = get_poll_key(device_code);
+        let mut conn = self.conn.write().await;
+
+        // The key only exists for an interval after each poll
+        let is_first: bool = conn
+            .set_nx(key.expose_secret(), true)
+            .wrap_err("Failed to record device poll in Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        if is_first {
+            let _: () = conn
+                .expire(
+                    key.expose_secret(),
+                    DEVICE_CODE_POLL_INTERVAL_SECONDS as i64,
+                )
+                .wrap_err("Failed to set expiry of device poll in Redis")
+                .map_err(DeviceCodeStoreError::UnexpectedError)?;
+        }
+
+        Ok(is_first)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DeviceAuthorizationRecord {
+    client_grant: ClientGrant,
+    user_code: String,
+    user_agent: Option<String>,
+    ip_address: IpAddr,
+    status: DeviceAuthorizationStatusRecord,
+}
+
+#[derive(Serialize, Deserialize)]
+enum DeviceAuthorizationStatusRecord {
+    Pending,
+    Approved(String),
+    Denied,
+}
+
+impl From<DeviceAuthorization> for DeviceAuthorizationRecord {
+    fn from(authorization: DeviceAuthorization) -> Self {
+        Self {
+            client_grant: authorization.client_grant,
+            user_code: authorization.user_code.as_ref().to_owned(),
+            user_agent: authorization.user_agent,
+            ip_address: authorization.ip_address,
+            status: authorization.status.into(),
+        }
+    }
+}
+
+impl From<DeviceAuthorizationStatus> for DeviceAuthorizationStatusRecord {
+    fn from(status: DeviceAuthorizationStatus) -> Self {
+        match status {
+            DeviceAuthorizationStatus::Pending => Self::Pending,
+            DeviceAuthorizationStatus::Approved(user_id) => {
+                Self::Approved(user_id.as_ref().to_owned())
+            }
+            DeviceAuthorizationStatus::Denied => Self::Denied,
+        }
+    }
+}
+
+impl TryFrom<DeviceAuthorizationRecord> for DeviceAuthorization {
+    type Error = DeviceCodeStoreError;
+
+    fn try_from(record: DeviceAuthorizationRecord) -> Result<Self, Self::Error> {
+        let status = match record.status {
+            DeviceAuthorizationStatusRecord::Pending => DeviceAuthorizationStatus::Pending,
+            DeviceAuthorizationStatusRecord::Approved(user_id) => {
+                DeviceAuthorizationStatus::Approved(
+                    UserId::parse(user_id).map_err(DeviceCodeStoreError::UnexpectedError)?,
+                )
+            }
+            DeviceAuthorizationStatusRecord::Denied => DeviceAuthorizationStatus::Denied,
+        };
+
+        Ok(Self {
+            client_grant: record.client_grant,
+            user_code: UserCode::parse(&record.user_code)
+                .map_err(DeviceCodeStoreError::UnexpectedError)?,
+            user_agent: record.user_agent,
+            ip_address: record.ip_address,
+            status,
+        })
+    }
+}
+
+fn serialize(record: DeviceAuthorizationRecord) -> Result<String, DeviceCodeStoreError> {
+    serde_json::to_string(&record)
+        .wrap_err("Failed to serialize device authorization")
+        .map_err(DeviceCodeStoreError::UnexpectedError)
+}
+
+fn deserialize(value: &str) -> Result<DeviceAuthorizationRecord, DeviceCodeStoreError> {
+    serde_json::from_str(value)
+        .wrap_err("failed to deserialize device authorization")
+        .map_err(DeviceCodeStoreError::UnexpectedError)
+}
+
+const DEVICE_CODE_KEY_PREFIX: &str = "device_code:";
+const USER_CODE_KEY_PREFIX: &str = "device_user_code:";
+const POLL_KEY_PREFIX: &str = "device_poll:";
+
+fn get_key(device_code: &DeviceCode) -> Secret<String> {
+    Secret::new(format!(
+        "{}{}",
+        DEVICE_CODE_KEY_PREFIX,
+        device_code.as_ref().expose_secret()
+    ))
+}
+
+fn get_user_code_key(user_code: &UserCode) -> String {
+    format!("{}{}", USER_CODE_KEY_PREFIX, user_code.as_ref())
+}
+
+fn get_poll_key(device_code: &DeviceCode) -> Secret<String> {
+    Secret::new(format!(
+        "{}{}",
+        POLL_KEY_PREFIX,
+        device_code.as_ref().expose_secret()
+    ))
+}
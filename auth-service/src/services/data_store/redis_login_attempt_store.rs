@@ -0,0 +1,177 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::{eyre, Context};
+use redis::{Commands, Connection};
+use secrecy::ExposeSecret;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::domain::{
+    Email, LoginAttemptStore, LoginAttemptStoreError, RateLimitKey, ACCOUNT_LOCKOUT_SECONDS,
+    LOGIN_ATTEMPT_WINDOW_SECONDS, MAX_FAILED_LOGINS,
+};
+
+pub struct RedisLoginAttemptStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisLoginAttemptStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl LoginAttemptStore for RedisLoginAttemptStore {
+    // The attempts of a key are a sorted set scored by the time of each attempt in milliseconds
+    #[tracing::instrument(name = "Record login attempt", skip_all)]
+    async fn record_attempt(&mut self, key: &RateLimitKey) -> Result<(), LoginAttemptStoreError> {
+        let key_name = get_attempts_key(key);
+        let now = now_millis()?;
+        let window = LOGIN_ATTEMPT_WINDOW_SECONDS * 1000;
+
+        let mut conn = self.conn.write().await;
+
+        let _: () = conn
+            .zrembyscore(&key_name, 0, now.saturating_sub(window))
+            .wrap_err("Failed to remove old login attempts from Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        let attempts: u32 = conn
+            .zcard(&key_name)
+            .wrap_err("Failed to count login attempts in Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        if attempts >= key.max_attempts() {
+            // Scores are doubles in Redis
+            let oldest: Vec<(String, f64)> = conn
+                .zrange_withscores(&key_name, 0, 0)
+                .wrap_err("Failed to get oldest login attempt from Redis")
+                .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+            let window_end = oldest
+                .first()
+                .map_or(now, |(_, time)| *time as u64 + window);
+
+            return Err(LoginAttemptStoreError::RateLimited {
+                retry_after_seconds: window_end.saturating_sub(now).div_ceil(1000),
+            });
+        }
+
+        // Members have to be unique, or attempts within the same millisecond count as one
+        let _: () = conn
+            .zadd(&key_name, Uuid::new_v4().to_string(), now)
+            .wrap_err("Failed to add login attempt to Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&key_name, LOGIN_ATTEMPT_WINDOW_SECONDS as i64)
+            .wrap_err("Failed to set expiry of login attempts in Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Check lockout", skip_all)]
+    async fn check_lockout(&self, email: &Email) -> Result<(), LoginAttemptStoreError> {
+        // TTL is negative when the key doesn't exist
+        let ttl: i64 = self
+            .conn
+            .write()
+            .await
+            .ttl(get_lockout_key(email))
+            .wrap_err("Failed to get account lockout from Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        if ttl > 0 {
+            return Err(LoginAttemptStoreError::AccountLocked {
+                retry_after_seconds: ttl as u64,
+            });
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Record login failure", skip_all)]
+    async fn record_failure(&mut self, email: &Email) -> Result<bool, LoginAttemptStoreError> {
+        let key = get_failures_key(email);
+        let mut conn = self.conn.write().await;
+
+        let failures: u32 = conn
+            .incr(&key, 1)
+            .wrap_err("Failed to increment login failures in Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        // Failures spread out further than a lockout period apart don't add up
+        let _: () = conn
+            .expire(&key, ACCOUNT_LOCKOUT_SECONDS as i64)
+            .wrap_err("Failed to set expiry of login failures in Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        if failures < MAX_FAILED_LOGINS {
+            return Ok(false);
+        }
+
+        let _: () = conn
+            .set_ex(get_lockout_key(email), true, ACCOUNT_LOCKOUT_SECONDS)
+            .wrap_err("Failed to lock account in Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .del(&key)
+            .wrap_err("Failed to reset login failures in Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(true)
+    }
+
+    #[tracing::instrument(name = "Clear login failures", skip_all)]
+    async fn clear_failures(&mut self, email: &Email) -> Result<(), LoginAttemptStoreError> {
+        let keys = [get_failures_key(email), get_lockout_key(email)];
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .del(&keys)
+            .wrap_err("Failed to clear login failures in Redis")
+            .map_err(LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+fn now_millis() -> Result<u64, LoginAttemptStoreError> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .map_err(|_| LoginAttemptStoreError::UnexpectedError(eyre!("clock is before Unix epoch")))
+}
+
+const ATTEMPTS_PREFIX: &str = "login_attempts:";
+const FAILURES_PREFIX: &str = "login_failures:";
+const LOCKOUT_PREFIX: &str = "login_lockout:";
+
+fn get_attempts_key(key: &RateLimitKey) -> String {
+    match key {
+        RateLimitKey::Account(email) => {
+            format!(
+                "{}account:{}",
+                ATTEMPTS_PREFIX,
+                email.as_ref().expose_secret()
+            )
+        }
+        RateLimitKey::Ip(ip) => format!("{}ip:{}", ATTEMPTS_PREFIX, ip),
+    }
+}
+
+fn get_failures_key(email: &Email) -> String {
+    format!("{}{}", FAILURES_PREFIX, email.as_ref().expose_secret())
+}
+
+fn get_lockout_key(email: &Email) -> String {
+    format!("{}{}", LOCKOUT_PREFIX, email.as_ref().expose_secret())
+}
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+        Email,
+    },
+    utils::auth::PASSWORD_RESET_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisPasswordResetTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPasswordResetTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for RedisPasswordResetTokenStore {
+    #[tracing::instrument(name = "Add password reset token", skip_all)]
+    async fn add_token(
+        &mut self,
+        token: PasswordResetToken,
+        email: Email,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let mut conn = self.conn.write().await;
+
+        // Point the email at the new token and store the token in one transaction, then drop the
+        // token it pointed at before
+        let (previous,): (Option<String>,) = redis::pipe()
+            .atomic()
+            .getset(
+                get_email_key(&email).expose_secret(),
+                token.as_ref().expose_secret(),
+            )
+            .expire(
+                get_email_key(&email).expose_secret(),
+                PASSWORD_RESET_TOKEN_TTL_SECONDS as i64,
+            )
+            .ignore()
+            .set_ex(
+                get_key(&token).expose_secret(),
+                email.as_ref().expose_secret(),
+                PASSWORD_RESET_TOKEN_TTL_SECONDS,
+            )
+            .ignore()
+            .query(&mut *conn)
+            .wrap_err("Failed to set password reset token in Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        if let Some(previous) = previous {
+            let previous = PasswordResetToken::parse(Secret::new(previous))
+                .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+            let _: () = conn
+                .del(get_key(&previous).expose_secret())
+                .wrap_err("Failed to delete previous password reset token from Redis")
+                .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Take password reset token", skip_all)]
+    async fn take_token(
+        &mut self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        // GETDEL makes sure that concurrent requests can't both use the token
+        let email: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_key(token).expose_secret())
+            .wrap_err("Failed to take password reset token from Redis")
+            .map_err(PasswordResetTokenStoreError::UnexpectedError)?;
+
+        match email {
+            Some(email) => Email::parse(Secret::new(email))
+                .map_err(PasswordResetTokenStoreError::UnexpectedError),
+            None => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+}
+
+const PASSWORD_RESET_TOKEN_KEY_PREFIX: &str = "password_reset_token:";
+const PASSWORD_RESET_EMAIL_KEY_PREFIX: &str = "password_reset_email:";
+
+fn get_key(token: &PasswordResetToken) -> Secret<String> {
+    Secret::new(format!(
+        "{}{}",
+        PASSWORD_RESET_TOKEN_KEY_PREFIX,
+        token.as_ref().expose_secret()
+    ))
+}
+
+fn get_email_key(email: &Email) -> Secret<String> {
+    Secret::new(format!(
+        "{}{}",
+        PASSWORD_RESET_EMAIL_KEY_PREFIX,
+        email.as_ref().expose_secret()
+    ))
+}
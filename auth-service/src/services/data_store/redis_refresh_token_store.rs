@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        data_stores::{
+            ClientGrant, RefreshToken, RefreshTokenFamilyId, RefreshTokenRecord, RefreshTokenStore,
+            RefreshTokenStoreError,
+        },
+        Email,
+    },
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisRefreshTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    #[tracing::instrument(name = "Add refresh token", skip_all)]
+    async fn add_token(
+        &mut self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let record_tuple = RefreshTokenTuple(
+            record.email.as_ref().expose_secret().to_owned(),
+            record.family_id.as_ref().to_owned(),
+            record.token_version,
+            record.client_grant,
+        );
+        let record_json = serde_json::to_string(&record_tuple)
+            .wrap_err("Failed to serialize refresh token record")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.write().await;
+
+        let _: () = conn
+            .set_ex(
+                get_token_key(&token).expose_secret(),
+                record_json,
+                REFRESH_TOKEN_TTL_SECONDS,
+            )
+            .wrap_err("Failed to set refresh token in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .set_ex(
+                get_family_key(&record.family_id),
+                token.as_ref().expose_secret(),
+                REFRESH_TOKEN_TTL_SECONDS,
+            )
+            .wrap_err("Failed to set refresh token family head in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Get refresh token", skip_all)]
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        let key = get_token_key(token);
+
+        match self
+            .conn
+            .write()
+            .await
+            .get::<_, String>(key.expose_secret())
+        {
+            Ok(value) => {
+                let data: RefreshTokenTuple = serde_json::from_str(&value)
+                    .wrap_err("failed to deserialize refresh token record")
+                    .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+                let email = Email::parse(Secret::new(data.0))
+                    .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+                let family_id = RefreshTokenFamilyId::parse(data.1)
+                    .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+                Ok(RefreshTokenRecord {
+                    client_grant: data.3,
+                    ..RefreshTokenRecord::new(email, family_id, data.2)
+                })
+            }
+            Err(_) => Err(RefreshTokenStoreError::TokenNotFound),
+        }
+    }
+
+    #[tracing::instrument(name = "Get refresh token family head", skip_all)]
+    async fn get_family_head(
+        &self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<RefreshToken, RefreshTokenStoreError> {
+        match self
+            .conn
+            .write()
+            .await
+            .get::<_, String>(get_family_key(family_id))
+        {
+            Ok(value) => RefreshToken::parse(Secret::new(value))
+                .map_err(RefreshTokenStoreError::UnexpectedError),
+            Err(_) => Err(RefreshTokenStoreError::TokenNotFound),
+        }
+    }
+
+    #[tracing::instrument(name = "Revoke refresh token family", skip_all)]
+    async fn revoke_family(
+        &mut self,
+        family_id: &RefreshTokenFamilyId,
+    ) -> Result<(), RefreshTokenStoreError> {
+        // Tokens of the family expire on their own; without a head none of them is valid anymore.
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .del(get_family_key(family_id))
+            .wrap_err("Failed to delete refresh token family from Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+// Records stored before tokens were issued to clients have no client grant
+#[derive(Serialize, Deserialize)]
+struct RefreshTokenTuple(
+    pub String,
+    pub String,
+    pub i32,
+    #[serde(default)] pub Option<ClientGrant>,
+);
+
+const REFRESH_TOKEN_KEY_PREFIX: &str = "refresh_token:";
+const REFRESH_TOKEN_FAMILY_KEY_PREFIX: &str = "refresh_token_family:";
+
+fn get_token_key(token: &RefreshToken) -> Secret<String> {
+    Secret::new(format!(
+        "{}{}",
+        REFRESH_TOKEN_KEY_PREFIX,
+        token.as_ref().expose_secret()
+    ))
+}
+
+fn get_family_key(family_id: &RefreshTokenFamilyId) -> String {
+    format!("{}{}", REFRESH_TOKEN_FAMILY_KEY_PREFIX, family_id.as_ref())
+}
@@ -0,0 +1,230 @@
+use std::{net::IpAddr, sync::Arc};
+
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        AuthenticationMethod, Email, RefreshTokenFamilyId, Session, SessionStore, SessionStoreError,
+    },
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+// Sessions expire when they weren't used for as long as a refresh token lives
+pub struct RedisSessionStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for RedisSessionStore {
+    #[tracing::instrument(name = "Add session", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        set_session(&mut conn, &session)?;
+
+        // The IDs of the sessions of a user, so they can be listed
+        let sessions_key = get_user_sessions_key(&session.email);
+
+        let _: () = conn
+            .sadd(&sessions_key, session.id.as_ref())
+            .wrap_err("Failed to add session to user sessions in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&sessions_key, REFRESH_TOKEN_TTL_SECONDS as i64)
+            .wrap_err("Failed to set expiry of user sessions in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Get session", skip_all)]
+    async fn get_session(&self, id: &RefreshTokenFamilyId) -> Result<Session, SessionStoreError> {
+        get_session(&mut *self.conn.write().await, id)?.ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    #[tracing::instrument(name = "Get sessions", skip_all)]
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut conn = self.conn.write().await;
+        let sessions_key = get_user_sessions_key(email);
+
+        let ids: Vec<String> = conn
+            .smembers(&sessions_key)
+            .wrap_err("Failed to get user sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let mut sessions = Vec::with_capacity(ids.len());
+
+        for id in ids {
+            let session = RefreshTokenFamilyId::parse(id.clone())
+                .map_err(SessionStoreError::UnexpectedError)
+                .and_then(|id| get_session(&mut conn, &id))?;
+
+            match session {
+                Some(session) => sessions.push(session),
+                // The session expired, so forget its ID too
+                None => {
+                    let _: () = conn
+                        .srem(&sessions_key, id)
+                        .wrap_err("Failed to remove expired session from Redis")
+                        .map_err(SessionStoreError::UnexpectedError)?;
+                }
+            }
+        }
+
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+        Ok(sessions)
+    }
+
+    #[tracing::instrument(name = "Touch session", skip_all)]
+    async fn touch_session(&mut self, id: &RefreshTokenFamilyId) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let mut session = get_session(&mut conn, id)?.ok_or(SessionStoreError::SessionNotFound)?;
+        session.last_seen_at = Utc::now().timestamp();
+
+        set_session(&mut conn, &session)?;
+
+        let _: () = conn
+            .expire(
+                get_user_sessions_key(&session.email),
+                REFRESH_TOKEN_TTL_SECONDS as i64,
+            )
+            .wrap_err("Failed to set expiry of user sessions in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Remove session", skip_all)]
+    async fn remove_session(&mut self, id: &RefreshTokenFamilyId) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let session = get_session(&mut conn, id)?.ok_or(SessionStoreError::SessionNotFound)?;
+
+        let _: () = conn
+            .del(get_session_key(id))
+            .wrap_err("Failed to delete session from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .srem(get_user_sessions_key(&session.email), id.as_ref())
+            .wrap_err("Failed to remove session from user sessions in Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Remove sessions", skip_all)]
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        let mut conn = self.conn.write().await;
+        let sessions_key = get_user_sessions_key(email);
+
+        let ids: Vec<String> = conn
+            .smembers(&sessions_key)
+            .wrap_err("Failed to get user sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        let keys: Vec<String> = ids
+            .iter()
+            .map(|id| format!("{}{}", SESSION_PREFIX, id))
+            .chain([sessions_key])
+            .collect();
+
+        let _: () = conn
+            .del(&keys)
+            .wrap_err("Failed to delete sessions from Redis")
+            .map_err(SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+fn get_session(
+    conn: &mut Connection,
+    id: &RefreshTokenFamilyId,
+) -> Result<Option<Session>, SessionStoreError> {
+    let value: Option<String> = conn
+        .get(get_session_key(id))
+        .wrap_err("Failed to get session from Redis")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    let Some(value) = value else {
+        return Ok(None);
+    };
+
+    let data: SessionData = serde_json::from_str(&value)
+        .wrap_err("Failed to deserialize session")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    Ok(Some(Session {
+        id: id.clone(),
+        email: Email::parse(Secret::new(data.email)).map_err(SessionStoreError::UnexpectedError)?,
+        created_at: data.created_at,
+        last_seen_at: data.last_seen_at,
+        user_agent: data.user_agent,
+        ip_address: data.ip_address,
+        amr: data.amr,
+    }))
+}
+
+fn set_session(conn: &mut Connection, session: &Session) -> Result<(), SessionStoreError> {
+    let data = SessionData {
+        email: session.email.as_ref().expose_secret().to_owned(),
+        created_at: session.created_at,
+        last_seen_at: session.last_seen_at,
+        user_agent: session.user_agent.clone(),
+        ip_address: session.ip_address,
+        amr: session.amr.clone(),
+    };
+
+    let value = serde_json::to_string(&data)
+        .wrap_err("Failed to serialize session")
+        .map_err(SessionStoreError::UnexpectedError)?;
+
+    conn.set_ex(
+        get_session_key(&session.id),
+        value,
+        REFRESH_TOKEN_TTL_SECONDS,
+    )
+    .wrap_err("Failed to set session in Redis")
+    .map_err(SessionStoreError::UnexpectedError)
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionData {
+    email: String,
+    created_at: i64,
+    last_seen_at: i64,
+    user_agent: Option<String>,
+    ip_address: IpAddr,
+    // Sessions stored before this was added only ever checked the password
+    #[serde(default = "password_only")]
+    amr: Vec<AuthenticationMethod>,
+}
+
+fn password_only() -> Vec<AuthenticationMethod> {
+    vec![AuthenticationMethod::Password]
+}
+
+const SESSION_PREFIX: &str = "session:";
+const USER_SESSIONS_PREFIX: &str = "user_sessions:";
+
+fn get_session_key(id: &RefreshTokenFamilyId) -> String {
+    format!("{}{}", SESSION_PREFIX, id.as_ref())
+}
+
+fn get_user_sessions_key(email: &Email) -> String {
+    format!("{}{}", USER_SESSIONS_PREFIX, email.as_ref().expose_secret())
+}
@@ -7,7 +7,10 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
 use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    data_stores::{
+        LoginAttemptId, TwoFAAttemptKey, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+        MAX_2FA_ATTEMPTS,
+    },
     Email,
 };
 
@@ -40,7 +43,7 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
             .conn
             .write()
             .await
-            .set_ex(key, two_fa_tuple_json, TEN_MINUTES_IN_SECONDS as u64)
+            .set_ex(key, two_fa_tuple_json, TEN_MINUTES_IN_SECONDS)
             .wrap_err("Failed to set 2FA code in Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
@@ -49,13 +52,13 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
 
     #[tracing::instrument(name = "Remove code", skip_all)]
     async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(&email);
+        let key = get_key(email);
 
         let _: () = self
             .conn
             .write()
             .await
-            .del(key)
+            .del(&key)
             .wrap_err("Failed to delete 2FA code from Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
@@ -70,30 +73,91 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
         let key = get_key(email);
 
         match self.conn.write().await.get::<_, String>(&key) {
-            Ok(value) => {
-                let data: TwoFATuple = serde_json::from_str(&value)
-                    .wrap_err("failed to deserialize 2FA tuple")
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
+            Ok(value) => parse_two_fa_tuple(&value),
+            Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        }
+    }
 
-                let login_attempt_id = LoginAttemptId::parse(Secret::new(data.0))
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
+    #[tracing::instrument(name = "Take code", skip_all)]
+    async fn take_code(
+        &mut self,
+        email: &Email,
+    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        // GETDEL makes sure that concurrent requests can't both use the code
+        let value: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_key(email))
+            .wrap_err("Failed to take 2FA code from Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-                let email_code = TwoFACode::parse(Secret::new(data.1))
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        match value {
+            Some(value) => parse_two_fa_tuple(&value),
+            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        }
+    }
 
-                Ok((login_attempt_id, email_code))
-            }
-            Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+    #[tracing::instrument(name = "Record attempt", skip_all)]
+    async fn record_attempt(&mut self, key: &TwoFAAttemptKey) -> Result<u32, TwoFACodeStoreError> {
+        let key = get_attempts_key(key);
+        let mut conn = self.conn.write().await;
+
+        // INCR is atomic, so every concurrent request gets its own count
+        let attempts: u32 = conn
+            .incr(&key, 1)
+            .wrap_err("Failed to increment 2FA attempts in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&key, TEN_MINUTES_IN_SECONDS as i64)
+            .wrap_err("Failed to set expiry of 2FA attempts in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        if attempts > MAX_2FA_ATTEMPTS {
+            return Err(TwoFACodeStoreError::TooManyAttempts);
         }
+
+        Ok(MAX_2FA_ATTEMPTS - attempts)
     }
 }
 
 #[derive(Serialize, Deserialize)]
 struct TwoFATuple(pub String, pub String);
 
+fn parse_two_fa_tuple(value: &str) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+    let data: TwoFATuple = serde_json::from_str(value)
+        .wrap_err("failed to deserialize 2FA tuple")
+        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    let login_attempt_id =
+        LoginAttemptId::parse(Secret::new(data.0)).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    let email_code =
+        TwoFACode::parse(Secret::new(data.1)).map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+    Ok((login_attempt_id, email_code))
+}
+
 const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const ATTEMPTS_PREFIX: &str = "two_fa_attempts:";
 
 fn get_key(email: &Email) -> String {
     format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref().expose_secret())
 }
+
+fn get_attempts_key(key: &TwoFAAttemptKey) -> String {
+    match key {
+        TwoFAAttemptKey::Login(login_attempt_id) => format!(
+            "{}login:{}",
+            ATTEMPTS_PREFIX,
+            login_attempt_id.as_ref().expose_secret()
+        ),
+        TwoFAAttemptKey::TotpEnrollment(email) => format!(
+            "{}totp_enrollment:{}",
+            ATTEMPTS_PREFIX,
+            email.as_ref().expose_secret()
+        ),
+    }
+}
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use chrono::{Duration as ChronoDuration, Utc};
+
+use crate::{
+    app_state::{KeyringType, SigningKeyStoreType, UserStoreType},
+    utils::auth::{EMAIL_VERIFICATION_TOKEN_TTL_SECONDS, KEYRING_RELOAD_INTERVAL_SECONDS},
+};
+
+// How often accounts whose deletion grace period is over are looked for
+pub const PURGE_DELETED_ACCOUNTS_INTERVAL: Duration = Duration::from_secs(3600);
+
+// How often the signing keys are reloaded, to pick up rotations made through other instances
+pub const RELOAD_KEYRING_INTERVAL: Duration = Duration::from_secs(KEYRING_RELOAD_INTERVAL_SECONDS);
+
+// Remove the accounts scheduled for deletion once their grace period is over. Runs forever.
+pub async fn purge_deleted_accounts(user_store: UserStoreType, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        match user_store.write().await.purge_deleted_users().await {
+            Ok(emails) if emails.is_empty() => {}
+            Ok(emails) => tracing::info!("Purged {} deleted accounts", emails.len()),
+            Err(e) => tracing::error!("failed to purge deleted accounts: {:?}", e),
+        }
+    }
+}
+
+// Keep the keyring in line with the shared signing keys. Runs forever.
+pub async fn reload_keyring(
+    keyring: KeyringType,
+    signing_key_store: SigningKeyStoreType,
+    interval: Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        // Retired keys have to outlive the longest-lived tokens they signed
+        let retired_after =
+            Utc::now() - ChronoDuration::seconds(EMAIL_VERIFICATION_TOKEN_TTL_SECONDS);
+
+        let entries = match signing_key_store.read().await.get_keys(retired_after).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::error!("failed to reload signing keys: {:?}", e);
+                continue;
+            }
+        };
+
+        if let Err(e) = keyring.write().await.replace_entries(entries) {
+            tracing::error!("failed to reload signing keys: {:?}", e);
+        }
+    }
+}
@@ -1,24 +1,145 @@
+use axum::http::{header::AUTHORIZATION, HeaderMap};
 use axum_extra::extract::cookie::{Cookie, SameSite};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use chrono::Utc;
 use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
+use jsonwebtoken::{decode, decode_header, encode, get_current_timestamp, Header, Validation};
 use secrecy::{ExposeSecret, Secret};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::{app_state::BannedTokenStoreType, domain::email::Email};
+use crate::{
+    app_state::{
+        AppState, BannedTokenStoreType, KeyringType, OAuthClientStoreType,
+        PersonalAccessTokenStoreType, RefreshTokenStoreType, SessionStoreType, UserStoreType,
+    },
+    domain::{
+        email::Email, AuthAPIError, AuthenticationMethod, AuthorizationGrant, ClientGrant,
+        JwtConfig, OAuthClient, OAuthClientStoreError, PersonalAccessToken, RefreshToken,
+        RefreshTokenFamilyId, RefreshTokenRecord, Session, SigningKey, User, UserId, UserRoles,
+        ACCOUNT_LOCKOUT_SECONDS,
+    },
+};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::{
+    client_info::ClientInfo,
+    constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+};
 
-// Create cookie with a new JWT auth token
+// Start a new session for the user, who logged in with the given methods, and create the auth
+// and refresh cookies belonging to it
+#[tracing::instrument(name = "Start session", skip_all)]
+pub async fn start_session(
+    state: &AppState,
+    user: &User,
+    token_version: i32,
+    amr: Vec<AuthenticationMethod>,
+    client: ClientInfo,
+) -> Result<(Cookie<'static>, Cookie<'static>)> {
+    let email = &user.email;
+    let session = Session {
+        amr,
+        ..Session::new(email.clone(), client.user_agent, client.ip_address)
+    };
+
+    let roles = state
+        .role_store
+        .read()
+        .await
+        .get_user_roles(&user.id)
+        .await?;
+
+    let auth_cookie = generate_auth_cookie(
+        user,
+        &roles,
+        token_version,
+        &session.id,
+        &state.jwt_config,
+        state.keyring.read().await.active(),
+    )?;
+
+    let refresh_cookie = generate_refresh_cookie(
+        email,
+        token_version,
+        session.id.clone(),
+        state.refresh_token_store.clone(),
+    )
+    .await?;
+
+    state
+        .session_store
+        .write()
+        .await
+        .add_session(session)
+        .await?;
+
+    Ok((auth_cookie, refresh_cookie))
+}
+
+// Start a new session for the user on behalf of an OAuth client, and issue the JWT auth token
+// and the first refresh token of it. Both carry the grant, so they can only be used by and
+// for that client.
+#[tracing::instrument(name = "Start client session", skip_all)]
+pub async fn start_client_session(
+    state: &AppState,
+    user: &User,
+    token_version: i32,
+    client_grant: ClientGrant,
+    client: ClientInfo,
+) -> Result<(Secret<String>, RefreshToken)> {
+    let email = &user.email;
+    let session = Session::new(email.clone(), client.user_agent, client.ip_address);
+
+    let auth_token = generate_access_token(
+        user,
+        &UserRoles::default(),
+        token_version,
+        &session.id,
+        Some(&client_grant),
+        &state.jwt_config,
+        state.keyring.read().await.active(),
+    )?;
+
+    let record = RefreshTokenRecord {
+        client_grant: Some(client_grant),
+        ..RefreshTokenRecord::new(email.clone(), session.id.clone(), token_version)
+    };
+    let refresh_token = generate_refresh_token(record, state.refresh_token_store.clone()).await?;
+
+    state
+        .session_store
+        .write()
+        .await
+        .add_session(session)
+        .await?;
+
+    Ok((auth_token, refresh_token))
+}
+
+// Create cookie with a new JWT auth token for the given session
 #[tracing::instrument(name = "Generate auth cookie", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+pub fn generate_auth_cookie(
+    user: &User,
+    roles: &UserRoles,
+    token_version: i32,
+    session_id: &RefreshTokenFamilyId,
+    jwt_config: &JwtConfig,
+    signing_key: &SigningKey,
+) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(
+        user,
+        roles,
+        token_version,
+        session_id,
+        jwt_config,
+        signing_key,
+    )?;
     Ok(create_auth_cookie(token))
 }
 
 // Create cookie and set the value to the passed-in token string
 #[tracing::instrument(name = "Create auth cookie", skip_all)]
-fn create_auth_cookie(token: Secret<String>) -> Cookie<'static> {
+pub fn create_auth_cookie(token: Secret<String>) -> Cookie<'static> {
     let cookie = Cookie::build((JWT_COOKIE_NAME, token.expose_secret().to_owned()))
         .path("/") // apple cookie to all URLs on the server
         .http_only(true) // prevent JavaScript from accessing the cookie
@@ -31,37 +152,429 @@ fn create_auth_cookie(token: Secret<String>) -> Cookie<'static> {
 // This value determines how long the JWT auth token is valid for
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
+// This value determines how long a refresh token can be exchanged for a new JWT auth token
+pub const REFRESH_TOKEN_TTL_SECONDS: u64 = 604_800; // 7 days
+
+// This value determines how long the link in the email verification email works for
+pub const EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: i64 = 86_400; // 24 hours
+
+// This value determines how long the link confirming a new email address works for
+pub const EMAIL_CHANGE_TOKEN_TTL_SECONDS: i64 = 3600; // 1 hour
+
+// This value determines how long a password reset token emailed to the user can be used for
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: u64 = 900; // 15 minutes
+
+// This value determines how long a client has to exchange an authorization code for tokens
+pub const AUTHORIZATION_CODE_TTL_SECONDS: u64 = 60;
+
+// This value determines how long a user has to approve a device, and so how long it polls
+pub const DEVICE_CODE_TTL_SECONDS: u64 = 600; // 10 minutes
+
+// This value determines how often a device may poll /token while its user hasn't approved it
+pub const DEVICE_CODE_POLL_INTERVAL_SECONDS: u64 = 5;
+
+// This value determines how soon every instance picks up a signing key rotated on another one
+pub const KEYRING_RELOAD_INTERVAL_SECONDS: u64 = 60;
+
+// This value determines how long a rotated signing key is published before it signs tokens, so
+// every instance has reloaded it by the time the first token it signed reaches them
+pub const SIGNING_KEY_ACTIVATION_DELAY_SECONDS: i64 = 2 * KEYRING_RELOAD_INTERVAL_SECONDS as i64;
+
+// Start a new refresh token family and create a cookie holding its first token
+#[tracing::instrument(name = "Generate refresh cookie", skip_all)]
+pub async fn generate_refresh_cookie(
+    email: &Email,
+    token_version: i32,
+    family_id: RefreshTokenFamilyId,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<Cookie<'static>> {
+    let record = RefreshTokenRecord::new(email.clone(), family_id, token_version);
+    let token = generate_refresh_token(record, refresh_token_store).await?;
+
+    Ok(create_refresh_cookie(&token))
+}
+
+// Start a new refresh token family with the given record and return its first token
+#[tracing::instrument(name = "Generate refresh token", skip_all)]
+pub async fn generate_refresh_token(
+    record: RefreshTokenRecord,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<RefreshToken> {
+    let token = RefreshToken::default();
+
+    refresh_token_store
+        .write()
+        .await
+        .add_token(token.clone(), record)
+        .await?;
+
+    Ok(token)
+}
+
+// Create refresh cookie and set the value to the passed-in refresh token
+#[tracing::instrument(name = "Create refresh cookie", skip_all)]
+pub fn create_refresh_cookie(token: &RefreshToken) -> Cookie<'static> {
+    Cookie::build((
+        REFRESH_COOKIE_NAME,
+        token.as_ref().expose_secret().to_owned(),
+    ))
+    .path("/")
+    .http_only(true)
+    .same_site(SameSite::Strict) // only the auth service itself ever needs to read it
+    .build()
+}
+
 // Create JWT auth token
+fn generate_auth_token(
+    user: &User,
+    roles: &UserRoles,
+    token_version: i32,
+    session_id: &RefreshTokenFamilyId,
+    jwt_config: &JwtConfig,
+    signing_key: &SigningKey,
+) -> Result<Secret<String>> {
+    generate_access_token(
+        user,
+        roles,
+        token_version,
+        session_id,
+        None,
+        jwt_config,
+        signing_key,
+    )
+}
+
+// Create JWT auth token, issued to the client of the grant if there is one. The user's roles and
+// permissions are only added to their own tokens, clients get the scope the user granted
+// instead.
 #[tracing::instrument(name = "Generate auth token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<Secret<String>> {
-    let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
-        .wrap_err("failed to create 10 minute time delta")?;
+pub fn generate_access_token(
+    user: &User,
+    roles: &UserRoles,
+    token_version: i32,
+    session_id: &RefreshTokenFamilyId,
+    client_grant: Option<&ClientGrant>,
+    jwt_config: &JwtConfig,
+    signing_key: &SigningKey,
+) -> Result<Secret<String>> {
+    let exp = expiration_time(TOKEN_TTL_SECONDS)?;
+    let now: usize = Utc::now()
+        .timestamp()
+        .try_into()
+        .wrap_err("failed to cast current time to usize")?;
+
+    let (roles, permissions) = match client_grant {
+        Some(_) => (Vec::new(), Vec::new()),
+        None => (roles.roles.clone(), roles.permissions.clone()),
+    };
+
+    let claims = Claims {
+        sub: user.id.as_ref().to_owned(),
+        email: jwt_config
+            .email_claim
+            .then(|| user.email.as_ref().expose_secret().to_owned()),
+        iss: jwt_config.issuer.clone(),
+        aud: jwt_config.audiences.clone(),
+        iat: now,
+        nbf: now,
+        exp,
+        jti: Uuid::new_v4().to_string(),
+        ver: token_version,
+        sid: session_id.as_ref().to_owned(),
+        client_id: client_grant.map(|grant| grant.client_id.clone()),
+        scope: client_grant.and_then(|grant| grant.scope.clone()),
+        roles,
+        permissions,
+    };
+
+    create_token(&claims, AUTH_TOKEN_TYPE, signing_key)
+}
+
+// Create JWT auth token for a machine client acting for itself, see the client credentials
+// grant of /token
+#[tracing::instrument(name = "Generate client token", skip_all)]
+pub fn generate_client_token(
+    client: &OAuthClient,
+    scope: String,
+    jwt_config: &JwtConfig,
+    signing_key: &SigningKey,
+) -> Result<Secret<String>> {
+    let exp = expiration_time(TOKEN_TTL_SECONDS)?;
+    let now: usize = Utc::now()
+        .timestamp()
+        .try_into()
+        .wrap_err("failed to cast current time to usize")?;
+
+    let claims = ClientClaims {
+        sub: client.id.clone(),
+        client_id: client.id.clone(),
+        scope,
+        iss: jwt_config.issuer.clone(),
+        aud: jwt_config.audiences.clone(),
+        iat: now,
+        nbf: now,
+        exp,
+        jti: Uuid::new_v4().to_string(),
+    };
+
+    create_token(&claims, CLIENT_TOKEN_TYPE, signing_key)
+}
+
+// Create the OpenID Connect ID token telling the client of the grant who the user is, and when
+// and how they logged in
+#[tracing::instrument(name = "Generate ID token", skip_all)]
+pub fn generate_id_token(
+    user: &User,
+    grant: &AuthorizationGrant,
+    jwt_config: &JwtConfig,
+    signing_key: &SigningKey,
+) -> Result<Secret<String>> {
+    let exp = expiration_time(TOKEN_TTL_SECONDS)?;
+    let now: usize = Utc::now()
+        .timestamp()
+        .try_into()
+        .wrap_err("failed to cast current time to usize")?;
+
+    let claims = IdTokenClaims {
+        iss: jwt_config.issuer.clone(),
+        sub: user.id.as_ref().to_owned(),
+        aud: grant.client_grant.client_id.clone(),
+        exp,
+        iat: now,
+        auth_time: grant.auth_time,
+        nonce: grant.nonce.clone(),
+        amr: grant.amr.clone(),
+    };
+
+    create_token(&claims, ID_TOKEN_TYPE, signing_key)
+}
+
+// Create the signed token emailed to a new user to prove they own the address
+#[tracing::instrument(name = "Generate email verification token", skip_all)]
+pub fn generate_email_verification_token(
+    email: &Email,
+    signing_key: &SigningKey,
+) -> Result<Secret<String>> {
+    let exp = expiration_time(EMAIL_VERIFICATION_TOKEN_TTL_SECONDS)?;
+
+    let claims = EmailVerificationClaims {
+        sub: email.as_ref().expose_secret().to_owned(),
+        exp,
+    };
+
+    create_token(&claims, EMAIL_VERIFICATION_TOKEN_TYPE, signing_key)
+}
+
+// Check the signature and expiry of an email verification token and return the address it verifies
+#[tracing::instrument(name = "Validate email verification token", skip_all)]
+pub async fn validate_email_verification_token(
+    token: &Secret<String>,
+    keyring: KeyringType,
+) -> Result<Email> {
+    let claims: EmailVerificationClaims = decode_token(
+        token,
+        EMAIL_VERIFICATION_TOKEN_TYPE,
+        keyring,
+        Validation::default(),
+    )
+    .await?;
+
+    Email::parse(Secret::new(claims.sub))
+}
+
+// Create the signed token emailed to the new address of a user, to prove they own it before
+// the account is moved to it
+#[tracing::instrument(name = "Generate email change token", skip_all)]
+pub fn generate_email_change_token(
+    email: &Email,
+    new_email: &Email,
+    token_version: i32,
+    signing_key: &SigningKey,
+) -> Result<Secret<String>> {
+    let exp = expiration_time(EMAIL_CHANGE_TOKEN_TTL_SECONDS)?;
+
+    let claims = EmailChangeClaims {
+        sub: email.as_ref().expose_secret().to_owned(),
+        new_email: new_email.as_ref().expose_secret().to_owned(),
+        ver: token_version,
+        exp,
+    };
+
+    create_token(&claims, EMAIL_CHANGE_TOKEN_TYPE, signing_key)
+}
+
+// Check the signature and expiry of an email change token and return the current and the
+// new address, along with the token version it was issued for
+#[tracing::instrument(name = "Validate email change token", skip_all)]
+pub async fn validate_email_change_token(
+    token: &Secret<String>,
+    keyring: KeyringType,
+) -> Result<(Email, Email, i32)> {
+    let claims: EmailChangeClaims = decode_token(
+        token,
+        EMAIL_CHANGE_TOKEN_TYPE,
+        keyring,
+        Validation::default(),
+    )
+    .await?;
+
+    Ok((
+        Email::parse(Secret::new(claims.sub))?,
+        Email::parse(Secret::new(claims.new_email))?,
+        claims.ver,
+    ))
+}
+
+// Create the signed token emailed to a user whose account got locked, to lift the lockout early
+#[tracing::instrument(name = "Generate account unlock token", skip_all)]
+pub fn generate_account_unlock_token(
+    email: &Email,
+    signing_key: &SigningKey,
+) -> Result<Secret<String>> {
+    let exp = expiration_time(ACCOUNT_LOCKOUT_SECONDS as i64)?;
+
+    let claims = AccountUnlockClaims {
+        sub: email.as_ref().expose_secret().to_owned(),
+        exp,
+    };
+
+    create_token(&claims, ACCOUNT_UNLOCK_TOKEN_TYPE, signing_key)
+}
+
+// Check the signature and expiry of an account unlock token and return the account it unlocks
+#[tracing::instrument(name = "Validate account unlock token", skip_all)]
+pub async fn validate_account_unlock_token(
+    token: &Secret<String>,
+    keyring: KeyringType,
+) -> Result<Email> {
+    let claims: AccountUnlockClaims = decode_token(
+        token,
+        ACCOUNT_UNLOCK_TOKEN_TYPE,
+        keyring,
+        Validation::default(),
+    )
+    .await?;
+
+    Email::parse(Secret::new(claims.sub))
+}
+
+// Expiration time `ttl_seconds` from now, as a JWT timestamp
+fn expiration_time(ttl_seconds: i64) -> Result<usize> {
+    let delta = chrono::Duration::try_seconds(ttl_seconds).wrap_err(format!(
+        "failed to create {} second time delta",
+        ttl_seconds
+    ))?;
 
     // Create JWT expiration time
     let exp = Utc::now()
         .checked_add_signed(delta)
-        .ok_or(eyre!("failed to add 10 minutes to current time"))?
+        .ok_or(eyre!(
+            "failed to add {} seconds to current time",
+            ttl_seconds
+        ))?
         .timestamp();
 
-    // Cast exp to a usize, which is what Claims expects
-    let exp: usize = exp.try_into().wrap_err(format!(
+    // Cast exp to a usize, which is what the claims expect
+    exp.try_into().wrap_err(format!(
         "failed to cast exp time to usize. exp time: {}",
         exp
-    ))?;
+    ))
+}
+
+// Client that authenticated with HTTP Basic auth at the endpoints for resource servers
+#[derive(Debug)]
+pub enum AuthenticatedClient {
+    // One of OAUTH_CLIENTS, the services of our own that tokens of logins are meant for
+    Service(String),
+    // A confidential client registered through /admin/clients
+    Registered(OAuthClient),
+}
+
+impl AuthenticatedClient {
+    // Whether a token issued to the client with the id, or to no client at all, was issued to
+    // this one. Services own the tokens of logins and personal access tokens.
+    pub fn owns(&self, client_id: Option<&str>) -> bool {
+        match self {
+            Self::Service(_) => client_id.is_none(),
+            Self::Registered(client) => client_id == Some(client.id.as_str()),
+        }
+    }
+}
 
-    let sub = email.as_ref().expose_secret().to_owned();
+// Check the HTTP Basic credentials against the services in OAUTH_CLIENTS, then against the
+// registered clients. Public clients have no secret to authenticate with.
+#[tracing::instrument(name = "Authenticate client", skip_all)]
+pub async fn authenticate_client(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<AuthenticatedClient, AuthAPIError> {
+    let (client_id, client_secret) =
+        basic_auth_credentials(headers).ok_or(AuthAPIError::InvalidClient)?;
 
-    let claims = Claims { sub, exp };
+    if let Some(service) = state
+        .oauth_clients
+        .iter()
+        .find(|client| client.matches(&client_id, &client_secret))
+    {
+        return Ok(AuthenticatedClient::Service(service.client_id.clone()));
+    }
+
+    let client = match state
+        .oauth_client_store
+        .read()
+        .await
+        .get_client(&client_id)
+        .await
+    {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => return Err(AuthAPIError::InvalidClient),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if !client.is_confidential() || !client.verify_secret(&client_secret) {
+        return Err(AuthAPIError::InvalidClient);
+    }
+
+    Ok(AuthenticatedClient::Registered(client))
+}
 
-    create_token(&claims)
+// Token sent with the `Bearer` scheme of the Authorization header, if any
+pub fn bearer_token(headers: &HeaderMap) -> Option<Secret<String>> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| Secret::new(token.to_owned()))
 }
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
+// Id and secret sent with HTTP Basic auth, if any
+pub fn basic_auth_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|credentials| STANDARD.decode(credentials).ok())
+        .and_then(|credentials| String::from_utf8(credentials).ok())
+        .and_then(|credentials| {
+            credentials
+                .split_once(':')
+                .map(|(id, secret)| (id.to_owned(), secret.to_owned()))
+        })
+}
+
+// Check if JWT auth token is valid by decoding it using the keyring key named in its header,
+// check that it was issued by us for one of our audiences and is valid at this time, and make
+// sure neither the token nor its session has been revoked since. Returns the claims along with
+// the user they identify.
 #[tracing::instrument(name = "Validate token", skip_all)]
 pub async fn validate_token(
     token: &Secret<String>,
     banned_token_store: BannedTokenStoreType,
-) -> Result<Claims> {
+    keyring: KeyringType,
+    user_store: UserStoreType,
+    session_store: SessionStoreType,
+    jwt_config: &JwtConfig,
+) -> Result<(Claims, User)> {
     match banned_token_store.read().await.contains_token(token).await {
         Ok(contains) => {
             if contains {
@@ -73,32 +586,355 @@ pub async fn validate_token(
         }
     }
 
-    decode::<Claims>(
+    // Client tokens have a type of their own, so they are never taken for a user's
+    let claims: Claims = decode_token(
+        token,
+        AUTH_TOKEN_TYPE,
+        keyring,
+        auth_token_validation(jwt_config),
+    )
+    .await?;
+    check_issued_at(claims.iat, jwt_config)?;
+
+    let user_id = UserId::parse(claims.sub.clone())?;
+    let (user, token_version) = {
+        let user_store = user_store.read().await;
+        let user = user_store.get_user_by_id(&user_id).await?;
+        let token_version = user_store.get_token_version(&user.email).await?;
+        (user, token_version)
+    };
+
+    if claims.ver != token_version {
+        return Err(eyre!("token has been revoked"));
+    }
+
+    let session_id = RefreshTokenFamilyId::parse(claims.sid.clone())?;
+    let session = session_store
+        .read()
+        .await
+        .get_session(&session_id)
+        .await
+        .wrap_err("session has been revoked")?;
+
+    if session.email != user.email {
+        return Err(eyre!("token does not belong to its session"));
+    }
+
+    Ok((claims, user))
+}
+
+// Check if a JWT auth token of a machine client is valid like validate_token does for users,
+// and make sure the client is still registered. Returns the claims along with the client.
+#[tracing::instrument(name = "Validate client token", skip_all)]
+pub async fn validate_client_token(
+    token: &Secret<String>,
+    banned_token_store: BannedTokenStoreType,
+    keyring: KeyringType,
+    oauth_client_store: OAuthClientStoreType,
+    jwt_config: &JwtConfig,
+) -> Result<(ClientClaims, OAuthClient)> {
+    if banned_token_store
+        .read()
+        .await
+        .contains_token(token)
+        .await?
+    {
+        return Err(eyre!("token is banned"));
+    }
+
+    let claims: ClientClaims = decode_token(
+        token,
+        CLIENT_TOKEN_TYPE,
+        keyring,
+        auth_token_validation(jwt_config),
+    )
+    .await?;
+    check_issued_at(claims.iat, jwt_config)?;
+
+    let client = oauth_client_store
+        .read()
+        .await
+        .get_client(&claims.client_id)
+        .await?;
+
+    if !client.is_machine_client() {
+        return Err(eyre!("client can't have tokens of its own"));
+    }
+
+    Ok((claims, client))
+}
+
+// Check if a personal access token is known and hasn't expired, and record that it was used.
+// Returns the token along with its user.
+#[tracing::instrument(name = "Validate personal access token", skip_all)]
+pub async fn validate_personal_access_token(
+    token: &Secret<String>,
+    personal_access_token_store: PersonalAccessTokenStoreType,
+    user_store: UserStoreType,
+) -> Result<(PersonalAccessToken, User)> {
+    let token_hash = PersonalAccessToken::hash(token.expose_secret());
+    let token = personal_access_token_store
+        .read()
+        .await
+        .get_token_by_hash(&token_hash)
+        .await?;
+
+    if token.is_expired() {
+        return Err(eyre!("token has expired"));
+    }
+
+    // Accounts scheduled for deletion are not found, so their tokens stop working with them
+    let user = user_store
+        .read()
+        .await
+        .get_user_by_id(&token.user_id)
+        .await?;
+
+    personal_access_token_store
+        .write()
+        .await
+        .touch_token(&token.id)
+        .await?;
+
+    Ok((token, user))
+}
+
+// A valid auth token, told apart by who it was issued to
+#[derive(Debug)]
+pub enum ValidatedToken {
+    // JWT issued to a user on login, or to an OAuth client acting for them
+    User(Claims, User),
+    // JWT issued to a machine client acting for itself
+    Client(ClientClaims, OAuthClient),
+    // Created by a user for their scripts
+    PersonalAccessToken(PersonalAccessToken, User),
+}
+
+impl ValidatedToken {
+    // Id of the OAuth client the token was issued to, None for logins and personal access tokens
+    pub fn client_id(&self) -> Option<&str> {
+        match self {
+            Self::User(claims, _) => claims.client_id.as_deref(),
+            Self::Client(_, client) => Some(&client.id),
+            Self::PersonalAccessToken(_, _) => None,
+        }
+    }
+}
+
+// Check if an auth token of any kind is valid, for the endpoints that serve users and machine
+// clients alike
+#[tracing::instrument(name = "Validate any token", skip_all)]
+pub async fn validate_any_token(
+    token: &Secret<String>,
+    state: &AppState,
+) -> Result<ValidatedToken> {
+    if PersonalAccessToken::is_personal_access_token(token.expose_secret()) {
+        let (token, user) = validate_personal_access_token(
+            token,
+            state.personal_access_token_store.clone(),
+            state.user_store.clone(),
+        )
+        .await?;
+
+        return Ok(ValidatedToken::PersonalAccessToken(token, user));
+    }
+
+    let header = decode_header(token.expose_secret()).wrap_err("failed to decode token header")?;
+
+    if header.typ.as_deref() == Some(CLIENT_TOKEN_TYPE) {
+        let (claims, client) = validate_client_token(
+            token,
+            state.banned_token_store.clone(),
+            state.keyring.clone(),
+            state.oauth_client_store.clone(),
+            &state.jwt_config,
+        )
+        .await?;
+
+        return Ok(ValidatedToken::Client(claims, client));
+    }
+
+    let (claims, user) = validate_token(
+        token,
+        state.banned_token_store.clone(),
+        state.keyring.clone(),
+        state.user_store.clone(),
+        state.session_store.clone(),
+        &state.jwt_config,
+    )
+    .await?;
+
+    Ok(ValidatedToken::User(claims, user))
+}
+
+// Auth tokens have to be issued by us for one of our audiences and be valid at this time
+fn auth_token_validation(jwt_config: &JwtConfig) -> Validation {
+    let mut validation = Validation::default();
+    validation.set_issuer(&[&jwt_config.issuer]);
+    validation.set_audience(&jwt_config.audiences);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation.validate_nbf = true;
+    validation.leeway = jwt_config.leeway_seconds;
+    validation
+}
+
+// The library leaves `iat` alone, a token from the future was not issued by our clock
+fn check_issued_at(iat: usize, jwt_config: &JwtConfig) -> Result<()> {
+    if iat as u64 > get_current_timestamp() + jwt_config.leeway_seconds {
+        return Err(eyre!("token was issued in the future"));
+    }
+
+    Ok(())
+}
+
+// Decode a token of the given type using the keyring key named in its header. The algorithm of
+// that key replaces the ones in `validation`.
+#[tracing::instrument(name = "Decode token", skip_all)]
+async fn decode_token<T: DeserializeOwned>(
+    token: &Secret<String>,
+    token_type: &str,
+    keyring: KeyringType,
+    mut validation: Validation,
+) -> Result<T> {
+    let header = decode_header(token.expose_secret()).wrap_err("failed to decode token header")?;
+
+    // Without this check a token issued for one purpose could be passed off as another
+    if header.typ.as_deref() != Some(token_type) {
+        return Err(eyre!("token is not of type {}", token_type));
+    }
+
+    let kid = header.kid.wrap_err("token header has no key id")?;
+    let keyring = keyring.read().await;
+    let signing_key = keyring
+        .find(&kid)
+        .wrap_err("token is not signed with a known key")?;
+
+    validation.algorithms = vec![signing_key.algorithm()];
+
+    decode::<T>(
         token.expose_secret(),
-        &DecodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-        &Validation::default(),
+        signing_key.decoding_key(),
+        &validation,
     )
     .map(|data| data.claims)
     .wrap_err("failed to decode token")
 }
 
-// Create JWT auth token by encoding claims using the JWT secret
+// Create a token of the given type by encoding claims using the signing key
 #[tracing::instrument(name = "Create token", skip_all)]
-fn create_token(claims: &Claims) -> Result<Secret<String>> {
-    let token = encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-    )
-    .wrap_err(eyre!("failed to create token"))?;
+fn create_token<T: Serialize>(
+    claims: &T,
+    token_type: &str,
+    signing_key: &SigningKey,
+) -> Result<Secret<String>> {
+    let mut header = Header::new(signing_key.algorithm());
+    header.typ = Some(token_type.to_owned());
+    header.kid = Some(signing_key.kid().to_owned());
+
+    let token = encode(&header, &claims, signing_key.encoding_key())
+        .wrap_err(eyre!("failed to create token"))?;
 
     Ok(Secret::new(token))
 }
 
+// Value of the `typ` header of each kind of token
+const AUTH_TOKEN_TYPE: &str = "JWT";
+const CLIENT_TOKEN_TYPE: &str = "client+jwt";
+const EMAIL_VERIFICATION_TOKEN_TYPE: &str = "email-verification+jwt";
+const ACCOUNT_UNLOCK_TOKEN_TYPE: &str = "account-unlock+jwt";
+const EMAIL_CHANGE_TOKEN_TYPE: &str = "email-change+jwt";
+// Plain JWT, like auth tokens, as OpenID Connect clients expect. Its audience is the client and
+// it lacks the claims of an auth token, so it can't be passed off as one.
+const ID_TOKEN_TYPE: &str = "JWT";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailVerificationClaims {
+    pub sub: String,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountUnlockClaims {
+    pub sub: String,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailChangeClaims {
+    pub sub: String,
+    pub new_email: String,
+    // Token version of the user when the change was requested, it goes up once the change
+    // is confirmed
+    pub ver: i32,
+    pub exp: usize,
+}
+
+// See OpenID Connect Core section 2
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    // ID of the user, the same as in auth tokens
+    pub sub: String,
+    // ID of the OAuth client
+    pub aud: String,
+    pub exp: usize,
+    pub iat: usize,
+    // When the user logged in, as a Unix timestamp in seconds
+    pub auth_time: i64,
+    // The nonce the client sent to /authorize, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    pub amr: Vec<AuthenticationMethod>,
+}
+
+// Claims of the JWT auth token of a machine client, which has no user or session behind it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientClaims {
+    // ID of the client, as no user is involved
+    pub sub: String,
+    pub client_id: String,
+    // Space separated scopes the client was registered with and requested
+    pub scope: String,
+    pub iss: String,
+    pub aud: Vec<String>,
+    pub iat: usize,
+    pub nbf: usize,
+    pub exp: usize,
+    // Unique ID of the token itself
+    pub jti: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
+    // ID of the user, see UserId
     pub sub: String,
+    // Email address of the user, only added when JwtConfig::email_claim is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub iss: String,
+    pub aud: Vec<String>,
+    pub iat: usize,
+    pub nbf: usize,
     pub exp: usize,
+    // Unique ID of the token itself
+    pub jti: String,
+    // Token version of the user, see UserStore::get_token_version
+    pub ver: i32,
+    // ID of the session the token was issued to, see SessionStore
+    pub sid: String,
+    // OAuth client the token was issued to, and the scope the user granted it. Tokens
+    // issued on login belong to the user themselves and have neither.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // Roles of the user and the permissions they have through them, as of when the token was
+    // issued. Changes only show up in the tokens issued after them, at the latest when the
+    // token is refreshed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permissions: Vec<String>,
 }
 
 #[cfg(test)]
@@ -107,14 +943,78 @@ mod tests {
     use std::sync::Arc;
     use tokio::sync::RwLock;
 
-    use crate::{domain::BannedTokenStore, services::HashsetBannedTokenStore};
+    use crate::{
+        domain::{
+            BannedTokenStore, CodeChallenge, Keyring, OAuthClientStore, Password,
+            RefreshTokenStore, SessionStore, User, UserStore,
+        },
+        services::{
+            HashmapOAuthClientStore, HashmapRefreshTokenStore, HashmapSessionStore,
+            HashmapUserStore, HashsetBannedTokenStore,
+        },
+    };
+    use std::net::{IpAddr, Ipv4Addr};
 
     use super::*;
 
+    fn signing_key() -> SigningKey {
+        SigningKey::generate_ed25519().unwrap()
+    }
+
+    fn keyring(signing_key: &SigningKey) -> KeyringType {
+        Arc::new(RwLock::new(
+            Keyring::new(signing_key.clone(), TOKEN_TTL_SECONDS).unwrap(),
+        ))
+    }
+
+    fn jwt_config() -> JwtConfig {
+        JwtConfig::new("issuer".to_owned(), vec!["audience".to_owned()], 60, false).unwrap()
+    }
+
+    fn user() -> User {
+        User::new(
+            Email::parse(Secret::new("test@example.com".to_owned())).unwrap(),
+            Password::parse(Secret::new("password123".to_owned())).unwrap(),
+            false,
+        )
+    }
+
+    // Store holding the test user, and that user
+    async fn user_store() -> (UserStoreType, User) {
+        let user = user();
+
+        let mut user_store = HashmapUserStore::default();
+        user_store.add_user(user.clone()).await.unwrap();
+
+        (Arc::new(RwLock::new(user_store)), user)
+    }
+
+    // Store holding a session of the test user, and the ID of that session
+    async fn session_store() -> (SessionStoreType, RefreshTokenFamilyId) {
+        let session = Session::new(
+            Email::parse(Secret::new("test@example.com".to_owned())).unwrap(),
+            None,
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+        );
+        let session_id = session.id.clone();
+
+        let mut session_store = HashmapSessionStore::default();
+        session_store.add_session(session).await.unwrap();
+
+        (Arc::new(RwLock::new(session_store)), session_id)
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(
+            &user(),
+            &UserRoles::default(),
+            0,
+            &RefreshTokenFamilyId::default(),
+            &jwt_config(),
+            &signing_key(),
+        )
+        .unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -134,50 +1034,806 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_generate_auth_token() {
+    async fn test_generate_refresh_cookie() {
         let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let refresh_token_store = Arc::new(RwLock::new(HashmapRefreshTokenStore::default()));
+
+        let family_id = RefreshTokenFamilyId::default();
+
+        let cookie =
+            generate_refresh_cookie(&email, 0, family_id.clone(), refresh_token_store.clone())
+                .await
+                .unwrap();
+        assert_eq!(cookie.name(), REFRESH_COOKIE_NAME);
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+
+        let token = RefreshToken::parse(Secret::new(cookie.value().to_owned())).unwrap();
+        let record = refresh_token_store
+            .read()
+            .await
+            .get_token(&token)
+            .await
+            .unwrap();
+        assert_eq!(record.email, email);
+        assert_eq!(record.family_id, family_id);
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_token() {
+        let signing_key = signing_key();
+        let result = generate_auth_token(
+            &user(),
+            &UserRoles::default(),
+            0,
+            &RefreshTokenFamilyId::default(),
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
         assert_eq!(result.expose_secret().split('.').count(), 3);
+
+        let header = decode_header(result.expose_secret()).unwrap();
+        assert_eq!(header.alg, signing_key.algorithm());
+        assert_eq!(header.kid.as_deref(), Some(signing_key.kid()));
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let (user_store, user) = user_store().await;
+        let signing_key = signing_key();
+        let (session_store, session_id) = session_store().await;
+        let token = generate_auth_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
         let banned_token_store: Arc<RwLock<dyn BannedTokenStore + Send + Sync>> =
             Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
 
-        let result = validate_token(&token, banned_token_store).await.unwrap();
+        let (claims, validated_user) = validate_token(
+            &token,
+            banned_token_store,
+            keyring(&signing_key),
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await
+        .unwrap();
 
-        assert_eq!(result.sub, "test@example.com");
+        assert_eq!(claims.sub, user.id.as_ref());
+        assert_eq!(claims.email, None);
+        assert_eq!(validated_user, user);
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
             .expect("valid timestamp")
             .timestamp();
 
-        assert!(result.exp > exp as usize);
+        assert!(claims.exp > exp as usize);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_email_claim() {
+        let (user_store, user) = user_store().await;
+        let signing_key = signing_key();
+        let (session_store, session_id) = session_store().await;
+        let jwt_config = JwtConfig {
+            email_claim: true,
+            ..jwt_config()
+        };
+        let token = generate_auth_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config,
+            &signing_key,
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let (claims, _) = validate_token(
+            &token,
+            banned_token_store,
+            keyring(&signing_key),
+            user_store,
+            session_store,
+            &jwt_config,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(claims.sub, user.id.as_ref());
+        assert_eq!(claims.email.as_deref(), Some("test@example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_of_unknown_user() {
+        let signing_key = signing_key();
+        let (session_store, session_id) = session_store().await;
+        // Not the user in the store, whose ID differs
+        let token = generate_auth_token(
+            &user(),
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            keyring(&signing_key),
+            user_store().await.0,
+            session_store,
+            &jwt_config(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_token_claims() {
+        let (_, user) = user_store().await;
+        let (_, session_id) = session_store().await;
+        let signing_key = signing_key();
+        let keyring = keyring(&signing_key);
+        let mut validation = Validation::default();
+        validation.validate_aud = false;
+
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let token = generate_auth_token(
+                &user,
+                &UserRoles::default(),
+                0,
+                &session_id,
+                &jwt_config(),
+                &signing_key,
+            )
+            .unwrap();
+            let claims: Claims =
+                decode_token(&token, AUTH_TOKEN_TYPE, keyring.clone(), validation.clone())
+                    .await
+                    .unwrap();
+
+            assert_eq!(claims.sid, session_id.as_ref());
+            assert_eq!((claims.client_id, claims.scope), (None, None));
+            assert_eq!(claims.iss, "issuer");
+            assert_eq!(claims.aud, vec!["audience".to_owned()]);
+            assert!(claims.nbf <= claims.exp && claims.iat <= claims.exp);
+            ids.push(claims.jti);
+        }
+
+        assert_ne!(ids[0], ids[1]);
+    }
+
+    #[tokio::test]
+    async fn test_generate_access_token_for_client() {
+        let (user_store, user) = user_store().await;
+        let (session_store, session_id) = session_store().await;
+        let signing_key = signing_key();
+        let client_grant = ClientGrant {
+            client_id: "client".to_owned(),
+            scope: Some("profile".to_owned()),
+        };
+
+        let token = generate_access_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            Some(&client_grant),
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
+
+        let (claims, _) = validate_token(
+            &token,
+            Arc::new(RwLock::new(HashsetBannedTokenStore::default())),
+            keyring(&signing_key),
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(claims.client_id.as_deref(), Some("client"));
+        assert_eq!(claims.scope.as_deref(), Some("profile"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_access_token_roles() {
+        let session_id = RefreshTokenFamilyId::default();
+        let signing_key = signing_key();
+        let keyring = keyring(&signing_key);
+        let mut validation = Validation::default();
+        validation.validate_aud = false;
+        let roles = UserRoles {
+            roles: vec!["analyst".to_owned()],
+            permissions: vec!["reports:read".to_owned()],
+        };
+        let client_grant = ClientGrant {
+            client_id: "client".to_owned(),
+            scope: None,
+        };
+
+        for (client_grant, expected) in [
+            (None, roles.clone()),
+            (Some(&client_grant), UserRoles::default()),
+        ] {
+            let token = generate_access_token(
+                &user(),
+                &roles,
+                0,
+                &session_id,
+                client_grant,
+                &jwt_config(),
+                &signing_key,
+            )
+            .unwrap();
+            let claims: Claims =
+                decode_token(&token, AUTH_TOKEN_TYPE, keyring.clone(), validation.clone())
+                    .await
+                    .unwrap();
+
+            // Clients only get the scope the user granted them
+            assert_eq!(claims.roles, expected.roles);
+            assert_eq!(claims.permissions, expected.permissions);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_id_token() {
+        let (user_store, user) = user_store().await;
+        let (session_store, _) = session_store().await;
+        let signing_key = signing_key();
+        let grant = AuthorizationGrant {
+            client_grant: ClientGrant {
+                client_id: "client".to_owned(),
+                scope: Some("openid".to_owned()),
+            },
+            redirect_uri: "https://app.example.com/callback".to_owned(),
+            code_challenge: CodeChallenge::parse("a".repeat(43)).unwrap(),
+            user_id: user.id.clone(),
+            user_agent: None,
+            ip_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            nonce: Some("nonce".to_owned()),
+            auth_time: 1_700_000_000,
+            amr: vec![
+                AuthenticationMethod::Password,
+                AuthenticationMethod::MultiFactor,
+            ],
+        };
+
+        let token = generate_id_token(&user, &grant, &jwt_config(), &signing_key).unwrap();
+
+        let mut validation = Validation::new(signing_key.algorithm());
+        validation.set_issuer(&["issuer"]);
+        validation.set_audience(&["client"]);
+        let claims = decode::<IdTokenClaims>(
+            token.expose_secret(),
+            signing_key.decoding_key(),
+            &validation,
+        )
+        .unwrap()
+        .claims;
+
+        assert_eq!(claims.sub, user.id.as_ref());
+        assert_eq!(claims.nonce.as_deref(), Some("nonce"));
+        assert_eq!(claims.auth_time, 1_700_000_000);
+        assert_eq!(claims.amr, grant.amr);
+
+        // It can't be used as an auth token
+        let result = validate_token(
+            &token,
+            Arc::new(RwLock::new(HashsetBannedTokenStore::default())),
+            keyring(&signing_key),
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_client_token() {
+        let (user_store, _) = user_store().await;
+        let (session_store, _) = session_store().await;
+        let signing_key = signing_key();
+        let (client, _) = OAuthClient::new(
+            "Job".to_owned(),
+            vec![],
+            vec!["reports:read".to_owned()],
+            true,
+        )
+        .unwrap();
+
+        let mut oauth_client_store = HashmapOAuthClientStore::default();
+        oauth_client_store.add_client(client.clone()).await.unwrap();
+        let oauth_client_store: OAuthClientStoreType = Arc::new(RwLock::new(oauth_client_store));
+
+        let token = generate_client_token(
+            &client,
+            "reports:read".to_owned(),
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
+
+        let (claims, validated_client) = validate_client_token(
+            &token,
+            Arc::new(RwLock::new(HashsetBannedTokenStore::default())),
+            keyring(&signing_key),
+            oauth_client_store,
+            &jwt_config(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(claims.sub, client.id);
+        assert_eq!(claims.client_id, client.id);
+        assert_eq!(claims.scope, "reports:read");
+        assert_eq!(validated_client, client);
+
+        // It can't be used as a user's auth token
+        let result = validate_token(
+            &token,
+            Arc::new(RwLock::new(HashsetBannedTokenStore::default())),
+            keyring(&signing_key),
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_wrong_issuer_or_audience() {
+        let (user_store, user) = user_store().await;
+        let (session_store, session_id) = session_store().await;
+        let signing_key = signing_key();
+
+        let test_cases = [
+            JwtConfig {
+                issuer: "other-issuer".to_owned(),
+                ..jwt_config()
+            },
+            JwtConfig {
+                audiences: vec!["other-audience".to_owned()],
+                ..jwt_config()
+            },
+        ];
+
+        for issuing_config in test_cases {
+            let token = generate_auth_token(
+                &user,
+                &UserRoles::default(),
+                0,
+                &session_id,
+                &issuing_config,
+                &signing_key,
+            )
+            .unwrap();
+
+            let result = validate_token(
+                &token,
+                Arc::new(RwLock::new(HashsetBannedTokenStore::default())),
+                keyring(&signing_key),
+                user_store.clone(),
+                session_store.clone(),
+                &jwt_config(),
+            )
+            .await;
+            assert!(result.is_err(), "{:?}", issuing_config);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_accepts_any_configured_audience() {
+        let (user_store, user) = user_store().await;
+        let (session_store, session_id) = session_store().await;
+        let signing_key = signing_key();
+        let token = generate_auth_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
+
+        let validating_config = JwtConfig {
+            audiences: vec!["other-audience".to_owned(), "audience".to_owned()],
+            ..jwt_config()
+        };
+
+        let result = validate_token(
+            &token,
+            Arc::new(RwLock::new(HashsetBannedTokenStore::default())),
+            keyring(&signing_key),
+            user_store,
+            session_store,
+            &validating_config,
+        )
+        .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_time_claims_with_leeway() {
+        let (user_store, user) = user_store().await;
+        let (session_store, session_id) = session_store().await;
+        let signing_key = signing_key();
+        let now = Utc::now().timestamp() as usize;
+
+        // Offsets from now of iat, nbf and exp, and whether a leeway of 60 seconds accepts them
+        let test_cases = [
+            ((-700, -700, -100), false),
+            ((-630, -630, -30), true),
+            ((0, 120, 600), false),
+            ((0, 30, 600), true),
+            ((120, 0, 600), false),
+            ((30, 0, 600), true),
+        ];
+
+        for ((iat, nbf, exp), accepted) in test_cases {
+            let claims = Claims {
+                sub: user.id.as_ref().to_owned(),
+                email: None,
+                iss: "issuer".to_owned(),
+                aud: vec!["audience".to_owned()],
+                iat: now.checked_add_signed(iat).unwrap(),
+                nbf: now.checked_add_signed(nbf).unwrap(),
+                exp: now.checked_add_signed(exp).unwrap(),
+                jti: Uuid::new_v4().to_string(),
+                ver: 0,
+                sid: session_id.as_ref().to_owned(),
+                client_id: None,
+                scope: None,
+                roles: Vec::new(),
+                permissions: Vec::new(),
+            };
+            let token = create_token(&claims, AUTH_TOKEN_TYPE, &signing_key).unwrap();
+
+            let result = validate_token(
+                &token,
+                Arc::new(RwLock::new(HashsetBannedTokenStore::default())),
+                keyring(&signing_key),
+                user_store.clone(),
+                session_store.clone(),
+                &jwt_config(),
+            )
+            .await;
+            assert_eq!(result.is_ok(), accepted, "{:?}", (iat, nbf, exp));
+        }
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_but_banned_token() {
-        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let (user_store, user) = user_store().await;
+        let signing_key = signing_key();
+        let (session_store, session_id) = session_store().await;
+        let token = generate_auth_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
         {
             let mut store = banned_token_store.write().await;
             store.add_token(token.clone()).await.unwrap();
         }
 
-        let result = validate_token(&token, banned_token_store).await;
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            keyring(&signing_key),
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await;
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_validate_token_signed_with_unknown_key() {
+        let (user_store, user) = user_store().await;
+        let (session_store, session_id) = session_store().await;
+        let token = generate_auth_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config(),
+            &signing_key(),
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            keyring(&signing_key()),
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_retired_key() {
+        let (user_store, user) = user_store().await;
+        let signing_key = signing_key();
+        let (session_store, session_id) = session_store().await;
+        let token = generate_auth_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let keyring = keyring(&signing_key);
+        keyring
+            .write()
+            .await
+            .rotate(self::signing_key(), Utc::now())
+            .unwrap();
+
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            keyring,
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.0.sub, user.id.as_ref());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_expired_key() {
+        let (user_store, user) = user_store().await;
+        let signing_key = signing_key();
+        let (session_store, session_id) = session_store().await;
+        let token = generate_auth_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let keyring = Arc::new(RwLock::new(Keyring::new(signing_key, 0).unwrap()));
+        keyring
+            .write()
+            .await
+            .rotate(self::signing_key(), Utc::now())
+            .unwrap();
+
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            keyring,
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_hs256_key() {
+        let (user_store, user) = user_store().await;
+        let signing_key = SigningKey::from_secret(&Secret::new("secret".to_owned()));
+        let (session_store, session_id) = session_store().await;
+        let token = generate_auth_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            keyring(&signing_key),
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.0.sub, user.id.as_ref());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_revoked_token_version() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let (user_store, user) = user_store().await;
+        let signing_key = signing_key();
+        let (session_store, session_id) = session_store().await;
+        let token = generate_auth_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        user_store
+            .write()
+            .await
+            .increment_token_version(&email)
+            .await
+            .unwrap();
+
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            keyring(&signing_key),
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_removed_session() {
+        let (user_store, user) = user_store().await;
+        let signing_key = signing_key();
+        let (session_store, session_id) = session_store().await;
+        let token = generate_auth_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        session_store
+            .write()
+            .await
+            .remove_session(&session_id)
+            .await
+            .unwrap();
+
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            keyring(&signing_key),
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_email_verification_token_roundtrip() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let signing_key = signing_key();
+        let token = generate_email_verification_token(&email, &signing_key).unwrap();
+
+        let result = validate_email_verification_token(&token, keyring(&signing_key))
+            .await
+            .unwrap();
+        assert_eq!(result, email);
+    }
+
+    #[tokio::test]
+    async fn test_email_change_token_roundtrip() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let new_email = Email::parse(Secret::new("new@example.com".to_owned())).unwrap();
+        let signing_key = signing_key();
+        let token = generate_email_change_token(&email, &new_email, 3, &signing_key).unwrap();
+
+        let result = validate_email_change_token(&token, keyring(&signing_key))
+            .await
+            .unwrap();
+        assert_eq!(result, (email, new_email, 3));
+
+        let result = validate_email_verification_token(&token, keyring(&signing_key)).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tokens_are_not_accepted_for_another_purpose() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let (user_store, user) = user_store().await;
+        let signing_key = signing_key();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let (session_store, session_id) = session_store().await;
+
+        let verification_token = generate_email_verification_token(&email, &signing_key).unwrap();
+        let result = validate_token(
+            &verification_token,
+            banned_token_store,
+            keyring(&signing_key),
+            user_store,
+            session_store,
+            &jwt_config(),
+        )
+        .await;
+        assert!(result.is_err());
+
+        let auth_token = generate_auth_token(
+            &user,
+            &UserRoles::default(),
+            0,
+            &session_id,
+            &jwt_config(),
+            &signing_key,
+        )
+        .unwrap();
+        let result = validate_email_verification_token(&auth_token, keyring(&signing_key)).await;
+        assert!(result.is_err());
+
+        let unlock_token = generate_account_unlock_token(&email, &signing_key).unwrap();
+        let result = validate_email_verification_token(&unlock_token, keyring(&signing_key)).await;
+        assert!(result.is_err());
+
+        let result = validate_account_unlock_token(&unlock_token, keyring(&signing_key))
+            .await
+            .unwrap();
+        assert_eq!(result, email);
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = Secret::new("invalid_token".to_owned());
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await;
+        let (session_store, _) = session_store().await;
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            keyring(&signing_key()),
+            user_store().await.0,
+            session_store,
+            &jwt_config(),
+        )
+        .await;
         assert!(result.is_err());
     }
 }
@@ -0,0 +1,352 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{request::Parts, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+use tower::{Layer, Service};
+
+use super::{
+    auth::{bearer_token, validate_personal_access_token, validate_token, Claims},
+    constants::JWT_COOKIE_NAME,
+};
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, PersonalAccessToken, RefreshTokenFamilyId, Session, User},
+};
+
+// Where the JWT auth token is looked for first when a request sends both an Authorization
+// header and the auth cookie. Only that one is checked then, an invalid token doesn't fall back
+// to the other.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AuthTokenPrecedence {
+    // `Authorization: Bearer`, as sent by mobile and server clients
+    #[default]
+    Header,
+    // The auth cookie, as sent by browsers
+    Cookie,
+}
+
+impl AuthTokenPrecedence {
+    pub fn parse(precedence: &str) -> Result<Self> {
+        match precedence {
+            "header" => Ok(Self::Header),
+            "cookie" => Ok(Self::Cookie),
+            _ => Err(eyre!("auth token precedence must be header or cookie")),
+        }
+    }
+}
+
+// The user a request is authenticated as, by the JWT auth token it sends as a bearer token or
+// in the auth cookie, or by one of their personal access tokens. Tokens issued to OAuth clients
+// don't let them act as the user here.
+#[derive(Debug)]
+pub struct AuthenticatedUser {
+    pub token: Secret<String>,
+    pub credential: UserCredential,
+    pub user: User,
+    // What the token lets the user do, see Claims::permissions and
+    // PersonalAccessToken::permissions
+    pub permissions: Vec<String>,
+}
+
+impl AuthenticatedUser {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+}
+
+// The kind of token a user authenticated with
+#[derive(Debug)]
+pub enum UserCredential {
+    // JWT auth token issued on login
+    Jwt(Claims),
+    // Token the user created for their scripts, which has no session
+    PersonalAccessToken(PersonalAccessToken),
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthenticatedUser {
+    type Rejection = AuthAPIError;
+
+    #[tracing::instrument(name = "Authenticate", skip_all)]
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = auth_token(&parts.headers, state.auth_token_precedence)
+            .ok_or(AuthAPIError::MissingToken)?;
+
+        if PersonalAccessToken::is_personal_access_token(token.expose_secret()) {
+            let (personal_access_token, user) = validate_personal_access_token(
+                &token,
+                state.personal_access_token_store.clone(),
+                state.user_store.clone(),
+            )
+            .await
+            .map_err(|_| AuthAPIError::InvalidToken)?;
+
+            // Personal access tokens live long, so the roles are looked up every time
+            let roles = state
+                .role_store
+                .read()
+                .await
+                .get_user_roles(&user.id)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+            return Ok(Self {
+                token,
+                permissions: personal_access_token.permissions(&roles),
+                credential: UserCredential::PersonalAccessToken(personal_access_token),
+                user,
+            });
+        }
+
+        let (claims, user) = validate_token(
+            &token,
+            state.banned_token_store.clone(),
+            state.keyring.clone(),
+            state.user_store.clone(),
+            state.session_store.clone(),
+            &state.jwt_config,
+        )
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+        if claims.client_id.is_some() {
+            return Err(AuthAPIError::InvalidToken);
+        }
+
+        Ok(Self {
+            token,
+            permissions: claims.permissions.clone(),
+            credential: UserCredential::Jwt(claims),
+            user,
+        })
+    }
+}
+
+// Lets only users whose token grants the permission through to the routes it is applied to,
+// e.g. `.route_layer(RequirePermission::new(state, ROLES_WRITE_PERMISSION))`, like the layer of
+// the same name in app-service. Others are rejected like by AuthenticatedUser without a valid
+// token, and with 403 without the permission. It is checked against the token, so a role takes
+// effect once the user gets a new one.
+#[derive(Clone)]
+pub struct RequirePermission {
+    state: AppState,
+    permission: &'static str,
+}
+
+impl RequirePermission {
+    pub fn new(state: AppState, permission: &'static str) -> Self {
+        Self { state, permission }
+    }
+}
+
+impl<S> Layer<S> for RequirePermission {
+    type Service = RequirePermissionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequirePermissionService {
+            inner,
+            state: self.state.clone(),
+            permission: self.permission,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequirePermissionService<S> {
+    inner: S,
+    state: AppState,
+    permission: &'static str,
+}
+
+impl<S> Service<Request> for RequirePermissionService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // The service that was polled ready handles the request, its clone the next one
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let state = self.state.clone();
+        let permission = self.permission;
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+
+            let user = match AuthenticatedUser::from_request_parts(&mut parts, &state).await {
+                Ok(user) => user,
+                Err(e) => return Ok(e.into_response()),
+            };
+
+            if !user.has_permission(permission) {
+                return Ok(AuthAPIError::MissingPermission.into_response());
+            }
+
+            inner.call(Request::from_parts(parts, body)).await
+        })
+    }
+}
+
+// Session the JWT auth token of the request was issued to, along with the user it belongs to.
+// For the handlers personal access tokens must not reach, as they would get around the scopes
+// of the token or take over the account.
+#[derive(Debug)]
+pub struct AuthenticatedSession {
+    pub session: Session,
+    pub user: User,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthenticatedSession {
+    type Rejection = AuthAPIError;
+
+    #[tracing::instrument(name = "Authenticate session", skip_all)]
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let AuthenticatedUser {
+            credential: UserCredential::Jwt(claims),
+            user,
+            ..
+        } = AuthenticatedUser::from_request_parts(parts, state).await?
+        else {
+            return Err(AuthAPIError::InvalidToken);
+        };
+
+        let session_id =
+            RefreshTokenFamilyId::parse(claims.sid).map_err(|_| AuthAPIError::InvalidToken)?;
+
+        let session = state
+            .session_store
+            .read()
+            .await
+            .get_session(&session_id)
+            .await
+            .map_err(|_| AuthAPIError::InvalidToken)?;
+
+        Ok(Self { session, user })
+    }
+}
+
+// The JWT auth token sent with the request, from the Authorization header or the auth cookie
+// depending on the precedence
+fn auth_token(headers: &HeaderMap, precedence: AuthTokenPrecedence) -> Option<Secret<String>> {
+    let from_cookie = || {
+        CookieJar::from_headers(headers)
+            .get(JWT_COOKIE_NAME)
+            .map(|cookie| Secret::new(cookie.value().to_owned()))
+    };
+
+    match precedence {
+        AuthTokenPrecedence::Header => bearer_token(headers).or_else(from_cookie),
+        AuthTokenPrecedence::Cookie => from_cookie().or_else(|| bearer_token(headers)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::header::{AUTHORIZATION, COOKIE};
+    use secrecy::ExposeSecret;
+
+    use super::*;
+
+    fn headers(bearer: Option<&str>, cookie: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        if let Some(bearer) = bearer {
+            let value = format!("Bearer {}", bearer);
+            headers.insert(AUTHORIZATION, value.parse().unwrap());
+        }
+
+        if let Some(cookie) = cookie {
+            let value = format!("{}={}", JWT_COOKIE_NAME, cookie);
+            headers.insert(COOKIE, value.parse().unwrap());
+        }
+
+        headers
+    }
+
+    fn token(headers: &HeaderMap, precedence: AuthTokenPrecedence) -> Option<String> {
+        auth_token(headers, precedence).map(|token| token.expose_secret().to_owned())
+    }
+
+    #[test]
+    fn test_auth_token_precedence() {
+        let both = headers(Some("from-header"), Some("from-cookie"));
+
+        assert_eq!(
+            token(&both, AuthTokenPrecedence::Header).as_deref(),
+            Some("from-header")
+        );
+        assert_eq!(
+            token(&both, AuthTokenPrecedence::Cookie).as_deref(),
+            Some("from-cookie")
+        );
+    }
+
+    #[test]
+    fn test_auth_token_falls_back_to_other_source() {
+        for precedence in [AuthTokenPrecedence::Header, AuthTokenPrecedence::Cookie] {
+            let header_only = headers(Some("from-header"), None);
+            assert_eq!(
+                token(&header_only, precedence).as_deref(),
+                Some("from-header")
+            );
+
+            let cookie_only = headers(None, Some("from-cookie"));
+            assert_eq!(
+                token(&cookie_only, precedence).as_deref(),
+                Some("from-cookie")
+            );
+
+            assert_eq!(token(&headers(None, None), precedence), None);
+        }
+    }
+
+    #[test]
+    fn test_auth_token_ignores_other_schemes() {
+        let mut basic = headers(None, Some("from-cookie"));
+        basic.insert(AUTHORIZATION, "Basic aWQ6c2VjcmV0".parse().unwrap());
+
+        assert_eq!(
+            token(&basic, AuthTokenPrecedence::Header).as_deref(),
+            Some("from-cookie")
+        );
+    }
+
+    #[test]
+    fn test_parse_auth_token_precedence() {
+        assert_eq!(
+            AuthTokenPrecedence::parse("header").unwrap(),
+            AuthTokenPrecedence::Header
+        );
+        assert_eq!(
+            AuthTokenPrecedence::parse("cookie").unwrap(),
+            AuthTokenPrecedence::Cookie
+        );
+        assert!(AuthTokenPrecedence::parse("body").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts, HeaderMap},
+};
+
+use crate::app_state::AppState;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+// The device a request came from, recorded with the sessions it starts
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: IpAddr,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+
+        // Only missing when the router is served without connect info, e.g. in unit tests
+        let peer_address = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip())
+            .unwrap_or(IpAddr::from([0, 0, 0, 0]));
+
+        let ip_address = client_ip_address(peer_address, &parts.headers, &state.trusted_proxies);
+
+        Ok(Self {
+            user_agent,
+            ip_address,
+        })
+    }
+}
+
+// Anyone can send X-Forwarded-For, so it is only read when the request comes from one of our
+// proxies. Each proxy appends the address it got the request from, so the client is the last
+// address that is not one of our proxies.
+fn client_ip_address(
+    peer_address: IpAddr,
+    headers: &HeaderMap,
+    trusted_proxies: &[IpAddr],
+) -> IpAddr {
+    if !trusted_proxies.contains(&peer_address) {
+        return peer_address;
+    }
+
+    let forwarded_addresses: Vec<&str> = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+
+    let mut client_address = peer_address;
+    for address in forwarded_addresses.into_iter().rev() {
+        // Whatever comes before an address we can't read was not added by our proxies
+        let Ok(address) = address.parse::<IpAddr>() else {
+            break;
+        };
+        client_address = address;
+        if !trusted_proxies.contains(&address) {
+            break;
+        }
+    }
+
+    client_address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROXY: [u8; 4] = [10, 0, 0, 1];
+    const CLIENT: [u8; 4] = [203, 0, 113, 7];
+
+    fn headers(forwarded_for: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in forwarded_for {
+            headers.append(X_FORWARDED_FOR, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_ignores_forwarded_for_from_untrusted_peer() {
+        let ip_address = client_ip_address(
+            IpAddr::from(CLIENT),
+            &headers(&["198.51.100.1"]),
+            &[IpAddr::from(PROXY)],
+        );
+
+        assert_eq!(ip_address, IpAddr::from(CLIENT));
+    }
+
+    #[test]
+    fn test_reads_forwarded_for_from_trusted_proxy() {
+        let ip_address = client_ip_address(
+            IpAddr::from(PROXY),
+            &headers(&["203.0.113.7"]),
+            &[IpAddr::from(PROXY)],
+        );
+
+        assert_eq!(ip_address, IpAddr::from(CLIENT));
+    }
+
+    #[test]
+    fn test_skips_addresses_spoofed_by_the_client() {
+        // The client sent 198.51.100.1 itself, our proxy appended the address it saw
+        let ip_address = client_ip_address(
+            IpAddr::from(PROXY),
+            &headers(&["198.51.100.1, 203.0.113.7"]),
+            &[IpAddr::from(PROXY)],
+        );
+
+        assert_eq!(ip_address, IpAddr::from(CLIENT));
+    }
+
+    #[test]
+    fn test_skips_chained_trusted_proxies() {
+        let ip_address = client_ip_address(
+            IpAddr::from(PROXY),
+            &headers(&["203.0.113.7", "10.0.0.2"]),
+            &[IpAddr::from(PROXY), IpAddr::from([10, 0, 0, 2])],
+        );
+
+        assert_eq!(ip_address, IpAddr::from(CLIENT));
+    }
+
+    #[test]
+    fn test_uses_peer_without_forwarded_for() {
+        let ip_address =
+            client_ip_address(IpAddr::from(PROXY), &headers(&[]), &[IpAddr::from(PROXY)]);
+
+        assert_eq!(ip_address, IpAddr::from(PROXY));
+    }
+
+    #[test]
+    fn test_stops_at_invalid_address() {
+        let ip_address = client_ip_address(
+            IpAddr::from(PROXY),
+            &headers(&["198.51.100.1, unknown, 10.0.0.1"]),
+            &[IpAddr::from(PROXY)],
+        );
+
+        assert_eq!(ip_address, IpAddr::from(PROXY));
+    }
+}
@@ -1,14 +1,34 @@
 use dotenvy::dotenv;
 use lazy_static::lazy_static;
 use secrecy::Secret;
-use std::env as std_env;
+use std::{env as std_env, net::IpAddr};
+
+use super::authenticated_user::AuthTokenPrecedence;
+use crate::domain::ClientCredentials;
 
 // Define a lazily evaluated static. lazy_static is needed because std_env::var is not a const function.
 lazy_static! {
     pub static ref JWT_SECRET: Secret<String> = set_token();
+    pub static ref JWT_SIGNING_KEY: Option<Secret<String>> =
+        set_optional_secret(env::JWT_SIGNING_KEY_ENV_VAR);
+    pub static ref JWT_PREVIOUS_SIGNING_KEY: Option<Secret<String>> =
+        set_optional_secret(env::JWT_PREVIOUS_SIGNING_KEY_ENV_VAR);
+    pub static ref JWT_PREVIOUS_SECRET: Option<Secret<String>> =
+        set_optional_secret(env::JWT_PREVIOUS_SECRET_ENV_VAR);
     pub static ref DATABASE_URL: Secret<String> = set_database_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host();
     pub static ref POSTMARK_AUTH_TOKEN: Secret<String> = set_postmark_auth_token();
+    pub static ref TOTP_ENCRYPTION_KEY: Secret<String> = set_totp_encryption_key();
+    pub static ref SIGNING_KEY_ENCRYPTION_KEY: Secret<String> = set_signing_key_encryption_key();
+    pub static ref ACCOUNT_DELETION_GRACE_PERIOD_SECONDS: Option<u64> =
+        set_account_deletion_grace_period();
+    pub static ref JWT_EMAIL_CLAIM: bool = set_jwt_email_claim();
+    pub static ref JWT_ISSUER: String = set_jwt_issuer();
+    pub static ref JWT_AUDIENCES: Vec<String> = set_jwt_audiences();
+    pub static ref JWT_LEEWAY_SECONDS: u64 = set_jwt_leeway();
+    pub static ref OAUTH_CLIENTS: Vec<ClientCredentials> = set_oauth_clients();
+    pub static ref AUTH_TOKEN_PRECEDENCE: AuthTokenPrecedence = set_auth_token_precedence();
+    pub static ref TRUSTED_PROXIES: Vec<IpAddr> = set_trusted_proxies();
 }
 
 fn set_token() -> Secret<String> {
@@ -20,6 +40,14 @@ fn set_token() -> Secret<String> {
     Secret::new(jwt_secret)
 }
 
+fn set_optional_secret(name: &str) -> Option<Secret<String>> {
+    dotenv().ok();
+    std_env::var(name)
+        .ok()
+        .filter(|key| !key.is_empty())
+        .map(Secret::new)
+}
+
 fn set_database_url() -> Secret<String> {
     dotenv().ok(); // Load environment variables
     let secret = std_env::var(env::DATABASE_URL_ENV_VAR).expect("DATABASE URL must be set.");
@@ -41,15 +69,176 @@ fn set_postmark_auth_token() -> Secret<String> {
     )
 }
 
+fn set_totp_encryption_key() -> Secret<String> {
+    dotenv().ok();
+    Secret::new(
+        std_env::var(env::TOTP_ENCRYPTION_KEY_ENV_VAR).expect("TOTP_ENCRYPTION_KEY must be set."),
+    )
+}
+
+fn set_signing_key_encryption_key() -> Secret<String> {
+    dotenv().ok();
+    Secret::new(
+        std_env::var(env::SIGNING_KEY_ENCRYPTION_KEY_ENV_VAR)
+            .expect("SIGNING_KEY_ENCRYPTION_KEY must be set."),
+    )
+}
+
+fn set_account_deletion_grace_period() -> Option<u64> {
+    dotenv().ok();
+    std_env::var(env::ACCOUNT_DELETION_GRACE_PERIOD_SECONDS_ENV_VAR)
+        .ok()
+        .filter(|seconds| !seconds.is_empty())
+        .map(|seconds| {
+            seconds
+                .parse()
+                .expect("ACCOUNT_DELETION_GRACE_PERIOD_SECONDS must be a number of seconds.")
+        })
+        .filter(|seconds| *seconds > 0)
+}
+
+fn set_jwt_email_claim() -> bool {
+    dotenv().ok();
+    std_env::var(env::JWT_EMAIL_CLAIM_ENV_VAR)
+        .map(|value| value == "true")
+        .unwrap_or(false)
+}
+
+fn set_jwt_issuer() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_ISSUER_ENV_VAR)
+        .ok()
+        .filter(|issuer| !issuer.is_empty())
+        .unwrap_or(DEFAULT_JWT_ISSUER.to_owned())
+}
+
+fn set_jwt_audiences() -> Vec<String> {
+    dotenv().ok();
+    std_env::var(env::JWT_AUDIENCES_ENV_VAR)
+        .ok()
+        .filter(|audiences| !audiences.is_empty())
+        .map(|audiences| {
+            audiences
+                .split(',')
+                .map(|audience| audience.trim().to_owned())
+                .filter(|audience| !audience.is_empty())
+                .collect()
+        })
+        .unwrap_or(vec![DEFAULT_JWT_AUDIENCE.to_owned()])
+}
+
+fn set_jwt_leeway() -> u64 {
+    dotenv().ok();
+    std_env::var(env::JWT_LEEWAY_SECONDS_ENV_VAR)
+        .ok()
+        .filter(|seconds| !seconds.is_empty())
+        .map(|seconds| {
+            seconds
+                .parse()
+                .expect("JWT_LEEWAY_SECONDS must be a number of seconds.")
+        })
+        .unwrap_or(DEFAULT_JWT_LEEWAY_SECONDS)
+}
+
+fn set_auth_token_precedence() -> AuthTokenPrecedence {
+    dotenv().ok();
+    std_env::var(env::AUTH_TOKEN_PRECEDENCE_ENV_VAR)
+        .ok()
+        .filter(|precedence| !precedence.is_empty())
+        .map(|precedence| {
+            AuthTokenPrecedence::parse(&precedence)
+                .expect("AUTH_TOKEN_PRECEDENCE must be header or cookie.")
+        })
+        .unwrap_or_default()
+}
+
+fn set_oauth_clients() -> Vec<ClientCredentials> {
+    dotenv().ok();
+    std_env::var(env::OAUTH_CLIENTS_ENV_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|credentials| !credentials.is_empty())
+        .map(|credentials| {
+            ClientCredentials::parse(credentials)
+                .expect("OAUTH_CLIENTS must be a comma separated list of id:secret pairs.")
+        })
+        .collect()
+}
+
+fn set_trusted_proxies() -> Vec<IpAddr> {
+    dotenv().ok();
+    std_env::var(env::TRUSTED_PROXIES_ENV_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(|address| {
+            address
+                .parse()
+                .expect("TRUSTED_PROXIES must be a comma separated list of IP addresses.")
+        })
+        .collect()
+}
+
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
+    // PKCS#8 PEM encoded Ed25519 private key, JWTs are signed with JWT_SECRET (HS256) when unset
+    pub const JWT_SIGNING_KEY_ENV_VAR: &str = "JWT_SIGNING_KEY";
+    // Keys replaced by the ones above, tokens they signed are accepted until they expire. Keys
+    // configured here are added to the shared signing keys on startup, keys rotated through
+    // /admin/rotate-signing-key are only kept there.
+    pub const JWT_PREVIOUS_SIGNING_KEY_ENV_VAR: &str = "JWT_PREVIOUS_SIGNING_KEY";
+    pub const JWT_PREVIOUS_SECRET_ENV_VAR: &str = "JWT_PREVIOUS_SECRET";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
     pub const POSTMARK_AUTH_TOKEN_ENV_VAR: &str = "POSTMARK_AUTH_TOKEN";
+    // Base64 encoded 256-bit key the authenticator app secrets are encrypted with
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    // Base64 encoded 256-bit key the signing keys shared through the database are encrypted with
+    pub const SIGNING_KEY_ENCRYPTION_KEY_ENV_VAR: &str = "SIGNING_KEY_ENCRYPTION_KEY";
+    // How long deleted accounts are kept before they are purged, they are deleted right away
+    // when unset or 0
+    pub const ACCOUNT_DELETION_GRACE_PERIOD_SECONDS_ENV_VAR: &str =
+        "ACCOUNT_DELETION_GRACE_PERIOD_SECONDS";
+    // Set to "true" to add the email address of the user to JWT auth tokens, they only
+    // identify the user by id otherwise
+    pub const JWT_EMAIL_CLAIM_ENV_VAR: &str = "JWT_EMAIL_CLAIM";
+    // Issuer JWT auth tokens are issued by and checked against. OpenID Connect clients expect
+    // it to be the public URL of the service.
+    pub const JWT_ISSUER_ENV_VAR: &str = "JWT_ISSUER";
+    // Comma separated audiences JWT auth tokens are issued for, tokens naming any of them
+    // are accepted
+    pub const JWT_AUDIENCES_ENV_VAR: &str = "JWT_AUDIENCES";
+    // Clock skew tolerated when checking the time claims of JWT auth tokens
+    pub const JWT_LEEWAY_SECONDS_ENV_VAR: &str = "JWT_LEEWAY_SECONDS";
+    // Comma separated id:secret pairs of the services allowed to introspect and revoke tokens,
+    // those endpoints refuse every request when unset
+    pub const OAUTH_CLIENTS_ENV_VAR: &str = "OAUTH_CLIENTS";
+    // "header" or "cookie", where the JWT auth token of requests that send both is taken from.
+    // The Authorization header wins when unset.
+    pub const AUTH_TOKEN_PRECEDENCE_ENV_VAR: &str = "AUTH_TOKEN_PRECEDENCE";
+    // Comma separated IP addresses of the proxies in front of the service, the client address
+    // is read from the X-Forwarded-For header of their requests. The header is ignored when unset.
+    pub const TRUSTED_PROXIES_ENV_VAR: &str = "TRUSTED_PROXIES";
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_COOKIE_NAME: &str = "refresh_token";
+// Scopes of OpenID Connect, for an ID token and for the email address of the user
+pub const OPENID_SCOPE: &str = "openid";
+pub const EMAIL_SCOPE: &str = "email";
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+pub const DEFAULT_JWT_ISSUER: &str = "auth-service";
+pub const DEFAULT_JWT_AUDIENCE: &str = "app-service";
+pub const DEFAULT_JWT_LEEWAY_SECONDS: u64 = 60;
+// Shown next to the account name in authenticator apps
+pub const TOTP_ISSUER: &str = "Auth";
+// Permissions of the admin endpoints, granted by the admin role the migrations create
+pub const ROLES_WRITE_PERMISSION: &str = "roles:write";
+pub const CLIENTS_WRITE_PERMISSION: &str = "clients:write";
+pub const ACCOUNTS_UNLOCK_PERMISSION: &str = "accounts:unlock";
+pub const SIGNING_KEYS_ROTATE_PERMISSION: &str = "signing_keys:rotate";
 
 pub mod prod {
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
@@ -65,6 +254,12 @@ pub mod prod {
 
 pub mod test {
     pub const APP_ADDRESS: &str = "127.0.0.1:0";
+    pub const JWT_ISSUER: &str = "test-auth-service";
+    pub const JWT_AUDIENCE: &str = "test-app-service";
+    pub const OAUTH_CLIENT_ID: &str = "test-gateway";
+    pub const OAUTH_CLIENT_SECRET: &str = "test-gateway-secret";
+    pub const TOTP_ENCRYPTION_KEY: &str = "dGVzdC10b3RwLWVuY3J5cHRpb24ta2V5LTMyLWJ5dGU=";
+    pub const SIGNING_KEY_ENCRYPTION_KEY: &str = "dGVzdC1zaWduaW5nLWtleS1lbmNyeXB0aW9uLTMyYnk=";
     pub mod email_client {
         use std::time::Duration;
 
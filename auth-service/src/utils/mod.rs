@@ -1,3 +1,5 @@
 pub mod auth;
+pub mod authenticated_user;
+pub mod client_info;
 pub mod constants;
 pub mod tracing;
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use auth_service::OAuthErrorResponse;
+use reqwest::{header::LOCATION, Url};
+
+use crate::helpers::{get_pkce_pair, TestApp, OAUTH_REDIRECT_URI};
+
+fn authorize_query<'a>(client_id: &'a str, code_challenge: &'a str) -> Vec<(&'a str, &'a str)> {
+    vec![
+        ("response_type", "code"),
+        ("client_id", client_id),
+        ("redirect_uri", OAUTH_REDIRECT_URI),
+        ("code_challenge", code_challenge),
+        ("code_challenge_method", "S256"),
+        ("state", "xyz"),
+    ]
+}
+
+// Where the redirect leads, and the query parameters it carries
+fn redirect_location(response: &reqwest::Response) -> (String, HashMap<String, String>) {
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .expect("No location header found");
+
+    parse_url(location)
+}
+
+// Paths are resolved against the address of the auth service
+fn parse_url(url: &str) -> (String, HashMap<String, String>) {
+    let mut url = Url::parse(AUTH_SERVICE_URL)
+        .and_then(|base| base.join(url))
+        .expect("Invalid URL");
+    let params = url.query_pairs().into_owned().collect();
+    url.set_query(None);
+
+    (url.to_string(), params)
+}
+
+const AUTH_SERVICE_URL: &str = "http://auth-service/";
+
+#[tokio::test]
+async fn should_redirect_to_client_with_code_if_logged_in() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(true).await;
+    let (_, code_challenge) = get_pkce_pair();
+    app.sign_up_and_log_in().await;
+
+    let response = app
+        .get_authorize(&authorize_query(&client_id, &code_challenge))
+        .await;
+    assert!(response.status().is_redirection());
+
+    let (location, params) = redirect_location(&response);
+    assert_eq!(location, OAUTH_REDIRECT_URI);
+    assert_eq!(params.get("state").map(String::as_str), Some("xyz"));
+    assert!(params.get("code").is_some_and(|code| !code.is_empty()));
+    assert!(!params.contains_key("error"));
+
+    // Every request gets a new code
+    let response = app
+        .get_authorize(&authorize_query(&client_id, &code_challenge))
+        .await;
+    let (_, other_params) = redirect_location(&response);
+    assert_ne!(other_params.get("code"), params.get("code"));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_redirect_to_login_page_if_not_logged_in() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(true).await;
+    let (_, code_challenge) = get_pkce_pair();
+
+    let response = app
+        .get_authorize(&authorize_query(&client_id, &code_challenge))
+        .await;
+    assert!(response.status().is_redirection());
+
+    let (location, params) = redirect_location(&response);
+    assert_eq!(location, AUTH_SERVICE_URL);
+
+    // The login page is told to come back to the same request
+    let return_to = params.get("return_to").expect("No return_to found");
+    assert!(return_to.starts_with("/authorize?"));
+    let (_, return_params) = parse_url(return_to);
+    assert_eq!(return_params.get("client_id"), Some(&client_id));
+    assert_eq!(return_params.get("code_challenge"), Some(&code_challenge));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_without_redirect_if_unknown_client_or_redirect_uri() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(true).await;
+    let (_, code_challenge) = get_pkce_pair();
+    app.sign_up_and_log_in().await;
+
+    let unknown_client = authorize_query("unknown-client", &code_challenge);
+    let mut unregistered_redirect_uri = authorize_query(&client_id, &code_challenge);
+    unregistered_redirect_uri[2] = ("redirect_uri", "https://evil.example.com/callback");
+    let mut missing_redirect_uri = authorize_query(&client_id, &code_challenge);
+    missing_redirect_uri.remove(2);
+
+    for query in [
+        unknown_client,
+        unregistered_redirect_uri,
+        missing_redirect_uri,
+    ] {
+        let response = app.get_authorize(&query).await;
+        assert_eq!(response.status().as_u16(), 400, "Query: {:?}", query);
+        assert_eq!(
+            response
+                .json::<OAuthErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to OAuthErrorResponse")
+                .error,
+            "invalid_request".to_owned(),
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_redirect_errors_to_client() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(true).await;
+    let (_, code_challenge) = get_pkce_pair();
+    app.sign_up_and_log_in().await;
+
+    let test_cases = [
+        (("response_type", "token"), "unsupported_response_type"),
+        (("code_challenge", "too-short"), "invalid_request"),
+        (("code_challenge_method", "plain"), "invalid_request"),
+    ];
+
+    for ((name, value), error) in test_cases {
+        let query: Vec<_> = authorize_query(&client_id, &code_challenge)
+            .into_iter()
+            .map(|param| {
+                if param.0 == name {
+                    (name, value)
+                } else {
+                    param
+                }
+            })
+            .collect();
+
+        let response = app.get_authorize(&query).await;
+        assert!(response.status().is_redirection(), "Query: {:?}", query);
+
+        let (location, params) = redirect_location(&response);
+        assert_eq!(location, OAUTH_REDIRECT_URI);
+        assert_eq!(params.get("error").map(String::as_str), Some(error));
+        assert_eq!(params.get("state").map(String::as_str), Some("xyz"));
+        assert!(!params.contains_key("code"));
+    }
+
+    // PKCE is required
+    let mut query = authorize_query(&client_id, &code_challenge);
+    query.retain(|(name, _)| !name.starts_with("code_challenge"));
+
+    let response = app.get_authorize(&query).await;
+    let (_, params) = redirect_location(&response);
+    assert_eq!(
+        params.get("error").map(String::as_str),
+        Some("invalid_request")
+    );
+
+    // Only scopes the client was registered with
+    let mut query = authorize_query(&client_id, &code_challenge);
+    query.push(("scope", "openid admin"));
+
+    let response = app.get_authorize(&query).await;
+    let (_, params) = redirect_location(&response);
+    assert_eq!(
+        params.get("error").map(String::as_str),
+        Some("invalid_scope")
+    );
+    assert!(!params.contains_key("code"));
+
+    app.clean_up().await;
+}
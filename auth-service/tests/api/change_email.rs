@@ -0,0 +1,306 @@
+use auth_service::ErrorResponse;
+use reqwest::Url;
+
+use crate::helpers::{
+    get_login_body, get_random_email, get_token_from_email, ExtractResponse, TestApp,
+};
+
+// Request the change and return the token emailed to the new address
+async fn request_change(app: &TestApp, new_email: &str) -> String {
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": new_email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let confirmation_email = app
+        .get_sent_email(new_email, "Confirm your new email address")
+        .await;
+
+    get_token_from_email(&confirmation_email)
+}
+
+#[tokio::test]
+async fn should_change_email_once_confirmed() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    let response = app.post_login(&get_login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = response
+        .get_auth_cookie()
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let new_email = get_random_email();
+    let confirmation_token = request_change(&app, &new_email).await;
+
+    let notification = app
+        .get_sent_email(&email, "Your email address is being changed")
+        .await;
+    assert!(notification["TextBody"]
+        .as_str()
+        .expect("No text body")
+        .contains(&new_email));
+
+    // Nothing changes until the new address is confirmed
+    assert_eq!(
+        app.post_login(&get_login_body(&email))
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let body = serde_json::json!({ "token": confirmation_token });
+
+    let response = app.post_confirm_email_change(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        app.post_login(&get_login_body(&email))
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+    assert_eq!(
+        app.post_login(&get_login_body(&new_email))
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    // The token can't be used twice
+    let response = app.post_confirm_email_change(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_confirmation_token_replayed_after_changing_back() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    assert_eq!(
+        app.post_login(&get_login_body(&email))
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let new_email = get_random_email();
+    let confirmation_token = request_change(&app, &new_email).await;
+    let body = serde_json::json!({ "token": confirmation_token });
+
+    let response = app.post_confirm_email_change(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Move the account back to the first address, which makes the first token's account
+    // exist again
+    assert_eq!(
+        app.post_login(&get_login_body(&new_email))
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+    let confirmation_token = request_change(&app, &email).await;
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": confirmation_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_confirm_email_change(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        app.post_login(&get_login_body(&email))
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+    assert_eq!(
+        app.post_login(&get_login_body(&new_email))
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_409_if_new_email_taken_before_confirmation() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    assert_eq!(
+        app.post_login(&get_login_body(&email))
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let new_email = get_random_email();
+    let confirmation_token = request_change(&app, &new_email).await;
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": new_email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": confirmation_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    assert_eq!(
+        app.post_login(&get_login_body(&email))
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_password_incorrect() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    assert_eq!(
+        app.post_login(&get_login_body(&email))
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": get_random_email(),
+            "password": "wrong_password",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Incorrect credentials".to_owned()
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    assert_eq!(
+        app.post_login(&get_login_body(&email))
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    for body in [
+        serde_json::json!({ "newEmail": "invalid", "password": "password123" }),
+        serde_json::json!({ "newEmail": email, "password": "password123" }),
+        serde_json::json!({ "newEmail": get_random_email(), "password": "short" }),
+    ] {
+        let response = app.post_change_email(&body).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            body
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_confirmation_token_invalid() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": "invalid" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_auth_cookie() {
+    let mut app = TestApp::new().await;
+
+    let body = serde_json::json!({
+        "newEmail": get_random_email(),
+        "password": "password123",
+    });
+
+    let response = app.post_change_email(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cookie_jar.add_cookie_str(
+        "jwt=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_change_email(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_with_personal_access_token() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+    let token = app.create_personal_access_token(&[]).await;
+
+    // Personal access tokens don't have a session, so they can't move the account to another address
+    let response = reqwest::Client::new()
+        .post(format!("{}/change-email", &app.address))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({
+            "newEmail": get_random_email(),
+            "password": "password123",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
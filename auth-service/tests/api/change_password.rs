@@ -0,0 +1,160 @@
+use auth_service::ErrorResponse;
+use reqwest::Url;
+
+use crate::helpers::{get_login_body, ExtractResponse, TestApp};
+
+fn change_password_body(current_password: &str, new_password: &str) -> serde_json::Value {
+    serde_json::json!({
+        "currentPassword": current_password,
+        "newPassword": new_password,
+    })
+}
+
+#[tokio::test]
+async fn should_return_200_and_revoke_other_sessions() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    let other_session_token = app.log_in(&email).await.auth_token;
+    let current_session_token = app.log_in(&email).await.auth_token;
+
+    let response = app
+        .post_change_password(&change_password_body("password123", "new_password123"))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The current session continues with new tokens
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+    assert!(response.get_refresh_cookie().is_some());
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_cookie.value() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    for token in [other_session_token, current_session_token] {
+        let response = app
+            .post_verify_token(&serde_json::json!({ "token": token }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.post_login(&get_login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "new_password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_current_password_incorrect() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    app.log_in(&email).await;
+
+    let response = app
+        .post_change_password(&change_password_body("wrong_password", "new_password123"))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Incorrect credentials".to_owned()
+    );
+
+    app.log_in(&email).await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_password_invalid() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    app.log_in(&email).await;
+
+    for body in [
+        change_password_body("password123", "short"),
+        change_password_body("password123", ""),
+    ] {
+        let response = app.post_change_password(&body).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            body
+        );
+    }
+
+    app.log_in(&email).await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_auth_cookie() {
+    let mut app = TestApp::new().await;
+
+    let body = change_password_body("password123", "new_password123");
+
+    let response = app.post_change_password(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cookie_jar.add_cookie_str(
+        "jwt=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_change_password(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    app.log_in(&email).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({ "password": "new_password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_with_personal_access_token() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+    let token = app.create_personal_access_token(&[]).await;
+
+    // Personal access tokens don't have a session, so they can't change the password
+    let response = reqwest::Client::new()
+        .post(format!("{}/change-password", &app.address))
+        .bearer_auth(&token)
+        .json(&change_password_body("password123", "new_password123"))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
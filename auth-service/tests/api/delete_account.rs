@@ -0,0 +1,279 @@
+use auth_service::{
+    routes::{SignupResponse, TwoFactorAuthResponse},
+    ErrorResponse,
+};
+use reqwest::Url;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{get_random_email, ExtractResponse, TestApp, TestUser};
+
+#[tokio::test]
+async fn should_return_200_and_delete_account() {
+    let mut app = TestApp::new().await;
+
+    let TestUser {
+        email,
+        auth_token: token,
+        ..
+    } = app.sign_up_and_log_in().await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+    assert!(auth_cookie.value().is_empty());
+
+    app.get_sent_email(&email, "Your account has been deleted")
+        .await;
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The address is free again
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_accept_recovery_code_as_confirmation() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let recovery_codes = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes
+        .expect("No recovery codes returned");
+
+    app.verify_email(&email).await;
+
+    // Accept the emailed 2FA code, it is left unused
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": recovery_codes[0],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Emailed codes are only valid for logging in
+    let response = app
+        .delete_account(&serde_json::json!({ "2FACode": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Recovery codes can be used only once
+    let response = app
+        .delete_account(&serde_json::json!({ "2FACode": recovery_codes[0] }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .delete_account(&serde_json::json!({ "2FACode": recovery_codes[1] }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.get_sent_email(&email, "Your account has been deleted")
+        .await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_password_incorrect() {
+    let mut app = TestApp::new().await;
+
+    let TestUser {
+        email,
+        auth_token: token,
+        ..
+    } = app.sign_up_and_log_in().await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "wrong_password" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Incorrect credentials".to_owned()
+    );
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.log_in(&email).await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_hide_account_during_grace_period() {
+    let mut app = TestApp::with_account_deletion_grace_period(Some(3600)).await;
+
+    let TestUser {
+        email,
+        auth_token: token,
+        ..
+    } = app.sign_up_and_log_in().await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.get_sent_email(&email, "Your account has been deleted")
+        .await;
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The address stays taken until the account is purged
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_confirmation_invalid() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+
+    for body in [
+        serde_json::json!({}),
+        serde_json::json!({ "password": "short" }),
+        serde_json::json!({ "2FACode": "invalid" }),
+        serde_json::json!({ "password": "password123", "2FACode": "123456" }),
+    ] {
+        let response = app.delete_account(&body).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            body
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_auth_cookie() {
+    let mut app = TestApp::new().await;
+
+    let body = serde_json::json!({ "password": "password123" });
+
+    let response = app.delete_account(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cookie_jar.add_cookie_str(
+        "jwt=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.delete_account(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_with_personal_access_token() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up_and_log_in().await.email;
+    let token = app.create_personal_access_token(&[]).await;
+
+    // Personal access tokens don't have a session, so they can't delete the account
+    let response = reqwest::Client::new()
+        .delete(format!("{}/account", &app.address))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "password": "password123" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.log_in(&email).await;
+
+    app.clean_up().await;
+}
@@ -0,0 +1,281 @@
+use auth_service::{
+    routes::{DeviceAuthorizationResponse, IntrospectResponse, TokenResponse, VerifyTokenResponse},
+    utils::constants::test,
+    ErrorResponse, OAuthErrorResponse,
+};
+
+use crate::helpers::TestApp;
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+async fn request_device_code(app: &TestApp, client_id: &str) -> DeviceAuthorizationResponse {
+    let response = app
+        .post_device_code(&[("client_id", client_id), ("scope", "profile")], None)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<DeviceAuthorizationResponse>()
+        .await
+        .expect("Could not deserialize response body to DeviceAuthorizationResponse")
+}
+
+async fn poll(app: &TestApp, client_id: &str, device_code: &str) -> reqwest::Response {
+    app.post_token(
+        &[
+            ("grant_type", DEVICE_CODE_GRANT),
+            ("device_code", device_code),
+            ("client_id", client_id),
+        ],
+        None,
+    )
+    .await
+}
+
+async fn oauth_error(response: reqwest::Response) -> String {
+    response
+        .json::<OAuthErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthErrorResponse")
+        .error
+}
+
+#[tokio::test]
+async fn should_issue_tokens_once_device_is_approved() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_device_client().await;
+    let authorization = request_device_code(&app, &client_id).await;
+
+    let verification_uri = format!("{}/device", test::JWT_ISSUER);
+    assert_eq!(authorization.verification_uri, verification_uri);
+    assert_eq!(
+        authorization.verification_uri_complete,
+        format!("{}?user_code={}", verification_uri, authorization.user_code)
+    );
+    assert_eq!(authorization.user_code.len(), 9);
+    assert!(authorization.expires_in > 0);
+    assert!(authorization.interval > 0);
+
+    // The device has to wait for the user, and not poll too often while it does
+    let response = poll(&app, &client_id, &authorization.device_code).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "authorization_pending");
+
+    let response = poll(&app, &client_id, &authorization.device_code).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "slow_down");
+
+    // Users may type the code without the dash and in lower case
+    app.sign_up_and_log_in().await;
+    let user_code = authorization.user_code.replace('-', "").to_lowercase();
+    let response = app
+        .post_verify_device(&serde_json::json!({ "userCode": user_code, "approve": true }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = poll(&app, &client_id, &authorization.device_code).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    assert!(tokens.refresh_token.is_some());
+    assert_eq!(tokens.scope.as_deref(), Some("profile"));
+
+    let verified = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse");
+    assert!(verified.user_id.is_some());
+    assert_eq!(verified.client_id, Some(client_id.clone()));
+
+    let introspection = app
+        .post_introspect(
+            &[("token", tokens.access_token.as_str())],
+            Some((test::OAUTH_CLIENT_ID, test::OAUTH_CLIENT_SECRET)),
+        )
+        .await
+        .json::<IntrospectResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectResponse");
+    assert_eq!(introspection.sub, verified.user_id);
+
+    // The device code and the user code are used up
+    let response = poll(&app, &client_id, &authorization.device_code).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "expired_token");
+
+    let response = app
+        .post_verify_device(
+            &serde_json::json!({ "userCode": authorization.user_code, "approve": true }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_access_denied_if_user_denies_device() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_device_client().await;
+    let authorization = request_device_code(&app, &client_id).await;
+
+    app.sign_up_and_log_in().await;
+    let response = app
+        .post_verify_device(
+            &serde_json::json!({ "userCode": authorization.user_code, "approve": false }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = poll(&app, &client_id, &authorization.device_code).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "access_denied");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_device_code_was_issued_to_another_client() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_device_client().await;
+    let (other_client_id, _) = app.register_device_client().await;
+    let authorization = request_device_code(&app, &client_id).await;
+
+    let test_cases = [
+        (other_client_id.as_str(), authorization.device_code.as_str()),
+        (client_id.as_str(), "not-a-device-code"),
+    ];
+
+    for (client_id, device_code) in test_cases {
+        let response = poll(&app, client_id, device_code).await;
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(oauth_error(response).await, "invalid_grant");
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_keep_approved_device_code_usable_after_poll_of_another_client() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_device_client().await;
+    let (other_client_id, _) = app.register_device_client().await;
+    let authorization = request_device_code(&app, &client_id).await;
+
+    app.sign_up_and_log_in().await;
+    let response = app
+        .post_verify_device(
+            &serde_json::json!({ "userCode": authorization.user_code, "approve": true }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = poll(&app, &other_client_id, &authorization.device_code).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "invalid_grant");
+
+    let response = poll(&app, &client_id, &authorization.device_code).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_client_credentials() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(true).await;
+
+    let test_cases = [
+        (vec![("client_id", "unknown-client")], None),
+        (vec![], None),
+        (vec![], Some((client_id.as_str(), "wrong-secret"))),
+    ];
+
+    for (body, client_credentials) in test_cases {
+        let response = app.post_device_code(&body, client_credentials).await;
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(oauth_error(response).await, "invalid_client");
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_scope_not_registered_for_client() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_device_client().await;
+
+    let response = app
+        .post_device_code(
+            &[
+                ("client_id", client_id.as_str()),
+                ("scope", "profile admin"),
+            ],
+            None,
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "invalid_scope");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_user_code() {
+    let mut app = TestApp::new().await;
+
+    // Only logged in users can approve devices
+    let response = app
+        .post_verify_device(&serde_json::json!({ "userCode": "WDJB-MJHT", "approve": true }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token"
+    );
+
+    app.sign_up_and_log_in().await;
+
+    for user_code in ["WDJB-MJHT", "not a code", ""] {
+        let response = app
+            .post_verify_device(&serde_json::json!({ "userCode": user_code, "approve": true }))
+            .await;
+        assert_eq!(response.status().as_u16(), 400, "User code: {}", user_code);
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid or expired code"
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_serve_device_page() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_device().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("content-type").unwrap(), "text/html");
+
+    app.clean_up().await;
+}
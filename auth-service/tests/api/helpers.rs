@@ -1,21 +1,45 @@
-use reqwest::{cookie::Jar, Client};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use reqwest::{cookie::Jar, redirect::Policy, Client};
+use ring::digest;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::{
     postgres::{PgConnectOptions, PgConnection, PgPoolOptions},
     Connection, Executor, PgPool,
 };
 use std::{str::FromStr, sync::Arc};
-use tokio::sync::RwLock;
+use tokio::sync::{OnceCell, RwLock};
 use wiremock::MockServer;
 
 use auth_service::{
-    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType},
-    domain::Email,
+    app_state::{
+        AppState, BannedTokenStoreType, KeyringType, RefreshTokenStoreType, RoleStoreType,
+        SigningKeyStoreType, TwoFACodeStoreType,
+    },
+    domain::{
+        ClientCredentials, Email, JwtConfig, Keyring, SecretCipher, SigningKey, TotpSecretCipher,
+        UserId,
+    },
     get_postgres_pool, get_redis_client,
+    routes::{
+        CreatePersonalAccessTokenResponse, RegisterOAuthClientResponse, TokenResponse,
+        VerifyTokenResponse,
+    },
     services::{
-        PostgresUserStore, PostmarkEmailClient, RedisBannedTokenStore, RedisTwoFACodeStore,
+        HashmapLoginAttemptStore, PostgresOAuthClientStore, PostgresPersonalAccessTokenStore,
+        PostgresRoleStore, PostgresSessionStore, PostgresSigningKeyStore, PostgresUserStore,
+        PostmarkEmailClient, RedisAuthorizationCodeStore, RedisBannedTokenStore,
+        RedisDeviceCodeStore, RedisPasswordResetTokenStore, RedisRefreshTokenStore,
+        RedisTwoFACodeStore,
+    },
+    utils::{
+        auth::TOKEN_TTL_SECONDS,
+        authenticated_user::AuthTokenPrecedence,
+        constants::{
+            test, DATABASE_URL, DEFAULT_JWT_LEEWAY_SECONDS, JWT_COOKIE_NAME, REDIS_HOST_NAME,
+            REFRESH_COOKIE_NAME,
+        },
     },
-    utils::constants::{test, DATABASE_URL, JWT_COOKIE_NAME, REDIS_HOST_NAME},
     Application,
 };
 use uuid::Uuid;
@@ -25,8 +49,13 @@ pub struct TestApp {
     pub cookie_jar: Arc<Jar>,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub keyring: KeyringType,
+    pub signing_key_store: SigningKeyStoreType,
+    pub role_store: RoleStoreType,
     pub http_client: reqwest::Client,
     pub email_server: MockServer,
+    admin_token: OnceCell<String>,
     pub db_name: String,
     pub clean_up_called: bool,
 }
@@ -41,27 +70,99 @@ impl Drop for TestApp {
 
 impl TestApp {
     pub async fn new() -> Self {
+        Self::with_account_deletion_grace_period(None).await
+    }
+
+    // Deleted accounts are kept for the grace period instead of being deleted right away
+    pub async fn with_account_deletion_grace_period(
+        account_deletion_grace_period_seconds: Option<u64>,
+    ) -> Self {
         // We are creating a new database for each test case, and we need to ensure each database has a unique name!
         let db_name = Uuid::new_v4().to_string();
         let pg_pool = configure_postgresql(db_name.clone()).await;
 
-        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+        let totp_cipher = TotpSecretCipher::new(&Secret::new(test::TOTP_ENCRYPTION_KEY.to_owned()))
+            .expect("Failed to create TOTP secret cipher");
+        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(
+            pg_pool.clone(),
+            totp_cipher,
+        )));
+        let session_store = Arc::new(RwLock::new(PostgresSessionStore::new(pg_pool.clone())));
+        let oauth_client_store =
+            Arc::new(RwLock::new(PostgresOAuthClientStore::new(pg_pool.clone())));
+        let personal_access_token_store = Arc::new(RwLock::new(
+            PostgresPersonalAccessTokenStore::new(pg_pool.clone()),
+        ));
+        let role_store = Arc::new(RwLock::new(PostgresRoleStore::new(pg_pool.clone())));
+        let signing_key_cipher =
+            SecretCipher::new(&Secret::new(test::SIGNING_KEY_ENCRYPTION_KEY.to_owned()))
+                .expect("Failed to create signing key cipher");
+        let signing_key_store: SigningKeyStoreType = Arc::new(RwLock::new(
+            PostgresSigningKeyStore::new(pg_pool, signing_key_cipher),
+        ));
 
         let redis_conn = Arc::new(RwLock::new(configure_redis()));
 
         let banned_token_store =
             Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_conn.clone())));
         let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn.clone())));
+        let refresh_token_store =
+            Arc::new(RwLock::new(RedisRefreshTokenStore::new(redis_conn.clone())));
+        let password_reset_token_store = Arc::new(RwLock::new(RedisPasswordResetTokenStore::new(
+            redis_conn.clone(),
+        )));
+        let authorization_code_store = Arc::new(RwLock::new(RedisAuthorizationCodeStore::new(
+            redis_conn.clone(),
+        )));
+        let device_code_store = Arc::new(RwLock::new(RedisDeviceCodeStore::new(redis_conn)));
+        // Every test logs in from the same address, so each app gets its own limits
+        let login_attempt_store = Arc::new(RwLock::new(HashmapLoginAttemptStore::default()));
 
         let email_server = MockServer::start().await;
         let base_url = email_server.uri();
         let email_client = Arc::new(configure_postmark_email_client(base_url));
 
+        let signing_key = SigningKey::generate_ed25519().expect("Failed to generate key");
+        signing_key_store
+            .write()
+            .await
+            .rotate(signing_key.clone(), Utc::now())
+            .await
+            .expect("Failed to store signing key");
+        let keyring = Arc::new(RwLock::new(
+            Keyring::new(signing_key, TOKEN_TTL_SECONDS).expect("Failed to create keyring"),
+        ));
+
         let app_state = AppState::new(
             user_store,
             banned_token_store.clone(),
             two_fa_code_store.clone(),
             email_client.clone(),
+            refresh_token_store.clone(),
+            password_reset_token_store,
+            login_attempt_store,
+            session_store,
+            keyring.clone(),
+            signing_key_store.clone(),
+            account_deletion_grace_period_seconds,
+            JwtConfig::new(
+                test::JWT_ISSUER.to_owned(),
+                vec![test::JWT_AUDIENCE.to_owned()],
+                DEFAULT_JWT_LEEWAY_SECONDS,
+                false,
+            )
+            .expect("Failed to create JWT config"),
+            vec![ClientCredentials {
+                client_id: test::OAUTH_CLIENT_ID.to_owned(),
+                client_secret: Secret::new(test::OAUTH_CLIENT_SECRET.to_owned()),
+            }],
+            oauth_client_store,
+            authorization_code_store,
+            device_code_store,
+            personal_access_token_store,
+            AuthTokenPrecedence::default(),
+            role_store.clone(),
+            vec![],
         );
 
         // port 0: find a random port for the auth service
@@ -88,8 +189,13 @@ impl TestApp {
             cookie_jar,
             banned_token_store,
             two_fa_code_store,
+            refresh_token_store,
+            keyring,
+            signing_key_store,
+            role_store,
             http_client,
             email_server,
+            admin_token: OnceCell::new(),
             db_name,
             clean_up_called: false,
         }
@@ -100,9 +206,447 @@ impl TestApp {
         self.clean_up_called = true;
     }
 
+    // Some emails are sent in the background, so wait for the latest one with the subject to arrive
+    pub async fn get_sent_email(&self, recipient: &str, subject: &str) -> serde_json::Value {
+        for _ in 0..100 {
+            let requests = self
+                .email_server
+                .received_requests()
+                .await
+                .expect("Request recording is disabled");
+
+            let email = requests
+                .iter()
+                .rev()
+                .map(|request| {
+                    serde_json::from_slice::<serde_json::Value>(&request.body)
+                        .expect("Email body is not JSON")
+                })
+                .find(|email| email["To"] == recipient && email["Subject"] == subject);
+
+            if let Some(email) = email {
+                return email;
+            }
+
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        panic!("No email \"{}\" sent to {}", subject, recipient);
+    }
+
+    // Verify the address of a new user with the token from the email sent on signup
+    pub async fn verify_email(&self, email: &str) {
+        let verification_email = self
+            .get_sent_email(email, "Verify your email address")
+            .await;
+
+        let body = serde_json::json!({
+            "token": get_token_from_email(&verification_email),
+        });
+
+        let response = self.post_verify_email(&body).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Sign up a new user without 2FA, returning their email address. It is left unverified, so
+    // they can't log in yet.
+    pub async fn sign_up_unverified(&self) -> String {
+        let random_email = get_random_email();
+
+        let signup_body = serde_json::json!({
+            "email": random_email,
+            "password": TEST_PASSWORD,
+            "requires2FA": false
+        });
+
+        let response = self.post_signup(&signup_body).await;
+        assert_eq!(response.status().as_u16(), 201);
+
+        random_email
+    }
+
+    // Sign up a new user without 2FA and verify their email address, returning it
+    pub async fn sign_up(&self) -> String {
+        let email = self.sign_up_unverified().await;
+
+        self.verify_email(&email).await;
+
+        email
+    }
+
+    // Log the user in, which leaves the auth and refresh cookies of the new session in the
+    // cookie jar of the app
+    pub async fn log_in(&self, email: &str) -> TestUser {
+        let response = self.post_login(&get_login_body(email)).await;
+
+        TestUser::logged_in(email, response)
+    }
+
+    // Log the user in from a device with the given user agent
+    pub async fn log_in_from(&self, email: &str, user_agent: &str) -> TestUser {
+        let response = self
+            .http_client
+            .post(format!("{}/login", &self.address))
+            .header("User-Agent", user_agent)
+            .json(&get_login_body(email))
+            .send()
+            .await
+            .expect("Failed to execute request.");
+
+        TestUser::logged_in(email, response)
+    }
+
+    pub async fn sign_up_and_log_in(&self) -> TestUser {
+        let email = self.sign_up().await;
+
+        self.log_in(&email).await
+    }
+
+    // Give the user the admin role the migrations create, like the first admin is given it by hand
+    pub async fn assign_admin_role(&self, user_id: &str) {
+        let user_id = UserId::parse(user_id.to_owned()).expect("Invalid user ID");
+        self.role_store
+            .write()
+            .await
+            .assign_role(&user_id, "admin")
+            .await
+            .expect("Failed to assign admin role");
+    }
+
+    // JWT auth token of an admin, who is logged in outside the cookie jar of the app so tests
+    // keep their own session in it
+    pub async fn get_admin_token(&self) -> String {
+        self.admin_token
+            .get_or_init(|| async {
+                let email = self.sign_up().await;
+                let client = Client::new();
+                let log_in = || async {
+                    let response = client
+                        .post(format!("{}/login", &self.address))
+                        .json(&get_login_body(&email))
+                        .send()
+                        .await
+                        .expect("Failed to execute request.");
+
+                    TestUser::logged_in(&email, response).auth_token
+                };
+
+                let token = log_in().await;
+                let response = self
+                    .post_verify_token(&serde_json::json!({ "token": token }))
+                    .await;
+                assert_eq!(response.status().as_u16(), 200);
+                let user_id = response
+                    .json::<VerifyTokenResponse>()
+                    .await
+                    .expect("Could not deserialize response body to VerifyTokenResponse")
+                    .user_id
+                    .expect("No user ID found");
+
+                // Permissions are added to the JWTs issued after the role is assigned
+                self.assign_admin_role(&user_id).await;
+
+                log_in().await
+            })
+            .await
+            .clone()
+    }
+
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_openid_configuration(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/.well-known/openid-configuration",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_userinfo(&self, access_token: Option<&str>) -> reqwest::Response {
+        let mut request = self.http_client.get(format!("{}/userinfo", &self.address));
+
+        if let Some(access_token) = access_token {
+            request = request.bearer_auth(access_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_rotate_signing_key<Body>(
+        &self,
+        body: &Body,
+        auth_token: Option<&str>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/rotate-signing-key", &self.address))
+            .json(body);
+
+        if let Some(auth_token) = auth_token {
+            request = request.bearer_auth(auth_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_unlock_account<Body>(
+        &self,
+        body: &Body,
+        auth_token: Option<&str>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/unlock-account", &self.address))
+            .json(body);
+
+        if let Some(auth_token) = auth_token {
+            request = request.bearer_auth(auth_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_register_oauth_client<Body>(
+        &self,
+        body: &Body,
+        auth_token: Option<&str>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/clients", &self.address))
+            .json(body);
+
+        if let Some(auth_token) = auth_token {
+            request = request.bearer_auth(auth_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn put_admin_role<Body>(&self, name: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .put(format!("{}/admin/roles/{}", &self.address, name))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn put_admin_user_role(&self, user_id: &str, role: &str) -> reqwest::Response {
+        self.http_client
+            .put(format!(
+                "{}/admin/users/{}/roles/{}",
+                &self.address, user_id, role
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_admin_user_role(&self, user_id: &str, role: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!(
+                "{}/admin/users/{}/roles/{}",
+                &self.address, user_id, role
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Register a client redirecting to OAUTH_REDIRECT_URI, returning its id and secret
+    pub async fn register_oauth_client(&self, confidential: bool) -> (String, Option<String>) {
+        let body = serde_json::json!({
+            "name": "Test app",
+            "redirectUris": [OAUTH_REDIRECT_URI],
+            "scopes": OAUTH_CLIENT_SCOPES,
+            "confidential": confidential,
+        });
+
+        let response = self
+            .post_register_oauth_client(&body, Some(&self.get_admin_token().await))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+
+        let response = response
+            .json::<RegisterOAuthClientResponse>()
+            .await
+            .expect("Could not deserialize response body to RegisterOAuthClientResponse");
+
+        (response.client_id, response.client_secret)
+    }
+
+    // Register a public client without redirect URIs, like a command-line tool that logs its
+    // users in with the device authorization grant
+    pub async fn register_device_client(&self) -> (String, Option<String>) {
+        let body = serde_json::json!({
+            "name": "Test CLI",
+            "scopes": OAUTH_CLIENT_SCOPES,
+            "confidential": false,
+        });
+
+        let response = self
+            .post_register_oauth_client(&body, Some(&self.get_admin_token().await))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+
+        let response = response
+            .json::<RegisterOAuthClientResponse>()
+            .await
+            .expect("Could not deserialize response body to RegisterOAuthClientResponse");
+
+        (response.client_id, response.client_secret)
+    }
+
+    // Register a confidential client that gets tokens of its own with the given scopes,
+    // returning its id and secret
+    pub async fn register_machine_client(&self, scopes: &[&str]) -> (String, String) {
+        let body = serde_json::json!({
+            "name": "Test job",
+            "scopes": scopes,
+            "confidential": true,
+        });
+
+        let response = self
+            .post_register_oauth_client(&body, Some(&self.get_admin_token().await))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+
+        let response = response
+            .json::<RegisterOAuthClientResponse>()
+            .await
+            .expect("Could not deserialize response body to RegisterOAuthClientResponse");
+
+        (
+            response.client_id,
+            response.client_secret.expect("No client secret found"),
+        )
+    }
+
+    // Register a machine client and get it a token of its own, returning the id and secret of
+    // the client along with the token
+    pub async fn get_machine_client_token(&self) -> (String, String, String) {
+        let (client_id, client_secret) = self.register_machine_client(&["reports:read"]).await;
+
+        let response = self
+            .post_token(
+                &[("grant_type", "client_credentials")],
+                Some((&client_id, &client_secret)),
+            )
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        let tokens = response
+            .json::<TokenResponse>()
+            .await
+            .expect("Could not deserialize response body to TokenResponse");
+
+        (client_id, client_secret, tokens.access_token)
+    }
+
+    // Redirects are not followed, so tests can check where they lead
+    pub async fn get_authorize<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        reqwest::Client::builder()
+            .cookie_provider(self.cookie_jar.clone())
+            .redirect(Policy::none())
+            .build()
+            .unwrap()
+            .get(format!("{}/authorize", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Form encoded like RFC 6749 asks for, authenticated with the given client id and secret
+    pub async fn post_token<Body>(
+        &self,
+        body: &Body,
+        client_credentials: Option<(&str, &str)>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/token", &self.address))
+            .form(body);
+
+        if let Some((client_id, client_secret)) = client_credentials {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_device(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/device", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Form encoded like /token, authenticated with the given client id and secret
+    pub async fn post_device_code<Body>(
+        &self,
+        body: &Body,
+        client_credentials: Option<(&str, &str)>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/device/code", &self.address))
+            .form(body);
+
+        if let Some((client_id, client_secret)) = client_credentials {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_device<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/device/verify", &self.address))
+            .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -113,7 +657,31 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_verification_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email/resend", &self.address))
             .json(body)
             .send()
             .await
@@ -125,7 +693,19 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_unlock_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/unlock-account", &self.address))
             .json(body)
             .send()
             .await
@@ -134,7 +714,23 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .post(format!("{}/logout", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout-all", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -145,7 +741,176 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/verify-2fa", &self.address))
+            .post(format!("{}/verify-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_enroll_totp(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/totp/enroll", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_totp<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/2fa/recovery-codes", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/recovery-codes", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_email_change<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-email/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_personal_access_tokens(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/personal-access-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_personal_access_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/personal-access-tokens", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Create a personal access token for the logged in user, returning the token
+    pub async fn create_personal_access_token(&self, scopes: &[&str]) -> String {
+        let response = self
+            .post_personal_access_token(&serde_json::json!({
+                "name": "CI",
+                "scopes": scopes,
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+
+        response
+            .json::<CreatePersonalAccessTokenResponse>()
+            .await
+            .expect("Could not deserialize response body to CreatePersonalAccessTokenResponse")
+            .token
+    }
+
+    pub async fn delete_personal_access_token(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/personal-access-tokens/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .delete(format!("{}/account", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/request", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
             .json(body)
             .send()
             .await
@@ -157,29 +922,147 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/verify-token", &self.address))
+            .post(format!("{}/verify-token", &self.address))
             .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
     }
+
+    // The token is sent as `Authorization: Bearer` rather than in the body
+    pub async fn post_verify_token_with_bearer(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/verify-token", &self.address))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Form encoded like RFC 7009 asks for, authenticated with the given client id and secret
+    pub async fn post_revoke<Body>(
+        &self,
+        body: &Body,
+        client_credentials: Option<(&str, &str)>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/revoke", &self.address))
+            .form(body);
+
+        if let Some((client_id, client_secret)) = client_credentials {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    // Form encoded like RFC 7662 asks for, authenticated with the given client id and secret
+    pub async fn post_introspect<Body>(
+        &self,
+        body: &Body,
+        client_credentials: Option<(&str, &str)>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/introspect", &self.address))
+            .form(body);
+
+        if let Some((client_id, client_secret)) = client_credentials {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+}
+
+// A user who signed up without 2FA, and the tokens of the session they logged in to
+pub struct TestUser {
+    pub email: String,
+    pub auth_token: String,
+    pub refresh_token: String,
+}
+
+impl TestUser {
+    fn logged_in(email: &str, response: reqwest::Response) -> Self {
+        assert_eq!(response.status().as_u16(), 200);
+
+        let auth_token = response
+            .get_auth_cookie()
+            .expect("No auth cookie found")
+            .value()
+            .to_owned();
+        let refresh_token = response
+            .get_refresh_cookie()
+            .expect("No refresh cookie found")
+            .value()
+            .to_owned();
+
+        Self {
+            email: email.to_owned(),
+            auth_token,
+            refresh_token,
+        }
+    }
+}
+
+// Password every test user signs up with
+pub const TEST_PASSWORD: &str = "password123";
+
+pub const OAUTH_REDIRECT_URI: &str = "https://app.example.com/callback";
+// Scopes the clients acting for their users are registered with
+pub const OAUTH_CLIENT_SCOPES: [&str; 3] = ["openid", "email", "profile"];
+
+// A random PKCE code verifier and its S256 code challenge
+pub fn get_pkce_pair() -> (String, String) {
+    let code_verifier = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let digest = digest::digest(&digest::SHA256, code_verifier.as_bytes());
+
+    (code_verifier, URL_SAFE_NO_PAD.encode(digest.as_ref()))
+}
+
+pub fn get_login_body(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "password": TEST_PASSWORD,
+    })
 }
 
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
 
+// Tokens are sent as the last word of the email text
+pub fn get_token_from_email(email: &serde_json::Value) -> String {
+    email["TextBody"]
+        .as_str()
+        .and_then(|text| text.split_whitespace().last())
+        .expect("No token found in email")
+        .to_owned()
+}
+
 pub trait ExtractResponse {
-    fn get_auth_cookie(&self) -> Option<reqwest::cookie::Cookie>;
-    fn find_cookie_by_name(&self, name: &str) -> Option<reqwest::cookie::Cookie>;
+    fn get_auth_cookie(&self) -> Option<reqwest::cookie::Cookie<'_>>;
+    fn get_refresh_cookie(&self) -> Option<reqwest::cookie::Cookie<'_>>;
+    fn find_cookie_by_name(&self, name: &str) -> Option<reqwest::cookie::Cookie<'_>>;
 }
 
 impl ExtractResponse for reqwest::Response {
-    fn get_auth_cookie(&self) -> Option<reqwest::cookie::Cookie> {
+    fn get_auth_cookie(&self) -> Option<reqwest::cookie::Cookie<'_>> {
         self.find_cookie_by_name(JWT_COOKIE_NAME)
     }
 
-    fn find_cookie_by_name(&self, name: &str) -> Option<reqwest::cookie::Cookie> {
+    fn get_refresh_cookie(&self) -> Option<reqwest::cookie::Cookie<'_>> {
+        self.find_cookie_by_name(REFRESH_COOKIE_NAME)
+    }
+
+    fn find_cookie_by_name(&self, name: &str) -> Option<reqwest::cookie::Cookie<'_>> {
         self.cookies().find(|cookie| cookie.name() == name)
     }
 }
@@ -0,0 +1,270 @@
+use auth_service::{
+    routes::{CreatePersonalAccessTokenResponse, IntrospectResponse, VerifyTokenResponse},
+    utils::constants::test,
+    ErrorResponse,
+};
+use reqwest::header::WWW_AUTHENTICATE;
+
+use crate::helpers::TestApp;
+
+const CLIENT: Option<(&str, &str)> = Some((test::OAUTH_CLIENT_ID, test::OAUTH_CLIENT_SECRET));
+
+#[tokio::test]
+async fn should_return_claims_of_active_token() {
+    let mut app = TestApp::new().await;
+
+    let token = app.sign_up_and_log_in().await.auth_token;
+
+    let response = app
+        .post_introspect(&[("token", token.as_str())], CLIENT)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let introspection = response
+        .json::<IntrospectResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectResponse");
+
+    let user_id = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse")
+        .user_id;
+
+    assert!(introspection.active);
+    assert_eq!(introspection.sub, user_id);
+    assert_eq!(introspection.iss.as_deref(), Some(test::JWT_ISSUER));
+    assert_eq!(introspection.aud, Some(vec![test::JWT_AUDIENCE.to_owned()]));
+    assert!(introspection.exp.unwrap() > introspection.iat.unwrap());
+    assert!(introspection.jti.is_some());
+
+    // The token type hint is optional, and doesn't change the answer
+    let response = app
+        .post_introspect(
+            &[
+                ("token", token.as_str()),
+                ("token_type_hint", "access_token"),
+            ],
+            CLIENT,
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(
+        response
+            .json::<IntrospectResponse>()
+            .await
+            .expect("Could not deserialize response body to IntrospectResponse")
+            .active
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_details_of_personal_access_token() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+
+    let response = app
+        .post_personal_access_token(&serde_json::json!({
+            "name": "CI",
+            "scopes": ["reports:read"],
+            "expiresInDays": 1,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let created = response
+        .json::<CreatePersonalAccessTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to CreatePersonalAccessTokenResponse");
+
+    let response = app
+        .post_introspect(&[("token", created.token.as_str())], CLIENT)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let introspection = response
+        .json::<IntrospectResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectResponse");
+
+    assert!(introspection.active);
+    assert!(introspection.sub.is_some());
+    assert_eq!(introspection.scope.as_deref(), Some("reports:read"));
+    assert_eq!(introspection.iat, Some(created.details.created_at as usize));
+    assert_eq!(
+        introspection.exp,
+        created.details.expires_at.map(|exp| exp as usize)
+    );
+    assert_eq!(introspection.client_id, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_inactive_for_invalid_tokens() {
+    let mut app = TestApp::new().await;
+
+    for token in ["invalid_token", ""] {
+        let response = app.post_introspect(&[("token", token)], CLIENT).await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        // Nothing but `active` is given away about inactive tokens
+        assert_eq!(
+            response
+                .json::<serde_json::Value>()
+                .await
+                .expect("Could not deserialize response body to JSON"),
+            serde_json::json!({ "active": false })
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_inactive_for_revoked_token() {
+    let mut app = TestApp::new().await;
+
+    let token = app.sign_up_and_log_in().await.auth_token;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_introspect(&[("token", token.as_str())], CLIENT)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<serde_json::Value>()
+            .await
+            .expect("Could not deserialize response body to JSON"),
+        serde_json::json!({ "active": false })
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_only_show_registered_clients_their_own_tokens() {
+    let mut app = TestApp::new().await;
+
+    let auth_token = app.sign_up_and_log_in().await.auth_token;
+    let (client_id, client_secret, client_token) = app.get_machine_client_token().await;
+    let (other_client_id, other_client_secret, _) = app.get_machine_client_token().await;
+
+    let test_cases = [
+        (
+            client_token.as_str(),
+            (client_id.as_str(), client_secret.as_str()),
+            true,
+        ),
+        (
+            auth_token.as_str(),
+            (client_id.as_str(), client_secret.as_str()),
+            false,
+        ),
+        (
+            client_token.as_str(),
+            (other_client_id.as_str(), other_client_secret.as_str()),
+            false,
+        ),
+        // Our services see the tokens of every client
+        (
+            client_token.as_str(),
+            (test::OAUTH_CLIENT_ID, test::OAUTH_CLIENT_SECRET),
+            true,
+        ),
+    ];
+
+    for (token, client_credentials, active) in test_cases {
+        let response = app
+            .post_introspect(&[("token", token)], Some(client_credentials))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        let introspection = response
+            .json::<IntrospectResponse>()
+            .await
+            .expect("Could not deserialize response body to IntrospectResponse");
+        assert_eq!(
+            introspection.active, active,
+            "Client credentials: {:?}",
+            client_credentials
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_without_valid_client_credentials() {
+    let mut app = TestApp::new().await;
+
+    let token = app.sign_up_and_log_in().await.auth_token;
+    let (client_id, _) = app.register_oauth_client(true).await;
+    // Public clients have no secret to authenticate with
+    let (public_client_id, _) = app.register_oauth_client(false).await;
+
+    let test_cases = [
+        None,
+        Some((test::OAUTH_CLIENT_ID, "wrong-secret")),
+        Some(("unknown-client", test::OAUTH_CLIENT_SECRET)),
+        Some((client_id.as_str(), "wrong-secret")),
+        Some((public_client_id.as_str(), "")),
+    ];
+
+    for client_credentials in test_cases {
+        let response = app
+            .post_introspect(&[("token", token.as_str())], client_credentials)
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Client credentials: {:?}",
+            client_credentials
+        );
+        assert_eq!(
+            response
+                .headers()
+                .get(WWW_AUTHENTICATE)
+                .and_then(|value| value.to_str().ok()),
+            Some("Basic")
+        );
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid client credentials".to_owned(),
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_request() {
+    let mut app = TestApp::new().await;
+
+    let test_cases: [&[(&str, &str)]; 2] = [&[("tok", "token")], &[]];
+
+    for test_case in test_cases {
+        let response = app.post_introspect(&test_case, CLIENT).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Malformed request: {:?}",
+            test_case
+        );
+    }
+
+    app.clean_up().await;
+}
@@ -0,0 +1,165 @@
+use auth_service::{
+    domain::SigningKey,
+    routes::VerifyTokenResponse,
+    utils::{auth::Claims, constants::test},
+};
+use chrono::Utc;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+
+use crate::helpers::{get_random_email, ExtractResponse, TestApp};
+
+#[tokio::test]
+async fn should_return_public_key_that_verifies_issued_tokens() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+    let token = auth_cookie.value().to_owned();
+
+    let response = app.get_jwks().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let jwks = response
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    let header = decode_header(&token).expect("Failed to decode token header");
+    assert_eq!(header.alg, Algorithm::EdDSA);
+
+    let kid = header.kid.expect("Token header has no kid");
+    let jwk = jwks.find(&kid).expect("Signing key is not published");
+
+    let decoding_key = DecodingKey::from_jwk(jwk).expect("Failed to build decoding key");
+    let mut validation = Validation::new(Algorithm::EdDSA);
+    validation.set_issuer(&[test::JWT_ISSUER]);
+    validation.set_audience(&[test::JWT_AUDIENCE]);
+    let claims = decode::<Claims>(&token, &decoding_key, &validation)
+        .expect("Failed to verify token with the published key")
+        .claims;
+
+    // Tokens identify the user by id and leave out the email address unless configured
+    let user_id = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse")
+        .user_id
+        .expect("No user id found");
+    assert_eq!(claims.sub, user_id);
+    assert_ne!(claims.sub, random_email);
+    assert_eq!(claims.email, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_publish_private_key_material() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_jwks().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<serde_json::Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    let keys = body["keys"].as_array().expect("keys should be an array");
+    assert_eq!(keys.len(), 1);
+
+    for key in keys {
+        assert_eq!(key["kty"], "OKP");
+        assert_eq!(key["crv"], "Ed25519");
+        assert!(key.get("d").is_none());
+        assert!(key.get("k").is_none());
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_publish_previous_key_and_accept_its_tokens_after_rotation() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let old_token = response
+        .get_auth_cookie()
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    // What a deployment with a new JWT_SIGNING_KEY and the old one as JWT_PREVIOUS_SIGNING_KEY
+    // starts with
+    let old_kid = app.keyring.read().await.active().kid().to_owned();
+    let new_key = SigningKey::generate_ed25519().expect("Failed to generate key");
+    let new_kid = new_key.kid().to_owned();
+    app.keyring
+        .write()
+        .await
+        .rotate(new_key, Utc::now())
+        .expect("Failed to rotate keyring");
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_token = response.get_auth_cookie().expect("No auth cookie found");
+    let header = decode_header(new_token.value()).expect("Failed to decode token header");
+    assert_eq!(header.kid, Some(new_kid.clone()));
+
+    let jwks = app
+        .get_jwks()
+        .await
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    assert!(jwks.find(&old_kid).is_some());
+    assert!(jwks.find(&new_kid).is_some());
+
+    app.clean_up().await;
+}
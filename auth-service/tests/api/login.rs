@@ -1,5 +1,10 @@
 use crate::helpers::{get_random_email, ExtractResponse, TestApp};
-use auth_service::{domain::Email, routes::TwoFactorAuthResponse, ErrorResponse};
+use auth_service::{
+    domain::{Email, ACCOUNT_LOCKOUT_SECONDS, MAX_FAILED_LOGINS, MAX_LOGIN_ATTEMPTS_PER_ACCOUNT},
+    routes::TwoFactorAuthResponse,
+    ErrorResponse,
+};
+use reqwest::header::RETRY_AFTER;
 use secrecy::{ExposeSecret, Secret};
 use wiremock::{
     matchers::{method, path},
@@ -22,6 +27,8 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
@@ -35,6 +42,12 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     assert!(!auth_cookie.value().is_empty());
 
+    let refresh_cookie = response
+        .get_refresh_cookie()
+        .expect("No refresh cookie found");
+
+    assert!(!refresh_cookie.value().is_empty());
+
     app.clean_up().await;
 }
 
@@ -54,6 +67,8 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     // Define an expectation for the mock server
     Mock::given(path("/email")) // Expect an HTTP request to the "/email" path
         .and(method("POST")) // Expect the HTTP method to be POST
@@ -188,6 +203,147 @@ async fn should_return_401_if_incorrect_credentials() {
     app.clean_up().await;
 }
 
+#[tokio::test]
+async fn should_return_429_if_account_locked_after_failed_logins() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let wrong_login_body = serde_json::json!({
+        "email": random_email,
+        "password": "wrong_password",
+    });
+
+    for _ in 0..MAX_FAILED_LOGINS {
+        let response = app.post_login(&wrong_login_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // Even the correct password is refused while the account is locked
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: u64 = response
+        .headers()
+        .get(RETRY_AFTER)
+        .expect("No Retry-After header found")
+        .to_str()
+        .expect("Retry-After header is not text")
+        .parse()
+        .expect("Retry-After header is not a number of seconds");
+    assert!(retry_after > 0 && retry_after <= ACCOUNT_LOCKOUT_SECONDS);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many login attempts, please try again later".to_owned()
+    );
+
+    app.get_sent_email(&random_email, "Your account has been locked")
+        .await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_lock_account_after_successful_login() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let wrong_login_body = serde_json::json!({
+        "email": random_email,
+        "password": "wrong_password",
+    });
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    // Only failures in a row lock the account
+    for _ in 0..2 {
+        for _ in 1..MAX_FAILED_LOGINS {
+            let response = app.post_login(&wrong_login_body).await;
+            assert_eq!(response.status().as_u16(), 401);
+        }
+
+        let response = app.post_login(&login_body).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_if_too_many_attempts_for_account() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    for _ in 0..MAX_LOGIN_ATTEMPTS_PER_ACCOUNT {
+        let response = app.post_login(&login_body).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().get(RETRY_AFTER).is_some());
+
+    // Other accounts are not affected
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
 #[tokio::test]
 async fn should_return_422_if_malformed_request() {
     let mut app = TestApp::new().await;
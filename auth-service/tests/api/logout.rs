@@ -1,4 +1,7 @@
-use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use auth_service::{
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    ErrorResponse,
+};
 use reqwest::Url;
 use secrecy::Secret;
 
@@ -20,6 +23,8 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
@@ -56,6 +61,115 @@ async fn should_return_200_if_valid_jwt_cookie() {
     app.clean_up().await;
 }
 
+#[tokio::test]
+async fn should_return_200_if_valid_bearer_token() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+    let token = auth_cookie.value().to_owned();
+
+    // Mobile and server clients send the token without any cookies
+    let response = reqwest::Client::new()
+        .post(format!("{}/logout", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let contains_token = app
+        .banned_token_store
+        .write()
+        .await
+        .contains_token(&Secret::new(token))
+        .await
+        .expect("Failed to check if token is banned");
+
+    assert!(contains_token, "Token should be banned after logout");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_refresh_token_on_logout() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let refresh_cookie = response
+        .get_refresh_cookie()
+        .expect("No refresh cookie found");
+
+    let refresh_token = refresh_cookie.value().to_owned();
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let refresh_cookie = response
+        .get_refresh_cookie()
+        .expect("No refresh cookie found");
+
+    assert!(refresh_cookie.value().is_empty());
+
+    let cookie_str = &format!(
+        "{}={}; HttpOnly; SameSite=Strict; Path=/",
+        REFRESH_COOKIE_NAME, refresh_token
+    );
+    app.cookie_jar.add_cookie_str(
+        cookie_str,
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
 #[tokio::test]
 async fn should_return_400_if_jwt_cookie_missing() {
     let mut app = TestApp::new().await;
@@ -100,6 +214,8 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
@@ -145,7 +261,7 @@ async fn should_return_401_if_invalid_token() {
         JWT_COOKIE_NAME
     );
     app.cookie_jar.add_cookie_str(
-        &cookie_str,
+        cookie_str,
         &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
     );
 
@@ -168,3 +284,133 @@ async fn should_return_401_if_invalid_token() {
 
     app.clean_up().await;
 }
+
+#[tokio::test]
+async fn should_revoke_every_session_on_logout_all() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    // A session on another device, followed by the current one
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let other_token = response
+        .get_auth_cookie()
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    let other_refresh_token = response
+        .get_refresh_cookie()
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .get_auth_cookie()
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+
+    assert!(auth_cookie.value().is_empty());
+
+    for token in [token, other_token] {
+        let response = app
+            .post_verify_token(&serde_json::json!({ "token": token }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let cookie_str = &format!(
+        "{}={}; HttpOnly; SameSite=Strict; Path=/",
+        REFRESH_COOKIE_NAME, other_refresh_token
+    );
+    app.cookie_jar.add_cookie_str(
+        cookie_str,
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Logging in again works as usual
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_cookie.value() }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing_on_logout_all() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned(),
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_with_personal_access_token_on_logout_all() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+    let token = app.create_personal_access_token(&[]).await;
+
+    // Personal access tokens don't have a session, so they can't log the user out everywhere
+    let response = reqwest::Client::new()
+        .post(format!("{}/logout-all", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
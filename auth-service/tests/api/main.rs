@@ -1,8 +1,30 @@
 mod helpers;
 
+mod authorize;
+mod change_email;
+mod change_password;
+mod delete_account;
+mod device_authorization;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod oauth_clients;
+mod openid_configuration;
+mod password_reset;
+mod personal_access_tokens;
+mod recovery_codes;
+mod refresh;
+mod revoke;
+mod roles;
 mod root;
+mod rotate_signing_key;
+mod sessions;
 mod signup;
+mod token;
+mod totp;
+mod unlock_account;
+mod userinfo;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
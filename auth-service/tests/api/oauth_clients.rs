@@ -0,0 +1,182 @@
+use auth_service::{routes::RegisterOAuthClientResponse, ErrorResponse};
+
+use crate::helpers::{TestApp, OAUTH_REDIRECT_URI};
+
+#[tokio::test]
+async fn should_return_201_with_secret_for_confidential_client() {
+    let mut app = TestApp::new().await;
+
+    let body = serde_json::json!({
+        "name": "Test app",
+        "redirectUris": [OAUTH_REDIRECT_URI, "http://localhost:3000/callback"],
+        "confidential": true,
+    });
+
+    let admin_token = app.get_admin_token().await;
+    let response = app
+        .post_register_oauth_client(&body, Some(&admin_token))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let client = response
+        .json::<RegisterOAuthClientResponse>()
+        .await
+        .expect("Could not deserialize response body to RegisterOAuthClientResponse");
+
+    assert!(!client.client_id.is_empty());
+    assert!(client.client_secret.is_some());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_201_without_secret_for_public_client() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client(false).await;
+
+    assert!(!client_id.is_empty());
+    assert_eq!(client_secret, None);
+
+    // Every client gets its own id
+    let (other_client_id, _) = app.register_oauth_client(false).await;
+    assert_ne!(client_id, other_client_id);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_201_for_machine_client() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_machine_client(&["reports:read"]).await;
+
+    assert!(!client_id.is_empty());
+    assert!(!client_secret.is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_201_for_device_client() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_device_client().await;
+
+    assert!(!client_id.is_empty());
+    assert_eq!(client_secret, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_client() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({ "name": "", "redirectUris": [OAUTH_REDIRECT_URI], "confidential": true }),
+        serde_json::json!({ "name": "Test app", "redirectUris": ["/callback"], "confidential": true }),
+        serde_json::json!({
+            "name": "Test app",
+            "redirectUris": ["https://app.example.com/callback#token"],
+            "confidential": true
+        }),
+        serde_json::json!({ "name": "Test job", "scopes": ["two words"], "confidential": true }),
+    ];
+
+    let admin_token = app.get_admin_token().await;
+    for test_case in test_cases {
+        let response = app
+            .post_register_oauth_client(&test_case, Some(&admin_token))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_auth_token() {
+    let mut app = TestApp::new().await;
+
+    let body = serde_json::json!({
+        "name": "Test app",
+        "redirectUris": [OAUTH_REDIRECT_URI],
+        "confidential": true,
+    });
+
+    let response = app
+        .post_register_oauth_client(&body, Some("not-a-token"))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid auth token".to_owned(),
+    );
+
+    let response = app.post_register_oauth_client(&body, None).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_if_not_admin() {
+    let mut app = TestApp::new().await;
+
+    let body = serde_json::json!({
+        "name": "Test app",
+        "redirectUris": [OAUTH_REDIRECT_URI],
+        "confidential": true,
+    });
+
+    let token = app.sign_up_and_log_in().await.auth_token;
+
+    let response = app.post_register_oauth_client(&body, Some(&token)).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing permission".to_owned(),
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({ "name": "Test app", "redirectUris": [OAUTH_REDIRECT_URI] }),
+        serde_json::json!({ "redirectUris": OAUTH_REDIRECT_URI, "confidential": true }),
+        serde_json::json!({ "name": "Test job", "scopes": "reports:read", "confidential": true }),
+    ];
+
+    let admin_token = app.get_admin_token().await;
+    for test_case in test_cases {
+        let response = app
+            .post_register_oauth_client(&test_case, Some(&admin_token))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+
+    app.clean_up().await;
+}
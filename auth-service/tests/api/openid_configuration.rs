@@ -0,0 +1,52 @@
+use auth_service::{routes::OpenIdConfiguration, utils::constants::test};
+use jsonwebtoken::Algorithm;
+
+use crate::helpers::TestApp;
+
+#[tokio::test]
+async fn should_return_provider_metadata() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_openid_configuration().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let configuration = response
+        .json::<OpenIdConfiguration>()
+        .await
+        .expect("Could not deserialize response body to OpenIdConfiguration");
+
+    // Endpoints are found under the issuer
+    assert_eq!(configuration.issuer, test::JWT_ISSUER);
+    assert_eq!(
+        configuration.authorization_endpoint,
+        format!("{}/authorize", test::JWT_ISSUER)
+    );
+    assert_eq!(
+        configuration.token_endpoint,
+        format!("{}/token", test::JWT_ISSUER)
+    );
+    assert_eq!(
+        configuration.userinfo_endpoint,
+        format!("{}/userinfo", test::JWT_ISSUER)
+    );
+    assert_eq!(
+        configuration.device_authorization_endpoint,
+        format!("{}/device/code", test::JWT_ISSUER)
+    );
+    assert_eq!(
+        configuration.jwks_uri,
+        format!("{}/.well-known/jwks.json", test::JWT_ISSUER)
+    );
+
+    assert_eq!(configuration.response_types_supported, vec!["code"]);
+    assert_eq!(configuration.code_challenge_methods_supported, vec!["S256"]);
+    assert!(configuration
+        .scopes_supported
+        .contains(&"openid".to_owned()));
+    assert_eq!(
+        configuration.id_token_signing_alg_values_supported,
+        vec![Algorithm::EdDSA]
+    );
+
+    app.clean_up().await;
+}
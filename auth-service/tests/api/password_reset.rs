@@ -0,0 +1,295 @@
+use auth_service::ErrorResponse;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{
+    get_login_body, get_random_email, get_token_from_email, ExtractResponse, TestApp,
+};
+
+async fn request_reset_token(app: &TestApp, email: &str) -> String {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let reset_email = app.get_sent_email(email, "Password reset").await;
+
+    get_token_from_email(&reset_email)
+}
+
+#[tokio::test]
+async fn should_reset_password_with_emailed_token() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up_unverified().await;
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "new-password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&get_login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Receiving the reset token proves the user owns the address, so it counts as verified
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "new-password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_202_without_sending_email_if_user_does_not_exist() {
+    let mut app = TestApp::new().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 202);
+
+    // Give the background task the chance to (wrongly) send an email
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_email() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": "invalid" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_outstanding_tokens() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up_unverified().await;
+    app.verify_email(&email).await;
+
+    let response = app.post_login(&get_login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+    let jwt = auth_cookie.value().to_owned();
+
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "new-password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": jwt }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The refresh cookie from the login is still in the jar
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // So is the JWT cookie
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_token_is_reused() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up_unverified().await;
+    let token = request_reset_token(&app, &email).await;
+
+    let body = serde_json::json!({
+        "token": token,
+        "newPassword": "new-password123",
+    });
+
+    let response = app.post_password_reset_confirm(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_password_reset_confirm(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_newer_token_was_requested() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up_unverified().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let first_token = get_token_from_email(&app.get_sent_email(&email, "Password reset").await);
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // The second email is sent in the background too, so wait for it to arrive
+    let mut second_token = first_token.clone();
+    for _ in 0..100 {
+        second_token = get_token_from_email(&app.get_sent_email(&email, "Password reset").await);
+        if second_token != first_token {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_ne!(second_token, first_token);
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": first_token,
+            "newPassword": "new-password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": second_token,
+            "newPassword": "new-password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = ["invalid", &"a".repeat(64)];
+
+    for test_case in test_cases {
+        let response = app
+            .post_password_reset_confirm(&serde_json::json!({
+                "token": test_case,
+                "newPassword": "new-password123",
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401, "Failed for {}", test_case);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid auth token".to_owned(),
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_keep_token_if_new_password_is_invalid() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up_unverified().await;
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "short",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "new-password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_request() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "e-mail": "a@a.com" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let test_cases = [
+        serde_json::json!({ "token": "a".repeat(64) }),
+        serde_json::json!({ "newPassword": "new-password123" }),
+        serde_json::json!({}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_password_reset_confirm(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Malformed request: {:?}",
+            test_case
+        );
+    }
+
+    app.clean_up().await;
+}
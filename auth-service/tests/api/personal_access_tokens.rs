@@ -0,0 +1,315 @@
+use auth_service::{
+    routes::{CreatePersonalAccessTokenResponse, PersonalAccessTokenResponse, VerifyTokenResponse},
+    ErrorResponse,
+};
+use reqwest::Url;
+
+use crate::helpers::TestApp;
+
+async fn create_token(
+    app: &TestApp,
+    body: &serde_json::Value,
+) -> CreatePersonalAccessTokenResponse {
+    let response = app.post_personal_access_token(body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<CreatePersonalAccessTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to CreatePersonalAccessTokenResponse")
+}
+
+async fn get_tokens(app: &TestApp) -> Vec<PersonalAccessTokenResponse> {
+    let response = app.get_personal_access_tokens().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<Vec<PersonalAccessTokenResponse>>()
+        .await
+        .expect("Could not deserialize response body to personal access tokens")
+}
+
+#[tokio::test]
+async fn should_create_and_list_tokens() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+
+    let created = create_token(
+        &app,
+        &serde_json::json!({
+            "name": "CI",
+            "scopes": ["reports:read"],
+            "expiresInDays": 30,
+        }),
+    )
+    .await;
+    assert!(created.token.starts_with("pat_"));
+    assert_eq!(created.details.name, "CI");
+    assert_eq!(created.details.scopes, vec!["reports:read".to_owned()]);
+    assert_eq!(
+        created.details.expires_at,
+        Some(created.details.created_at + 30 * 86_400)
+    );
+
+    let other = create_token(&app, &serde_json::json!({ "name": "Backup script" })).await;
+    assert_eq!(other.details.expires_at, None);
+
+    let tokens = get_tokens(&app).await;
+    assert_eq!(tokens.len(), 2);
+    assert!(tokens.iter().all(|token| token.last_used_at.is_none()));
+
+    // The token itself is never shown again
+    let response = app.get_personal_access_tokens().await;
+    assert!(!response.text().await.unwrap().contains(&created.token));
+
+    // Using a token is recorded
+    let response = app.post_verify_token_with_bearer(&created.token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = get_tokens(&app).await;
+    let used = tokens
+        .iter()
+        .find(|token| token.id == created.details.id)
+        .unwrap();
+    assert!(used.last_used_at.is_some());
+
+    // Other users' tokens are not listed
+    app.sign_up_and_log_in().await;
+    assert!(get_tokens(&app).await.is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_verify_token() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+
+    let created = create_token(
+        &app,
+        &serde_json::json!({ "name": "CI", "scopes": ["reports:read", "reports:write"] }),
+    )
+    .await;
+
+    let response = app.post_verify_token_with_bearer(&created.token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let verified = response
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse");
+    assert!(verified.user_id.is_some());
+    assert_eq!(verified.client_id, None);
+    assert_eq!(
+        verified.scope.as_deref(),
+        Some("reports:read reports:write")
+    );
+
+    // Sent in the body like a JWT works too
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": created.token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_token_with_bearer("pat_unknown").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_authenticated_with_token() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+
+    let created = create_token(&app, &serde_json::json!({ "name": "CI" })).await;
+    let other = create_token(&app, &serde_json::json!({ "name": "Deploy" })).await;
+
+    // Scripts send the token without any cookies. It can't be used to manage tokens, which would
+    // get around its scopes or lock the user out of their other scripts, or anything else that
+    // needs a session.
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("{}/personal-access-tokens", &app.address))
+        .bearer_auth(&created.token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = client
+        .post(format!("{}/personal-access-tokens", &app.address))
+        .bearer_auth(&created.token)
+        .json(&serde_json::json!({ "name": "Escalated" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = client
+        .delete(format!(
+            "{}/personal-access-tokens/{}",
+            &app.address, other.details.id
+        ))
+        .bearer_auth(&created.token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = client
+        .get(format!("{}/sessions", &app.address))
+        .bearer_auth(&created.token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Both tokens are left
+    assert_eq!(get_tokens(&app).await.len(), 2);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_token() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+
+    let created = create_token(&app, &serde_json::json!({ "name": "CI" })).await;
+    let kept = create_token(&app, &serde_json::json!({ "name": "Backup script" })).await;
+
+    let response = app.delete_personal_access_token(&created.details.id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_token_with_bearer(&created.token).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let tokens = get_tokens(&app).await;
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0].id, kept.details.id);
+
+    let response = app.post_verify_token_with_bearer(&kept.token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_token_unknown() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+    let others_token = create_token(&app, &serde_json::json!({ "name": "CI" })).await;
+
+    app.sign_up_and_log_in().await;
+
+    for id in [
+        others_token.details.id.clone(),
+        uuid::Uuid::new_v4().to_string(),
+    ] {
+        let response = app.delete_personal_access_token(&id).await;
+        assert_eq!(response.status().as_u16(), 404, "Failed for id: {}", id);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Personal access token not found".to_owned()
+        );
+    }
+
+    // The other user's token still works
+    let response = app.post_verify_token_with_bearer(&others_token.token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+
+    let test_cases = [
+        serde_json::json!({ "name": " " }),
+        serde_json::json!({ "name": "CI", "scopes": ["two words"] }),
+        serde_json::json!({ "name": "CI", "scopes": [""] }),
+        serde_json::json!({ "name": "CI", "expiresInDays": 0 }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_personal_access_token(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+
+    assert!(get_tokens(&app).await.is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+
+    let test_cases = [
+        serde_json::json!({}),
+        serde_json::json!({ "name": 1 }),
+        serde_json::json!({ "name": "CI", "scopes": "reports:read" }),
+        serde_json::json!({ "name": "CI", "expiresInDays": -1 }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_personal_access_token(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_auth_cookie() {
+    let mut app = TestApp::new().await;
+
+    let body = serde_json::json!({ "name": "CI" });
+
+    let response = app.post_personal_access_token(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_personal_access_tokens().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .delete_personal_access_token(&uuid::Uuid::new_v4().to_string())
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cookie_jar.add_cookie_str(
+        "jwt=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_personal_access_token(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
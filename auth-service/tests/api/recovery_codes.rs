@@ -0,0 +1,211 @@
+use auth_service::{
+    domain::RECOVERY_CODE_COUNT,
+    routes::{
+        RecoveryCodesCountResponse, RecoveryCodesResponse, SignupResponse, TwoFactorAuthResponse,
+    },
+    ErrorResponse,
+};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{get_random_email, ExtractResponse, TestApp};
+
+// Sign up a user who requires 2FA and return their recovery codes
+async fn signup(app: &TestApp, email: &str) -> Vec<String> {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(email).await;
+
+    // Accept the emailed 2FA codes, they are left unused
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes
+        .expect("No recovery codes returned")
+}
+
+async fn login_with_recovery_code(app: &TestApp, email: &str, code: &str) -> reqwest::Response {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    }))
+    .await
+}
+
+async fn remaining_recovery_codes(app: &TestApp) -> usize {
+    let response = app.get_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<RecoveryCodesCountResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesCountResponse")
+        .remaining
+}
+
+#[tokio::test]
+async fn should_accept_each_recovery_code_once() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let recovery_codes = signup(&app, &email).await;
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    let response = login_with_recovery_code(&app, &email, &recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.get_auth_cookie().is_some());
+
+    assert_eq!(
+        remaining_recovery_codes(&app).await,
+        RECOVERY_CODE_COUNT - 1
+    );
+
+    let response = login_with_recovery_code(&app, &email, &recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Codes are accepted regardless of case
+    let response =
+        login_with_recovery_code(&app, &email, &recovery_codes[1].to_ascii_uppercase()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_accept_recovery_codes_of_another_user() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email).await;
+
+    let other_email = get_random_email();
+    let other_recovery_codes = signup(&app, &other_email).await;
+
+    let response = login_with_recovery_code(&app, &email, &other_recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_replace_recovery_codes_on_regeneration() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let old_recovery_codes = signup(&app, &email).await;
+
+    let response = login_with_recovery_code(&app, &email, &old_recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_recovery_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+
+    assert_eq!(new_recovery_codes.len(), RECOVERY_CODE_COUNT);
+    assert_eq!(remaining_recovery_codes(&app).await, RECOVERY_CODE_COUNT);
+
+    let response = login_with_recovery_code(&app, &email, &old_recovery_codes[1]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = login_with_recovery_code(&app, &email, &new_recovery_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_2fa_is_not_enabled() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse");
+    assert!(response.recovery_codes.is_none());
+
+    app.verify_email(&email).await;
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "2FA is not enabled".to_owned(),
+    );
+
+    assert_eq!(remaining_recovery_codes(&app).await, 0);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_auth_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,158 @@
+use auth_service::{
+    domain::{RefreshToken, RefreshTokenStoreError},
+    utils::constants::REFRESH_COOKIE_NAME,
+    ErrorResponse,
+};
+use reqwest::Url;
+use secrecy::Secret;
+
+use crate::helpers::{ExtractResponse, TestApp};
+
+fn set_refresh_cookie(app: &TestApp, token: &str) {
+    let cookie_str = &format!(
+        "{}={}; HttpOnly; SameSite=Strict; Path=/",
+        REFRESH_COOKIE_NAME, token
+    );
+    app.cookie_jar.add_cookie_str(
+        cookie_str,
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+}
+
+#[tokio::test]
+async fn should_return_200_and_rotate_tokens_if_valid_refresh_cookie() {
+    let mut app = TestApp::new().await;
+
+    let refresh_token = app.sign_up_and_log_in().await.refresh_token;
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    let refresh_cookie = response
+        .get_refresh_cookie()
+        .expect("No refresh cookie found");
+    assert!(!refresh_cookie.value().is_empty());
+    assert_ne!(refresh_cookie.value(), refresh_token);
+
+    let old_token = RefreshToken::parse(Secret::new(refresh_token)).unwrap();
+    let new_token = RefreshToken::parse(Secret::new(refresh_cookie.value().to_owned())).unwrap();
+
+    {
+        let refresh_token_store = app.refresh_token_store.read().await;
+        let record = refresh_token_store
+            .get_token(&old_token)
+            .await
+            .expect("Failed to get refresh token from store");
+
+        assert_eq!(
+            refresh_token_store
+                .get_family_head(&record.family_id)
+                .await
+                .expect("Failed to get refresh token family head from store"),
+            new_token
+        );
+    }
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned(),
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_refresh_token() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = ["invalid", &"a".repeat(64)];
+
+    for test_case in test_cases.iter() {
+        set_refresh_cookie(&app, test_case);
+
+        let response = app.post_refresh().await;
+
+        assert_eq!(response.status().as_u16(), 401, "Failed for {}", test_case);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid auth token".to_owned(),
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_and_revoke_family_if_refresh_token_reused() {
+    let mut app = TestApp::new().await;
+
+    let first_refresh_token = app.sign_up_and_log_in().await.refresh_token;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let second_refresh_token = response
+        .get_refresh_cookie()
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    // Replay the already rotated token
+    set_refresh_cookie(&app, &first_refresh_token);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The newest token of the family must be revoked as well
+    set_refresh_cookie(&app, &second_refresh_token);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let second_token = RefreshToken::parse(Secret::new(second_refresh_token)).unwrap();
+    {
+        let refresh_token_store = app.refresh_token_store.read().await;
+        let record = refresh_token_store
+            .get_token(&second_token)
+            .await
+            .expect("Failed to get refresh token from store");
+
+        assert_eq!(
+            refresh_token_store
+                .get_family_head(&record.family_id)
+                .await
+                .unwrap_err(),
+            RefreshTokenStoreError::TokenNotFound
+        );
+    }
+
+    app.clean_up().await;
+}
@@ -0,0 +1,273 @@
+use auth_service::{
+    routes::CreatePersonalAccessTokenResponse, utils::constants::test, ErrorResponse,
+};
+
+use crate::helpers::{TestApp, TestUser};
+
+const CLIENT: Option<(&str, &str)> = Some((test::OAUTH_CLIENT_ID, test::OAUTH_CLIENT_SECRET));
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn should_revoke_access_token() {
+    let mut app = TestApp::new().await;
+
+    let auth_token = app.sign_up_and_log_in().await.auth_token;
+    assert_eq!(verify_token_status(&app, &auth_token).await, 200);
+
+    let response = app
+        .post_revoke(&[("token", auth_token.as_str())], CLIENT)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &auth_token).await, 401);
+
+    // Only the access token is revoked, the session lives on
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_refresh_token_and_its_session() {
+    let mut app = TestApp::new().await;
+
+    // The hint only changes which kind of token is looked for first
+    for token_type_hint in ["refresh_token", "access_token", ""] {
+        let TestUser {
+            auth_token,
+            refresh_token,
+            ..
+        } = app.sign_up_and_log_in().await;
+
+        let mut body = vec![("token", refresh_token.as_str())];
+        if !token_type_hint.is_empty() {
+            body.push(("token_type_hint", token_type_hint));
+        }
+
+        let response = app.post_revoke(&body, CLIENT).await;
+        assert_eq!(
+            response.status().as_u16(),
+            200,
+            "Token type hint: {}",
+            token_type_hint
+        );
+
+        let response = app.post_refresh().await;
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Token type hint: {}",
+            token_type_hint
+        );
+
+        // Auth tokens issued to the session stop working with it
+        assert_eq!(verify_token_status(&app, &auth_token).await, 401);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_personal_access_token() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+
+    let response = app
+        .post_personal_access_token(&serde_json::json!({ "name": "CI" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let created = response
+        .json::<CreatePersonalAccessTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to CreatePersonalAccessTokenResponse");
+    assert_eq!(verify_token_status(&app, &created.token).await, 200);
+
+    let response = app
+        .post_revoke(&[("token", created.token.as_str())], CLIENT)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &created.token).await, 401);
+
+    // The token is gone from the user's list rather than just banned
+    let response = app.get_personal_access_tokens().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<serde_json::Value>()
+            .await
+            .expect("Could not deserialize response body to JSON"),
+        serde_json::json!([])
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_200_for_invalid_or_revoked_tokens() {
+    let mut app = TestApp::new().await;
+
+    for token in ["invalid_token", ""] {
+        let response = app.post_revoke(&[("token", token)], CLIENT).await;
+        assert_eq!(response.status().as_u16(), 200, "Token: {}", token);
+    }
+
+    let TestUser {
+        auth_token,
+        refresh_token,
+        ..
+    } = app.sign_up_and_log_in().await;
+
+    for token in [&auth_token, &refresh_token] {
+        for _ in 0..2 {
+            let response = app.post_revoke(&[("token", token.as_str())], CLIENT).await;
+            assert_eq!(response.status().as_u16(), 200);
+        }
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_token_of_registered_client() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret, token) = app.get_machine_client_token().await;
+    assert_eq!(verify_token_status(&app, &token).await, 200);
+
+    let response = app
+        .post_revoke(
+            &[("token", token.as_str())],
+            Some((&client_id, &client_secret)),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_revoke_tokens_of_another_client() {
+    let mut app = TestApp::new().await;
+
+    let TestUser {
+        auth_token,
+        refresh_token,
+        ..
+    } = app.sign_up_and_log_in().await;
+    let (client_id, client_secret, client_token) = app.get_machine_client_token().await;
+    let (other_client_id, other_client_secret, _) = app.get_machine_client_token().await;
+
+    // Tokens of logins belong to our services, and tokens of clients to the client only
+    let test_cases = [
+        (
+            auth_token.as_str(),
+            (client_id.as_str(), client_secret.as_str()),
+        ),
+        (
+            refresh_token.as_str(),
+            (client_id.as_str(), client_secret.as_str()),
+        ),
+        (
+            client_token.as_str(),
+            (other_client_id.as_str(), other_client_secret.as_str()),
+        ),
+        (
+            client_token.as_str(),
+            (test::OAUTH_CLIENT_ID, test::OAUTH_CLIENT_SECRET),
+        ),
+    ];
+
+    for (token, client_credentials) in test_cases {
+        let response = app
+            .post_revoke(&[("token", token)], Some(client_credentials))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            200,
+            "Client credentials: {:?}",
+            client_credentials
+        );
+    }
+
+    assert_eq!(verify_token_status(&app, &auth_token).await, 200);
+    assert_eq!(verify_token_status(&app, &client_token).await, 200);
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_without_valid_client_credentials() {
+    let mut app = TestApp::new().await;
+
+    let auth_token = app.sign_up_and_log_in().await.auth_token;
+    let (client_id, _) = app.register_oauth_client(true).await;
+    // Public clients have no secret to authenticate with
+    let (public_client_id, _) = app.register_oauth_client(false).await;
+
+    let test_cases = [
+        None,
+        Some((test::OAUTH_CLIENT_ID, "wrong-secret")),
+        Some(("unknown-client", test::OAUTH_CLIENT_SECRET)),
+        Some((client_id.as_str(), "wrong-secret")),
+        Some((public_client_id.as_str(), "")),
+    ];
+
+    for client_credentials in test_cases {
+        let response = app
+            .post_revoke(&[("token", auth_token.as_str())], client_credentials)
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Client credentials: {:?}",
+            client_credentials
+        );
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid client credentials".to_owned(),
+        );
+    }
+
+    assert_eq!(verify_token_status(&app, &auth_token).await, 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_request() {
+    let mut app = TestApp::new().await;
+
+    let test_cases: [&[(&str, &str)]; 2] = [&[("tok", "token")], &[]];
+
+    for test_case in test_cases {
+        let response = app.post_revoke(&test_case, CLIENT).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Malformed request: {:?}",
+            test_case
+        );
+    }
+
+    app.clean_up().await;
+}
@@ -0,0 +1,303 @@
+use auth_service::{
+    routes::{CreatePersonalAccessTokenResponse, VerifyTokenResponse},
+    ErrorResponse,
+};
+
+use crate::helpers::{ExtractResponse, TestApp};
+
+// Give the user the admin role and refresh their JWT auth token so it carries the permission to
+// manage roles
+async fn make_admin(app: &TestApp, user_id: &str) -> String {
+    app.assign_admin_role(user_id).await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .get_auth_cookie()
+        .expect("No auth cookie found")
+        .value()
+        .to_owned()
+}
+
+// Log in a new admin, returning their ID and JWT auth token
+async fn log_in_as_admin(app: &TestApp) -> (String, String) {
+    let token = app.sign_up_and_log_in().await.auth_token;
+    let user_id = verify_token(app, &token).await.user_id.unwrap();
+    let token = make_admin(app, &user_id).await;
+
+    (user_id, token)
+}
+
+async fn verify_token(app: &TestApp, token: &str) -> VerifyTokenResponse {
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse")
+}
+
+async fn set_role(app: &TestApp, name: &str, permissions: &[&str]) {
+    let body = serde_json::json!({ "permissions": permissions });
+
+    let response = app.put_admin_role(name, &body).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn assert_error(response: reqwest::Response, status: u16, message: &str) {
+    assert_eq!(response.status().as_u16(), status);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        message
+    );
+}
+
+#[tokio::test]
+async fn should_add_roles_to_new_tokens() {
+    let mut app = TestApp::new().await;
+
+    let (user_id, token) = log_in_as_admin(&app).await;
+
+    set_role(&app, "analyst", &["reports:read", "reports:write"]).await;
+    set_role(&app, "viewer", &["reports:read"]).await;
+
+    for role in ["analyst", "viewer"] {
+        let response = app.put_admin_user_role(&user_id, role).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // Tokens issued before keep what they were issued with
+    let verified = verify_token(&app, &token).await;
+    assert_eq!(verified.roles, vec!["admin"]);
+    assert_eq!(
+        verified.permissions,
+        vec![
+            "accounts:unlock",
+            "clients:write",
+            "roles:write",
+            "signing_keys:rotate"
+        ]
+    );
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = response.get_auth_cookie().expect("No auth cookie found");
+
+    let verified = verify_token(&app, token.value()).await;
+    assert_eq!(verified.roles, vec!["admin", "analyst", "viewer"]);
+    assert_eq!(
+        verified.permissions,
+        vec![
+            "accounts:unlock",
+            "clients:write",
+            "reports:read",
+            "reports:write",
+            "roles:write",
+            "signing_keys:rotate"
+        ]
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_unassign_role() {
+    let mut app = TestApp::new().await;
+
+    let (user_id, _) = log_in_as_admin(&app).await;
+
+    set_role(&app, "analyst", &["reports:read"]).await;
+
+    let response = app.put_admin_user_role(&user_id, "analyst").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_admin_user_role(&user_id, "analyst").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_admin_user_role(&user_id, "analyst").await;
+    assert_error(response, 404, "Role not found").await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = response.get_auth_cookie().expect("No auth cookie found");
+
+    assert_eq!(verify_token(&app, token.value()).await.roles, vec!["admin"]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_limit_personal_access_tokens_to_their_scopes() {
+    let mut app = TestApp::new().await;
+
+    let (user_id, _) = log_in_as_admin(&app).await;
+
+    set_role(&app, "analyst", &["reports:read", "reports:write"]).await;
+
+    let response = app.put_admin_user_role(&user_id, "analyst").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_personal_access_token(&serde_json::json!({
+            "name": "CI",
+            "scopes": ["reports:read"],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let created = response
+        .json::<CreatePersonalAccessTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to CreatePersonalAccessTokenResponse");
+
+    // Roles are looked up when the token is used, not when it was created
+    let verified = verify_token(&app, &created.token).await;
+    assert_eq!(verified.roles, vec!["admin", "analyst"]);
+    assert_eq!(verified.permissions, vec!["reports:read"]);
+
+    // Nor can the token be used for what its scopes leave out
+    let response = reqwest::Client::new()
+        .put(format!("{}/admin/roles/viewer", &app.address))
+        .bearer_auth(&created.token)
+        .json(&serde_json::json!({ "permissions": ["reports:read"] }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_error(response, 403, "Missing permission").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_grant_personal_access_tokens_without_scopes_any_permission() {
+    let mut app = TestApp::new().await;
+
+    log_in_as_admin(&app).await;
+
+    let token = app.create_personal_access_token(&[]).await;
+
+    let verified = verify_token(&app, &token).await;
+    assert_eq!(verified.roles, vec!["admin"]);
+    assert!(verified.permissions.is_empty());
+
+    // A script token of an admin can't manage roles unless it was created for that
+    let response = reqwest::Client::new()
+        .put(format!("{}/admin/roles/viewer", &app.address))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({ "permissions": ["reports:read"] }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_error(response, 403, "Missing permission").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_role_or_user() {
+    let mut app = TestApp::new().await;
+
+    let (user_id, _) = log_in_as_admin(&app).await;
+
+    let response = app.put_admin_user_role(&user_id, "analyst").await;
+    assert_error(response, 404, "Role not found").await;
+
+    set_role(&app, "analyst", &["reports:read"]).await;
+
+    for unknown_user_id in ["00000000-0000-0000-0000-000000000000", "not-a-uuid"] {
+        let response = app.put_admin_user_role(unknown_user_id, "analyst").await;
+        assert_error(response, 404, "User not found").await;
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_role() {
+    let mut app = TestApp::new().await;
+
+    log_in_as_admin(&app).await;
+
+    for (name, permissions) in [
+        ("analyst", serde_json::json!([""])),
+        ("analyst", serde_json::json!(["reports read"])),
+        ("analyst\"", serde_json::json!([])),
+    ] {
+        let body = serde_json::json!({ "permissions": permissions });
+
+        let response = app.put_admin_role(name, &body).await;
+        assert_error(response, 400, "Invalid credentials").await;
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_without_permission() {
+    let mut app = TestApp::new().await;
+
+    let token = app.sign_up_and_log_in().await.auth_token;
+    let user_id = verify_token(&app, &token).await.user_id.unwrap();
+
+    let body = serde_json::json!({ "permissions": ["reports:read"] });
+
+    let response = app.put_admin_role("analyst", &body).await;
+    assert_error(response, 403, "Missing permission").await;
+
+    let response = app.put_admin_user_role(&user_id, "admin").await;
+    assert_error(response, 403, "Missing permission").await;
+
+    let response = app.delete_admin_user_role(&user_id, "admin").await;
+    assert_error(response, 403, "Missing permission").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_200_once_token_has_permission() {
+    let mut app = TestApp::new().await;
+
+    let token = app.sign_up_and_log_in().await.auth_token;
+    let user_id = verify_token(&app, &token).await.user_id.unwrap();
+
+    let body = serde_json::json!({ "permissions": ["reports:read"] });
+
+    let response = app.put_admin_role("analyst", &body).await;
+    assert_error(response, 403, "Missing permission").await;
+
+    // The permission is checked against the token, which only has it once it is refreshed
+    make_admin(&app, &user_id).await;
+
+    let response = app.put_admin_role("analyst", &body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_auth_token() {
+    let mut app = TestApp::new().await;
+
+    let body = serde_json::json!({ "permissions": ["reports:read"] });
+
+    let response = app.put_admin_role("analyst", &body).await;
+    assert_error(response, 400, "Missing auth token").await;
+
+    let response = reqwest::Client::new()
+        .put(format!("{}/admin/roles/analyst", &app.address))
+        .bearer_auth("not-a-token")
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use auth_service::{
+    domain::SigningKey, routes::RotateSigningKeyResponse, tasks::reload_keyring, ErrorResponse,
+};
+use chrono::Utc;
+use jsonwebtoken::{decode_header, jwk::JwkSet, Algorithm};
+
+use crate::helpers::TestApp;
+
+#[tokio::test]
+async fn should_return_200_and_publish_key_before_it_signs() {
+    let mut app = TestApp::new().await;
+
+    let user = app.sign_up_and_log_in().await;
+    let old_kid = app.keyring.read().await.active().kid().to_owned();
+    let admin_token = app.get_admin_token().await;
+
+    let response = app
+        .post_rotate_signing_key(&serde_json::json!({}), Some(&admin_token))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<RotateSigningKeyResponse>()
+        .await
+        .expect("Could not deserialize response body to RotateSigningKeyResponse");
+    assert_ne!(body.kid, old_kid);
+    assert!(body.activates_at > Utc::now().timestamp());
+
+    let jwks = app
+        .get_jwks()
+        .await
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+    assert!(jwks.find(&old_kid).is_some());
+    assert!(jwks.find(&body.kid).is_some());
+
+    // The other instances may not know the new key yet
+    let new_user = app.log_in(&user.email).await;
+    let header = decode_header(&new_user.auth_token).expect("Failed to decode token header");
+    assert_eq!(header.kid, Some(old_kid));
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": user.auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Stored for the other instances, along with the time the previous key retires at
+    let entries = app
+        .signing_key_store
+        .read()
+        .await
+        .get_keys(Utc::now())
+        .await
+        .expect("Failed to get signing keys");
+    assert_eq!(entries.len(), 2);
+    for entry in entries {
+        match entry.key.kid() == body.kid {
+            true => {
+                assert_eq!(entry.activated_at.timestamp(), body.activates_at);
+                assert_eq!(entry.retired_at, None);
+            }
+            false => assert_eq!(
+                entry.retired_at.map(|at| at.timestamp()),
+                Some(body.activates_at)
+            ),
+        }
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_sign_with_key_rotated_by_another_instance_after_reload() {
+    let mut app = TestApp::new().await;
+
+    let user = app.sign_up_and_log_in().await;
+
+    let new_key = SigningKey::generate_ed25519().expect("Failed to generate key");
+    let new_kid = new_key.kid().to_owned();
+    app.signing_key_store
+        .write()
+        .await
+        .rotate(new_key, Utc::now())
+        .await
+        .expect("Failed to rotate signing key");
+
+    tokio::spawn(reload_keyring(
+        app.keyring.clone(),
+        app.signing_key_store.clone(),
+        Duration::from_millis(50),
+    ));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let new_user = app.log_in(&user.email).await;
+    let header = decode_header(&new_user.auth_token).expect("Failed to decode token header");
+    assert_eq!(header.kid, Some(new_kid));
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": user.auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_store_a_shared_secret() {
+    let mut app = TestApp::new().await;
+    let admin_token = app.get_admin_token().await;
+
+    let body = serde_json::json!({
+        "algorithm": "HS256",
+        "key": "a-new-shared-secret"
+    });
+
+    let response = app.post_rotate_signing_key(&body, Some(&admin_token)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let kid = response
+        .json::<RotateSigningKeyResponse>()
+        .await
+        .expect("Could not deserialize response body to RotateSigningKeyResponse")
+        .kid;
+
+    let entries = app
+        .signing_key_store
+        .read()
+        .await
+        .get_keys(Utc::now())
+        .await
+        .expect("Failed to get signing keys");
+    let entry = entries
+        .iter()
+        .find(|entry| entry.key.kid() == kid)
+        .expect("Rotated key not stored");
+    assert_eq!(entry.key.algorithm(), Algorithm::HS256);
+
+    // The same key can't be rotated to again
+    let response = app.post_rotate_signing_key(&body, Some(&admin_token)).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_auth_token_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_rotate_signing_key(&serde_json::json!({}), None)
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_auth_token() {
+    let mut app = TestApp::new().await;
+    let old_kid = app.keyring.read().await.active().kid().to_owned();
+
+    let response = app
+        .post_rotate_signing_key(&serde_json::json!({}), Some("not-a-token"))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(app.keyring.read().await.keys().count(), 1);
+    assert_eq!(app.keyring.read().await.active().kid(), old_kid);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_if_not_admin() {
+    let mut app = TestApp::new().await;
+
+    let token = app.sign_up_and_log_in().await.auth_token;
+
+    let response = app
+        .post_rotate_signing_key(&serde_json::json!({}), Some(&token))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing permission".to_owned(),
+    );
+    assert_eq!(app.keyring.read().await.keys().count(), 1);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_key() {
+    let mut app = TestApp::new().await;
+    let admin_token = app.get_admin_token().await;
+
+    let test_cases = [
+        serde_json::json!({ "algorithm": "HS256" }),
+        serde_json::json!({ "algorithm": "HS256", "key": "" }),
+        serde_json::json!({ "algorithm": "EdDSA", "key": "not a pem" }),
+        serde_json::json!({ "algorithm": "RS256" }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app
+            .post_rotate_signing_key(test_case, Some(&admin_token))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid signing key".to_owned(),
+        );
+    }
+
+    app.clean_up().await;
+}
@@ -0,0 +1,175 @@
+use auth_service::{routes::SessionResponse, ErrorResponse};
+use reqwest::Url;
+
+use crate::helpers::{TestApp, TestUser};
+
+async fn get_sessions(app: &TestApp) -> Vec<SessionResponse> {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<Vec<SessionResponse>>()
+        .await
+        .expect("Could not deserialize response body to sessions")
+}
+
+#[tokio::test]
+async fn should_list_sessions_of_user() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    app.log_in_from(&email, "laptop").await;
+    app.log_in_from(&email, "phone").await;
+
+    let sessions = get_sessions(&app).await;
+    assert_eq!(sessions.len(), 2);
+
+    let current = sessions.iter().find(|session| session.current).unwrap();
+    assert_eq!(current.user_agent.as_deref(), Some("phone"));
+    assert!(current.ip_address.is_loopback());
+
+    let other = sessions.iter().find(|session| !session.current).unwrap();
+    assert_eq!(other.user_agent.as_deref(), Some("laptop"));
+
+    // Other users' sessions are not listed
+    let other_email = app.sign_up().await;
+    app.log_in_from(&other_email, "tablet").await;
+
+    let sessions = get_sessions(&app).await;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].user_agent.as_deref(), Some("tablet"));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_other_session() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    let TestUser {
+        auth_token: other_session_token,
+        refresh_token: other_session_refresh_token,
+        ..
+    } = app.log_in_from(&email, "laptop").await;
+    app.log_in_from(&email, "phone").await;
+
+    let sessions = get_sessions(&app).await;
+    let other = sessions.iter().find(|session| !session.current).unwrap();
+
+    let response = app.delete_session(&other.id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_session_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The current session still works
+    let sessions = get_sessions(&app).await;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+
+    // Nor can the revoked session be refreshed
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "refresh_token={}; HttpOnly; SameSite=Strict; Path=/",
+            other_session_refresh_token
+        ),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_session_unknown() {
+    let mut app = TestApp::new().await;
+
+    let other_email = app.sign_up().await;
+    app.log_in_from(&other_email, "laptop").await;
+    let others_session_id = get_sessions(&app).await[0].id.clone();
+
+    let email = app.sign_up().await;
+    app.log_in_from(&email, "phone").await;
+
+    for id in [
+        others_session_id,
+        uuid::Uuid::new_v4().to_string(),
+        "invalid".to_owned(),
+    ] {
+        let response = app.delete_session(&id).await;
+        assert_eq!(response.status().as_u16(), 404, "Failed for id: {}", id);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Session not found".to_owned()
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_accept_bearer_token() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up().await;
+    let laptop_token = app.log_in_from(&email, "laptop").await.auth_token;
+    app.log_in_from(&email, "phone").await;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/sessions", &app.address))
+        .bearer_auth(&laptop_token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sessions = response
+        .json::<Vec<SessionResponse>>()
+        .await
+        .expect("Could not deserialize response body to sessions");
+    let current = sessions.iter().find(|session| session.current).unwrap();
+    assert_eq!(current.user_agent.as_deref(), Some("laptop"));
+
+    // The Authorization header takes precedence over the cookie of the phone
+    let response = app
+        .http_client
+        .get(format!("{}/sessions", &app.address))
+        .bearer_auth("invalid")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_auth_cookie() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.delete_session(&uuid::Uuid::new_v4().to_string()).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cookie_jar.add_cookie_str(
+        "jwt=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
@@ -1,4 +1,4 @@
-use auth_service::{routes::SignupResponse, ErrorResponse};
+use auth_service::{domain::RECOVERY_CODE_COUNT, routes::SignupResponse, ErrorResponse};
 
 use crate::helpers::{get_random_email, TestApp};
 
@@ -15,17 +15,18 @@ async fn should_return_201_if_valid_input() {
 
     assert_eq!(response.status().as_u16(), 201);
 
-    let expected_response = SignupResponse {
-        message: "User created successfully!".to_owned(),
-    };
+    let response = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to UserBody");
 
     // Assert that we are getting the correct response body!
+    assert_eq!(response.message, "User created successfully!".to_owned());
+
+    // Users who require 2FA get their recovery codes right away
     assert_eq!(
-        response
-            .json::<SignupResponse>()
-            .await
-            .expect("Could not deserialize response body to UserBody"),
-        expected_response
+        response.recovery_codes.map(|codes| codes.len()),
+        Some(RECOVERY_CODE_COUNT)
     );
 
     app.clean_up().await;
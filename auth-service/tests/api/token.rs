@@ -0,0 +1,706 @@
+use auth_service::{
+    domain::{AuthenticationMethod, Email},
+    routes::{IntrospectResponse, TokenResponse, VerifyTokenResponse},
+    utils::{auth::IdTokenClaims, constants::test},
+    OAuthErrorResponse,
+};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, DecodingKey, Validation};
+use reqwest::header::{CACHE_CONTROL, LOCATION};
+use secrecy::{ExposeSecret, Secret};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{get_pkce_pair, get_random_email, TestApp, OAUTH_REDIRECT_URI};
+
+// Get an authorization code for the client on behalf of the logged in user, returning the code
+// and its PKCE code verifier
+async fn authorize(app: &TestApp, client_id: &str) -> (String, String) {
+    authorize_with(app, client_id, &[("scope", "profile")]).await
+}
+
+// Like authorize, with the given scope and other parameters
+async fn authorize_with(
+    app: &TestApp,
+    client_id: &str,
+    params: &[(&str, &str)],
+) -> (String, String) {
+    let (code_verifier, code_challenge) = get_pkce_pair();
+
+    let mut query = vec![
+        ("response_type", "code"),
+        ("client_id", client_id),
+        ("redirect_uri", OAUTH_REDIRECT_URI),
+        ("code_challenge", &code_challenge),
+        ("code_challenge_method", "S256"),
+    ];
+    query.extend(params);
+
+    let response = app.get_authorize(&query).await;
+    assert!(response.status().is_redirection());
+
+    let location = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|location| reqwest::Url::parse(location).ok())
+        .expect("No location header found");
+
+    let code = location
+        .query_pairs()
+        .find(|(name, _)| name == "code")
+        .map(|(_, code)| code.into_owned())
+        .expect("No code found");
+
+    (code, code_verifier)
+}
+
+fn code_exchange<'a>(code: &'a str, code_verifier: &'a str) -> Vec<(&'a str, &'a str)> {
+    vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", OAUTH_REDIRECT_URI),
+        ("code_verifier", code_verifier),
+    ]
+}
+
+async fn oauth_error(response: reqwest::Response) -> String {
+    response
+        .json::<OAuthErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthErrorResponse")
+        .error
+}
+
+// Verify the ID token with the published keys, as a client would
+async fn decode_id_token(app: &TestApp, id_token: &str, client_id: &str) -> IdTokenClaims {
+    let jwks = app
+        .get_jwks()
+        .await
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    let header = decode_header(id_token).expect("Failed to decode ID token header");
+    let jwk = header
+        .kid
+        .and_then(|kid| jwks.find(&kid).cloned())
+        .expect("Signing key is not published");
+    let decoding_key = DecodingKey::from_jwk(&jwk).expect("Failed to build decoding key");
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[test::JWT_ISSUER]);
+    validation.set_audience(&[client_id]);
+
+    decode::<IdTokenClaims>(id_token, &decoding_key, &validation)
+        .expect("Failed to verify ID token")
+        .claims
+}
+
+async fn introspect(app: &TestApp, token: &str) -> IntrospectResponse {
+    app.post_introspect(
+        &[("token", token)],
+        Some((test::OAUTH_CLIENT_ID, test::OAUTH_CLIENT_SECRET)),
+    )
+    .await
+    .json::<IntrospectResponse>()
+    .await
+    .expect("Could not deserialize response body to IntrospectResponse")
+}
+
+#[tokio::test]
+async fn should_exchange_code_for_tokens_of_confidential_client() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client(true).await;
+    let client_secret = client_secret.expect("No client secret found");
+    app.sign_up_and_log_in().await;
+    let (code, code_verifier) = authorize(&app, &client_id).await;
+
+    let response = app
+        .post_token(
+            &code_exchange(&code, &code_verifier),
+            Some((&client_id, &client_secret)),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get(CACHE_CONTROL)
+            .and_then(|value| value.to_str().ok()),
+        Some("no-store")
+    );
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(tokens.token_type, "Bearer");
+    assert!(tokens.expires_in > 0);
+    assert_eq!(tokens.scope.as_deref(), Some("profile"));
+    // Only OpenID Connect requests get an ID token
+    assert_eq!(tokens.id_token, None);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The token tells who it was issued to
+    let introspection = introspect(&app, &tokens.access_token).await;
+    assert!(introspection.active);
+    assert_eq!(introspection.client_id, Some(client_id.clone()));
+    assert_eq!(introspection.scope.as_deref(), Some("profile"));
+
+    // The secret can be sent in the form too
+    let (code, code_verifier) = authorize(&app, &client_id).await;
+    let mut body = code_exchange(&code, &code_verifier);
+    body.extend([
+        ("client_id", client_id.as_str()),
+        ("client_secret", client_secret.as_str()),
+    ]);
+
+    let response = app.post_token(&body, None).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_exchange_code_for_tokens_of_public_client() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(false).await;
+    app.sign_up_and_log_in().await;
+    let (code, code_verifier) = authorize(&app, &client_id).await;
+
+    let mut body = code_exchange(&code, &code_verifier);
+    body.push(("client_id", &client_id));
+
+    let response = app.post_token(&body, None).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    assert!(introspect(&app, &tokens.access_token).await.active);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_code_is_used_twice() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(false).await;
+    app.sign_up_and_log_in().await;
+    let (code, code_verifier) = authorize(&app, &client_id).await;
+
+    let mut body = code_exchange(&code, &code_verifier);
+    body.push(("client_id", &client_id));
+
+    let response = app.post_token(&body, None).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_token(&body, None).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "invalid_grant");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_code_does_not_match_the_request() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(false).await;
+    let (other_client_id, _) = app.register_oauth_client(false).await;
+    app.sign_up_and_log_in().await;
+
+    let (other_code_verifier, _) = get_pkce_pair();
+    let test_cases = [
+        ("code_verifier", other_code_verifier.as_str()),
+        ("redirect_uri", "https://app.example.com/other"),
+        ("client_id", other_client_id.as_str()),
+        ("code", "not-a-code"),
+    ];
+
+    for (name, value) in test_cases {
+        // A failed exchange uses up the code, so each case needs a new one
+        let (code, code_verifier) = authorize(&app, &client_id).await;
+
+        let mut body = code_exchange(&code, &code_verifier);
+        body.push(("client_id", &client_id));
+        let body: Vec<_> = body
+            .into_iter()
+            .map(|param| {
+                if param.0 == name {
+                    (name, value)
+                } else {
+                    param
+                }
+            })
+            .collect();
+
+        let response = app.post_token(&body, None).await;
+        assert_eq!(response.status().as_u16(), 400, "Body: {:?}", body);
+        assert_eq!(oauth_error(response).await, "invalid_grant");
+
+        if name == "code" {
+            continue;
+        }
+
+        // Once a code is used wrong it's gone for good
+        let mut body = code_exchange(&code, &code_verifier);
+        body.push(("client_id", &client_id));
+
+        let response = app.post_token(&body, None).await;
+        assert_eq!(response.status().as_u16(), 400);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_client_credentials() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(true).await;
+    app.sign_up_and_log_in().await;
+    let (code, code_verifier) = authorize(&app, &client_id).await;
+
+    let body = code_exchange(&code, &code_verifier);
+    let mut without_secret = body.clone();
+    without_secret.push(("client_id", &client_id));
+
+    let test_cases = [
+        (body.clone(), Some((client_id.as_str(), "wrong-secret"))),
+        (body.clone(), Some(("unknown-client", "secret"))),
+        (body, None),
+        (without_secret, None),
+    ];
+
+    for (body, client_credentials) in test_cases {
+        let response = app.post_token(&body, client_credentials).await;
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Client credentials: {:?}",
+            client_credentials
+        );
+        assert_eq!(oauth_error(response).await, "invalid_client");
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_refresh_tokens() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(false).await;
+    app.sign_up_and_log_in().await;
+    let (code, code_verifier) = authorize(&app, &client_id).await;
+
+    let mut body = code_exchange(&code, &code_verifier);
+    body.push(("client_id", &client_id));
+
+    let tokens = app
+        .post_token(&body, None)
+        .await
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    let refresh_token = tokens.refresh_token.expect("No refresh token found");
+
+    let refresh = |refresh_token: String| {
+        let client_id = client_id.clone();
+        let app = &app;
+        async move {
+            app.post_token(
+                &[
+                    ("grant_type", "refresh_token"),
+                    ("refresh_token", refresh_token.as_str()),
+                    ("client_id", client_id.as_str()),
+                ],
+                None,
+            )
+            .await
+        }
+    };
+
+    let response = refresh(refresh_token.clone()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let refreshed = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    let refreshed_token = refreshed.refresh_token.expect("No refresh token found");
+    assert_ne!(refreshed_token, refresh_token);
+    assert_eq!(refreshed.scope.as_deref(), Some("profile"));
+    assert_eq!(
+        introspect(&app, &refreshed.access_token).await.client_id,
+        Some(client_id.clone())
+    );
+
+    // Reusing the rotated token revokes the whole session
+    let response = refresh(refresh_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "invalid_grant");
+
+    let response = refresh(refreshed_token).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(!introspect(&app, &refreshed.access_token).await.active);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_refresh_token_was_issued_to_someone_else() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(false).await;
+    let (other_client_id, _) = app.register_oauth_client(false).await;
+    let login_refresh_token = app.sign_up_and_log_in().await.refresh_token;
+    let (code, code_verifier) = authorize(&app, &client_id).await;
+
+    let mut body = code_exchange(&code, &code_verifier);
+    body.push(("client_id", &client_id));
+
+    let tokens = app
+        .post_token(&body, None)
+        .await
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    let test_cases = [
+        (
+            tokens.refresh_token.as_deref().unwrap_or_default(),
+            other_client_id.as_str(),
+        ),
+        (login_refresh_token.as_str(), client_id.as_str()),
+    ];
+
+    for (refresh_token, client_id) in test_cases {
+        let body = [
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", client_id),
+        ];
+
+        let response = app.post_token(&body, None).await;
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(oauth_error(response).await, "invalid_grant");
+    }
+
+    // The session of the browser is not affected
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_unsupported_or_missing_grant_type() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(false).await;
+
+    let test_cases = [
+        (Some("password"), "unsupported_grant_type"),
+        (None, "invalid_request"),
+    ];
+
+    for (grant_type, error) in test_cases {
+        let mut body = vec![("client_id", client_id.as_str())];
+        if let Some(grant_type) = grant_type {
+            body.push(("grant_type", grant_type));
+        }
+
+        let response = app.post_token(&body, None).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Grant type: {:?}",
+            grant_type
+        );
+        assert_eq!(oauth_error(response).await, error);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_issue_id_token_for_openid_scope() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(false).await;
+    app.sign_up_and_log_in().await;
+    let (code, code_verifier) = authorize_with(
+        &app,
+        &client_id,
+        &[("scope", "openid email"), ("nonce", "n-0S6_WzA2Mj")],
+    )
+    .await;
+
+    let mut body = code_exchange(&code, &code_verifier);
+    body.push(("client_id", &client_id));
+
+    let response = app.post_token(&body, None).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    let id_token = tokens.id_token.expect("No ID token found");
+
+    let claims = decode_id_token(&app, &id_token, &client_id).await;
+
+    let user_id = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse")
+        .user_id
+        .expect("No user id found");
+    assert_eq!(claims.sub, user_id);
+    assert_eq!(claims.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+    assert_eq!(claims.amr, vec![AuthenticationMethod::Password]);
+    assert!(claims.auth_time > 0 && claims.auth_time as usize <= claims.iat);
+
+    // The ID token is not an auth token
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": id_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Refreshing the tokens doesn't issue another one
+    let response = app
+        .post_token(
+            &[
+                ("grant_type", "refresh_token"),
+                (
+                    "refresh_token",
+                    tokens.refresh_token.as_deref().unwrap_or_default(),
+                ),
+                ("client_id", &client_id),
+            ],
+            None,
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    assert_eq!(tokens.id_token, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_report_2fa_in_id_token() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client(false).await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let (login_attempt_id, two_fa_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .expect("Failed to get 2FA code from store");
+
+    let verify_2fa_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id.as_ref().expose_secret(),
+        "2FACode": two_fa_code.as_ref().expose_secret(),
+    });
+
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let (code, code_verifier) = authorize_with(&app, &client_id, &[("scope", "openid")]).await;
+
+    let mut body = code_exchange(&code, &code_verifier);
+    body.push(("client_id", &client_id));
+
+    let tokens = app
+        .post_token(&body, None)
+        .await
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    let id_token = tokens.id_token.expect("No ID token found");
+
+    let claims = decode_id_token(&app, &id_token, &client_id).await;
+    assert_eq!(claims.nonce, None);
+    assert!(claims.amr.contains(&AuthenticationMethod::Password));
+    assert!(claims.amr.contains(&AuthenticationMethod::OneTimePassword));
+    assert!(claims.amr.contains(&AuthenticationMethod::MultiFactor));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_issue_client_token_for_client_credentials() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app
+        .register_machine_client(&["reports:read", "reports:write"])
+        .await;
+
+    let response = app
+        .post_token(
+            &[("grant_type", "client_credentials")],
+            Some((&client_id, &client_secret)),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(tokens.token_type, "Bearer");
+    assert_eq!(tokens.scope.as_deref(), Some("reports:read reports:write"));
+    // There is no user who would have to log in again
+    assert_eq!(tokens.refresh_token, None);
+    assert_eq!(tokens.id_token, None);
+
+    // The token identifies the client, not a user
+    let verified = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse");
+    assert_eq!(verified.user_id, None);
+    assert_eq!(verified.client_id, Some(client_id.clone()));
+    assert_eq!(
+        verified.scope.as_deref(),
+        Some("reports:read reports:write")
+    );
+
+    let introspection = introspect(&app, &tokens.access_token).await;
+    assert!(introspection.active);
+    assert_eq!(introspection.sub, Some(client_id.clone()));
+    assert_eq!(introspection.client_id, Some(client_id.clone()));
+
+    // Clients can ask for fewer scopes
+    let response = app
+        .post_token(
+            &[
+                ("grant_type", "client_credentials"),
+                ("scope", "reports:read"),
+            ],
+            Some((&client_id, &client_secret)),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    assert_eq!(tokens.scope.as_deref(), Some("reports:read"));
+
+    // Client tokens are not accepted where a user is needed
+    let response = app.get_userinfo(Some(&tokens.access_token)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // and can be revoked by the client like any other
+    let response = app
+        .post_revoke(
+            &[("token", tokens.access_token.as_str())],
+            Some((&client_id, &client_secret)),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!introspect(&app, &tokens.access_token).await.active);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_client_credentials_are_not_allowed() {
+    let mut app = TestApp::new().await;
+
+    let (machine_client_id, machine_client_secret) =
+        app.register_machine_client(&["reports:read"]).await;
+    let (client_id, client_secret) = app.register_oauth_client(true).await;
+    let client_secret = client_secret.expect("No client secret found");
+    let (public_client_id, _) = app.register_oauth_client(false).await;
+
+    let grant = [("grant_type", "client_credentials")];
+    let with_scope = [
+        ("grant_type", "client_credentials"),
+        ("scope", "reports:read reports:write"),
+    ];
+    let with_public_client = [
+        ("grant_type", "client_credentials"),
+        ("client_id", public_client_id.as_str()),
+    ];
+
+    let test_cases = [
+        // Clients acting for their users only don't get tokens of their own
+        (
+            &grant[..],
+            Some((client_id.as_str(), client_secret.as_str())),
+            "unauthorized_client",
+        ),
+        (&with_public_client[..], None, "unauthorized_client"),
+        (
+            &with_scope[..],
+            Some((machine_client_id.as_str(), machine_client_secret.as_str())),
+            "invalid_scope",
+        ),
+    ];
+
+    for (body, client_credentials, error) in test_cases {
+        let response = app.post_token(&body.to_vec(), client_credentials).await;
+        assert_eq!(response.status().as_u16(), 400, "Request: {:?}", body);
+        assert_eq!(oauth_error(response).await, error);
+    }
+
+    app.clean_up().await;
+}
@@ -0,0 +1,277 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use auth_service::{
+    domain::{TotpSecret, TwoFAMethod, MAX_2FA_ATTEMPTS, RECOVERY_CODE_COUNT},
+    routes::{EnrollTotpResponse, RecoveryCodesResponse, TwoFactorAuthResponse},
+    ErrorResponse,
+};
+use reqwest::Url;
+use secrecy::{ExposeSecret, Secret};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{get_login_body, ExtractResponse, TestApp};
+
+async fn enroll(app: &TestApp) -> TotpSecret {
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    assert!(response.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(response
+        .otpauth_uri
+        .contains(&format!("secret={}", response.secret)));
+
+    TotpSecret::parse(Secret::new(response.secret)).expect("Invalid TOTP secret")
+}
+
+fn current_code(secret: &TotpSecret) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Clock is before the Unix epoch")
+        .as_secs();
+
+    secret.code_at(now).expose_secret().to_owned()
+}
+
+// Still accepted now, to allow for clock drift
+fn next_code(secret: &TotpSecret) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Clock is before the Unix epoch")
+        .as_secs();
+
+    secret.code_at(now + 30).expose_secret().to_owned()
+}
+
+// A code that is never accepted along with `code`
+fn wrong_code(code: &str) -> String {
+    let code: u32 = code.parse().expect("Code is not numeric");
+    format!("{:06}", (code + 500_000) % 1_000_000)
+}
+
+#[tokio::test]
+async fn should_check_authenticator_app_code_on_login_after_enrollment() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up_and_log_in().await.email;
+    let secret = enroll(&app).await;
+
+    let confirmation_code = current_code(&secret);
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": confirmation_code }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let recovery_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    // No 2FA code is emailed to users of an authenticator app
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_login(&get_login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let response = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(response.two_fa_method, TwoFAMethod::AuthenticatorApp);
+
+    // The code of the next time step, since the current one was used for the confirmation
+    let code = next_code(&secret);
+
+    let verify_body = |code: &str| {
+        serde_json::json!({
+            "email": email,
+            "loginAttemptId": response.login_attempt_id,
+            "2FACode": code,
+        })
+    };
+
+    let response = app.post_verify_2fa(&verify_body(&wrong_code(&code))).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_verify_2fa(&verify_body(&confirmation_code)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_verify_2fa(&verify_body(&code)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_keep_email_2fa_until_enrollment_is_confirmed() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up_and_log_in().await.email;
+    let secret = enroll(&app).await;
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": wrong_code(&current_code(&secret)) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Incorrect credentials".to_owned(),
+    );
+
+    // 2FA is still disabled, so logging in doesn't ask for a code at all
+    let response = app.post_login(&get_login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_only_accept_the_latest_enrolled_secret() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+    let first_secret = enroll(&app).await;
+    let second_secret = enroll(&app).await;
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": current_code(&first_secret) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": current_code(&second_secret) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_if_too_many_incorrect_confirmation_codes() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+    let secret = enroll(&app).await;
+
+    for _ in 1..MAX_2FA_ATTEMPTS {
+        let response = app
+            .post_confirm_totp(&serde_json::json!({ "code": wrong_code(&current_code(&secret)) }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": wrong_code(&current_code(&secret)) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Enrolling again doesn't start a fresh count
+    let secret = enroll(&app).await;
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": current_code(&secret) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many failed attempts, please try again later".to_owned(),
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_confirmed_without_enrollment() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_code_is_invalid() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+    enroll(&app).await;
+
+    for code in ["", "12345", "abcdef"] {
+        let response = app
+            .post_confirm_totp(&serde_json::json!({ "code": code }))
+            .await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for code: {}", code);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_auth_cookie() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "code": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cookie_jar.add_cookie_str(
+        "jwt=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_enroll_totp().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+
+    let response = app
+        .post_confirm_totp(&serde_json::json!({ "2FACode": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,146 @@
+use auth_service::{domain::MAX_FAILED_LOGINS, ErrorResponse};
+
+use crate::helpers::{get_login_body, get_random_email, get_token_from_email, TestApp};
+
+async fn signup_and_lock_account(app: &TestApp) -> String {
+    let random_email = app.sign_up().await;
+
+    let wrong_login_body = serde_json::json!({
+        "email": random_email,
+        "password": "wrong_password",
+    });
+
+    for _ in 0..MAX_FAILED_LOGINS {
+        let response = app.post_login(&wrong_login_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.post_login(&get_login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    random_email
+}
+
+#[tokio::test]
+async fn should_unlock_account_with_token_from_email() {
+    let mut app = TestApp::new().await;
+
+    let random_email = signup_and_lock_account(&app).await;
+
+    let lockout_email = app
+        .get_sent_email(&random_email, "Your account has been locked")
+        .await;
+
+    let body = serde_json::json!({
+        "token": get_token_from_email(&lockout_email),
+    });
+
+    let response = app.post_unlock_account(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&get_login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_unlock_token() {
+    let mut app = TestApp::new().await;
+
+    let random_email = signup_and_lock_account(&app).await;
+
+    // The token proving the email address can't be used to unlock the account
+    let verification_email = app
+        .get_sent_email(&random_email, "Verify your email address")
+        .await;
+
+    for token in [
+        "invalid".to_owned(),
+        get_token_from_email(&verification_email),
+    ] {
+        let response = app
+            .post_unlock_account(&serde_json::json!({ "token": token }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.post_login(&get_login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_unlock_account_by_admin() {
+    let mut app = TestApp::new().await;
+
+    let random_email = signup_and_lock_account(&app).await;
+
+    let admin_token = app.get_admin_token().await;
+    let response = app
+        .post_admin_unlock_account(
+            &serde_json::json!({ "email": random_email }),
+            Some(&admin_token),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&get_login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_admin_to_unlock_account_by_admin() {
+    let mut app = TestApp::new().await;
+
+    let random_email = signup_and_lock_account(&app).await;
+    let body = serde_json::json!({ "email": random_email });
+
+    let response = app.post_admin_unlock_account(&body, None).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned(),
+    );
+
+    let response = app
+        .post_admin_unlock_account(&body, Some("incorrect"))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Users without the accounts:unlock permission can't unlock accounts either
+    let token = app.sign_up_and_log_in().await.auth_token;
+    let response = app.post_admin_unlock_account(&body, Some(&token)).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app.post_login(&get_login_body(&random_email)).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_unlock_account(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let admin_token = app.get_admin_token().await;
+    let response = app
+        .post_admin_unlock_account(&serde_json::json!({}), Some(&admin_token))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,127 @@
+use auth_service::routes::{TokenResponse, UserInfoResponse, VerifyTokenResponse};
+use reqwest::header::LOCATION;
+
+use crate::helpers::{get_pkce_pair, TestApp, TestUser, OAUTH_REDIRECT_URI};
+
+// Have a public client go through the authorization code flow for the logged in user with the
+// given scope, returning its access token
+async fn access_token(app: &TestApp, scope: &str) -> String {
+    let (client_id, _) = app.register_oauth_client(false).await;
+    let (code_verifier, code_challenge) = get_pkce_pair();
+
+    let response = app
+        .get_authorize(&[
+            ("response_type", "code"),
+            ("client_id", &client_id),
+            ("redirect_uri", OAUTH_REDIRECT_URI),
+            ("code_challenge", &code_challenge),
+            ("code_challenge_method", "S256"),
+            ("scope", scope),
+        ])
+        .await;
+
+    let code = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|location| reqwest::Url::parse(location).ok())
+        .and_then(|location| {
+            location
+                .query_pairs()
+                .find(|(name, _)| name == "code")
+                .map(|(_, code)| code.into_owned())
+        })
+        .expect("No code found");
+
+    let response = app
+        .post_token(
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", &code),
+                ("redirect_uri", OAUTH_REDIRECT_URI),
+                ("code_verifier", &code_verifier),
+                ("client_id", &client_id),
+            ],
+            None,
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+        .access_token
+}
+
+#[tokio::test]
+async fn should_return_claims_of_user() {
+    let mut app = TestApp::new().await;
+
+    let TestUser {
+        email, auth_token, ..
+    } = app.sign_up_and_log_in().await;
+    let access_token = access_token(&app, "openid email").await;
+
+    let response = app.get_userinfo(Some(&access_token)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let user_info = response
+        .json::<UserInfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserInfoResponse");
+
+    let user_id = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse")
+        .user_id
+        .expect("No user id found");
+    assert_eq!(user_info.sub, user_id);
+    assert_eq!(user_info.email, Some(email));
+    assert_eq!(user_info.email_verified, Some(true));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_leave_out_email_without_email_scope() {
+    let mut app = TestApp::new().await;
+
+    app.sign_up_and_log_in().await;
+    let access_token = access_token(&app, "openid").await;
+
+    let response = app.get_userinfo(Some(&access_token)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let user_info = response
+        .json::<UserInfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserInfoResponse");
+    assert_eq!(user_info.email, None);
+    assert_eq!(user_info.email_verified, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_without_openid_access_token() {
+    let mut app = TestApp::new().await;
+
+    let auth_token = app.sign_up_and_log_in().await.auth_token;
+    let access_token = access_token(&app, "profile").await;
+
+    // The auth tokens of the user themselves, access tokens granted without the openid scope,
+    // and anything else are all refused
+    for token in [auth_token.as_str(), access_token.as_str(), "invalid"] {
+        let response = app.get_userinfo(Some(token)).await;
+        assert_eq!(response.status().as_u16(), 401, "{}", token);
+    }
+
+    let response = app.get_userinfo(None).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
@@ -1,5 +1,5 @@
 use auth_service::{
-    domain::{Email, LoginAttemptId, TwoFACode},
+    domain::{Email, LoginAttemptId, TwoFACode, MAX_2FA_ATTEMPTS},
     ErrorResponse,
 };
 use secrecy::{ExposeSecret, Secret};
@@ -24,6 +24,8 @@ async fn should_return_200_if_correct_code() {
     let response = app.post_signup(&signup_body).await;
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
@@ -116,6 +118,8 @@ async fn should_return_401_if_invalid_credentials() {
     let response = app.post_signup(&test_user).await;
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&email).await;
+
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
@@ -179,6 +183,8 @@ async fn should_return_401_if_old_code() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
@@ -250,6 +256,8 @@ async fn should_return_401_if_same_code_twice() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
@@ -306,6 +314,225 @@ async fn should_return_401_if_same_code_twice() {
     app.clean_up().await;
 }
 
+#[tokio::test]
+async fn should_log_in_once_with_concurrent_requests_with_same_code() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let (login_attempt_id, two_fa_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .expect("Failed to get 2FA code from store");
+
+    let verify_2fa_request = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id.as_ref().expose_secret(),
+        "2FACode": two_fa_code.as_ref().expose_secret(),
+    });
+
+    let (first, second, third) = tokio::join!(
+        app.post_verify_2fa(&verify_2fa_request),
+        app.post_verify_2fa(&verify_2fa_request),
+        app.post_verify_2fa(&verify_2fa_request),
+    );
+
+    let statuses = [first, second, third].map(|response| response.status().as_u16());
+    assert_eq!(
+        statuses.iter().filter(|status| **status == 200).count(),
+        1,
+        "Statuses: {:?}",
+        statuses
+    );
+    assert!(statuses.iter().all(|status| [200, 401].contains(status)));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_if_too_many_incorrect_codes() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let (login_attempt_id, two_fa_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .expect("Failed to get 2FA code from store");
+
+    let verify_2fa_request = |code: &str| {
+        serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id.as_ref().expose_secret(),
+            "2FACode": code,
+        })
+    };
+
+    let correct_code = two_fa_code.as_ref().expose_secret().to_owned();
+    let incorrect_code = if correct_code == "000000" {
+        "111111"
+    } else {
+        "000000"
+    };
+
+    for _ in 1..MAX_2FA_ATTEMPTS {
+        let response = app
+            .post_verify_2fa(&verify_2fa_request(incorrect_code))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_verify_2fa(&verify_2fa_request(incorrect_code))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many failed attempts, please log in again".to_owned()
+    );
+
+    // The code of the used up login attempt is no longer accepted
+    let response = app
+        .post_verify_2fa(&verify_2fa_request(&correct_code))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    // The failed codes locked the account, so logging in again doesn't start a fresh count
+    app.get_sent_email(&random_email, "Your account has been locked")
+        .await;
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_count_attempts_with_another_login_attempt_id() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let email = Email::parse(Secret::new(random_email.clone())).unwrap();
+    let (login_attempt_id, two_fa_code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&email)
+        .await
+        .expect("Failed to get 2FA code from store");
+
+    for _ in 0..=MAX_2FA_ATTEMPTS {
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": random_email,
+                "loginAttemptId": LoginAttemptId::default().as_ref().expose_secret(),
+                "2FACode": two_fa_code.as_ref().expose_secret(),
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id.as_ref().expose_secret(),
+            "2FACode": two_fa_code.as_ref().expose_secret(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
 #[tokio::test]
 async fn should_return_422_if_malformed_input() {
     let mut app = TestApp::new().await;
@@ -0,0 +1,154 @@
+use auth_service::ErrorResponse;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{
+    get_login_body, get_random_email, get_token_from_email, ExtractResponse, TestApp,
+};
+
+#[tokio::test]
+async fn should_refuse_login_until_email_is_verified() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up_unverified().await;
+
+    let response = app.post_login(&get_login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(response.get_auth_cookie().is_none());
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Email address not verified".to_owned(),
+    );
+
+    let verification_email = app
+        .get_sent_email(&email, "Verify your email address")
+        .await;
+
+    let response = app
+        .post_verify_email(&serde_json::json!({
+            "token": get_token_from_email(&verification_email),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&get_login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up_unverified().await;
+    app.verify_email(&email).await;
+
+    let response = app.post_login(&get_login_body(&email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // An auth token is signed with the same key, but must not be accepted here
+    let auth_token = response
+        .get_auth_cookie()
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let test_cases = ["invalid".to_owned(), auth_token];
+
+    for test_case in test_cases.iter() {
+        let response = app
+            .post_verify_email(&serde_json::json!({ "token": test_case }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401, "Failed for {}", test_case);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid auth token".to_owned(),
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_resend_verification_email_only_while_unverified() {
+    let mut app = TestApp::new().await;
+
+    let email = app.sign_up_unverified().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // Uses the token from the resent email
+    app.verify_email(&email).await;
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // Give the background task the chance to (wrongly) send another email
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_202_without_sending_email_if_user_does_not_exist() {
+    let mut app = TestApp::new().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_request() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_verify_email(&serde_json::json!({ "code": "abc" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "e-mail": "a@a.com" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.clean_up().await;
+}
@@ -1,7 +1,16 @@
 use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
 
+use serde::Deserialize;
+use uuid::Uuid;
+
 use crate::helpers::{get_random_email, ExtractResponse, TestApp};
 
+#[derive(Deserialize)]
+struct VerifyTokenResponse {
+    #[serde(rename = "userId")]
+    user_id: Option<String>,
+}
+
 #[tokio::test]
 async fn should_return_200_valid_token() {
     let mut app = TestApp::new().await;
@@ -17,6 +26,8 @@ async fn should_return_200_valid_token() {
     let response = app.post_signup(&signup_body).await;
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
@@ -39,6 +50,74 @@ async fn should_return_200_valid_token() {
 
     assert_eq!(response.status().as_u16(), 200, "Expected 200 OK response");
 
+    let user_id = response
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse")
+        .user_id
+        .expect("No user id found");
+    assert!(Uuid::parse_str(&user_id).is_ok());
+
+    // The id identifies the user, not the token
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_cookie.value() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<VerifyTokenResponse>()
+            .await
+            .expect("Could not deserialize response body to VerifyTokenResponse")
+            .user_id,
+        Some(user_id)
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_accept_bearer_token() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&random_email).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response.get_auth_cookie().expect("No auth cookie found");
+
+    let response = app.post_verify_token_with_bearer(auth_cookie.value()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse")
+        .user_id
+        .is_some());
+
+    let response = app.post_verify_token_with_bearer("invalid_token").await;
+    assert_eq!(response.status().as_u16(), 401);
+
     app.clean_up().await;
 }
 
@@ -90,6 +169,8 @@ async fn should_return_401_if_banned_token() {
     let response = app.post_signup(&signup_body).await;
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",